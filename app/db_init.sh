@@ -1,9 +1,4 @@
 #!/bin/bash
-diesel setup
-diesel migration generate auto_anime
-for dir in migrations/*_auto_anime; do
-    if [ -d "$dir" ]; then
-        cp init.sql "$dir/up.sql"
-    fi
-done
-diesel migration run
+# migrations are embedded in the binary and also applied on every startup,
+# this only creates / upgrades the database without starting the servers
+cargo run -- --migrate-only
@@ -0,0 +1,7 @@
+DROP TABLE IF EXISTS "anime_broadcast";
+DROP TABLE IF EXISTS "anime_filter";
+DROP TABLE IF EXISTS "anime_list";
+DROP TABLE IF EXISTS "anime_seed";
+DROP TABLE IF EXISTS "anime_subgroup";
+DROP TABLE IF EXISTS "anime_task";
+DROP TABLE IF EXISTS "anime_progress";
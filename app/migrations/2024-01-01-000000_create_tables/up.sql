@@ -1,9 +1,10 @@
--- Your SQL goes here
+-- Baseline schema, tables are created only if missing so databases set up
+-- by the old db_init.sh can adopt the embedded migrations.
+
 -- ----------------------------
 -- Table structure for anime_broadcast
 -- ----------------------------
-DROP TABLE IF EXISTS "anime_broadcast";
-CREATE TABLE anime_broadcast (
+CREATE TABLE IF NOT EXISTS anime_broadcast (
   id INTEGER PRIMARY KEY AUTOINCREMENT,
   mikan_id INTEGER NOT NULL,
   year INTEGER NOT NULL,
@@ -13,8 +14,7 @@ CREATE TABLE anime_broadcast (
 -- ----------------------------
 -- Table structure for anime_filter
 -- ----------------------------
-DROP TABLE IF EXISTS "anime_filter";
-CREATE TABLE "anime_filter" (
+CREATE TABLE IF NOT EXISTS "anime_filter" (
   "id" INTEGER PRIMARY KEY AUTOINCREMENT,
   "mikan_id" INTEGER NOT NULL,
   "filter_type" TEXT NOT NULL,
@@ -25,8 +25,7 @@ CREATE TABLE "anime_filter" (
 -- ----------------------------
 -- Table structure for anime_list
 -- ----------------------------
-DROP TABLE IF EXISTS "anime_list";
-CREATE TABLE "anime_list" (
+CREATE TABLE IF NOT EXISTS "anime_list" (
   "id" INTEGER PRIMARY KEY AUTOINCREMENT,
   "mikan_id" INTEGER NOT NULL DEFAULT NULL,
   "anime_name" TEXT NOT NULL DEFAULT NULL,
@@ -46,8 +45,7 @@ CREATE TABLE "anime_list" (
 -- ----------------------------
 -- Table structure for anime_seed
 -- ----------------------------
-DROP TABLE IF EXISTS "anime_seed";
-CREATE TABLE "anime_seed" (
+CREATE TABLE IF NOT EXISTS "anime_seed" (
   "id" INTEGER PRIMARY KEY AUTOINCREMENT,
   "mikan_id" INTEGER NOT NULL,
   "subgroup_id" INTEGER NOT NULL,
@@ -62,8 +60,7 @@ CREATE TABLE "anime_seed" (
 -- ----------------------------
 -- Table structure for anime_subgroup
 -- ----------------------------
-DROP TABLE IF EXISTS "anime_subgroup";
-CREATE TABLE anime_subgroup (
+CREATE TABLE IF NOT EXISTS anime_subgroup (
   id INTEGER PRIMARY KEY AUTOINCREMENT,
   subgroup_id INTEGER NOT NULL,
   subgroup_name TEXT NOT NULL,
@@ -73,8 +70,7 @@ CREATE TABLE anime_subgroup (
 -- ----------------------------
 -- Table structure for anime_task
 -- ----------------------------
-DROP TABLE IF EXISTS "anime_task";
-CREATE TABLE "anime_task" (
+CREATE TABLE IF NOT EXISTS "anime_task" (
   "id" INTEGER PRIMARY KEY AUTOINCREMENT,
   "mikan_id" INTEGER NOT NULL,
   "episode" INTEGER NOT NULL,
@@ -88,12 +84,12 @@ CREATE TABLE "anime_task" (
 -- ----------------------------
 -- Table structure for anime_progress
 -- ----------------------------
-DROP TABLE IF EXISTS "anime_progress";
-CREATE TABLE `anime_progress` (
+CREATE TABLE IF NOT EXISTS `anime_progress` (
   `id` INTEGER PRIMARY KEY AUTOINCREMENT,
   `progress_id` TEXT NOT NULL,
   `mikan_id` INTEGER NOT NULL,
   `episode` INTEGER NOT NULL,
   `torrent_name` TEXT NOT NULL,
   `progress_status` INTEGER NOT NULL
-);
\ No newline at end of file
+);
+
@@ -0,0 +1,7 @@
+DROP TABLE IF EXISTS "anime_task_upgrade";
+
+ALTER TABLE "anime_seed" DROP COLUMN "episode_end";
+ALTER TABLE "anime_seed" DROP COLUMN "publish_date";
+
+ALTER TABLE "anime_list" DROP COLUMN "source";
+ALTER TABLE "anime_list" DROP COLUMN "seed_mode";
@@ -0,0 +1,22 @@
+ALTER TABLE "anime_list" ADD COLUMN "seed_mode" INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE "anime_list" ADD COLUMN "source" TEXT NOT NULL DEFAULT 'mikan';
+
+ALTER TABLE "anime_seed" ADD COLUMN "publish_date" TEXT NOT NULL DEFAULT '';
+ALTER TABLE "anime_seed" ADD COLUMN "episode_end" INTEGER NOT NULL DEFAULT -1;
+
+-- ----------------------------
+-- Table structure for anime_task_upgrade
+-- ----------------------------
+CREATE TABLE "anime_task_upgrade" (
+  "id" INTEGER PRIMARY KEY AUTOINCREMENT,
+  "mikan_id" INTEGER NOT NULL,
+  "episode" INTEGER NOT NULL,
+  "old_torrent_name" TEXT NOT NULL,
+  "new_torrent_name" TEXT NOT NULL,
+  "old_filename" TEXT NOT NULL,
+  "new_filename" TEXT NOT NULL DEFAULT '',
+  "upgrade_status" INTEGER NOT NULL DEFAULT 0,
+  "reason" TEXT NOT NULL DEFAULT '',
+  "created_at" TEXT NOT NULL,
+  "finished_at" TEXT NOT NULL DEFAULT ''
+);
@@ -0,0 +1,12 @@
+-- ----------------------------
+-- Table structure for scheduler_job
+-- ----------------------------
+CREATE TABLE "scheduler_job" (
+  "id" INTEGER PRIMARY KEY AUTOINCREMENT,
+  "job_name" TEXT NOT NULL UNIQUE,
+  "schedule" TEXT NOT NULL,
+  "enabled" INTEGER NOT NULL DEFAULT 1,
+  "last_run" TEXT NOT NULL DEFAULT '',
+  "next_run" TEXT NOT NULL DEFAULT '',
+  "last_error" TEXT NOT NULL DEFAULT ''
+);
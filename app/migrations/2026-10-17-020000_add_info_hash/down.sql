@@ -0,0 +1,6 @@
+ALTER TABLE "anime_task_upgrade" DROP COLUMN "new_info_hash";
+ALTER TABLE "anime_task" DROP COLUMN "info_hash";
+
+ALTER TABLE "anime_seed" DROP COLUMN "file_list";
+ALTER TABLE "anime_seed" DROP COLUMN "total_size";
+ALTER TABLE "anime_seed" DROP COLUMN "info_hash";
@@ -0,0 +1,12 @@
+ALTER TABLE "anime_seed" ADD COLUMN "info_hash" TEXT NOT NULL DEFAULT '';
+ALTER TABLE "anime_seed" ADD COLUMN "total_size" BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE "anime_seed" ADD COLUMN "file_list" TEXT NOT NULL DEFAULT '[]';
+
+ALTER TABLE "anime_task" ADD COLUMN "info_hash" TEXT NOT NULL DEFAULT '';
+ALTER TABLE "anime_task_upgrade" ADD COLUMN "new_info_hash" TEXT NOT NULL DEFAULT '';
+
+-- mikan names the seeds "<info hash>.torrent", batch tasks add "#<episode>"
+UPDATE "anime_task" SET "info_hash" = lower(substr("torrent_name", 1, 40))
+  WHERE instr("torrent_name", '.torrent') = 41;
+UPDATE "anime_task_upgrade" SET "new_info_hash" = lower(substr("new_torrent_name", 1, 40))
+  WHERE instr("new_torrent_name", '.torrent') = 41;
@@ -0,0 +1,15 @@
+-- ----------------------------
+-- Table structure for download_job
+-- ----------------------------
+CREATE TABLE "download_job" (
+  "id" INTEGER PRIMARY KEY AUTOINCREMENT,
+  "seed_url" TEXT NOT NULL UNIQUE,
+  "mikan_id" INTEGER NOT NULL,
+  "episode" INTEGER NOT NULL,
+  "job_status" INTEGER NOT NULL DEFAULT 0,
+  "attempts" INTEGER NOT NULL DEFAULT 0,
+  "next_attempt" TEXT NOT NULL DEFAULT '',
+  "last_error" TEXT NOT NULL DEFAULT '',
+  "updated_at" TEXT NOT NULL DEFAULT ''
+);
+CREATE INDEX "download_job_status_next_attempt" ON "download_job" ("job_status", "next_attempt");
@@ -0,0 +1,6 @@
+ALTER TABLE "anime_list" DROP COLUMN "alt_names";
+ALTER TABLE "anime_list" DROP COLUMN "studio";
+ALTER TABLE "anime_list" DROP COLUMN "tags";
+ALTER TABLE "anime_list" DROP COLUMN "rating_votes";
+ALTER TABLE "anime_list" DROP COLUMN "rating_rank";
+ALTER TABLE "anime_list" DROP COLUMN "air_date";
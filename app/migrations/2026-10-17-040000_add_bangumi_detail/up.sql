@@ -0,0 +1,7 @@
+-- details from the bangumi v0 api, tags and alt_names are json
+ALTER TABLE "anime_list" ADD COLUMN "air_date" TEXT NOT NULL DEFAULT '';
+ALTER TABLE "anime_list" ADD COLUMN "rating_rank" INTEGER NOT NULL DEFAULT -1;
+ALTER TABLE "anime_list" ADD COLUMN "rating_votes" INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE "anime_list" ADD COLUMN "tags" TEXT NOT NULL DEFAULT '[]';
+ALTER TABLE "anime_list" ADD COLUMN "studio" TEXT NOT NULL DEFAULT '';
+ALTER TABLE "anime_list" ADD COLUMN "alt_names" TEXT NOT NULL DEFAULT '[]';
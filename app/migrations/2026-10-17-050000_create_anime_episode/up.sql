@@ -0,0 +1,13 @@
+-- ----------------------------
+-- Table structure for anime_episode
+-- ----------------------------
+CREATE TABLE "anime_episode" (
+  "id" INTEGER PRIMARY KEY AUTOINCREMENT,
+  "mikan_id" INTEGER NOT NULL,
+  "episode" INTEGER NOT NULL,
+  "episode_type" INTEGER NOT NULL DEFAULT 0,
+  "title" TEXT NOT NULL DEFAULT '',
+  "air_date" TEXT NOT NULL DEFAULT '',
+  "duration" INTEGER NOT NULL DEFAULT 0
+);
+CREATE UNIQUE INDEX "anime_episode_mikan_id_episode" ON "anime_episode" ("mikan_id", "episode", "episode_type");
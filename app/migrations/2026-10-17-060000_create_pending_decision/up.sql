@@ -0,0 +1,15 @@
+-- ----------------------------
+-- Table structure for pending_decision
+-- ----------------------------
+CREATE TABLE "pending_decision" (
+  "id" INTEGER PRIMARY KEY AUTOINCREMENT,
+  "mikan_id" INTEGER NOT NULL,
+  "episode" INTEGER NOT NULL,
+  "first_seen" TEXT NOT NULL,
+  "deadline" TEXT NOT NULL,
+  "seed_url" TEXT NOT NULL,
+  "seed_name" TEXT NOT NULL DEFAULT '',
+  "subgroup_id" INTEGER NOT NULL,
+  "updated_at" TEXT NOT NULL
+);
+CREATE UNIQUE INDEX "pending_decision_mikan_id_episode" ON "pending_decision" ("mikan_id", "episode");
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS "subgroup_release";
@@ -0,0 +1,28 @@
+-- ----------------------------
+-- Table structure for subgroup_release
+-- ----------------------------
+CREATE TABLE "subgroup_release" (
+  "id" INTEGER PRIMARY KEY AUTOINCREMENT,
+  "subgroup_id" INTEGER NOT NULL,
+  "mikan_id" INTEGER NOT NULL,
+  "episode" INTEGER NOT NULL,
+  "first_seen" TEXT NOT NULL,
+  "resolution" INTEGER NOT NULL DEFAULT 0,
+  "size" BIGINT NOT NULL DEFAULT 0
+);
+CREATE UNIQUE INDEX "subgroup_release_subgroup_id_mikan_id_episode" ON "subgroup_release" ("subgroup_id", "mikan_id", "episode");
+
+-- the releases of the seeds already stored, the resolution is guessed from the seed name
+INSERT INTO "subgroup_release" ("subgroup_id", "mikan_id", "episode", "first_seen", "resolution", "size")
+SELECT "subgroup_id", "mikan_id", "episode", MIN("publish_date"),
+  MAX(CASE
+    WHEN "seed_name" LIKE '%2160%' OR "seed_name" LIKE '%4K%' THEN 2160
+    WHEN "seed_name" LIKE '%1080%' THEN 1080
+    WHEN "seed_name" LIKE '%720%' THEN 720
+    WHEN "seed_name" LIKE '%480%' THEN 480
+    ELSE 0
+  END),
+  MAX("total_size")
+FROM "anime_seed"
+WHERE "episode_end" = -1 AND "publish_date" != ''
+GROUP BY "subgroup_id", "mikan_id", "episode";
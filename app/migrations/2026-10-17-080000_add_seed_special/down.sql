@@ -0,0 +1 @@
+ALTER TABLE "anime_seed" DROP COLUMN "is_special";
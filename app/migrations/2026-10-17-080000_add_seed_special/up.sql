@@ -0,0 +1,2 @@
+-- a special like "12.5" is kept with the episode it follows, e.g. 12
+ALTER TABLE "anime_seed" ADD COLUMN "is_special" INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+-- the legacy values are not restored
@@ -0,0 +1,11 @@
+-- rows written before the enums may hold values the typed columns can not load
+UPDATE "anime_list" SET "anime_type" = 0 WHERE "anime_type" NOT IN (0, 1, 2);
+UPDATE "anime_list" SET "anime_status" = -1 WHERE "anime_status" NOT IN (-1, 0, 1);
+
+-- mikan numbers sunday 0, the other days follow the anime_type
+UPDATE "anime_list" SET "update_day" = CASE "anime_type"
+    WHEN 1 THEN 8
+    WHEN 2 THEN 9
+    ELSE 7
+  END
+  WHERE "update_day" NOT BETWEEN 1 AND 9;
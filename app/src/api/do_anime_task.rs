@@ -1,9 +1,20 @@
+use crate::api::scheduler::TIME_FORMAT;
 use crate::api::spider_task::do_spider_task;
+use crate::error::error::handle_error;
+use crate::models::anime_episode::AnimeEpisode;
 use crate::models::anime_list::{AnimeList, AnimeListJson};
 use crate::models::anime_seed::AnimeSeed;
 use crate::models::anime_task::{AnimeTask, AnimeTaskJson};
-use crate::mods::config::Config;
-use crate::mods::{anime_filter, qb_api::QbitTaskExecutor, spider::Mikan, video_proccessor};
+use crate::models::anime_task_upgrade::AnimeTaskUpgradeJson;
+use crate::models::download_job::DownloadJob;
+use crate::models::enums::{
+    AnimeStatus, JobStatus, NewStatus, QbTaskStatus, RenameStatus, SubscribeStatus, TaskState,
+};
+use crate::mods::config::{Config, DownloadQueueConfig};
+use crate::mods::seed_source::{SeedSources, SOURCE_MIKAN};
+use crate::mods::spider::{self, Mikan};
+use crate::mods::torrent_client::{self, get_anime_dir, TorrentClient, TorrentFile};
+use crate::mods::{anime_filter, magnet, subgroup_stats, title_parser, video_proccessor};
 use crate::v2::anime::AnimeMikanIdReqJson;
 use crate::{dao, v2, WebData};
 use actix_web::web;
@@ -12,97 +23,166 @@ use anyhow::Error;
 use chrono::prelude::*;
 use diesel::r2d2::{ConnectionManager, PooledConnection};
 use diesel::SqliteConnection;
-use futures::future::join_all;
+use futures::stream::{self, StreamExt};
 use log;
-use once_cell::sync::Lazy;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::{self, File, OpenOptions};
 use std::io::{prelude::*, Read, Write};
 use std::path::Path;
 use std::sync::Arc;
-use tokio::sync::{watch, RwLock as TokioRwLock};
-use tokio::time::{self, sleep, Duration};
-
-pub fn handle_error<E: std::fmt::Debug>(e: E, message: &str) -> anyhow::Error {
-    log::error!("{}, error: {:?}", message, e);
-    Error::msg(format!("{:?}", e))
-}
-
-static AUTO_UPDATE_INTERVAL: Lazy<Arc<TokioRwLock<u64>>> =
-    Lazy::new(|| Arc::new(TokioRwLock::new(1800)));
-
-static INTERVAL_CHANNEL: Lazy<(watch::Sender<u64>, watch::Receiver<u64>)> = Lazy::new(|| {
-    let (tx, rx) = watch::channel(1800);
-    (tx, rx)
-});
+use tokio::sync::RwLock as TokioRwLock;
+use tokio::time::{sleep, Duration};
 
 #[allow(dead_code)]
 pub enum DownloadSeedStatus {
-    SUCCESS(AnimeSeed),
-    FAILED(AnimeSeed),
+    Success(AnimeSeed),
+    Failed(AnimeSeed, String), // the reason is kept by download_job
 }
 
 #[allow(dead_code)]
 pub async fn create_anime_task_bulk(
     mikan: &Mikan,
-    qb_task_executor: &QbitTaskExecutor,
+    qb_task_executor: &dyn TorrentClient,
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    queue_config: &DownloadQueueConfig,
 ) -> Result<(), Error> {
-    if !qb_task_executor.is_login {
-        log::warn!("qbittorrent client not started");
+    if !qb_task_executor.is_login() {
+        log::warn!("torrent client not started");
         return Ok(());
     }
 
     // 取出订阅的全部番剧列表
-    let anime_list_vec = dao::anime_list::get_by_subscribestatus(db_connection, 1)
-        .await
-        .unwrap();
+    let anime_list_vec =
+        dao::anime_list::get_by_subscribestatus(db_connection, SubscribeStatus::Subscribed).await?;
     log::debug!("anime list: {:?}", anime_list_vec);
 
     // 得到订阅的全部种子
     let mut anime_seed_map: HashMap<i32, Vec<AnimeSeed>> = HashMap::new();
     for anime_list in anime_list_vec {
         let ret_anime_seeds =
-            dao::anime_seed::get_anime_seed_by_mikan_id(db_connection, anime_list.mikan_id)
-                .await
-                .unwrap();
+            dao::anime_seed::get_episode_seed_by_mikan_id(db_connection, anime_list.mikan_id)
+                .await?;
         anime_seed_map.insert(anime_list.mikan_id, ret_anime_seeds);
     }
 
     // 过滤并下载
-    filter_and_download(&mikan, qb_task_executor, db_connection, anime_seed_map)
-        .await
-        .unwrap();
+    filter_and_download(
+        mikan,
+        qb_task_executor,
+        db_connection,
+        anime_seed_map,
+        queue_config,
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[allow(dead_code)]
+pub async fn create_upgrade_task_bulk(
+    mikan: &Mikan,
+    qb_task_executor: &dyn TorrentClient,
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    window_hours: i64,
+) -> Result<(), Error> {
+    if !qb_task_executor.is_login() {
+        log::warn!("torrent client not started");
+        return Ok(());
+    }
 
+    let anime_list_vec =
+        dao::anime_list::get_by_subscribestatus(db_connection, SubscribeStatus::Subscribed).await?;
+    let mut anime_seed_map: HashMap<i32, Vec<AnimeSeed>> = HashMap::new();
+    for anime_list in anime_list_vec {
+        let ret_anime_seeds =
+            dao::anime_seed::get_episode_seed_by_mikan_id(db_connection, anime_list.mikan_id)
+                .await?;
+        anime_seed_map.insert(anime_list.mikan_id, ret_anime_seeds);
+    }
+
+    let upgrade_vec = anime_filter::filter_upgrade(db_connection, anime_seed_map, window_hours)
+        .await
+        .map_err(|e| handle_error(e, "Failed to filter upgrade seeds"))?;
+
+    // the replacement is downloaded as an extra qb task, anime_task is switched after it finished
+    for (task, anime_seed) in upgrade_vec {
+        match download_seed_handler(anime_seed, mikan).await? {
+            DownloadSeedStatus::Success(anime_seed) => {
+                if let Err(e) = create_qb_task(qb_task_executor, db_connection, &anime_seed).await {
+                    log::warn!("Failed to create upgrade task: {}, {}", anime_seed.seed_name, e);
+                    continue;
+                }
+                dao::anime_seed::update_anime_seed_status(db_connection, &anime_seed.seed_url)
+                    .await?;
+                save_torrent_meta(db_connection, &anime_seed).await?;
+
+                // a batch task is named "<hash>.torrent#<episode>"
+                let old_torrent_name = spider::get_task_torrent_name(&task.torrent_name);
+                let old_seed_name = match dao::anime_seed::get_anime_seed_by_seed_url(
+                    db_connection,
+                    old_torrent_name,
+                )
+                .await
+                {
+                    Ok(old_seed) => old_seed.seed_name,
+                    Err(_) => old_torrent_name.to_string(),
+                };
+                dao::anime_task_upgrade::add(
+                    db_connection,
+                    &AnimeTaskUpgradeJson {
+                        mikan_id: task.mikan_id,
+                        episode: task.episode,
+                        old_torrent_name: task.torrent_name.clone(),
+                        new_torrent_name: spider::get_torrent_name(&anime_seed.seed_url),
+                        old_filename: task.filename.clone(),
+                        reason: format!("[{}] -> [{}]", old_seed_name, anime_seed.seed_name),
+                        new_info_hash: anime_seed.info_hash.clone(),
+                    },
+                )
+                .await?;
+            }
+            DownloadSeedStatus::Failed(anime_seed, e) => {
+                log::warn!(
+                    "Failed to download upgrade seed: {}, {}",
+                    anime_seed.seed_name,
+                    e
+                )
+            }
+        }
+    }
     Ok(())
 }
 
 #[allow(dead_code)]
 pub async fn create_anime_task_single(
     mikan: &Mikan,
-    qb_task_executor: &QbitTaskExecutor,
+    qb_task_executor: &dyn TorrentClient,
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
     mikan_id: i32,
     episode: i32, // anime_task_idx
+    queue_config: &DownloadQueueConfig,
 ) -> Result<(), Error> {
-    if !qb_task_executor.is_login {
-        log::warn!("qbittorrent client not started");
+    if !qb_task_executor.is_login() {
+        log::warn!("torrent client not started");
         return Ok(());
     }
 
     let anime_seed_vec =
-        dao::anime_seed::get_by_mikanid_and_episode(db_connection, mikan_id, episode)
-            .await
-            .unwrap();
+        dao::anime_seed::get_by_mikanid_and_episode(db_connection, mikan_id, episode).await?;
 
     let anime_seed_map = vec![(mikan_id, anime_seed_vec)].into_iter().collect();
 
-    filter_and_download(&mikan, &qb_task_executor, db_connection, anime_seed_map)
-        .await
-        .unwrap();
+    filter_and_download(
+        mikan,
+        qb_task_executor,
+        db_connection,
+        anime_seed_map,
+        queue_config,
+    )
+    .await?;
 
     Ok(())
 }
@@ -111,40 +191,69 @@ pub async fn create_anime_task_single(
 pub async fn create_anime_task_by_seed(
     mikan: &Mikan,
     anime_seed: AnimeSeed,
-    qb_task_executor: &QbitTaskExecutor,
+    qb_task_executor: &dyn TorrentClient,
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
 ) -> Result<(), Error> {
-    match download_seed_handler(anime_seed, mikan).await.unwrap() {
-        DownloadSeedStatus::SUCCESS(anime_seed) => {
-            let anime_task_info = AnimeTaskJson {
-                mikan_id: anime_seed.mikan_id.clone(),
-                episode: anime_seed.episode.clone(),
-                torrent_name: anime_seed
-                    .seed_url
-                    .rsplit("/")
-                    .next()
-                    .unwrap_or(&anime_seed.seed_url)
-                    .to_string(),
-                qb_task_status: 0,
-                rename_status: 0,
-                filename: "".to_string(),
-                is_new: 0,
-            };
-
-            match create_qb_task(&qb_task_executor, db_connection, &anime_seed).await {
+    match download_seed_handler(anime_seed, mikan).await? {
+        DownloadSeedStatus::Success(anime_seed) => {
+            match create_qb_task(qb_task_executor, db_connection, &anime_seed).await {
                 Ok(_) => {
-                    dao::anime_task::add(db_connection, &anime_task_info)
-                        .await
-                        .unwrap();
+                    save_torrent_meta(db_connection, &anime_seed).await?;
+                    for anime_task_info in get_anime_task_json_vec(&anime_seed) {
+                        dao::anime_task::add(db_connection, &anime_task_info).await?;
+                    }
                     Ok(())
                 }
                 Err(e) => Err(e),
             }
         }
-        DownloadSeedStatus::FAILED(_) => Err(Error::msg("Failed to download seed.")),
+        DownloadSeedStatus::Failed(_, e) => {
+            Err(Error::msg(format!("Failed to download seed: {}", e)))
+        }
     }
 }
 
+// one anime_task per episode, a batch seed holds episode..=episode_end
+pub fn get_anime_task_json_vec(anime_seed: &AnimeSeed) -> Vec<AnimeTaskJson> {
+    let torrent_name = spider::get_torrent_name(&anime_seed.seed_url);
+    let episodes: Vec<(i32, String)> = if anime_seed.episode_end == -1 {
+        vec![(anime_seed.episode, torrent_name)]
+    } else {
+        (anime_seed.episode..=anime_seed.episode_end)
+            .map(|ep| (ep, spider::get_batch_task_name(&torrent_name, ep)))
+            .collect()
+    };
+    episodes
+        .into_iter()
+        .map(|(episode, torrent_name)| AnimeTaskJson {
+            mikan_id: anime_seed.mikan_id,
+            episode,
+            torrent_name,
+            qb_task_status: QbTaskStatus::Downloading,
+            rename_status: RenameStatus::Pending,
+            filename: "".to_string(),
+            is_new: NewStatus::Watched,
+            info_hash: anime_seed.info_hash.clone(),
+        })
+        .collect()
+}
+
+// keep what was parsed from the seed file, see download_seed_handler
+pub async fn save_torrent_meta(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    anime_seed: &AnimeSeed,
+) -> Result<(), Error> {
+    dao::anime_seed::update_torrent_meta(
+        db_connection,
+        &anime_seed.seed_url,
+        &anime_seed.info_hash,
+        anime_seed.total_size,
+        &anime_seed.file_list,
+    )
+    .await?;
+    Ok(())
+}
+
 pub async fn get_video_config(
     download_path: &str,
 ) -> Result<(File, HashMap<String, VideoConfig>), Error> {
@@ -180,9 +289,12 @@ pub async fn create_anime_task_from_exist_files(
 ) -> Result<(), Error> {
     let video_file_lock = video_file_lock.read().await;
 
-    let download_path = {
+    let (download_path, scraper_config) = {
         let config_unlock = config.read().await;
-        config_unlock.download_path.clone()
+        (
+            config_unlock.download_path.clone(),
+            config_unlock.scraper.clone(),
+        )
     };
 
     let (_, video_config) = get_video_config(&download_path)
@@ -201,7 +313,7 @@ pub async fn create_anime_task_from_exist_files(
         }
 
         let mikan_id = match Regex::new(r"\((\d+)\)")?.captures(&filename) {
-            Some(captures) => captures.get(1).unwrap().as_str().parse::<i32>().unwrap(),
+            Some(captures) => captures.get(1).unwrap().as_str().parse::<i32>()?,
             None => {
                 log::info!("Failed to get mikan id, file name: [{}]", filename);
                 continue;
@@ -218,7 +330,8 @@ pub async fn create_anime_task_from_exist_files(
         }
 
         // Update anime list
-        let mikan = Mikan::new().unwrap();
+        let mikan = Mikan::new(&scraper_config)
+            .map_err(|e| handle_error(e, "Failed to create mikan client"))?;
         let anime = match mikan.get_anime_by_mikan_id(mikan_id).await {
             Ok(res) => res,
             Err(e) => {
@@ -241,9 +354,11 @@ pub async fn create_anime_task_from_exist_files(
                 bangumi_rank: "".to_string(),
                 bangumi_summary: "".to_string(),
                 website: "".to_string(),
-                anime_status: -1,
+                anime_status: AnimeStatus::Unknown,
                 total_episodes: -1,
                 new_finished_episode: 0,
+                seed_mode: 0,
+                source: SOURCE_MIKAN.to_string(),
             },
         )
         .await?;
@@ -253,7 +368,10 @@ pub async fn create_anime_task_from_exist_files(
             let config = config.read().await;
             config.img_path.clone()
         };
-        mikan.download_img(&img_url, &save_path).await.unwrap();
+        mikan
+            .download_img(&img_url, &save_path)
+            .await
+            .map_err(|e| handle_error(e, "Failed to download anime image"))?;
 
         // Update anime seed
         if v2::anime::seed_update(web_data.clone(), web::Json(AnimeMikanIdReqJson { mikan_id }))
@@ -300,14 +418,24 @@ pub async fn create_anime_task_from_exist_files(
                 }
             };
 
+            // the torrent is gone from the client already, the hash only comes from a parsed seed
+            let info_hash = dao::anime_seed::get_anime_seed_by_seed_url(
+                db_connection,
+                spider::get_task_torrent_name(&cur_video_config.torrent_name),
+            )
+            .await
+            .map(|seed| seed.info_hash)
+            .unwrap_or_default();
+
             let anime_task = AnimeTaskJson {
                 mikan_id,
                 episode: cur_video_config.episode,
                 torrent_name: cur_video_config.torrent_name.clone(),
-                qb_task_status: 1,
-                rename_status: 1,
+                qb_task_status: QbTaskStatus::Finished,
+                rename_status: RenameStatus::Renamed,
                 filename: video.clone(),
-                is_new: 0,
+                is_new: NewStatus::Watched,
+                info_hash,
             };
 
             if let Err(e) = dao::anime_task::add(db_connection, &anime_task).await {
@@ -325,7 +453,7 @@ pub async fn create_anime_task_from_exist_files(
 
             match dao::anime_seed::update_anime_seed_status(
                 db_connection,
-                &cur_video_config.torrent_name,
+                spider::get_task_torrent_name(&cur_video_config.torrent_name),
             )
             .await
             {
@@ -345,110 +473,190 @@ pub async fn create_anime_task_from_exist_files(
 #[allow(dead_code)]
 pub async fn filter_and_download(
     mikan: &Mikan,
-    qb_task_executor: &QbitTaskExecutor,
+    qb_task_executor: &dyn TorrentClient,
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
-    anime_seed_map: HashMap<i32, Vec<AnimeSeed>>,
+    mut anime_seed_map: HashMap<i32, Vec<AnimeSeed>>,
+    queue_config: &DownloadQueueConfig,
 ) -> Result<(), Error> {
-    let anime_task_set = dao::anime_task::get_exist_anime_task_set(db_connection)
-        .await
-        .unwrap();
+    let mut anime_task_set = dao::anime_task::get_exist_anime_task_set(db_connection).await?;
+
+    // 排队中的种子占住它的集数, 失败多次的种子不再选用
+    let job_map: HashMap<String, JobStatus> = dao::download_job::get_unfinished(db_connection)
+        .await?
+        .into_iter()
+        .map(|job| (job.seed_url, job.job_status))
+        .collect();
+    for anime_seed_vec in anime_seed_map.values_mut() {
+        anime_seed_vec.retain(|anime_seed| match job_map.get(&anime_seed.seed_url) {
+            Some(JobStatus::Dead) => false,
+            Some(_) => {
+                for episode in anime_seed.episode..=anime_seed.episode.max(anime_seed.episode_end) {
+                    anime_task_set.insert((anime_seed.mikan_id, episode));
+                }
+                false
+            }
+            None => true,
+        });
+    }
 
     // 过滤出新种子
-    let new_anime_seed_vec = anime_filter::filter_v3(db_connection, anime_seed_map, anime_task_set)
-        .await
-        .unwrap();
+    let new_anime_seed_vec =
+        anime_filter::filter_v3(db_connection, anime_seed_map, anime_task_set).await?;
 
     log::debug!("new anime seed: {:?}", new_anime_seed_vec);
-    // println!("new_anime_seed_vec: {:?}", new_anime_seed_vec);
 
-    // 下载种子
-    let mut download_success_vec: Vec<AnimeSeed> = Vec::new();
-    let mut download_failed_vec: Vec<AnimeSeed> = Vec::new();
+    // 加入下载队列
+    let now = Local::now().format(TIME_FORMAT).to_string();
+    for anime_seed in &new_anime_seed_vec {
+        dao::download_job::enqueue(db_connection, anime_seed, &now).await?;
+    }
 
-    if new_anime_seed_vec.len() > 0 {
-        let task_res_vec = join_all(
-            new_anime_seed_vec
-                .into_iter()
-                .map(|anime_seed| download_seed_handler(anime_seed, &mikan)),
-        )
-        .await;
+    process_download_queue(mikan, qb_task_executor, db_connection, queue_config).await
+}
 
-        for task_res in task_res_vec {
-            match task_res {
-                Ok(status) => match status {
-                    DownloadSeedStatus::SUCCESS(anime_seed) => {
-                        download_success_vec.push(anime_seed)
-                    }
-                    DownloadSeedStatus::FAILED(anime_seed) => download_failed_vec.push(anime_seed),
-                },
-                Err(_) => continue,
+pub async fn retry_download_queue(
+    qb_task_executor: &Arc<TokioRwLock<Box<dyn TorrentClient>>>,
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    config: &Arc<TokioRwLock<Config>>,
+) -> Result<(), Error> {
+    let qb = qb_task_executor.read().await;
+    if !qb.is_login() {
+        return Ok(());
+    }
+    let (queue_config, scraper_config) = {
+        let config = config.read().await;
+        (config.download_queue.clone(), config.scraper.clone())
+    };
+    let mikan = Mikan::new(&scraper_config)
+        .map_err(|e| handle_error(e, "Failed to create mikan client"))?;
+    process_download_queue(&mikan, qb.as_ref(), db_connection, &queue_config).await
+}
+
+// fetch the due seeds of download_job with at most queue_config.workers at a time
+pub async fn process_download_queue(
+    mikan: &Mikan,
+    qb_task_executor: &dyn TorrentClient,
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    queue_config: &DownloadQueueConfig,
+) -> Result<(), Error> {
+    let now = Local::now().format(TIME_FORMAT).to_string();
+    let mut claimed_vec: Vec<(DownloadJob, AnimeSeed)> = Vec::new();
+    for job in dao::download_job::get_due(db_connection, &now).await? {
+        let job_id = job.id.unwrap_or_default();
+        if !dao::download_job::claim(db_connection, job_id, &now).await? {
+            continue;
+        }
+        match dao::anime_seed::get_anime_seed_by_seed_url(db_connection, &job.seed_url).await {
+            Ok(anime_seed) => claimed_vec.push((job, anime_seed)),
+            Err(_) => {
+                dao::download_job::mark_failed(db_connection, job_id, "seed not found", None, &now)
+                    .await?
             }
         }
     }
-    log::debug!("download failed vec: {:?}", download_failed_vec);
-
-    // 更新 anime_seed table
-    let mut anime_task_info_vec: Vec<AnimeTaskJson> = Vec::new();
-    for anime_seed in &download_success_vec {
-        dao::anime_seed::update_anime_seed_status(db_connection, &anime_seed.seed_url)
-            .await
-            .unwrap();
+    if claimed_vec.is_empty() {
+        return Ok(());
+    }
 
-        anime_task_info_vec.push(AnimeTaskJson {
-            mikan_id: anime_seed.mikan_id.clone(),
-            episode: anime_seed.episode.clone(),
-            torrent_name: anime_seed
-                .seed_url
-                .rsplit("/")
-                .next()
-                .unwrap_or(&anime_seed.seed_url)
-                .to_string(),
-            qb_task_status: 0,
-            rename_status: 0,
-            filename: "".to_string(),
-            is_new: 0,
-        })
+    // 下载种子
+    let task_res_vec: Vec<(DownloadJob, Result<DownloadSeedStatus, Error>)> =
+        stream::iter(claimed_vec)
+            .map(|(job, anime_seed)| async move {
+                (job, download_seed_handler(anime_seed, mikan).await)
+            })
+            .buffer_unordered(queue_config.workers.max(1))
+            .collect()
+            .await;
+
+    let now = Local::now().format(TIME_FORMAT).to_string();
+    let mut download_success_vec: Vec<(DownloadJob, AnimeSeed)> = Vec::new();
+    for (job, task_res) in task_res_vec {
+        let error = match task_res {
+            Ok(DownloadSeedStatus::Success(anime_seed)) => {
+                download_success_vec.push((job, anime_seed));
+                continue;
+            }
+            Ok(DownloadSeedStatus::Failed(_, e)) => e,
+            Err(e) => e.to_string(),
+        };
+        mark_download_job_failed(db_connection, &job, &error, queue_config, &now).await?;
     }
 
-    // 插入 anime_task
-    dao::anime_task::add_bulk(db_connection, &anime_task_info_vec)
-        .await
-        .unwrap();
+    // a job is done once its torrent is added, otherwise it is retried like a failed download
+    for (job, anime_seed) in &download_success_vec {
+        if let Err(e) = create_qb_task(qb_task_executor, db_connection, anime_seed).await {
+            mark_download_job_failed(db_connection, job, &e.to_string(), queue_config, &now)
+                .await?;
+            continue;
+        }
 
-    // 添加到qb
-    for anime_seed in &download_success_vec {
-        create_qb_task(&qb_task_executor, db_connection, anime_seed)
-            .await
-            .unwrap();
+        // 更新 anime_seed table
+        dao::anime_seed::update_anime_seed_status(db_connection, &anime_seed.seed_url).await?;
+        save_torrent_meta(db_connection, anime_seed).await?;
+        dao::download_job::mark_done(db_connection, job.id.unwrap_or_default(), &now).await?;
+
+        // 插入 anime_task
+        dao::anime_task::add_bulk(db_connection, &get_anime_task_json_vec(anime_seed)).await?;
+    }
+    Ok(())
+}
+
+// back to pending after the backoff of queue_config, dead after the last attempt
+async fn mark_download_job_failed(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    job: &DownloadJob,
+    error: &str,
+    queue_config: &DownloadQueueConfig,
+    now: &str,
+) -> Result<(), Error> {
+    let attempts = job.attempts + 1;
+    let next_attempt = queue_config.backoff(attempts).map(|secs| {
+        (Local::now() + chrono::Duration::seconds(secs))
+            .format(TIME_FORMAT)
+            .to_string()
+    });
+    match &next_attempt {
+        Some(next_attempt) => log::warn!(
+            "Failed to download seed {}, attempt {}, retry at {}: {}",
+            job.seed_url,
+            attempts,
+            next_attempt,
+            error
+        ),
+        None => log::error!(
+            "Failed to download seed {} after {} attempts: {}",
+            job.seed_url,
+            attempts,
+            error
+        ),
     }
+    dao::download_job::mark_failed(
+        db_connection,
+        job.id.unwrap_or_default(),
+        error,
+        next_attempt.as_deref(),
+        now,
+    )
+    .await?;
     Ok(())
 }
 
 #[allow(dead_code)]
 pub async fn create_qb_task(
-    qb_task_executor: &QbitTaskExecutor,
+    qb_task_executor: &dyn TorrentClient,
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
     anime_seed: &AnimeSeed,
 ) -> Result<(), Error> {
     let anime_name = dao::anime_list::get_by_mikanid(db_connection, anime_seed.mikan_id.clone())
-        .await
-        .unwrap()
+        .await?
         .anime_name;
+    let speed_limit = dao::anime_filter::get_speed_limit(anime_seed.mikan_id, db_connection).await?;
 
     match qb_task_executor
-        .qb_api_add_torrent(&anime_name, &anime_seed)
+        .add_torrent(&anime_name, anime_seed, speed_limit)
         .await
     {
-        Ok(_) => {
-            {
-                let mut interval = AUTO_UPDATE_INTERVAL.write().await;
-                *interval = 5;
-                log::info!("Interval set to 5 after task creation: {:?}", interval);
-                let _ = INTERVAL_CHANNEL.0.send(*interval);
-            }
-            Ok(())
-        }
-
+        Ok(_) => Ok(()),
         Err(e) => {
             log::warn!("failed to create qb task, err: {}", e);
             Err(Error::from(e))
@@ -459,14 +667,12 @@ pub async fn create_qb_task(
 // This is a ugly failure.
 #[allow(dead_code)]
 pub async fn update_qb_task_status(
-    qb_task_executor: &QbitTaskExecutor,
+    qb_task_executor: &dyn TorrentClient,
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
 ) -> Result<(), Error> {
-    if let Ok(fn_task_vec) = qb_task_executor.qb_api_completed_torrent_list().await {
+    if let Ok(fn_task_vec) = qb_task_executor.completed_torrent_set().await {
         for fn_task in fn_task_vec {
-            dao::anime_task::update_qb_task_status(db_connection, fn_task)
-                .await
-                .unwrap();
+            dao::anime_task::update_qb_task_status_by_info_hash(db_connection, &fn_task).await?;
         }
     } else {
         log::warn!("failed to get finished torrent")
@@ -474,12 +680,20 @@ pub async fn update_qb_task_status(
     Ok(())
 }
 
+// the seed is parsed right after it is downloaded, a seed which fails to parse is not used
 pub async fn download_seed_handler(
-    anime_seed: AnimeSeed,
+    mut anime_seed: AnimeSeed,
     mikan: &Mikan,
 ) -> Result<DownloadSeedStatus, Error> {
     log::info!("processing {}", anime_seed.seed_name);
     // println!("processing {}", anime_seed.seed_name);
+
+    // a magnet has nothing to download, its files are known once the torrent client resolved it
+    if let Some(info_hash) = magnet::get_info_hash(&anime_seed.seed_url) {
+        anime_seed.info_hash = info_hash;
+        return Ok(DownloadSeedStatus::Success(anime_seed));
+    }
+
     match mikan
         .download_seed(
             &anime_seed.seed_url,
@@ -487,8 +701,19 @@ pub async fn download_seed_handler(
         )
         .await
     {
-        Ok(_) => Ok(DownloadSeedStatus::SUCCESS(anime_seed)),
-        Err(_) => Ok(DownloadSeedStatus::FAILED(anime_seed)),
+        Ok(_) => match torrent_client::read_torrent_meta(&anime_seed) {
+            Ok(meta) => {
+                anime_seed.info_hash = meta.info_hash;
+                anime_seed.total_size = meta.total_size;
+                anime_seed.file_list = serde_json::to_string(&meta.files)?;
+                Ok(DownloadSeedStatus::Success(anime_seed))
+            }
+            Err(e) => {
+                log::warn!("Failed to parse seed {}, {}", anime_seed.seed_name, e);
+                Ok(DownloadSeedStatus::Failed(anime_seed, format!("invalid seed file: {}", e)))
+            }
+        },
+        Err(e) => Ok(DownloadSeedStatus::Failed(anime_seed, e.to_string())),
     }
 }
 
@@ -496,15 +721,19 @@ pub async fn download_seed_handler(
 pub async fn get_under_update_task_list(
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
 ) -> Result<(), Error> {
-    let new_sub_annime_vec =
-        dao::anime_list::get_by_subscribe_and_anime_status(&1, &-1, db_connection)
-            .await
-            .unwrap();
+    let new_sub_annime_vec = dao::anime_list::get_by_subscribe_and_anime_status(
+        &SubscribeStatus::Subscribed,
+        &AnimeStatus::Unknown,
+        db_connection,
+    )
+    .await?;
 
-    let mut subscribed_anime_vec =
-        dao::anime_list::get_by_subscribe_and_anime_status(&1, &0, db_connection)
-            .await
-            .unwrap();
+    let mut subscribed_anime_vec = dao::anime_list::get_by_subscribe_and_anime_status(
+        &SubscribeStatus::Subscribed,
+        &AnimeStatus::Airing,
+        db_connection,
+    )
+    .await?;
 
     subscribed_anime_vec.extend(new_sub_annime_vec);
 
@@ -515,7 +744,7 @@ pub async fn get_under_update_task_list(
     let under_update_task_list: Vec<AnimeList> = subscribed_anime_vec
         .into_iter()
         .filter(|anime_list| {
-            let update_day = anime_list.update_day;
+            let update_day = i32::from(anime_list.update_day);
             update_day == weekday || update_day - 1 == weekday || update_day + 1 == weekday
         })
         .collect();
@@ -529,191 +758,258 @@ pub async fn get_under_update_task_list(
 
 #[allow(dead_code)]
 pub async fn run(
-    qb_task_executor: &Arc<TokioRwLock<QbitTaskExecutor>>,
+    qb_task_executor: &Arc<TokioRwLock<Box<dyn TorrentClient>>>,
+    config: &Arc<TokioRwLock<Config>>,
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
-) {
+) -> Result<(), Error> {
     // spider_task
-    let mikan = Mikan::new().unwrap();
-    let subscribed_anime_vec = dao::anime_list::get_by_subscribestatus(db_connection, 1)
-        .await
-        .unwrap();
+    let sources = {
+        let config = config.read().await;
+        SeedSources::new(&config).map_err(|e| handle_error(e, "Failed to create seed sources"))?
+    };
+    let mikan = sources.mikan.clone();
+    let subscribed_anime_vec =
+        dao::anime_list::get_by_subscribestatus(db_connection, SubscribeStatus::Subscribed)
+            .await?;
 
-    let st_anime_vec = do_spider_task(&mikan, subscribed_anime_vec, db_connection).await;
+    let st_anime_vec = do_spider_task(&sources, subscribed_anime_vec, db_connection).await;
+    subgroup_stats::record_releases(db_connection, &st_anime_vec).await?;
     let _new_seed_vec = dao::anime_seed::add_bulk_with_response(db_connection, st_anime_vec)
-        .await
-        .unwrap()
+        .await?
         .success_vec;
 
     log::debug!("Create anime task start");
+    let (upgrade_policy, queue_config) = {
+        let config = config.read().await;
+        (
+            config.anime_config.upgrade_policy.clone(),
+            config.download_queue.clone(),
+        )
+    };
     let qb = qb_task_executor.read().await;
-    create_anime_task_bulk(&mikan, &qb, db_connection)
-        .await
-        .unwrap();
+    create_anime_task_bulk(&mikan, qb.as_ref(), db_connection, &queue_config).await?;
+
+    if upgrade_policy.enable {
+        if let Err(e) =
+            create_upgrade_task_bulk(&mikan, qb.as_ref(), db_connection, upgrade_policy.window_hours).await
+        {
+            log::warn!("Failed to create upgrade task, err: {}", e);
+        }
+    }
     drop(qb);
     log::debug!("Create anime task done");
+    Ok(())
 }
 
-#[allow(dead_code)]
-pub async fn run_task(
-    status: &Arc<TokioRwLock<bool>>,
-    qb_task_executor: &Arc<TokioRwLock<QbitTaskExecutor>>,
+// refresh the bangumi rank, summary and total episodes of the subscribed anime
+pub async fn refresh_bangumi_info(
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
-) {
-    let interval = 120;
-    {
-        let mut writer = status.write().await;
-        *writer = true;
-    }
-    log::debug!("Start scheduled task");
-    loop {
-        let reader = status.read().await;
-        let val = *reader;
-        drop(reader);
-
-        if val {
-            log::debug!("Running scheduled task with interval 2 min");
-            run(qb_task_executor, db_connection).await;
-            time::sleep(Duration::from_secs(interval)).await;
-        } else {
-            break;
+    config: &Arc<TokioRwLock<Config>>,
+) -> Result<(), Error> {
+    let scraper_config = config.read().await.scraper.clone();
+    let mikan = Mikan::new(&scraper_config)
+        .map_err(|e| handle_error(e, "Failed to create mikan client"))?;
+    let anime_vec =
+        dao::anime_list::get_by_subscribestatus(db_connection, SubscribeStatus::Subscribed).await?;
+
+    // bangumi info is only linked from mikan pages
+    for anime in anime_vec.iter().filter(|anime| anime.source == SOURCE_MIKAN) {
+        if let Err(e) =
+            v2::anime::update_bangumi_info(db_connection, &mikan, &scraper_config, anime.mikan_id)
+                .await
+        {
+            log::warn!("Failed to refresh bangumi info of {}, err: {}", anime.anime_name, e);
         }
     }
+    Ok(())
 }
 
-#[allow(dead_code)]
-pub async fn exit_task(status: &Arc<TokioRwLock<bool>>) {
-    log::debug!("Stop scheduled task");
-    let mut writer = status.write().await;
-    *writer = false;
-    log::debug!("Task status has been changed to false");
-    // println!("{}", writer);
+// an aired episode of a subscribed anime without a task or a queued seed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EpisodeGap {
+    pub mikan_id: i32,
+    pub anime_name: String,
+    pub episode: i32,
+    pub title: String,    // from bangumi, empty when unknown
+    pub air_date: String, // "%Y-%m-%d", empty when unknown
 }
 
-pub async fn change_task_interval(
-    interval: i32,
-    status: &Arc<TokioRwLock<bool>>,
-    qb_task_executor: &Arc<TokioRwLock<QbitTaskExecutor>>,
-    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
-) {
-    let interval_sec = interval as u64 * 60;
-    {
-        let mut writer = status.write().await;
-        *writer = true;
-    }
-    log::debug!("Start scheduled task");
-    loop {
-        let reader = status.read().await;
-        let val = *reader;
-        drop(reader);
-
-        if val {
-            log::debug!("Running scheduled task with interval: {} min", interval);
-            run(qb_task_executor, db_connection).await;
-            time::sleep(Duration::from_secs(interval_sec)).await;
-        } else {
-            break;
-        }
+/*
+    Episodes which should have been released by today. With air dates from bangumi
+    these are the main episodes aired before today, so the subgroups get a day;
+    otherwise every episode of a finished anime, or the ones up to the last episode
+    which has been downloaded.
+*/
+pub fn aired_episodes(
+    anime: &AnimeList,
+    main_episode_vec: &[AnimeEpisode],
+    last_episode: i32,
+    today: &str,
+) -> Vec<i32> {
+    if main_episode_vec.iter().any(|ep| !ep.air_date.is_empty()) {
+        return main_episode_vec
+            .iter()
+            .filter(|ep| !ep.air_date.is_empty() && ep.air_date.as_str() < today)
+            .map(|ep| ep.episode)
+            .collect();
     }
+    let last_episode = if anime.total_episodes <= 0 {
+        last_episode
+    } else if anime.anime_status == AnimeStatus::Finished {
+        anime.total_episodes
+    } else {
+        last_episode.min(anime.total_episodes)
+    };
+    (1..=last_episode).collect()
 }
 
-#[allow(dead_code)]
-pub async fn get_task_status(status: &Arc<TokioRwLock<bool>>) -> Result<bool, Error> {
-    let reader = status.read().await;
-    let val = *reader;
-    drop(reader);
-    Ok(val)
-}
+// compares the aired episodes of the subscribed anime with anime_task and download_job
+pub async fn find_episode_gaps(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    today: &str,
+) -> Result<Vec<EpisodeGap>, Error> {
+    let mut gap_vec: Vec<EpisodeGap> = Vec::new();
+    let anime_vec =
+        dao::anime_list::get_by_subscribestatus(db_connection, SubscribeStatus::Subscribed).await?;
+
+    for anime in anime_vec {
+        let mikan_id = anime.mikan_id;
+        let mut taken_set: HashSet<i32> =
+            dao::anime_task::get_exist_anime_task_set_by_mikanid(db_connection, mikan_id)
+                .await?
+                .into_iter()
+                .map(|(_, episode)| episode)
+                .collect();
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct VideoConfig {
-    pub torrent_name: String,
-    pub mikan_id: i32,
-    pub episode: i32,
-    pub subtitle_nb: i32,
-    pub subtitle: Vec<String>,
-}
+        // a queued batch holds all of its episodes
+        let seed_map: HashMap<String, AnimeSeed> =
+            dao::anime_seed::get_anime_seed_by_mikan_id(db_connection, mikan_id)
+                .await?
+                .into_iter()
+                .map(|anime_seed| (anime_seed.seed_url.clone(), anime_seed))
+                .collect();
+        for job in dao::download_job::get_by_mikan_id(db_connection, mikan_id).await? {
+            if matches!(job.job_status, JobStatus::Pending | JobStatus::Running) {
+                let episode_end = seed_map
+                    .get(&job.seed_url)
+                    .map_or(job.episode, |s| s.episode_end.max(job.episode));
+                taken_set.extend(job.episode..=episode_end);
+            }
+        }
+        // a held episode is waiting for a preferred subgroup, not missing
+        for pending in dao::pending_decision::get_by_mikan_id(db_connection, mikan_id).await? {
+            taken_set.insert(pending.episode);
+        }
 
-#[allow(dead_code)]
-pub async fn auto_update_rename_extract(
-    video_file_lock: &Arc<TokioRwLock<bool>>,
-    pool: &diesel::r2d2::Pool<ConnectionManager<diesel::SqliteConnection>>,
-    qb_task_executor: &Arc<TokioRwLock<QbitTaskExecutor>>,
-    config: &Arc<TokioRwLock<Config>>
-) -> Result<(), Error> {
-    log::info!("Start auto rename and update thread");
-    let mut lazy_mode_cnt = 0;
-    let mut interval_rx = INTERVAL_CHANNEL.1.clone();
+        let main_episode_vec =
+            dao::anime_episode::get_main_by_mikan_id(db_connection, mikan_id).await?;
+        let episode_filter =
+            dao::anime_filter::get_local_episode_filter_by_mikan_id(&mikan_id, db_connection)
+                .await?;
+        let last_episode = taken_set.iter().max().cloned().unwrap_or(0);
 
-    loop {
-        {
-            let mut db_connection = pool.get().unwrap();
-
-            match auto_update_handler(qb_task_executor, &mut db_connection).await {
-                Ok(nb_new_finished_task) if nb_new_finished_task >= 0 => {
-                    auto_rename_and_extract_handler(
-                        video_file_lock,
-                        qb_task_executor,
-                        &mut db_connection,
-                        config
-                    )
-                    .await
-                    .map_err(|e| handle_error(e, "Failed to execute rename task"))?;
-                }
-                Err(e) => {
-                    handle_error(e, "Failed to get finished task");
-                }
-                _ => {}
+        for episode in aired_episodes(&anime, &main_episode_vec, last_episode, today) {
+            if episode < episode_filter || taken_set.contains(&episode) {
+                continue;
             }
+            let main_episode = main_episode_vec.iter().find(|ep| ep.episode == episode);
+            gap_vec.push(EpisodeGap {
+                mikan_id,
+                anime_name: anime.anime_name.clone(),
+                episode,
+                title: main_episode.map_or(String::new(), |ep| ep.title.clone()),
+                air_date: main_episode.map_or(String::new(), |ep| ep.air_date.clone()),
+            });
         }
+    }
+    Ok(gap_vec)
+}
 
-        let mut interval = {
-            let read_guard = AUTO_UPDATE_INTERVAL.read().await;
-            *read_guard
-        };
-
-        // log::debug!("Current auto-update interval: {:?}", interval);
+// queues a seed never tried before for every gap, skipping the avoided subgroups
+pub async fn backfill_episode_gaps(
+    qb_task_executor: &Arc<TokioRwLock<Box<dyn TorrentClient>>>,
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    config: &Arc<TokioRwLock<Config>>,
+) -> Result<(), Error> {
+    let qb = qb_task_executor.read().await;
+    if !qb.is_login() {
+        return Ok(());
+    }
+    let (queue_config, scraper_config) = {
+        let config = config.read().await;
+        (config.download_queue.clone(), config.scraper.clone())
+    };
+    let mikan = Mikan::new(&scraper_config)
+        .map_err(|e| handle_error(e, "Failed to create mikan client"))?;
 
-        if interval == 5 {
-            lazy_mode_cnt += 1;
-            if lazy_mode_cnt >= 360 {
-                let mut write_guard = AUTO_UPDATE_INTERVAL.write().await;
-                *write_guard = 1800;
-                interval = 1800;
-                log::info!("Interval reset to 1800 due to lazy mode count reaching threshold.");
+    let today = Local::now().format("%Y-%m-%d").to_string();
+    let mut gap_map: HashMap<i32, HashSet<i32>> = HashMap::new();
+    for gap in find_episode_gaps(db_connection, &today).await? {
+        gap_map.entry(gap.mikan_id).or_default().insert(gap.episode);
+    }
 
-                let _ = INTERVAL_CHANNEL.0.send(*write_guard);
-            }
-        } else {
-            lazy_mode_cnt = 0;
-        }
+    let now = Local::now().format(TIME_FORMAT).to_string();
+    for (mikan_id, gap_set) in gap_map {
+        let tried_set: HashSet<String> =
+            dao::download_job::get_by_mikan_id(db_connection, mikan_id)
+                .await?
+                .into_iter()
+                .map(|job| job.seed_url)
+                .collect();
+        let anime_seed_vec =
+            dao::anime_seed::get_episode_seed_by_mikan_id(db_connection, mikan_id).await?;
+        let picked_vec = anime_filter::filter_gap(
+            db_connection,
+            mikan_id,
+            anime_seed_vec,
+            &gap_set,
+            &tried_set,
+        )
+        .await?;
 
-        tokio::select! {
-            _ = sleep(Duration::from_secs(interval)) => {
-            },
-            _ = interval_rx.changed() => {
-                log::info!("Interval has been updated, restarting sleep.");
-            }
+        log::info!(
+            "backfill {} of {} missing episodes of {}",
+            picked_vec.len(),
+            gap_set.len(),
+            mikan_id
+        );
+        for anime_seed in &picked_vec {
+            dao::download_job::enqueue(db_connection, anime_seed, &now).await?;
         }
     }
+
+    process_download_queue(&mikan, qb.as_ref(), db_connection, &queue_config).await
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VideoConfig {
+    pub torrent_name: String,
+    pub mikan_id: i32,
+    pub episode: i32,
+    pub subtitle_nb: i32,
+    pub subtitle: Vec<String>,
 }
 
 pub async fn auto_update_handler(
-    qb_task_executor: &Arc<TokioRwLock<QbitTaskExecutor>>,
+    qb_task_executor: &Arc<TokioRwLock<Box<dyn TorrentClient>>>,
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
 ) -> Result<i32, Error> {
     let qb = qb_task_executor.read().await;
+    resolve_magnet_metadata(qb.as_ref(), db_connection).await?;
+
     let finished_task_set = qb
-        .qb_api_completed_torrent_set()
+        .completed_torrent_set()
         .await
         .map_err(|e| handle_error(e, "Failed to get finished task list"))?;
-    let under_update_task_list = dao::anime_task::get_by_qbtaskstatus(db_connection, 0)
-        .await
-        .map_err(|e| handle_error(e, "Failed to get under update task list"))?;
+    let under_update_task_list =
+        dao::anime_task::get_by_qbtaskstatus(db_connection, QbTaskStatus::Downloading)
+            .await
+            .map_err(|e| handle_error(e, "Failed to get under update task list"))?;
 
     let mut task_cnt = 0;
 
     for task in under_update_task_list {
-        if finished_task_set.contains(&task.torrent_name) {
+        if finished_task_set.contains(&task.info_hash) {
             // println!("{}", task.torrent_name);
             dao::anime_task::update_qb_task_status(db_connection, task.torrent_name.to_string())
                 .await
@@ -736,43 +1032,86 @@ pub async fn auto_update_handler(
     Ok(task_cnt)
 }
 
+// save the file list of magnet seeds once the torrent client fetched their metadata
+pub async fn resolve_magnet_metadata(
+    qb_task_executor: &dyn TorrentClient,
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+) -> Result<(), Error> {
+    let seed_vec = dao::anime_seed::get_unresolved_magnet_seeds(db_connection)
+        .await
+        .map_err(|e| handle_error(e, "Failed to get unresolved magnet seeds"))?;
+
+    for mut anime_seed in seed_vec {
+        let torrent_files = match qb_task_executor.torrent_files(&anime_seed.info_hash).await {
+            Ok(torrent_files) if !torrent_files.is_empty() => torrent_files,
+            Ok(_) => continue,
+            Err(e) => {
+                log::debug!("Failed to get files of magnet {}, {}", anime_seed.info_hash, e);
+                continue;
+            }
+        };
+        anime_seed.total_size = torrent_files.iter().map(|f| f.size).sum();
+        anime_seed.file_list = serde_json::to_string(&torrent_files)?;
+        save_torrent_meta(db_connection, &anime_seed).await?;
+        log::info!("resolved metadata of magnet: {}", anime_seed.seed_name);
+    }
+    Ok(())
+}
+
 #[allow(dead_code)]
 pub async fn auto_rename_and_extract_handler(
     video_file_lock: &Arc<TokioRwLock<bool>>,
-    qb_task_executor: &Arc<TokioRwLock<QbitTaskExecutor>>,
+    qb_task_executor: &Arc<TokioRwLock<Box<dyn TorrentClient>>>,
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
     config: &Arc<TokioRwLock<Config>>
 ) -> Result<(), Error> {
     let _guard = video_file_lock.write().await;
     let qb = qb_task_executor.read().await;
-    let download_path = {
+    let (download_path, keep_seeding) = {
         let config_unlock = config.read().await;
-        config_unlock.download_path.clone()
+        (
+            config_unlock.download_path.clone(),
+            config_unlock.seeding_policy.enable,
+        )
     };
 
     let (mut file, mut video_config) = get_video_config(&download_path)
         .await
         .map_err(|e| handle_error(e, "Failed to get video config"))?;
 
-    let task_list = dao::anime_task::get_by_task_status(db_connection, 1, 0)
+    let task_list = dao::anime_task::get_by_task_status(db_connection, TaskState::Downloaded)
         .await
         .map_err(|e| handle_error(e, "Failed to get anime task by task status."))?;
 
     log::debug!("{:?}", task_list);
 
+    // torrents to be deleted from qb after all of their episodes are renamed, name -> info hash
+    // a torrent which keeps seeding is removed later by auto_seeding_handler
+    let mut renamed_torrent_map: HashMap<String, String> = HashMap::new();
+
     // rename -> extract -> write VideoConfig
     for task in task_list {
         // rename
-        if let Ok((cur_file_name, cur_total_file_path)) =
-            rename_file(&download_path, &qb, db_connection, &task).await
+        if let Ok((cur_file_name, cur_total_file_path, renamed_by_client)) = rename_file(
+            &download_path,
+            qb.as_ref(),
+            db_connection,
+            &task,
+            keep_seeding,
+        )
+        .await
         {
+            let task_state = if renamed_by_client {
+                TaskState::Seeding
+            } else {
+                TaskState::Renamed
+            };
             dao::anime_task::update_task_status(
                 db_connection,
                 &task.torrent_name,
-                1,
-                1,
+                task_state,
                 &cur_file_name,
-                1,
+                NewStatus::New,
             )
             .await
             .map_err(|e| {
@@ -814,20 +1153,7 @@ pub async fn auto_rename_and_extract_handler(
             })?;
 
             // extract subtitles
-            let subtitle_vec = if let Some(extension) = Path::new(&cur_file_name).extension() {
-                if extension == "mkv" || extension == "mp4" {
-                    video_proccessor::extract_subtitle(&cur_total_file_path)
-                        .await
-                        .unwrap_or_else(|_| {
-                            log::warn!("Failed to extract subtitles for {:?}", cur_file_name);
-                            vec![]
-                        })
-                } else {
-                    vec![]
-                }
-            } else {
-                vec![]
-            };
+            let subtitle_vec = extract_subtitle(&cur_file_name, &cur_total_file_path).await;
 
             // write VideoConfig
             let cur_config = VideoConfig {
@@ -839,57 +1165,352 @@ pub async fn auto_rename_and_extract_handler(
             };
 
             video_config.insert(cur_file_name, cur_config);
-            qb.qb_api_del_torrent(&task.torrent_name)
+            if !renamed_by_client {
+                renamed_torrent_map.insert(
+                    spider::get_task_torrent_name(&task.torrent_name).to_string(),
+                    task.info_hash.clone(),
+                );
+            }
+        } else {
+            log::info!("Failed to execute rename task for anime_task: {:?}", task);
+        }
+    }
+
+    for (torrent_name, info_hash) in renamed_torrent_map {
+        let episode_task_vec =
+            dao::anime_task::get_by_torrent_name_with_batch(db_connection, &torrent_name)
+                .await
+                .map_err(|e| handle_error(e, "Failed to get anime task by torrent name."))?;
+        // the other episodes of a batch torrent may still be downloading or seeding
+        if episode_task_vec.iter().any(|t| {
+            t.rename_status == RenameStatus::Pending || t.qb_task_status == QbTaskStatus::Seeding
+        }) {
+            continue;
+        }
+        qb.del_torrent(&info_hash, true).await.map_err(|e| {
+            handle_error(
+                e,
+                format!("Failed to delete task for qb: {}", torrent_name).as_str(),
+            )
+        })?;
+    }
+
+    auto_upgrade_handler(&download_path, qb.as_ref(), db_connection, &mut video_config)
+        .await
+        .map_err(|e| handle_error(e, "Failed to replace upgraded anime task"))?;
+
+    file.seek(std::io::SeekFrom::Start(0))
+        .map_err(|e| handle_error(e, "Failed to seek in video config file."))?;
+    file.set_len(0)
+        .map_err(|e| handle_error(e, "Failed to truncate video config file."))?;
+    let video_config = serde_json::to_string_pretty(&video_config)
+        .map_err(|e| handle_error(e, "Failed to serialize video config."))?;
+    file.write_all(video_config.as_bytes())
+        .map_err(|e| handle_error(e, "Failed to update video config file."))?;
+    Ok(())
+}
+
+/*
+    Removes the seeding torrents which reached the seeding policy, the renamed
+    files are kept. The longest seeding torrents go first once there are more
+    than max_active_seeds, and everything goes once the policy is disabled.
+*/
+pub async fn auto_seeding_handler(
+    qb_task_executor: &Arc<TokioRwLock<Box<dyn TorrentClient>>>,
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    config: &Arc<TokioRwLock<Config>>,
+) -> Result<(), Error> {
+    let policy = config.read().await.seeding_policy.clone();
+
+    let task_list = dao::anime_task::get_by_task_status(db_connection, TaskState::Seeding)
+        .await
+        .map_err(|e| handle_error(e, "Failed to get anime task by task status."))?;
+    if task_list.is_empty() {
+        return Ok(());
+    }
+
+    let qb = qb_task_executor.read().await;
+    let seeding_stats = qb
+        .seeding_stats()
+        .await
+        .map_err(|e| handle_error(e, "Failed to get seeding stats"))?;
+
+    let mut seeding_vec = Vec::new();
+    for info_hash in task_list
+        .into_iter()
+        .map(|t| t.info_hash)
+        .collect::<HashSet<_>>()
+    {
+        // a batch torrent seeds until all of its episodes are renamed
+        let episode_task_vec = dao::anime_task::get_by_info_hash(db_connection, &info_hash)
+            .await
+            .map_err(|e| handle_error(e, "Failed to get anime task by info hash."))?;
+        if episode_task_vec
+            .iter()
+            .any(|t| t.rename_status == RenameStatus::Pending)
+        {
+            continue;
+        }
+        match seeding_stats.get(&info_hash) {
+            Some(stats) => seeding_vec.push((info_hash, *stats)),
+            // removed from the torrent client by hand
+            None => {
+                dao::anime_task::finish_seeding(db_connection, &info_hash).await?;
+            }
+        }
+    }
+    seeding_vec.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.seeding_time));
+
+    let nb_over_limit = if policy.max_active_seeds > 0 {
+        seeding_vec
+            .len()
+            .saturating_sub(policy.max_active_seeds as usize)
+    } else {
+        0
+    };
+    for (i, (info_hash, stats)) in seeding_vec.into_iter().enumerate() {
+        let is_done = !policy.enable
+            || i < nb_over_limit
+            || (policy.ratio_limit > 0.0 && stats.ratio >= policy.ratio_limit)
+            || (policy.seeding_minutes > 0 && stats.seeding_time >= policy.seeding_minutes * 60);
+        if !is_done {
+            continue;
+        }
+        qb.del_torrent(&info_hash, false).await.map_err(|e| {
+            handle_error(
+                e,
+                format!("Failed to delete seeding task for qb: {}", info_hash).as_str(),
+            )
+        })?;
+        dao::anime_task::finish_seeding(db_connection, &info_hash).await?;
+        log::info!(
+            "Stop seeding {}, ratio: {:.2}, seeding time: {}s",
+            info_hash,
+            stats.ratio,
+            stats.seeding_time
+        );
+    }
+    Ok(())
+}
+
+async fn extract_subtitle(cur_file_name: &str, cur_total_file_path: &str) -> Vec<String> {
+    if let Some(extension) = Path::new(cur_file_name).extension() {
+        if extension == "mkv" || extension == "mp4" {
+            return video_proccessor::extract_subtitle(cur_total_file_path)
+                .await
+                .unwrap_or_else(|_| {
+                    log::warn!("Failed to extract subtitles for {:?}", cur_file_name);
+                    vec![]
+                });
+        }
+    }
+    vec![]
+}
+
+/*
+    Replace the file of an episode by its finished upgrade torrent. A seeding
+    episode hands the seeding over to the upgrade torrent: the old torrent is
+    removed (its files are kept) before the file is swapped, unless other
+    episodes of a batch torrent still use it.
+*/
+pub async fn auto_upgrade_handler(
+    download_path: &str,
+    qb_task_executor: &dyn TorrentClient,
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    video_config: &mut HashMap<String, VideoConfig>,
+) -> Result<(), Error> {
+    let upgrade_vec = dao::anime_task_upgrade::get_by_upgrade_status(db_connection, 0).await?;
+    if upgrade_vec.is_empty() {
+        return Ok(());
+    }
+    let finished_task_set = qb_task_executor
+        .completed_torrent_set()
+        .await
+        .map_err(|e| handle_error(e, "Failed to get finished task list"))?;
+
+    for upgrade in upgrade_vec {
+        if !finished_task_set.contains(&upgrade.new_info_hash) {
+            continue;
+        }
+        let upgrade_id = upgrade.id.unwrap_or_default();
+        let task = match dao::anime_task::get_by_torrent_name(db_connection, &upgrade.old_torrent_name)
+            .await
+        {
+            Ok(task) => task,
+            Err(_) => {
+                log::warn!("anime_task of upgrade {:?} has been deleted", upgrade);
+                dao::anime_task_upgrade::update_upgrade_status(db_connection, upgrade_id, -1, "")
+                    .await?;
+                continue;
+            }
+        };
+
+        let is_seeding = task.qb_task_status == QbTaskStatus::Seeding;
+        if is_seeding && !task.info_hash.is_empty() {
+            let shared = dao::anime_task::get_by_info_hash(db_connection, &task.info_hash)
+                .await?
+                .iter()
+                .any(|t| t.torrent_name != task.torrent_name);
+            if !shared {
+                if let Err(e) = qb_task_executor.del_torrent(&task.info_hash, false).await {
+                    log::info!("Failed to delete old torrent {}, {}", task.info_hash, e);
+                    continue;
+                }
+            }
+        }
+
+        let new_task = AnimeTask {
+            torrent_name: upgrade.new_torrent_name.clone(),
+            info_hash: upgrade.new_info_hash.clone(),
+            ..task
+        };
+        let (new_file_name, new_total_path, renamed_by_client) = match rename_file(
+            download_path,
+            qb_task_executor,
+            db_connection,
+            &new_task,
+            is_seeding,
+        )
+        .await
+        {
+            Ok(res) => res,
+            Err(_) => {
+                log::info!("Failed to rename upgrade task: {:?}", upgrade);
+                continue;
+            }
+        };
+
+        // remove the replaced video and its subtitles, the new file may reuse the old name
+        let anime_dir = Path::new(&new_total_path)
+            .parent()
+            .map(|p| p.to_path_buf())
+            .unwrap_or_default();
+        if let Some(old_config) = video_config.remove(&upgrade.old_filename) {
+            for subtitle in old_config.subtitle {
+                let _ = fs::remove_file(anime_dir.join(subtitle));
+            }
+        }
+        if upgrade.old_filename != new_file_name && !upgrade.old_filename.is_empty() {
+            if let Err(e) = fs::remove_file(anime_dir.join(&upgrade.old_filename)) {
+                log::warn!("Failed to remove replaced file {}, {}", upgrade.old_filename, e);
+            }
+        }
+
+        let subtitle_vec = extract_subtitle(&new_file_name, &new_total_path).await;
+        video_config.insert(
+            new_file_name.clone(),
+            VideoConfig {
+                torrent_name: upgrade.new_torrent_name.clone(),
+                mikan_id: upgrade.mikan_id,
+                episode: upgrade.episode,
+                subtitle_nb: subtitle_vec.len() as i32,
+                subtitle: subtitle_vec,
+            },
+        );
+
+        dao::anime_task::update_torrent_name(
+            db_connection,
+            &upgrade.old_torrent_name,
+            &upgrade.new_torrent_name,
+            &upgrade.new_info_hash,
+            &new_file_name,
+        )
+        .await?;
+        dao::anime_progress::update_torrent_name(
+            &upgrade.old_torrent_name,
+            &upgrade.new_torrent_name,
+            db_connection,
+        )
+        .await?;
+        dao::anime_task_upgrade::update_upgrade_status(db_connection, upgrade_id, 1, &new_file_name)
+            .await?;
+
+        // the upgrade torrent renamed by the client seeds on in place of the old one
+        if !renamed_by_client {
+            dao::anime_task::finish_seeding(db_connection, &upgrade.new_info_hash).await?;
+            qb_task_executor
+                .del_torrent(&upgrade.new_info_hash, true)
                 .await
                 .map_err(|e| {
                     handle_error(
                         e,
-                        format!("Failed to delete task for qb: {:?}", task).as_str(),
+                        format!("Failed to delete upgrade task for qb: {:?}", upgrade).as_str(),
                     )
                 })?;
-        } else {
-            log::info!("Failed to execute rename task for anime_task: {:?}", task);
         }
+        log::info!("replaced {} by {}", upgrade.old_filename, new_file_name);
     }
-
-    file.seek(std::io::SeekFrom::Start(0)).unwrap();
-    file.set_len(0).unwrap();
-    file.write_all(
-        serde_json::to_string_pretty(&video_config)
-            .unwrap()
-            .as_bytes(),
-    )
-    .map_err(|e| handle_error(e, "Failed to update video config file."))?;
     Ok(())
 }
 
-#[allow(dead_code)]
+const VIDEO_EXTENSIONS: [&str; 5] = ["mkv", "mp4", "avi", "ts", "webm"];
+
+// The video of an episode inside a torrent. A single episode torrent holds one video,
+// the videos of a batch torrent are told apart by the episode parsed from their file name.
+pub fn get_episode_file(torrent_files: &[TorrentFile], episode: i32) -> Option<&TorrentFile> {
+    let video_files: Vec<&TorrentFile> = torrent_files
+        .iter()
+        .filter(|f| {
+            Path::new(&f.name)
+                .extension()
+                .map(|ext| VIDEO_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+                .unwrap_or(false)
+        })
+        .collect();
+    if video_files.len() == 1 {
+        return video_files.into_iter().next();
+    }
+    video_files
+        .into_iter()
+        .filter(|f| {
+            let file_name = Path::new(&f.name)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            title_parser::parse_title(&file_name).episode_number() == Some(episode)
+        })
+        .max_by_key(|f| f.size)
+}
+
+/*
+    Returns (new_file_name, new_total_path, renamed_by_client). With keep_seeding
+    the torrent client moves the file itself, so the torrent can go on seeding
+    from the renamed file. A client which can not do that falls back to fs::rename.
+*/
 pub async fn rename_file(
     path: &str,
-    qb_task_executor: &QbitTaskExecutor,
+    qb_task_executor: &dyn TorrentClient,
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
     anime_task: &AnimeTask,
-) -> Result<(String, String), Error> {
+    keep_seeding: bool,
+) -> Result<(String, String, bool), Error> {
 
     let anime_name = dao::anime_list::get_by_mikanid(db_connection, anime_task.mikan_id)
         .await
         .map_err(|e| handle_error(e, "Failed to get anime name."))?
         .anime_name;
 
-    // println!("{:?}", anime_task.torrent_name);
-    let file_name = qb_task_executor
-        .qb_api_torrent_info(&anime_task.torrent_name)
+    let torrent_name = spider::get_task_torrent_name(&anime_task.torrent_name);
+    let torrent_files = qb_task_executor
+        .torrent_files(&anime_task.info_hash)
         .await
-        .map_err(|e| handle_error(e, "Failed to get original video name."))?
-        .name;
-    if file_name.len() == 0 {
-        return Err(Error::msg("Failed to get original video name."));
+        .map_err(|e| handle_error(e, "Failed to get original video name."))?;
+    if torrent_files.is_empty() {
+        return Err(Error::msg(format!(
+            "Metadata of torrent {} is not resolved yet.",
+            anime_task.info_hash
+        )));
     }
+    let file_name = match get_episode_file(&torrent_files, anime_task.episode) {
+        Some(torrent_file) => torrent_file.name.clone(),
+        None => return Err(Error::msg("Failed to get original video name.")),
+    };
 
     // Total name: path/anime_name(mikan_id)/video_name.mp4
     let total_path = format!(
-        "{}/{}({})/{}",
-        path, anime_name, anime_task.mikan_id, file_name
+        "{}/{}",
+        get_anime_dir(path, &anime_name, anime_task.mikan_id),
+        file_name
     );
     log::debug!("total_path: {}", total_path);
 
@@ -899,7 +1520,7 @@ pub async fn rename_file(
     };
     log::debug!("extension: {}", extension);
 
-    let quary_item = format!("%{}", anime_task.torrent_name);
+    let quary_item = format!("%{}", torrent_name);
     let subgroup_id = dao::anime_seed::get_anime_seed_by_seed_url(db_connection, &quary_item)
         .await
         .map_err(|e| handle_error(e, "Failed to get subgroup_id"))?
@@ -918,8 +1539,9 @@ pub async fn rename_file(
         anime_name, anime_task.episode, subgroup, extension
     );
     let new_total_path = format!(
-        "{}/{}({})/{}",
-        path, anime_name, anime_task.mikan_id, new_file_name
+        "{}/{}",
+        get_anime_dir(path, &anime_name, anime_task.mikan_id),
+        new_file_name
     );
 
     log::info!(
@@ -928,9 +1550,33 @@ pub async fn rename_file(
         new_total_path
     );
 
-    let _ = fs::rename(&total_path, &new_total_path);
+    if keep_seeding {
+        match qb_task_executor
+            .rename_file(&anime_task.info_hash, &file_name, &new_file_name)
+            .await
+        {
+            Ok(_) => {
+                // the client moves the file in the background, the task stays pending without it
+                for _ in 0..10 {
+                    if Path::new(&new_total_path).exists() {
+                        return Ok((new_file_name, new_total_path, true));
+                    }
+                    sleep(Duration::from_millis(500)).await;
+                }
+                return Err(Error::msg(format!(
+                    "Renamed file {} does not exist yet.",
+                    new_total_path
+                )));
+            }
+            Err(e) => log::info!("Torrent client can not rename {}, {}", file_name, e),
+        }
+    }
 
-    Ok((new_file_name, new_total_path))
+    // the task stays pending when the file can not be moved, it is retried on the next tick
+    fs::rename(&total_path, &new_total_path)
+        .map_err(|e| handle_error(e, "Failed to rename file"))?;
+
+    Ok((new_file_name, new_total_path, false))
 }
 
 #[allow(dead_code)]
@@ -941,16 +1587,53 @@ pub async fn add_default_filter(
     let config = config.read().await;
 
     for sub in &config.anime_config.subgroup_filter.preference {
-        let _ = dao::anime_filter::add_global_subgroup_filter(sub, db_connection)
-            .await
-            .unwrap();
+        dao::anime_filter::add_global_subgroup_filter(sub, db_connection).await?;
     }
 
     for sub in &config.anime_config.subgroup_filter.avoid {
-        let _ = dao::anime_filter::add_global_subgroup_filter(&-sub, db_connection)
-            .await
-            .unwrap();
+        dao::anime_filter::add_global_subgroup_filter(&-sub, db_connection).await?;
+    }
+
+    let quality_filter = &config.anime_config.quality_filter;
+    let mut quality_vals: Vec<(&str, Option<i32>)> = quality_filter
+        .resolution
+        .iter()
+        .map(|r| (anime_filter::FILTER_RESOLUTION, Some(*r)))
+        .collect();
+    for (quality_type, values) in [
+        (anime_filter::FILTER_CODEC, &quality_filter.codec),
+        (anime_filter::FILTER_SUBTITLE, &quality_filter.subtitle),
+        (anime_filter::FILTER_CONTAINER, &quality_filter.container),
+    ] {
+        for value in values {
+            quality_vals.push((
+                quality_type,
+                anime_filter::quality_filter_val(quality_type, value),
+            ));
+        }
     }
+    let mut quality_vec: Vec<(&str, i32)> = Vec::new();
+    for (quality_type, quality_val) in quality_vals {
+        match quality_val {
+            Some(val) => quality_vec.push((quality_type, val)),
+            None => log::warn!("unknown {} in quality_filter config", quality_type),
+        }
+    }
+    // the config is the source of the global rules, a removed value is dropped from db
+    dao::anime_filter::replace_global_quality_filter(
+        &anime_filter::QUALITY_FILTER_TYPES,
+        &quality_vec,
+        db_connection,
+    )
+    .await?;
+
+    let grace_policy = &config.anime_config.grace_policy;
+    let grace_hours = if grace_policy.enable {
+        grace_policy.hours.clamp(0, i32::MAX as i64) as i32
+    } else {
+        0
+    };
+    dao::anime_filter::set_grace_hours(0, grace_hours, db_connection).await?;
     Ok(())
 }
 
@@ -986,8 +1669,9 @@ pub async fn get_filepath_by_torrent_name(
         .anime_name;
 
     let path = format!(
-        "{}/{}({})/{}",
-        download_path, anime_name, anime_task.mikan_id, anime_task.filename
+        "{}/{}",
+        get_anime_dir(download_path, &anime_name, anime_task.mikan_id),
+        anime_task.filename
     );
     Ok(path)
 }
@@ -995,6 +1679,8 @@ pub async fn get_filepath_by_torrent_name(
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::error::error::AnimeError;
+    use crate::mods::torrent_client;
     use crate::Config;
     use crate::Pool;
 
@@ -1009,7 +1695,7 @@ mod test {
             .expect("Failed to create pool.");
 
         let _qb = Arc::new(TokioRwLock::new(
-            QbitTaskExecutor::new_with_config(&config)
+            torrent_client::new_torrent_client(&config)
                 .await
                 .expect("Failed to create qb client"),
         ));
@@ -1022,4 +1708,255 @@ mod test {
             .await
             .unwrap();
     }
+
+    #[test]
+    fn test_get_episode_file() {
+        let torrent_file = |name: &str, size: i64| TorrentFile {
+            index: 0,
+            name: name.to_string(),
+            size,
+        };
+        let single = vec![torrent_file("[ANi] Frieren - 05 [1080P].mp4", 700)];
+        assert_eq!(
+            get_episode_file(&single, 5).map(|f| f.name.as_str()),
+            Some("[ANi] Frieren - 05 [1080P].mp4")
+        );
+
+        let batch = vec![
+            torrent_file("[VCB-Studio] Frieren/[VCB-Studio] Frieren [01][1080p].mkv", 1500),
+            torrent_file("[VCB-Studio] Frieren/[VCB-Studio] Frieren [02][1080p].mkv", 1400),
+            torrent_file("[VCB-Studio] Frieren/[VCB-Studio] Frieren [02][1080p].sc.ass", 40),
+            torrent_file("[VCB-Studio] Frieren/SPs/[VCB-Studio] Frieren [NCOP][1080p].mkv", 90),
+        ];
+        assert_eq!(
+            get_episode_file(&batch, 2).map(|f| f.name.as_str()),
+            Some("[VCB-Studio] Frieren/[VCB-Studio] Frieren [02][1080p].mkv")
+        );
+        assert!(get_episode_file(&batch, 3).is_none());
+    }
+
+    #[test]
+    fn test_aired_episodes() {
+        use crate::models::enums::{AnimeType, EpisodeType, UpdateDay};
+
+        let anime = |anime_status: AnimeStatus, total_episodes: i32| AnimeList {
+            id: None,
+            mikan_id: 3143,
+            anime_name: "葬送的芙莉莲".to_string(),
+            update_day: UpdateDay::Friday,
+            img_url: "".to_string(),
+            anime_type: AnimeType::Tv,
+            subscribe_status: SubscribeStatus::Subscribed,
+            bangumi_id: 400602,
+            bangumi_rank: "".to_string(),
+            bangumi_summary: "".to_string(),
+            website: "".to_string(),
+            anime_status,
+            total_episodes,
+            new_finished_episode: 0,
+            seed_mode: 0,
+            source: SOURCE_MIKAN.to_string(),
+            air_date: "".to_string(),
+            rating_rank: -1,
+            rating_votes: 0,
+            tags: "[]".to_string(),
+            studio: "".to_string(),
+            alt_names: "[]".to_string(),
+        };
+        let main_episode = |episode: i32, air_date: &str| AnimeEpisode {
+            id: None,
+            mikan_id: 3143,
+            episode,
+            episode_type: EpisodeType::Main,
+            title: "".to_string(),
+            air_date: air_date.to_string(),
+            duration: 0,
+        };
+
+        // an episode airing today is not missing yet
+        let main_episode_vec = vec![
+            main_episode(1, "2023-09-29"),
+            main_episode(2, "2023-10-06"),
+            main_episode(3, "2023-10-13"),
+        ];
+        let airing = anime(AnimeStatus::Airing, 28);
+        assert_eq!(
+            aired_episodes(&airing, &main_episode_vec, 1, "2023-10-13"),
+            vec![1, 2]
+        );
+
+        // without air dates
+        assert_eq!(
+            aired_episodes(&airing, &[], 4, "2023-10-13"),
+            vec![1, 2, 3, 4]
+        );
+        assert_eq!(
+            aired_episodes(&anime(AnimeStatus::Finished, 3), &[], 1, "2023-10-13"),
+            vec![1, 2, 3]
+        );
+        assert_eq!(
+            aired_episodes(&anime(AnimeStatus::Unknown, -1), &[], 0, "2023-10-13"),
+            Vec::<i32>::new()
+        );
+    }
+
+    // a client which refuses the torrents of fail_info_hash
+    struct MockClient {
+        fail_info_hash: String,
+    }
+
+    #[async_trait::async_trait]
+    impl TorrentClient for MockClient {
+        fn is_login(&self) -> bool {
+            true
+        }
+        async fn reconnect(&self) -> Result<(), AnimeError> {
+            Ok(())
+        }
+        async fn add_torrent(
+            &self,
+            _anime_name: &str,
+            anime_seed_info: &AnimeSeed,
+            _speed_limit: crate::mods::config::SpeedLimit,
+        ) -> Result<(), AnimeError> {
+            if anime_seed_info.info_hash == self.fail_info_hash {
+                return Err(AnimeError::Downloader("add failed".to_string()));
+            }
+            Ok(())
+        }
+        async fn torrent_info(
+            &self,
+            _info_hash: &str,
+        ) -> Result<torrent_client::TorrentInfo, AnimeError> {
+            Err(AnimeError::Downloader("no torrent info".to_string()))
+        }
+        async fn torrent_files(&self, _info_hash: &str) -> Result<Vec<TorrentFile>, AnimeError> {
+            Ok(vec![])
+        }
+        async fn del_torrent(
+            &self,
+            _info_hash: &str,
+            _delete_files: bool,
+        ) -> Result<(), AnimeError> {
+            Ok(())
+        }
+        async fn rename_file(
+            &self,
+            _info_hash: &str,
+            _old_path: &str,
+            _new_path: &str,
+        ) -> Result<(), AnimeError> {
+            Ok(())
+        }
+        async fn pause_torrent(&self, _info_hash: &str) -> Result<(), AnimeError> {
+            Ok(())
+        }
+        async fn resume_torrent(&self, _info_hash: &str) -> Result<(), AnimeError> {
+            Ok(())
+        }
+        async fn completed_torrent_set(&self) -> Result<HashSet<String>, AnimeError> {
+            Ok(HashSet::new())
+        }
+        async fn seeding_stats(
+            &self,
+        ) -> Result<HashMap<String, torrent_client::SeedingStats>, AnimeError> {
+            Ok(HashMap::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_process_download_queue_add_failed() {
+        use crate::models::anime_seed::AnimeSeedJson;
+        use crate::models::enums::{AnimeType, SeedStatus, UpdateDay};
+        use crate::mods::config::ScraperConfig;
+
+        let pool = Pool::builder()
+            .max_size(1)
+            .build(ConnectionManager::<SqliteConnection>::new(":memory:"))
+            .unwrap();
+        let db_connection = &mut pool.get().unwrap();
+        dao::run_migrations(db_connection).unwrap();
+
+        dao::anime_list::add(
+            db_connection,
+            AnimeListJson {
+                mikan_id: 3143,
+                anime_name: "葬送的芙莉莲".to_string(),
+                update_day: UpdateDay::Friday,
+                img_url: "".to_string(),
+                anime_type: AnimeType::Tv,
+                subscribe_status: SubscribeStatus::Subscribed,
+                bangumi_id: -1,
+                bangumi_rank: "".to_string(),
+                bangumi_summary: "".to_string(),
+                website: "".to_string(),
+                anime_status: AnimeStatus::Airing,
+                total_episodes: -1,
+                new_finished_episode: 0,
+                seed_mode: 0,
+                source: SOURCE_MIKAN.to_string(),
+            },
+        )
+        .await
+        .unwrap();
+        let info_hash = |episode: i32| format!("{:040x}", episode);
+        let seed_json_vec: Vec<AnimeSeedJson> = (1..=2)
+            .map(|episode| AnimeSeedJson {
+                mikan_id: 3143,
+                subgroup_id: 370,
+                episode,
+                seed_name: format!("[LoliHouse] Frieren - {:02} [1080p]", episode),
+                seed_url: format!("magnet:?xt=urn:btih:{}", info_hash(episode)),
+                seed_status: SeedStatus::Unused,
+                seed_size: "".to_string(),
+                publish_date: "".to_string(),
+                episode_end: -1,
+                is_special: false,
+            })
+            .collect();
+        dao::anime_seed::add_bulk(db_connection, seed_json_vec)
+            .await
+            .unwrap();
+        let now = Local::now().format(TIME_FORMAT).to_string();
+        for anime_seed in dao::anime_seed::get_anime_seed_by_mikan_id(db_connection, 3143)
+            .await
+            .unwrap()
+        {
+            dao::download_job::enqueue(db_connection, &anime_seed, &now)
+                .await
+                .unwrap();
+        }
+
+        let mikan = Mikan::new(&ScraperConfig::default()).unwrap();
+        let client = MockClient {
+            fail_info_hash: info_hash(2),
+        };
+        process_download_queue(
+            &mikan,
+            &client,
+            db_connection,
+            &DownloadQueueConfig::default(),
+        )
+        .await
+        .unwrap();
+
+        // only the added torrent has a task, the other job waits for its next attempt
+        let task_vec = dao::anime_task::get_all(db_connection).await.unwrap();
+        assert_eq!(task_vec.iter().map(|t| t.episode).collect::<Vec<_>>(), [1]);
+        let mut job_vec = dao::download_job::get_by_mikan_id(db_connection, 3143)
+            .await
+            .unwrap();
+        job_vec.sort_by_key(|job| job.episode);
+        assert_eq!(job_vec[0].job_status, JobStatus::Done);
+        assert_eq!(
+            (job_vec[1].job_status, job_vec[1].attempts),
+            (JobStatus::Pending, 1)
+        );
+        assert!(!job_vec[1].next_attempt.is_empty());
+        let anime_seed =
+            dao::anime_seed::get_anime_seed_by_seed_url(db_connection, &job_vec[1].seed_url)
+                .await
+                .unwrap();
+        assert_eq!(anime_seed.seed_status, SeedStatus::Unused);
+    }
 }
@@ -1,2 +1,3 @@
 pub mod do_anime_task;
+pub mod scheduler;
 pub mod spider_task;
@@ -0,0 +1,493 @@
+use crate::api::do_anime_task;
+use crate::dao;
+use crate::error::error::AnimeError;
+use crate::models::scheduler_job::{SchedulerJob, SchedulerJobJson};
+use crate::mods::config::Config;
+use crate::mods::torrent_client::TorrentClient;
+use crate::Pool;
+use anyhow::Error;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use std::collections::{BTreeSet, HashSet};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Notify, RwLock as TokioRwLock};
+use tokio::time::{sleep, Duration};
+
+pub const JOB_SEED_REFRESH: &str = "seed_refresh";
+pub const JOB_QB_STATUS_POLL: &str = "qb_status_poll";
+pub const JOB_RENAME_EXTRACT: &str = "rename_extract";
+pub const JOB_METADATA_REFRESH: &str = "metadata_refresh";
+pub const JOB_DOWNLOADER_RECONNECT: &str = "downloader_reconnect";
+pub const JOB_SEEDING_CHECK: &str = "seeding_check";
+pub const JOB_DOWNLOAD_QUEUE: &str = "download_queue";
+pub const JOB_EPISODE_BACKFILL: &str = "episode_backfill";
+
+// (job_name, schedule, enabled), only used to create the jobs on first start,
+// seed_refresh waits for POST /v2/setting/start like the task loop it replaced
+const DEFAULT_JOBS: [(&str, &str, bool); 8] = [
+    (JOB_SEED_REFRESH, "@every 2m", false),
+    (JOB_QB_STATUS_POLL, "@every 1m", true),
+    (JOB_RENAME_EXTRACT, "@every 1m", true),
+    (JOB_METADATA_REFRESH, "0 4 * * *", true),
+    (JOB_DOWNLOADER_RECONNECT, "@every 30s", true),
+    (JOB_SEEDING_CHECK, "@every 10m", true),
+    (JOB_DOWNLOAD_QUEUE, "@every 1m", true),
+    (JOB_EPISODE_BACKFILL, "@every 6h", false),
+];
+
+// the scheduler wakes up at least this often, even without any due job
+const MAX_WAIT: Duration = Duration::from_secs(3600);
+
+pub const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+fn format_time(time: DateTime<Local>) -> String {
+    time.format(TIME_FORMAT).to_string()
+}
+
+fn parse_time(time: &str) -> Option<DateTime<Local>> {
+    NaiveDateTime::parse_from_str(time, TIME_FORMAT)
+        .ok()
+        .and_then(|t| Local.from_local_datetime(&t).earliest())
+}
+
+/*
+    "@every 30m" runs at a fixed interval (s, m, h or d), anything else is a
+    cron expression, either 5 fields (min hour day month weekday) or the
+    6/7 fields with seconds understood by the cron crate. The weekday of the
+    5 fields is the usual 0-7 with sunday as 0 or 7, the cron crate counts
+    1-7 from sunday, see cron_weekday.
+*/
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    Interval(chrono::Duration),
+    Cron(Box<cron::Schedule>),
+}
+
+impl Schedule {
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        match self {
+            Schedule::Interval(interval) => Some(after + *interval),
+            Schedule::Cron(schedule) => schedule.after(&after).next(),
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = AnimeError;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let spec = spec.trim();
+        let invalid = |reason: String| {
+            AnimeError::BadRequest(format!("invalid schedule {}: {}", spec, reason))
+        };
+
+        if let Some(every) = spec.strip_prefix("@every") {
+            let every = every.trim();
+            let (num, unit) = every.split_at(every.len().saturating_sub(1));
+            let num = num.parse::<i64>().map_err(|e| invalid(e.to_string()))?;
+            let interval = match unit {
+                "s" => chrono::Duration::seconds(num),
+                "m" => chrono::Duration::minutes(num),
+                "h" => chrono::Duration::hours(num),
+                "d" => chrono::Duration::days(num),
+                _ => return Err(invalid(format!("unknown unit {}", unit))),
+            };
+            if interval <= chrono::Duration::zero() {
+                return Err(invalid("interval must be positive".to_string()));
+            }
+            return Ok(Schedule::Interval(interval));
+        }
+
+        // the cron crate starts with a seconds field
+        let fields: Vec<&str> = spec.split_whitespace().collect();
+        let expr = if fields.len() == 5 {
+            let weekday = cron_weekday(fields[4]).map_err(invalid)?;
+            format!("0 {} {}", fields[..4].join(" "), weekday)
+        } else {
+            spec.to_string()
+        };
+        cron::Schedule::from_str(&expr)
+            .map(|schedule| Schedule::Cron(Box::new(schedule)))
+            .map_err(|e| invalid(e.to_string()))
+    }
+}
+
+const WEEKDAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+// a weekday of the 5 fields cron, 0-7 or a name, sunday is 0
+fn parse_weekday(day: &str) -> Result<usize, String> {
+    if let Some(pos) = WEEKDAY_NAMES
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(day))
+    {
+        return Ok(pos);
+    }
+    match day.parse::<usize>() {
+        Ok(num) if num <= 7 => Ok(num),
+        _ => Err(format!("invalid weekday {}", day)),
+    }
+}
+
+// expand the weekday field into the days of the cron crate, sunday is 1
+fn cron_weekday(field: &str) -> Result<String, String> {
+    if field == "*" || field == "?" {
+        return Ok(field.to_string());
+    }
+    let mut days = BTreeSet::new();
+    for item in field.split(',') {
+        let (base, step) = match item.split_once('/') {
+            Some((base, step)) => (
+                base,
+                Some(
+                    step.parse::<usize>()
+                        .map_err(|_| format!("invalid step {}", step))?,
+                ),
+            ),
+            None => (item, None),
+        };
+        let (start, end) = match base.split_once('-') {
+            _ if base == "*" || base == "?" => (0, 6),
+            Some((start, end)) => (parse_weekday(start)?, parse_weekday(end)?),
+            // "5/2" runs from friday to the end of the week
+            None => {
+                let day = parse_weekday(base)?;
+                (day, if step.is_some() { 6 } else { day })
+            }
+        };
+        if start > end || step == Some(0) {
+            return Err(format!("invalid weekday {}", item));
+        }
+        for day in (start..=end).step_by(step.unwrap_or(1)) {
+            days.insert(day % 7 + 1);
+        }
+    }
+    Ok(days
+        .iter()
+        .map(|day| day.to_string())
+        .collect::<Vec<_>>()
+        .join(","))
+}
+
+/*
+    Runs the named jobs stored in scheduler_job. The schedules, the next run
+    and the result of the last run live in the db, so they survive a restart
+    and a job missed while the app was down runs right after start. Each due
+    job runs in its own task so a long seed_refresh does not hold back the
+    others, a job still running is not started again.
+*/
+#[derive(Clone)]
+pub struct Scheduler {
+    pool: Pool,
+    qb_task_executor: Arc<TokioRwLock<Box<dyn TorrentClient>>>,
+    config: Arc<TokioRwLock<Config>>,
+    video_file_lock: Arc<TokioRwLock<bool>>,
+    notify: Arc<Notify>,
+    running: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Scheduler {
+    pub fn new(
+        pool: Pool,
+        qb_task_executor: Arc<TokioRwLock<Box<dyn TorrentClient>>>,
+        config: Arc<TokioRwLock<Config>>,
+        video_file_lock: Arc<TokioRwLock<bool>>,
+    ) -> Self {
+        Self {
+            pool,
+            qb_task_executor,
+            config,
+            video_file_lock,
+            notify: Arc::new(Notify::new()),
+            running: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    pub async fn init_jobs(&self) -> Result<(), AnimeError> {
+        let db_connection = &mut self.pool.get()?;
+        let now = Local::now();
+        for (job_name, schedule, enabled) in DEFAULT_JOBS {
+            let first_run = Schedule::from_str(schedule)?
+                .next_after(now)
+                .map(format_time)
+                .unwrap_or_default();
+            dao::scheduler_job::add_if_missing(
+                db_connection,
+                &SchedulerJobJson {
+                    job_name: job_name.to_string(),
+                    schedule: schedule.to_string(),
+                    enabled,
+                },
+                &first_run,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn get_jobs(&self) -> Result<Vec<SchedulerJob>, AnimeError> {
+        let db_connection = &mut self.pool.get()?;
+        Ok(dao::scheduler_job::get_all(db_connection).await?)
+    }
+
+    pub async fn update_job(&self, item: &SchedulerJobJson) -> Result<SchedulerJob, AnimeError> {
+        let next_run = Schedule::from_str(&item.schedule)?
+            .next_after(Local::now())
+            .map(format_time)
+            .unwrap_or_default();
+
+        let db_connection = &mut self.pool.get()?;
+        dao::scheduler_job::get_by_job_name(db_connection, &item.job_name).await?;
+        dao::scheduler_job::update_schedule(
+            db_connection,
+            &item.job_name,
+            &item.schedule,
+            item.enabled,
+            &next_run,
+        )
+        .await?;
+        self.notify.notify_one();
+
+        Ok(dao::scheduler_job::get_by_job_name(db_connection, &item.job_name).await?)
+    }
+
+    pub async fn set_enabled(
+        &self,
+        job_name: &str,
+        enabled: bool,
+    ) -> Result<SchedulerJob, AnimeError> {
+        let job = {
+            let db_connection = &mut self.pool.get()?;
+            dao::scheduler_job::get_by_job_name(db_connection, job_name).await?
+        };
+        self.update_job(&SchedulerJobJson {
+            job_name: job.job_name,
+            schedule: job.schedule,
+            enabled,
+        })
+        .await
+    }
+
+    // run a job on the next tick, its schedule is unchanged
+    pub async fn trigger(&self, job_name: &str) -> Result<(), AnimeError> {
+        let db_connection = &mut self.pool.get()?;
+        dao::scheduler_job::get_by_job_name(db_connection, job_name).await?;
+        dao::scheduler_job::update_next_run(db_connection, job_name, &format_time(Local::now()))
+            .await?;
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    pub async fn run(&self) {
+        log::info!("Start scheduler");
+        loop {
+            let wait = match self.tick().await {
+                Ok(wait) => wait,
+                Err(e) => {
+                    log::error!("Scheduler tick failed, err: {}", e);
+                    MAX_WAIT
+                }
+            };
+            tokio::select! {
+                _ = sleep(wait) => {},
+                _ = self.notify.notified() => {
+                    log::debug!("Scheduler jobs have been updated");
+                }
+            }
+        }
+    }
+
+    // start the due jobs, returns how long to sleep until the next one
+    async fn tick(&self) -> Result<Duration, Error> {
+        let job_vec = {
+            let db_connection = &mut self.pool.get()?;
+            dao::scheduler_job::get_all(db_connection).await?
+        };
+
+        let mut wait = MAX_WAIT;
+        for job in job_vec.into_iter().filter(|job| job.enabled) {
+            let next_run = match parse_time(&job.next_run) {
+                Some(next_run) => next_run,
+                None => continue,
+            };
+            if next_run <= Local::now() {
+                // the job wakes the scheduler up once it is done
+                if self.running.lock().unwrap().insert(job.job_name.clone()) {
+                    let scheduler = self.clone();
+                    tokio::spawn(async move {
+                        // a job which fails to record its run is retried, the other jobs go on
+                        if let Err(e) = scheduler.run_job(&job).await {
+                            log::error!("Record scheduled job {} failed, err: {}", job.job_name, e);
+                        }
+                        scheduler.running.lock().unwrap().remove(&job.job_name);
+                        scheduler.notify.notify_one();
+                    });
+                }
+                continue;
+            }
+            let job_wait = (next_run - Local::now()).to_std().unwrap_or_default();
+            wait = wait.min(job_wait);
+        }
+        Ok(wait)
+    }
+
+    async fn run_job(&self, job: &SchedulerJob) -> Result<Option<DateTime<Local>>, Error> {
+        log::debug!("Run scheduled job: {}", job.job_name);
+        let last_run = Local::now();
+        let last_error = match self.execute(&job.job_name).await {
+            Ok(_) => String::new(),
+            Err(e) => {
+                log::warn!("Scheduled job {} failed, err: {}", job.job_name, e);
+                e.to_string()
+            }
+        };
+
+        // a broken schedule in the db stops the job until it is saved again
+        let next_run = Schedule::from_str(&job.schedule)
+            .map_err(|e| log::warn!("{}", e))
+            .ok()
+            .and_then(|schedule| schedule.next_after(Local::now()));
+
+        let db_connection = &mut self.pool.get()?;
+        dao::scheduler_job::update_run_result(
+            db_connection,
+            &job.job_name,
+            &format_time(last_run),
+            &next_run.map(format_time).unwrap_or_default(),
+            &last_error,
+        )
+        .await?;
+        Ok(next_run)
+    }
+
+    // the connection is only held while the job runs
+    async fn execute(&self, job_name: &str) -> Result<(), Error> {
+        let db_connection = &mut self.pool.get()?;
+        match job_name {
+            JOB_SEED_REFRESH => {
+                do_anime_task::run(&self.qb_task_executor, &self.config, db_connection).await
+            }
+            JOB_QB_STATUS_POLL => {
+                let nb_new_finished_task =
+                    do_anime_task::auto_update_handler(&self.qb_task_executor, db_connection)
+                        .await?;
+                // rename the finished episodes right away instead of on the next rename tick
+                if nb_new_finished_task > 0 {
+                    dao::scheduler_job::update_next_run(
+                        db_connection,
+                        JOB_RENAME_EXTRACT,
+                        &format_time(Local::now()),
+                    )
+                    .await?;
+                    self.notify.notify_one();
+                }
+                Ok(())
+            }
+            JOB_RENAME_EXTRACT => {
+                do_anime_task::auto_rename_and_extract_handler(
+                    &self.video_file_lock,
+                    &self.qb_task_executor,
+                    db_connection,
+                    &self.config,
+                )
+                .await
+            }
+            JOB_METADATA_REFRESH => {
+                do_anime_task::refresh_bangumi_info(db_connection, &self.config).await
+            }
+            // keeps is_login up to date, the torrent client logs in again once it is back
+            JOB_DOWNLOADER_RECONNECT => {
+                let qb = self.qb_task_executor.read().await;
+                Ok(qb.reconnect().await?)
+            }
+            JOB_SEEDING_CHECK => {
+                do_anime_task::auto_seeding_handler(
+                    &self.qb_task_executor,
+                    db_connection,
+                    &self.config,
+                )
+                .await
+            }
+            // retries the seeds whose backoff is over
+            JOB_DOWNLOAD_QUEUE => {
+                do_anime_task::retry_download_queue(
+                    &self.qb_task_executor,
+                    db_connection,
+                    &self.config,
+                )
+                .await
+            }
+            // downloads the aired episodes which were never downloaded
+            JOB_EPISODE_BACKFILL => {
+                do_anime_task::backfill_episode_gaps(
+                    &self.qb_task_executor,
+                    db_connection,
+                    &self.config,
+                )
+                .await
+            }
+            _ => Err(Error::msg(format!("unknown job: {}", job_name))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_schedule() {
+        let now = parse_time("2026-10-17 10:15:30").unwrap();
+
+        let every = Schedule::from_str("@every 30m").unwrap();
+        assert_eq!(
+            format_time(every.next_after(now).unwrap()),
+            "2026-10-17 10:45:30"
+        );
+
+        let daily = Schedule::from_str("0 4 * * *").unwrap();
+        assert_eq!(
+            format_time(daily.next_after(now).unwrap()),
+            "2026-10-18 04:00:00"
+        );
+
+        let quarter = Schedule::from_str("0 */15 * * * *").unwrap();
+        assert_eq!(
+            format_time(quarter.next_after(now).unwrap()),
+            "2026-10-17 10:30:00"
+        );
+
+        for spec in ["@every 0m", "@every 5x", "@every", "every 5m", "* * *"] {
+            let e = Schedule::from_str(spec).unwrap_err();
+            assert_eq!(e.code(), "bad_request");
+        }
+
+        for (_, spec, _) in DEFAULT_JOBS {
+            assert!(Schedule::from_str(spec).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_schedule_weekday() {
+        // a saturday
+        let now = parse_time("2026-10-17 10:15:30").unwrap();
+        let next =
+            |spec: &str| format_time(Schedule::from_str(spec).unwrap().next_after(now).unwrap());
+
+        assert_eq!(next("0 9 * * 1-5"), "2026-10-19 09:00:00");
+        assert_eq!(next("0 9 * * 0"), "2026-10-18 09:00:00");
+        assert_eq!(next("0 9 * * 7"), "2026-10-18 09:00:00");
+        assert_eq!(next("0 9 * * 5-7"), "2026-10-18 09:00:00");
+        assert_eq!(next("0 9 * * sat"), "2026-10-24 09:00:00");
+        assert_eq!(next("0 9 * * MON,wed"), "2026-10-19 09:00:00");
+        assert_eq!(next("0 9 * * */2"), "2026-10-18 09:00:00");
+        assert_eq!(next("0 9 * * 3/2"), "2026-10-21 09:00:00");
+
+        // the 6 fields go to the cron crate unchanged, sunday is 1 there
+        assert_eq!(next("0 0 9 * * 1"), "2026-10-18 09:00:00");
+
+        for spec in ["0 9 * * 8", "0 9 * * 5-1", "0 9 * * 1/0", "0 9 * * xyz"] {
+            let e = Schedule::from_str(spec).unwrap_err();
+            assert_eq!(e.code(), "bad_request");
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use crate::dao;
 use crate::models;
+use crate::models::enums::AnimeType;
+use crate::mods::seed_source::SeedSources;
 use crate::mods::spider;
 use diesel::r2d2::{ConnectionManager, PooledConnection};
 use diesel::SqliteConnection;
@@ -10,13 +12,13 @@ use std::collections::HashMap;
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SpiderTaskAnime {
     pub mikan_id: i32,
-    pub anime_type: i32,
+    pub anime_type: AnimeType,
     pub subgroup_id: i32,
     pub subgroup_name: String,
 }
 
 pub async fn do_spider_task(
-    mikan: &spider::Mikan,
+    sources: &SeedSources,
     subscribed_anime_vec: Vec<models::anime_list::AnimeList>,
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
 ) -> Vec<models::anime_seed::AnimeSeedJson> {
@@ -26,8 +28,8 @@ pub async fn do_spider_task(
     if !subscribed_anime_vec.is_empty() {
         let task_res_vec = join_all(
             subscribed_anime_vec
-                .into_iter()
-                .map(|a| get_st_anime(a, &mikan)),
+                .iter()
+                .map(|a| get_st_anime(a, sources)),
         )
         .await;
 
@@ -60,11 +62,25 @@ pub async fn do_spider_task(
         .await
         .unwrap();
 
-    if !st_anime_vec.is_empty() {
+    let anime_map: HashMap<i32, &models::anime_list::AnimeList> = subscribed_anime_vec
+        .iter()
+        .map(|a| (a.mikan_id, a))
+        .collect();
+
+    // the subgroups of an anime are listed together, a source may answer them in one request
+    let mut st_subgroup_id_map: HashMap<i32, Vec<i32>> = HashMap::new();
+    for st_a in st_anime_vec {
+        st_subgroup_id_map
+            .entry(st_a.mikan_id)
+            .or_default()
+            .push(st_a.subgroup_id);
+    }
+
+    if !st_subgroup_id_map.is_empty() {
         let task_res_vec = join_all(
-            st_anime_vec
-                .into_iter()
-                .map(|st_a| get_st_seed(st_a.mikan_id, st_a.subgroup_id, st_a.anime_type, &mikan)),
+            st_subgroup_id_map
+                .iter()
+                .map(|(id, subgroup_id_vec)| get_st_seed(anime_map[id], subgroup_id_vec, sources)),
         )
         .await;
 
@@ -83,29 +99,36 @@ pub async fn do_spider_task(
 }
 
 pub async fn get_st_anime(
-    a: models::anime_list::AnimeList,
-    mikan: &spider::Mikan,
+    a: &models::anime_list::AnimeList,
+    sources: &SeedSources,
 ) -> Result<Vec<SpiderTaskAnime>, ()> {
-    match mikan.get_subgroup(a.mikan_id).await {
+    let source = match sources.get(&a.source) {
+        Some(source) => source,
+        None => {
+            log::warn!("seed source [{}] of {} is not enabled", a.source, a.anime_name);
+            return Err(());
+        }
+    };
+    match source.list_subgroups(a).await {
         Ok(s_vec) => Ok(convert_to_spidertaskanime(a, &s_vec)),
         Err(_) => Err(()),
     }
 }
 
 pub async fn get_st_seed(
-    mikan_id: i32,
-    subgroup_id: i32,
-    anime_type: i32,
-    mikan: &spider::Mikan,
+    a: &models::anime_list::AnimeList,
+    subgroup_id_vec: &[i32],
+    sources: &SeedSources,
 ) -> Result<Vec<models::anime_seed::AnimeSeedJson>, ()> {
-    match mikan.get_seed(mikan_id, subgroup_id, anime_type).await {
+    let source = sources.get(&a.source).ok_or(())?;
+    match source.list_anime_seeds(a, subgroup_id_vec).await {
         Ok(seed_vec) => Ok(convert_spiderseed_to_animeseed(&seed_vec)),
         Err(_) => Err(()),
     }
 }
 
 pub fn convert_to_spidertaskanime(
-    a: models::anime_list::AnimeList,
+    a: &models::anime_list::AnimeList,
     s_vec: &Vec<spider::Subgroup>,
 ) -> Vec<SpiderTaskAnime> {
     let mut st_anime_vec: Vec<SpiderTaskAnime> = Vec::new();
@@ -133,6 +156,9 @@ pub fn convert_spiderseed_to_animeseed(
             seed_url: s.seed_url.to_string(),
             seed_status: s.seed_status,
             seed_size: s.seed_size.to_string(),
+            publish_date: s.publish_date.to_string(),
+            episode_end: s.episode_end,
+            is_special: s.is_special,
         })
         .collect()
 }
@@ -141,7 +167,9 @@ pub fn convert_spiderseed_to_animeseed(
 mod test {
     use crate::api::spider_task;
     use crate::dao;
-    use crate::mods::spider;
+    use crate::models::enums::SubscribeStatus;
+    use crate::mods::config::Config;
+    use crate::mods::seed_source::SeedSources;
     use crate::Pool;
     use actix_web::web;
     use diesel::r2d2::ConnectionManager;
@@ -157,13 +185,15 @@ mod test {
         let pool = web::Data::new(database_pool);
 
         let db_connection = &mut pool.get().unwrap();
-        let subscribed_anime_vec = dao::anime_list::get_by_subscribestatus(db_connection, 1)
+        let subscribed_anime_vec =
+            dao::anime_list::get_by_subscribestatus(db_connection, SubscribeStatus::Subscribed)
             .await
             .unwrap();
-        let mikan = spider::Mikan::new().unwrap();
+        let config = Config::load_config("./config/config.yaml").await.unwrap();
+        let sources = SeedSources::new(&config).unwrap();
 
         let st_anime_vec =
-            spider_task::do_spider_task(&mikan, subscribed_anime_vec, db_connection).await;
+            spider_task::do_spider_task(&sources, subscribed_anime_vec, db_connection).await;
         let new_seed_vec = dao::anime_seed::add_bulk_with_response(db_connection, st_anime_vec)
             .await
             .unwrap();
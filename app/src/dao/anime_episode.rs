@@ -0,0 +1,52 @@
+use crate::models::anime_episode::*;
+use crate::models::enums::EpisodeType;
+use crate::schema::anime_episode::dsl::*;
+use diesel::dsl::replace_into;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::RunQueryDsl;
+
+// an episode already stored is replaced, bangumi may fix its title or air date
+pub async fn add_vec(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    item_vec: Vec<AnimeEpisodeJson>,
+) -> Result<usize, diesel::result::Error> {
+    let new_episode_vec: Vec<PostAnimeEpisode> = item_vec
+        .iter()
+        .map(|item| PostAnimeEpisode {
+            mikan_id: &item.mikan_id,
+            episode: &item.episode,
+            episode_type: &item.episode_type,
+            title: &item.title,
+            air_date: &item.air_date,
+            duration: &item.duration,
+        })
+        .collect();
+    replace_into(anime_episode)
+        .values(&new_episode_vec)
+        .execute(db_connection)
+}
+
+// all types of episodes, main episodes first
+pub async fn get_by_mikan_id(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    query_mikan_id: i32,
+) -> Result<Vec<AnimeEpisode>, diesel::result::Error> {
+    let result: Vec<AnimeEpisode> = anime_episode
+        .filter(mikan_id.eq(query_mikan_id))
+        .order((episode_type.asc(), episode.asc()))
+        .load::<AnimeEpisode>(db_connection)?;
+    Ok(result)
+}
+
+pub async fn get_main_by_mikan_id(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    query_mikan_id: i32,
+) -> Result<Vec<AnimeEpisode>, diesel::result::Error> {
+    let result: Vec<AnimeEpisode> = anime_episode
+        .filter(mikan_id.eq(query_mikan_id))
+        .filter(episode_type.eq(EpisodeType::Main))
+        .order(episode.asc())
+        .load::<AnimeEpisode>(db_connection)?;
+    Ok(result)
+}
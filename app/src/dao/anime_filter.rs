@@ -1,4 +1,5 @@
 use crate::models::anime_filter::*;
+use crate::mods::config::SpeedLimit;
 use crate::schema::anime_filter::dsl::*;
 use diesel::dsl::{insert_into, update};
 use diesel::prelude::*;
@@ -213,7 +214,7 @@ pub async fn get_local_episode_filter_by_mikan_id(
 ) -> Result<i32, diesel::result::Error> {
     match anime_filter
         .filter(mikan_id.eq(&quary_mikan_id))
-        .filter(filter_type.eq(&"subgroup"))
+        .filter(filter_type.eq(&"episode"))
         .filter(object.eq(&0))
         .first::<AnimeFilter>(db_connection)
     {
@@ -222,6 +223,212 @@ pub async fn get_local_episode_filter_by_mikan_id(
     }
 }
 
+// global: mikan_id 0, local: mikan_id of the anime
+pub async fn add_quality_filter(
+    query_mikan_id: i32,
+    query_type: &str,
+    query_val: i32,
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+) -> Result<AnimeFilter, diesel::result::Error> {
+    let query_object = if query_mikan_id == 0 { 1 } else { 0 };
+    match anime_filter
+        .filter(mikan_id.eq(&query_mikan_id))
+        .filter(filter_type.eq(&query_type))
+        .filter(filter_val.eq(&query_val))
+        .filter(object.eq(&query_object))
+        .first::<AnimeFilter>(db_connection)
+    {
+        Ok(result) => Ok(result),
+        Err(_) => {
+            let new_anime_filer = PostAnimeFilter {
+                mikan_id: &query_mikan_id,
+                filter_type: query_type,
+                filter_val: &query_val,
+                object: &query_object,
+            };
+            insert_into(anime_filter)
+                .values(&new_anime_filer)
+                .execute(db_connection)?;
+            let result = anime_filter.order(id.desc()).first(db_connection)?;
+            Ok(result)
+        }
+    }
+}
+
+// the global rules of query_types are replaced by quality_vec, e.g. on a config change
+pub async fn replace_global_quality_filter(
+    query_types: &[&str],
+    quality_vec: &[(&str, i32)],
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+) -> Result<usize, diesel::result::Error> {
+    db_connection.transaction(|conn| {
+        delete(
+            anime_filter
+                .filter(filter_type.eq_any(query_types))
+                .filter(object.eq(&1)),
+        )
+        .execute(conn)?;
+        let new_anime_filter_vec: Vec<PostAnimeFilter> = quality_vec
+            .iter()
+            .map(|(quality_type, quality_val)| PostAnimeFilter {
+                mikan_id: &0,
+                filter_type: quality_type,
+                filter_val: quality_val,
+                object: &1,
+            })
+            .collect();
+        insert_into(anime_filter)
+            .values(&new_anime_filter_vec)
+            .execute(conn)
+    })
+}
+
+// global: mikan_id 0, local: mikan_id of the anime
+#[allow(dead_code)]
+pub async fn delete_quality_filter(
+    query_mikan_id: i32,
+    query_type: &str,
+    query_val: i32,
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+) -> Result<usize, diesel::result::Error> {
+    let query_object = if query_mikan_id == 0 { 1 } else { 0 };
+    delete(
+        anime_filter
+            .filter(mikan_id.eq(&query_mikan_id))
+            .filter(filter_type.eq(&query_type))
+            .filter(filter_val.eq(&query_val))
+            .filter(object.eq(&query_object)),
+    )
+    .execute(db_connection)
+}
+
+// global + local quality rules (resolution, codec, subtitle, container) of an anime
+#[allow(dead_code)]
+pub async fn get_quality_filter_by_mikan_id(
+    query_mikan_id: &i32,
+    query_types: &[&str],
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+) -> Result<Vec<AnimeFilter>, diesel::result::Error> {
+    anime_filter
+        .filter(filter_type.eq_any(query_types))
+        .filter(
+            object
+                .eq(&1)
+                .or(object.eq(&0).and(mikan_id.eq(&query_mikan_id))),
+        )
+        .order(id.asc())
+        .load::<AnimeFilter>(db_connection)
+}
+
+/*
+    The speed limit (KiB/s) of an anime is stored as a local rule with
+    filter_type download_limit or upload_limit, it replaces config.speed_limit.
+*/
+pub const LIMIT_DOWNLOAD: &str = "download_limit";
+pub const LIMIT_UPLOAD: &str = "upload_limit";
+
+// 0 removes the limit of the anime
+pub async fn set_speed_limit(
+    query_mikan_id: i32,
+    query_type: &str,
+    query_val: i32,
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+) -> Result<(), diesel::result::Error> {
+    delete(
+        anime_filter
+            .filter(mikan_id.eq(&query_mikan_id))
+            .filter(filter_type.eq(&query_type))
+            .filter(object.eq(&0)),
+    )
+    .execute(db_connection)?;
+    if query_val > 0 {
+        insert_into(anime_filter)
+            .values(&PostAnimeFilter {
+                mikan_id: &query_mikan_id,
+                filter_type: query_type,
+                filter_val: &query_val,
+                object: &0,
+            })
+            .execute(db_connection)?;
+    }
+    Ok(())
+}
+
+pub async fn get_speed_limit(
+    query_mikan_id: i32,
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+) -> Result<SpeedLimit, diesel::result::Error> {
+    let mut speed_limit = SpeedLimit::default();
+    for filter in anime_filter
+        .filter(mikan_id.eq(&query_mikan_id))
+        .filter(filter_type.eq_any([LIMIT_DOWNLOAD, LIMIT_UPLOAD]))
+        .filter(object.eq(&0))
+        .load::<AnimeFilter>(db_connection)?
+    {
+        if filter.fiter_type == LIMIT_DOWNLOAD {
+            speed_limit.download = filter.filter_val as i64;
+        } else {
+            speed_limit.upload = filter.filter_val as i64;
+        }
+    }
+    Ok(speed_limit)
+}
+
+/*
+    The grace period (hours) is stored with filter_type grace_hours, the global
+    one (synced from config.anime_config.grace_policy) as mikan_id 0 and object 1,
+    the one of an anime as a local rule which replaces the global one.
+*/
+pub const GRACE_HOURS: &str = "grace_hours";
+
+// a negative value removes the rule, 0 disables the grace period
+pub async fn set_grace_hours(
+    query_mikan_id: i32,
+    query_val: i32,
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+) -> Result<(), diesel::result::Error> {
+    let query_object = if query_mikan_id == 0 { 1 } else { 0 };
+    delete(
+        anime_filter
+            .filter(mikan_id.eq(&query_mikan_id))
+            .filter(filter_type.eq(GRACE_HOURS))
+            .filter(object.eq(&query_object)),
+    )
+    .execute(db_connection)?;
+    if query_val >= 0 {
+        insert_into(anime_filter)
+            .values(&PostAnimeFilter {
+                mikan_id: &query_mikan_id,
+                filter_type: GRACE_HOURS,
+                filter_val: &query_val,
+                object: &query_object,
+            })
+            .execute(db_connection)?;
+    }
+    Ok(())
+}
+
+pub async fn get_grace_hours(
+    query_mikan_id: i32,
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+) -> Result<i32, diesel::result::Error> {
+    let filter_vec = anime_filter
+        .filter(filter_type.eq(GRACE_HOURS))
+        .filter(
+            object
+                .eq(&1)
+                .and(mikan_id.eq(&0))
+                .or(object.eq(&0).and(mikan_id.eq(&query_mikan_id))),
+        )
+        .load::<AnimeFilter>(db_connection)?;
+    let hours = filter_vec
+        .iter()
+        .find(|filter| filter.object == 0)
+        .or(filter_vec.first())
+        .map_or(0, |filter| filter.filter_val);
+    Ok(hours)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -1,4 +1,5 @@
 use crate::models::anime_list::*;
+use crate::models::enums::{AnimeStatus, SubscribeStatus};
 use crate::schema::anime_list::dsl::*;
 use anyhow::Result;
 use diesel::dsl::insert_into;
@@ -32,6 +33,8 @@ pub async fn add(
                 anime_status: &item.anime_status,
                 total_episodes: &item.total_episodes,
                 new_finished_episode: &item.new_finished_episode,
+                seed_mode: &item.seed_mode,
+                source: &item.source,
             };
             insert_into(anime_list)
                 .values(&new_anime_list)
@@ -68,6 +71,8 @@ pub async fn add_vec(
                 anime_status: &item.anime_status,
                 total_episodes: &item.total_episodes,
                 new_finished_episode: &item.new_finished_episode,
+                seed_mode: &item.seed_mode,
+                source: &item.source,
             };
             insert_into(anime_list)
                 .values(&new_anime_list)
@@ -92,7 +97,7 @@ pub async fn get_by_mikanid(
 
 pub async fn get_by_subscribestatus(
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
-    query_subscribestatus: i32,
+    query_subscribestatus: SubscribeStatus,
 ) -> Result<Vec<AnimeList>, diesel::result::Error> {
     let result: Vec<AnimeList> = anime_list
         .filter(subscribe_status.eq(query_subscribestatus))
@@ -104,7 +109,7 @@ pub async fn get_by_subscribestatus(
 pub async fn update_subscribestatus_by_mikanid(
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
     query_mikanid: i32,
-    update_subscribestatus: i32,
+    update_subscribestatus: SubscribeStatus,
 ) -> Result<(), diesel::result::Error> {
     diesel::update(anime_list.filter(mikan_id.eq(query_mikanid)))
         .set(subscribe_status.eq(update_subscribestatus))
@@ -116,7 +121,7 @@ pub async fn update_subscribestatus_by_mikanid(
 pub async fn update_animestatus_by_mikanid(
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
     query_mikanid: i32,
-    update_animestatus: i32,
+    update_animestatus: AnimeStatus,
 ) -> Result<(), diesel::result::Error> {
     diesel::update(anime_list.filter(mikan_id.eq(query_mikanid)))
         .set(anime_status.eq(update_animestatus))
@@ -124,6 +129,18 @@ pub async fn update_animestatus_by_mikanid(
     Ok(())
 }
 
+// update seed_mode by mikan_id
+pub async fn update_seedmode_by_mikanid(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    query_mikanid: i32,
+    update_seedmode: i32,
+) -> Result<(), diesel::result::Error> {
+    diesel::update(anime_list.filter(mikan_id.eq(query_mikanid)))
+        .set(seed_mode.eq(update_seedmode))
+        .execute(db_connection)?;
+    Ok(())
+}
+
 // update bangumi info by mikan_id
 pub async fn update_bangumiinfo_by_mikanid(
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
@@ -137,6 +154,12 @@ pub async fn update_bangumiinfo_by_mikanid(
             bangumi_summary.eq(item.bangumi_summary),
             website.eq(item.website),
             total_episodes.eq(item.total_episodes),
+            air_date.eq(item.air_date),
+            rating_rank.eq(item.rating_rank),
+            rating_votes.eq(item.rating_votes),
+            tags.eq(item.tags),
+            studio.eq(item.studio),
+            alt_names.eq(item.alt_names),
         ))
         .execute(db_connection)?;
     Ok(())
@@ -186,8 +209,8 @@ pub async fn search_by_anime_name(
 
 #[allow(dead_code)]
 pub async fn get_by_subscribe_and_anime_status(
-    query_subscribe_status: &i32,
-    query_anime_status: &i32,
+    query_subscribe_status: &SubscribeStatus,
+    query_anime_status: &AnimeStatus,
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
 ) -> Result<Vec<AnimeList>, diesel::result::Error> {
     let result: Vec<AnimeList> = anime_list
@@ -148,6 +148,19 @@ pub async fn delete_by_torrent_name(
     Ok(())
 }
 
+// keep the progress of an episode when its torrent is replaced by an upgrade
+#[allow(dead_code)]
+pub async fn update_torrent_name(
+    query_torrent_name: &str,
+    new_torrent_name: &str,
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+) -> Result<(), diesel::result::Error> {
+    diesel::update(anime_progress.filter(torrent_name.eq(&query_torrent_name)))
+        .set(torrent_name.eq(new_torrent_name))
+        .execute(db_connection)?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -1,4 +1,5 @@
 use crate::models::anime_seed::*;
+use crate::models::enums::SeedStatus;
 use crate::schema::anime_seed::dsl::*;
 use diesel::dsl::insert_into;
 use diesel::prelude::*;
@@ -31,6 +32,9 @@ pub async fn add(
                 seed_url: &item.seed_url,
                 seed_status: &item.seed_status,
                 seed_size: &item.seed_size,
+                publish_date: &item.publish_date,
+                episode_end: &item.episode_end,
+                is_special: &item.is_special,
             };
             insert_into(anime_seed)
                 .values(&new_anime_seed)
@@ -62,6 +66,9 @@ pub async fn add_bulk(
                 seed_url: &item.seed_url,
                 seed_status: &item.seed_status,
                 seed_size: &item.seed_size,
+                publish_date: &item.publish_date,
+                episode_end: &item.episode_end,
+                is_special: &item.is_special,
             };
             insert_into(anime_seed)
                 .values(&new_anime_seed)
@@ -93,6 +100,9 @@ pub async fn add_bulk_with_response(
                 seed_url: &item.seed_url,
                 seed_status: &item.seed_status,
                 seed_size: &item.seed_size,
+                publish_date: &item.publish_date,
+                episode_end: &item.episode_end,
+                is_special: &item.is_special,
             };
             insert_into(anime_seed)
                 .values(&new_anime_seed)
@@ -115,7 +125,7 @@ pub async fn update_anime_seed_status(
     item: &str, // seed_url
 ) -> Result<(), diesel::result::Error> {
     diesel::update(anime_seed.filter(seed_url.like(format!("%{}%", item))))
-        .set(seed_status.eq(1))
+        .set(seed_status.eq(SeedStatus::Used))
         .execute(db_connection)
         .expect("save failed");
     Ok(())
@@ -124,7 +134,7 @@ pub async fn update_anime_seed_status(
 pub async fn update_seedstatus_by_seedurl(
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
     query_seedurl: &str,
-    update_seedstatus: i32,
+    update_seedstatus: SeedStatus,
 ) -> Result<(), diesel::result::Error> {
     diesel::update(anime_seed.filter(seed_url.eq(query_seedurl)))
         .set(seed_status.eq(update_seedstatus))
@@ -132,11 +142,41 @@ pub async fn update_seedstatus_by_seedurl(
     Ok(())
 }
 
+// info hash, total size and file list parsed from the downloaded seed
+pub async fn update_torrent_meta(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    query_seedurl: &str,
+    new_info_hash: &str,
+    new_total_size: i64,
+    new_file_list: &str,
+) -> Result<(), diesel::result::Error> {
+    diesel::update(anime_seed.filter(seed_url.eq(query_seedurl)))
+        .set((
+            info_hash.eq(new_info_hash),
+            total_size.eq(new_total_size),
+            file_list.eq(new_file_list),
+        ))
+        .execute(db_connection)?;
+    Ok(())
+}
+
+// magnet seeds sent to the torrent client before it fetched their metadata
+pub async fn get_unresolved_magnet_seeds(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+) -> Result<Vec<AnimeSeed>, diesel::result::Error> {
+    anime_seed
+        .filter(seed_url.like("magnet:%"))
+        .filter(seed_status.eq(SeedStatus::Used))
+        .filter(info_hash.ne(""))
+        .filter(total_size.eq(0))
+        .load::<AnimeSeed>(db_connection)
+}
+
 #[allow(dead_code)]
 pub async fn update_seedstatus_by_seedurl_with_response(
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
     query_seedurl: &str,
-    update_seedstatus: i32,
+    update_seedstatus: SeedStatus,
 ) -> Result<DaoResponse<AnimeSeed>, diesel::result::Error> {
     let mut success_vec: Vec<AnimeSeed> = Vec::new();
     let mut failed_vec: Vec<AnimeSeed> = Vec::new();
@@ -156,8 +196,14 @@ pub async fn update_seedstatus_by_seedurl_with_response(
                 episode: 0,
                 seed_name: "".to_string(),
                 seed_url: query_seedurl.to_string(),
-                seed_status: -1,
+                seed_status: SeedStatus::Unknown,
                 seed_size: "".to_string(),
+                publish_date: "".to_string(),
+                episode_end: -1,
+                info_hash: "".to_string(),
+                total_size: 0,
+                file_list: "[]".to_string(),
+                is_special: false,
             });
         }
     }
@@ -179,12 +225,13 @@ pub async fn update_seedstatus_by_mikanid_episode(
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
     query_mikanid: i32,
     query_episode: i32,
-    update_seedstatus: i32,
+    update_seedstatus: SeedStatus,
 ) -> Result<(), diesel::result::Error> {
     diesel::update(
         anime_seed
             .filter(mikan_id.eq(query_mikanid))
-            .filter(episode.eq(query_episode)),
+            .filter(episode.eq(query_episode))
+            .filter(is_special.eq(false)),
     )
     .set(seed_status.eq(update_seedstatus))
     .execute(db_connection)?;
@@ -205,6 +252,17 @@ pub async fn get_anime_seed_by_mikan_id(
     }
 }
 
+// specials are left out, they are only downloaded by hand
+pub async fn get_episode_seed_by_mikan_id(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    query_mikanid: i32,
+) -> Result<Vec<AnimeSeed>, diesel::result::Error> {
+    anime_seed
+        .filter(mikan_id.eq(&query_mikanid))
+        .filter(is_special.eq(false))
+        .load::<AnimeSeed>(db_connection)
+}
+
 #[allow(dead_code)]
 pub async fn get_anime_seed_by_seed_url(
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
@@ -266,6 +324,7 @@ pub async fn get_by_mikanid_and_episode(
     match anime_seed
         .filter(mikan_id.eq(&query_mikanid))
         .filter(episode.eq(&query_episode))
+        .filter(is_special.eq(false))
         .load::<AnimeSeed>(db_connection)
     {
         Ok(result) => Ok(result),
@@ -310,8 +369,11 @@ mod test {
                     .to_string(),
             seed_url: "/Download/20231021/55829bc76527a4868f9fd5c40e769f618f30e85b.torrent"
                 .to_string(),
-            seed_status: 0,
+            seed_status: SeedStatus::Unused,
             seed_size: "349.4MB".to_string(),
+            publish_date: "2023-10-21 18:28:00".to_string(),
+            episode_end: -1,
+            is_special: false,
         };
 
         add(db_connection, test_anime_seed_json).await.unwrap();
@@ -334,8 +396,11 @@ mod test {
                 episode: 1,
                 seed_name: "test_seed_name_0".to_string(),
                 seed_url: "test_seed_url_0".to_string(),
-                seed_status: 0,
+                seed_status: SeedStatus::Unused,
                 seed_size: "test_seed_size_0".to_string(),
+                publish_date: "".to_string(),
+                episode_end: -1,
+                is_special: false,
             },
             AnimeSeedJson {
                 mikan_id: 123,
@@ -343,8 +408,11 @@ mod test {
                 episode: 1,
                 seed_name: "test_seed_name_1".to_string(),
                 seed_url: "test_seed_url_1".to_string(),
-                seed_status: 0,
+                seed_status: SeedStatus::Unused,
                 seed_size: "test_seed_size_1".to_string(),
+                publish_date: "".to_string(),
+                episode_end: -1,
+                is_special: false,
             },
         ];
 
@@ -1,4 +1,5 @@
 use crate::models::anime_task::*;
+use crate::models::enums::{NewStatus, QbTaskStatus, TaskState};
 use crate::schema::anime_task::dsl::*;
 use diesel::dsl::{insert_into, update};
 use diesel::prelude::*;
@@ -18,6 +19,7 @@ pub async fn add(
     {
         Ok(result) => Ok(result),
         Err(_) => {
+            item.state()?;
             let new_anime_task = PostAnimeTask {
                 mikan_id: &item.mikan_id,
                 episode: &item.episode,
@@ -26,6 +28,7 @@ pub async fn add(
                 rename_status: &item.rename_status,
                 filename: &item.filename,
                 is_new: &item.is_new,
+                info_hash: &item.info_hash,
             };
             insert_into(anime_task)
                 .values(&new_anime_task)
@@ -42,6 +45,17 @@ pub async fn update_anime_task(
     torrent_name_val: &str,
     update_data: UpdateAnimeTask,
 ) -> Result<AnimeTask, diesel::result::Error> {
+    if update_data.qb_task_status.is_some() || update_data.rename_status.is_some() {
+        let task: AnimeTask = anime_task
+            .filter(torrent_name.eq(torrent_name_val))
+            .first(db_connection)?;
+        let next = TaskState::from_status(
+            update_data.qb_task_status.unwrap_or(task.qb_task_status),
+            update_data.rename_status.unwrap_or(task.rename_status),
+        )?;
+        task.state()?.transition_to(next)?;
+    }
+
     let target = anime_task.filter(torrent_name.eq(torrent_name_val));
     diesel::update(target)
         .set(&update_data)
@@ -58,6 +72,10 @@ pub async fn add_bulk(
 ) -> Result<i32, diesel::result::Error> {
     let mut success_num: i32 = 0;
 
+    for item in item_vec {
+        item.state()?;
+    }
+
     for item in item_vec {
         if let Err(_) = anime_task
             .filter(torrent_name.eq(&item.torrent_name))
@@ -71,6 +89,7 @@ pub async fn add_bulk(
                 rename_status: &item.rename_status,
                 filename: &item.filename,
                 is_new: &item.is_new,
+                info_hash: &item.info_hash,
             };
             insert_into(anime_task)
                 .values(&new_anime_task)
@@ -126,7 +145,8 @@ pub async fn delete_anime_task_by_torrent_name(
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
     item: &str, // torrent_name
 ) -> Result<(), diesel::result::Error> {
-    let _r = delete(anime_task.filter(torrent_name.like(&item)))
+    let batch_item = format!("{}#%", item);
+    let _r = delete(anime_task.filter(torrent_name.like(&item).or(torrent_name.like(&batch_item))))
         .execute(db_connection)
         .expect("Error deleting anime_task");
     Ok(())
@@ -160,35 +180,77 @@ pub async fn update_qb_task_status(
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
     item: String, // torrent_name
 ) -> Result<(), diesel::result::Error> {
+    let batch_item = format!("{}#%", item);
     if let Ok(_) = anime_task
-        .filter(torrent_name.like(&item))
+        .filter(torrent_name.like(&item).or(torrent_name.like(&batch_item)))
         .first::<AnimeTask>(db_connection)
     {
-        update(anime_task.filter(torrent_name.like(&item)))
-            .set(qb_task_status.eq(1))
-            .execute(db_connection)
-            .expect("save failed");
+        update(
+            anime_task
+                .filter(torrent_name.like(&item).or(torrent_name.like(&batch_item)))
+                .filter(qb_task_status.eq(QbTaskStatus::Downloading)),
+        )
+        .set(qb_task_status.eq(QbTaskStatus::Finished))
+        .execute(db_connection)
+        .expect("save failed");
     }
     Ok(())
 }
 
+// every task of the torrent, a batch torrent has one task per episode
+pub async fn update_qb_task_status_by_info_hash(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    item: &str, // info_hash
+) -> Result<usize, diesel::result::Error> {
+    update(
+        anime_task
+            .filter(info_hash.eq(item))
+            .filter(qb_task_status.eq(QbTaskStatus::Downloading)),
+    )
+    .set(qb_task_status.eq(QbTaskStatus::Finished))
+    .execute(db_connection)
+}
+
+// the torrent has been removed from the torrent client, its renamed files are kept
+pub async fn finish_seeding(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    item: &str, // info_hash
+) -> Result<usize, diesel::result::Error> {
+    update(
+        anime_task
+            .filter(info_hash.eq(item))
+            .filter(qb_task_status.eq(QbTaskStatus::Seeding)),
+    )
+    .set(qb_task_status.eq(QbTaskStatus::Finished))
+    .execute(db_connection)
+}
+
+pub async fn get_by_info_hash(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    item: &str, // info_hash
+) -> Result<Vec<AnimeTask>, diesel::result::Error> {
+    anime_task
+        .filter(info_hash.eq(item))
+        .load::<AnimeTask>(db_connection)
+}
+
 #[allow(dead_code)]
 pub async fn update_task_status(
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
     item: &str, // torrent_name
-    new_qb_status: i32,
-    new_rename_status: i32,
+    new_state: TaskState,
     new_filename: &str,
-    isnew: i32,
+    isnew: NewStatus,
 ) -> Result<(), diesel::result::Error> {
-    if let Ok(_) = anime_task
+    if let Ok(task) = anime_task
         .filter(torrent_name.like(&item))
         .first::<AnimeTask>(db_connection)
     {
+        task.state()?.transition_to(new_state)?;
         update(anime_task.filter(torrent_name.like(&item)))
             .set((
-                qb_task_status.eq(new_qb_status),
-                rename_status.eq(new_rename_status),
+                qb_task_status.eq(new_state.qb_task_status()),
+                rename_status.eq(new_state.rename_status()),
                 filename.eq(new_filename),
                 is_new.eq(isnew),
             ))
@@ -198,6 +260,26 @@ pub async fn update_task_status(
     Ok(())
 }
 
+// point the task of an episode to the torrent which replaced it
+#[allow(dead_code)]
+pub async fn update_torrent_name(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    item: &str, // old torrent_name
+    new_torrent_name: &str,
+    new_info_hash: &str,
+    new_filename: &str,
+) -> Result<(), diesel::result::Error> {
+    update(anime_task.filter(torrent_name.eq(&item)))
+        .set((
+            torrent_name.eq(new_torrent_name),
+            info_hash.eq(new_info_hash),
+            filename.eq(new_filename),
+            is_new.eq(NewStatus::New),
+        ))
+        .execute(db_connection)?;
+    Ok(())
+}
+
 // query all data from anime_task
 pub async fn get_all(
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
@@ -235,7 +317,7 @@ pub async fn get_exist_anime_task_set_by_mikanid(
 
 pub async fn get_by_qbtaskstatus(
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
-    query_qbtaskstatus: i32,
+    query_qbtaskstatus: QbTaskStatus,
 ) -> Result<Vec<AnimeTask>, diesel::result::Error> {
     let result: Vec<AnimeTask> = anime_task
         .filter(qb_task_status.eq(query_qbtaskstatus))
@@ -246,12 +328,11 @@ pub async fn get_by_qbtaskstatus(
 #[allow(dead_code)]
 pub async fn get_by_task_status(
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
-    query_qbtask_status: i32,
-    query_rename_status: i32,
+    query_state: TaskState,
 ) -> Result<Vec<AnimeTask>, diesel::result::Error> {
     let result: Vec<AnimeTask> = anime_task
-        .filter(qb_task_status.eq(query_qbtask_status))
-        .filter(rename_status.eq(query_rename_status))
+        .filter(qb_task_status.eq(query_state.qb_task_status()))
+        .filter(rename_status.eq(query_state.rename_status()))
         .load::<AnimeTask>(db_connection)?;
     Ok(result)
 }
@@ -267,11 +348,27 @@ pub async fn get_by_torrent_name(
     Ok(result)
 }
 
+// the task of a torrent, or the tasks of all episodes inside a batch torrent
+#[allow(dead_code)]
+pub async fn get_by_torrent_name_with_batch(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    query_torrent_name: &str,
+) -> Result<Vec<AnimeTask>, diesel::result::Error> {
+    let result: Vec<AnimeTask> = anime_task
+        .filter(
+            torrent_name
+                .eq(query_torrent_name)
+                .or(torrent_name.like(format!("{}#%", query_torrent_name))),
+        )
+        .load::<AnimeTask>(db_connection)?;
+    Ok(result)
+}
+
 #[allow(dead_code)]
 pub async fn update_isnew_status(
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
     item: &str, // torrent_name
-    isnew: i32,
+    isnew: NewStatus,
 ) -> Result<(), diesel::result::Error> {
     if let Ok(_) = anime_task
         .filter(torrent_name.like(&item))
@@ -288,6 +385,7 @@ pub async fn update_isnew_status(
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::models::enums::RenameStatus;
     use crate::Pool;
     use actix_web::web;
     use diesel::r2d2::ConnectionManager;
@@ -307,10 +405,11 @@ mod test {
             mikan_id: 3061,
             episode: 1,
             torrent_name: "test_torrent_name".to_string(),
-            qb_task_status: 0,
-            rename_status: 0,
+            qb_task_status: QbTaskStatus::Downloading,
+            rename_status: RenameStatus::Pending,
             filename: "test_file_name".to_string(),
-            is_new: 0,
+            is_new: NewStatus::Watched,
+            info_hash: String::new(),
         };
 
         add(db_connection, &test_anime_task_json).await.unwrap();
@@ -331,19 +430,21 @@ mod test {
                 mikan_id: 3061,
                 episode: 1,
                 torrent_name: "test_torrent_name_1".to_string(),
-                qb_task_status: 0,
-                rename_status: 0,
+                qb_task_status: QbTaskStatus::Downloading,
+                rename_status: RenameStatus::Pending,
                 filename: "test_file_name_1".to_string(),
-                is_new: 1,
+                is_new: NewStatus::New,
+                info_hash: String::new(),
             },
             AnimeTaskJson {
                 mikan_id: 114514,
                 episode: 1919810,
                 torrent_name: "test_torrent_name_2".to_string(),
-                qb_task_status: 0,
-                rename_status: 0,
+                qb_task_status: QbTaskStatus::Downloading,
+                rename_status: RenameStatus::Pending,
                 filename: "test_file_name_2".to_string(),
-                is_new: 1,
+                is_new: NewStatus::New,
+                info_hash: String::new(),
             },
         ];
         add_bulk(db_connection, &test_anime_task_json)
@@ -0,0 +1,81 @@
+use crate::models::anime_task_upgrade::*;
+use crate::schema::anime_task_upgrade::dsl::*;
+use chrono::Local;
+use diesel::dsl::{insert_into, update};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::RunQueryDsl;
+
+fn now() -> String {
+    Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+// insert a downloading upgrade
+#[allow(dead_code)]
+pub async fn add(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    item: &AnimeTaskUpgradeJson,
+) -> Result<AnimeTaskUpgrade, diesel::result::Error> {
+    let create_time = now();
+    let new_upgrade = PostAnimeTaskUpgrade {
+        mikan_id: &item.mikan_id,
+        episode: &item.episode,
+        old_torrent_name: &item.old_torrent_name,
+        new_torrent_name: &item.new_torrent_name,
+        old_filename: &item.old_filename,
+        new_filename: "",
+        upgrade_status: &0,
+        reason: &item.reason,
+        created_at: &create_time,
+        finished_at: "",
+        new_info_hash: &item.new_info_hash,
+    };
+    insert_into(anime_task_upgrade)
+        .values(&new_upgrade)
+        .execute(db_connection)?;
+    let result = anime_task_upgrade
+        .order(id.desc())
+        .first::<AnimeTaskUpgrade>(db_connection)?;
+    Ok(result)
+}
+
+#[allow(dead_code)]
+pub async fn get_by_upgrade_status(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    query_upgrade_status: i32,
+) -> Result<Vec<AnimeTaskUpgrade>, diesel::result::Error> {
+    let result: Vec<AnimeTaskUpgrade> = anime_task_upgrade
+        .filter(upgrade_status.eq(query_upgrade_status))
+        .load::<AnimeTaskUpgrade>(db_connection)?;
+    Ok(result)
+}
+
+#[allow(dead_code)]
+pub async fn get_by_mikan_id(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    query_mikan_id: i32,
+) -> Result<Vec<AnimeTaskUpgrade>, diesel::result::Error> {
+    let result: Vec<AnimeTaskUpgrade> = anime_task_upgrade
+        .filter(mikan_id.eq(query_mikan_id))
+        .order(id.desc())
+        .load::<AnimeTaskUpgrade>(db_connection)?;
+    Ok(result)
+}
+
+// finish an upgrade, upgrade_status: 1 replaced, -1 failed
+#[allow(dead_code)]
+pub async fn update_upgrade_status(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    query_id: i32,
+    new_upgrade_status: i32,
+    new_file_name: &str,
+) -> Result<(), diesel::result::Error> {
+    update(anime_task_upgrade.filter(id.eq(query_id)))
+        .set((
+            upgrade_status.eq(new_upgrade_status),
+            new_filename.eq(new_file_name),
+            finished_at.eq(now()),
+        ))
+        .execute(db_connection)?;
+    Ok(())
+}
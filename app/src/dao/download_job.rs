@@ -0,0 +1,162 @@
+use crate::models::anime_seed::AnimeSeed;
+use crate::models::download_job::*;
+use crate::models::enums::JobStatus;
+use crate::schema::download_job::dsl::*;
+use diesel::dsl::{insert_or_ignore_into, update};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::RunQueryDsl;
+
+// a seed keeps a single job, queueing it again leaves the existing job as it is
+pub async fn enqueue(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    anime_seed: &AnimeSeed,
+    now: &str,
+) -> Result<(), diesel::result::Error> {
+    let new_job = PostDownloadJob {
+        seed_url: &anime_seed.seed_url,
+        mikan_id: &anime_seed.mikan_id,
+        episode: &anime_seed.episode,
+        job_status: &JobStatus::Pending,
+        next_attempt: now,
+        updated_at: now,
+    };
+    insert_or_ignore_into(download_job)
+        .values(&new_job)
+        .execute(db_connection)?;
+    Ok(())
+}
+
+pub async fn get_by_status(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    query_status: JobStatus,
+) -> Result<Vec<DownloadJob>, diesel::result::Error> {
+    let result: Vec<DownloadJob> = download_job
+        .filter(job_status.eq(query_status))
+        .order(id.asc())
+        .load::<DownloadJob>(db_connection)?;
+    Ok(result)
+}
+
+// jobs which are not done, they hold their episode until the seed is fetched or dead
+pub async fn get_unfinished(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+) -> Result<Vec<DownloadJob>, diesel::result::Error> {
+    let result: Vec<DownloadJob> = download_job
+        .filter(job_status.ne(JobStatus::Done))
+        .load::<DownloadJob>(db_connection)?;
+    Ok(result)
+}
+
+pub async fn get_by_mikan_id(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    query_mikan_id: i32,
+) -> Result<Vec<DownloadJob>, diesel::result::Error> {
+    let result: Vec<DownloadJob> = download_job
+        .filter(mikan_id.eq(query_mikan_id))
+        .load::<DownloadJob>(db_connection)?;
+    Ok(result)
+}
+
+pub async fn get_due(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    now: &str,
+) -> Result<Vec<DownloadJob>, diesel::result::Error> {
+    let result: Vec<DownloadJob> = download_job
+        .filter(job_status.eq(JobStatus::Pending))
+        .filter(next_attempt.le(now))
+        .order(next_attempt.asc())
+        .load::<DownloadJob>(db_connection)?;
+    Ok(result)
+}
+
+// pending -> running, false when another worker took the job first
+pub async fn claim(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    job_id: i32,
+    now: &str,
+) -> Result<bool, diesel::result::Error> {
+    let rows = update(
+        download_job
+            .filter(id.eq(job_id))
+            .filter(job_status.eq(JobStatus::Pending)),
+    )
+    .set((job_status.eq(JobStatus::Running), updated_at.eq(now)))
+    .execute(db_connection)?;
+    Ok(rows == 1)
+}
+
+pub async fn mark_done(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    job_id: i32,
+    now: &str,
+) -> Result<(), diesel::result::Error> {
+    update(download_job.filter(id.eq(job_id)))
+        .set((
+            job_status.eq(JobStatus::Done),
+            last_error.eq(""),
+            updated_at.eq(now),
+        ))
+        .execute(db_connection)?;
+    Ok(())
+}
+
+// back to pending until next_attempt, or dead when there is no attempt left
+pub async fn mark_failed(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    job_id: i32,
+    error: &str,
+    new_next_attempt: Option<&str>,
+    now: &str,
+) -> Result<(), diesel::result::Error> {
+    let new_status = match new_next_attempt {
+        Some(_) => JobStatus::Pending,
+        None => JobStatus::Dead,
+    };
+    update(download_job.filter(id.eq(job_id)))
+        .set((
+            job_status.eq(new_status),
+            attempts.eq(attempts + 1),
+            next_attempt.eq(new_next_attempt.unwrap_or("")),
+            last_error.eq(error),
+            updated_at.eq(now),
+        ))
+        .execute(db_connection)?;
+    Ok(())
+}
+
+// a dead job starts over with all its attempts, returns false when the job is not dead
+pub async fn retry(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    job_id: i32,
+    now: &str,
+) -> Result<bool, diesel::result::Error> {
+    let rows = update(
+        download_job
+            .filter(id.eq(job_id))
+            .filter(job_status.eq(JobStatus::Dead)),
+    )
+    .set((
+        job_status.eq(JobStatus::Pending),
+        attempts.eq(0),
+        next_attempt.eq(now),
+        updated_at.eq(now),
+    ))
+    .execute(db_connection)?;
+    Ok(rows == 1)
+}
+
+// a job left running by a stopped process is queued again on start
+pub async fn reset_running(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    now: &str,
+) -> Result<usize, diesel::result::Error> {
+    let rows = update(download_job.filter(job_status.eq(JobStatus::Running)))
+        .set((
+            job_status.eq(JobStatus::Pending),
+            next_attempt.eq(now),
+            updated_at.eq(now),
+        ))
+        .execute(db_connection)?;
+    Ok(rows)
+}
@@ -1,7 +1,1306 @@
 pub mod anime_broadcast;
+pub mod anime_episode;
 pub mod anime_filter;
 pub mod anime_list;
+pub mod anime_progress;
 pub mod anime_seed;
 pub mod anime_subgroup;
 pub mod anime_task;
-pub mod anime_progress;
\ No newline at end of file
+pub mod anime_task_upgrade;
+pub mod download_job;
+pub mod pending_decision;
+pub mod scheduler_job;
+pub mod subgroup_release;
+
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::sqlite::SqliteConnection;
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use std::error::Error;
+
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
+
+// apply the migrations shipped with the binary, returns the versions that were run
+pub fn run_migrations(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+    let versions = db_connection.run_pending_migrations(MIGRATIONS)?;
+    Ok(versions.iter().map(|v| v.to_string()).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::models::anime_broadcast::AnimeBroadcastJson;
+    use crate::models::anime_episode::AnimeEpisodeJson;
+    use crate::models::anime_list::{AnimeListJson, BangumiInfoJson};
+    use crate::models::anime_progess::AnimeProgressJson;
+    use crate::models::anime_seed::AnimeSeedJson;
+    use crate::models::anime_subgroup::AnimeSubgroupJson;
+    use crate::models::anime_task::{AnimeTaskJson, UpdateAnimeTask};
+    use crate::models::anime_task_upgrade::AnimeTaskUpgradeJson;
+    use crate::models::enums::{
+        AnimeStatus, AnimeType, EpisodeType, JobStatus, NewStatus, QbTaskStatus, RenameStatus,
+        SeedStatus, SubscribeStatus, TaskState, UpdateDay,
+    };
+    use crate::models::pending_decision::PendingDecisionJson;
+    use crate::models::scheduler_job::SchedulerJobJson;
+    use crate::models::subgroup_release::SubgroupReleaseJson;
+    use crate::Pool;
+
+    // every connection to ":memory:" is a new database, so the pool holds a single one
+    fn memory_pool() -> Pool {
+        let pool = Pool::builder()
+            .max_size(1)
+            .build(ConnectionManager::<SqliteConnection>::new(":memory:"))
+            .expect("Failed to create pool.");
+        run_migrations(&mut pool.get().unwrap()).unwrap();
+        pool
+    }
+
+    fn anime_list_json(mikan_id: i32, anime_name: &str) -> AnimeListJson {
+        AnimeListJson {
+            mikan_id,
+            anime_name: anime_name.to_string(),
+            update_day: UpdateDay::Monday,
+            img_url: "/images/Bangumi/202310/69e733eb.jpg".to_string(),
+            anime_type: AnimeType::Tv,
+            subscribe_status: SubscribeStatus::Unsubscribed,
+            bangumi_id: -1,
+            bangumi_rank: "".to_string(),
+            bangumi_summary: "".to_string(),
+            website: "".to_string(),
+            anime_status: AnimeStatus::Unknown,
+            total_episodes: -1,
+            new_finished_episode: 0,
+            seed_mode: 0,
+            source: "mikan".to_string(),
+        }
+    }
+
+    fn anime_seed_json(mikan_id: i32, subgroup_id: i32, episode: i32, hash: &str) -> AnimeSeedJson {
+        AnimeSeedJson {
+            mikan_id,
+            subgroup_id,
+            episode,
+            seed_name: format!("[Sub] Anime - {:02} [1080p]", episode),
+            seed_url: format!("/Download/20231006/{}.torrent", hash),
+            seed_status: SeedStatus::Unused,
+            seed_size: "500MB".to_string(),
+            publish_date: "2023-10-06 17:01:12".to_string(),
+            episode_end: -1,
+            is_special: false,
+        }
+    }
+
+    fn anime_task_json(mikan_id: i32, episode: i32, torrent_name: &str) -> AnimeTaskJson {
+        AnimeTaskJson {
+            mikan_id,
+            episode,
+            torrent_name: torrent_name.to_string(),
+            qb_task_status: QbTaskStatus::Downloading,
+            rename_status: RenameStatus::Pending,
+            filename: "".to_string(),
+            is_new: NewStatus::Watched,
+            info_hash: torrent_name.split('.').next().unwrap().to_string(),
+        }
+    }
+
+    #[test]
+    fn test_run_migrations() {
+        let pool = Pool::builder()
+            .max_size(1)
+            .build(ConnectionManager::<SqliteConnection>::new(":memory:"))
+            .unwrap();
+        let db_connection = &mut pool.get().unwrap();
+        let versions = run_migrations(db_connection).unwrap();
+        assert_eq!(versions.len(), 11);
+        // applied migrations are recorded, a restart runs nothing
+        assert!(run_migrations(db_connection).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_clamp_legacy_enums() {
+        use diesel::RunQueryDsl;
+
+        let pool = memory_pool();
+        let db_connection = &mut pool.get().unwrap();
+        anime_list::add_vec(
+            db_connection,
+            vec![
+                anime_list_json(3143, "葬送的芙莉莲"),
+                anime_list_json(3144, "米奇与达利"),
+            ],
+        )
+        .await
+        .unwrap();
+        diesel::sql_query(
+            "UPDATE anime_list SET update_day = 0, anime_type = 5, anime_status = 3 WHERE mikan_id = 3143",
+        )
+        .execute(db_connection)
+        .unwrap();
+        diesel::sql_query(
+            "UPDATE anime_list SET update_day = 0, anime_type = 1 WHERE mikan_id = 3144",
+        )
+        .execute(db_connection)
+        .unwrap();
+        assert!(anime_list::get_by_mikanid(db_connection, 3143)
+            .await
+            .is_err());
+
+        // the legacy rows are loadable once the clamp migration has run over them
+        db_connection.revert_last_migration(MIGRATIONS).unwrap();
+        assert_eq!(run_migrations(db_connection).unwrap().len(), 1);
+        let anime = anime_list::get_by_mikanid(db_connection, 3143)
+            .await
+            .unwrap();
+        assert_eq!(
+            (anime.update_day, anime.anime_type, anime.anime_status),
+            (UpdateDay::Sunday, AnimeType::Tv, AnimeStatus::Unknown)
+        );
+        let anime = anime_list::get_by_mikanid(db_connection, 3144)
+            .await
+            .unwrap();
+        assert_eq!(
+            (anime.update_day, anime.anime_type),
+            (UpdateDay::Movie, AnimeType::Movie)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_anime_list() {
+        let pool = memory_pool();
+        let db_connection = &mut pool.get().unwrap();
+
+        anime_list::add(db_connection, anime_list_json(3143, "葬送的芙莉莲"))
+            .await
+            .unwrap();
+        let num = anime_list::add_vec(
+            db_connection,
+            vec![
+                anime_list_json(3143, "葬送的芙莉莲"),
+                anime_list_json(3144, "米奇与达利"),
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(num, 1);
+        assert_eq!(anime_list::get_all(db_connection).await.unwrap().len(), 2);
+
+        anime_list::update_subscribestatus_by_mikanid(
+            db_connection,
+            3143,
+            SubscribeStatus::Subscribed,
+        )
+        .await
+        .unwrap();
+        anime_list::update_animestatus_by_mikanid(db_connection, 3143, AnimeStatus::Airing)
+            .await
+            .unwrap();
+        anime_list::update_seedmode_by_mikanid(db_connection, 3143, 1)
+            .await
+            .unwrap();
+        anime_list::update_bangumiinfo_by_mikanid(
+            db_connection,
+            3143,
+            BangumiInfoJson {
+                bangumi_id: 400602,
+                bangumi_rank: "9.1".to_string(),
+                bangumi_summary: "summary".to_string(),
+                website: "https://frieren-anime.jp".to_string(),
+                total_episodes: 28,
+                air_date: "2023-09-29".to_string(),
+                rating_rank: 1,
+                rating_votes: 20000,
+                tags: r#"["奇幻"]"#.to_string(),
+                studio: "MADHOUSE".to_string(),
+                alt_names: r#"["Frieren"]"#.to_string(),
+            },
+        )
+        .await
+        .unwrap();
+        anime_list::update_new_finished_episode_nb(db_connection, &3143, &2)
+            .await
+            .unwrap();
+
+        let anime = anime_list::get_by_mikanid(db_connection, 3143)
+            .await
+            .unwrap();
+        assert_eq!(anime.subscribe_status, SubscribeStatus::Subscribed);
+        assert_eq!(anime.anime_status, AnimeStatus::Airing);
+        assert_eq!(anime.seed_mode, 1);
+        assert_eq!(anime.bangumi_id, 400602);
+        assert_eq!(anime.total_episodes, 28);
+        assert_eq!(anime.studio, "MADHOUSE");
+        assert_eq!(anime.rating_rank, 1);
+        assert_eq!(anime.source, "mikan");
+        assert_eq!(
+            anime_list::get_new_finished_episode_nb(db_connection, &3143)
+                .await
+                .unwrap(),
+            2
+        );
+
+        assert_eq!(
+            anime_list::get_by_subscribestatus(db_connection, SubscribeStatus::Subscribed)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            anime_list::get_by_subscribe_and_anime_status(
+                &SubscribeStatus::Subscribed,
+                &AnimeStatus::Airing,
+                db_connection
+            )
+            .await
+            .unwrap()
+            .len(),
+            1
+        );
+        assert_eq!(
+            anime_list::get_mikanid_by_anime_name("米奇与达利", db_connection)
+                .await
+                .unwrap(),
+            3144
+        );
+        assert_eq!(
+            anime_list::search_by_anime_name(db_connection, "芙莉莲")
+                .await
+                .unwrap()[0]
+                .mikan_id,
+            3143
+        );
+
+        assert_eq!(
+            anime_list::del_by_mikan_id(db_connection, 3144)
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(anime_list::get_by_mikanid(db_connection, 3144)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_anime_broadcast() {
+        let pool = memory_pool();
+        let db_connection = &mut pool.get().unwrap();
+
+        anime_broadcast::add(
+            db_connection,
+            AnimeBroadcastJson {
+                mikan_id: 3143,
+                year: 2023,
+                season: 4,
+            },
+        )
+        .await
+        .unwrap();
+        let num = anime_broadcast::add_vec(
+            db_connection,
+            vec![
+                AnimeBroadcastJson {
+                    mikan_id: 3143,
+                    year: 2023,
+                    season: 4,
+                },
+                AnimeBroadcastJson {
+                    mikan_id: 3144,
+                    year: 2024,
+                    season: 1,
+                },
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(num, 1);
+        assert_eq!(
+            anime_broadcast::get_by_year_season(db_connection, 2023, 4)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            anime_broadcast::get_all(db_connection).await.unwrap().len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_anime_subgroup() {
+        let pool = memory_pool();
+        let db_connection = &mut pool.get().unwrap();
+
+        anime_subgroup::add(
+            db_connection,
+            AnimeSubgroupJson {
+                subgroup_id: 583,
+                subgroup_name: "ANi".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+        let num = anime_subgroup::add_vec(
+            db_connection,
+            vec![
+                AnimeSubgroupJson {
+                    subgroup_id: 583,
+                    subgroup_name: "ANi".to_string(),
+                },
+                AnimeSubgroupJson {
+                    subgroup_id: 382,
+                    subgroup_name: "喵萌奶茶屋".to_string(),
+                },
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(num, 1);
+        assert_eq!(
+            anime_subgroup::get_by_subgroupid(db_connection, &382)
+                .await
+                .unwrap()
+                .subgroup_name,
+            "喵萌奶茶屋"
+        );
+        assert_eq!(
+            anime_subgroup::get_all(db_connection).await.unwrap().len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_anime_seed() {
+        let pool = memory_pool();
+        let db_connection = &mut pool.get().unwrap();
+
+        let seed = anime_seed::add(db_connection, anime_seed_json(3143, 583, 1, "a1"))
+            .await
+            .unwrap();
+        assert_eq!(seed.episode_end, -1);
+        assert!(seed.info_hash.is_empty());
+        anime_seed::update_torrent_meta(db_connection, &seed.seed_url, "a1", 1024, "[]")
+            .await
+            .unwrap();
+        let seed = anime_seed::get_anime_seed_by_seed_url(db_connection, &seed.seed_url)
+            .await
+            .unwrap();
+        assert_eq!((seed.info_hash.as_str(), seed.total_size), ("a1", 1024));
+
+        // a used magnet seed waits for its metadata until the total size is known
+        let mut magnet = anime_seed_json(3146, 583, 3, "m3");
+        magnet.seed_url = "magnet:?xt=urn:btih:m3".to_string();
+        magnet.seed_status = SeedStatus::Used;
+        anime_seed::add(db_connection, magnet).await.unwrap();
+        anime_seed::update_torrent_meta(db_connection, "magnet:?xt=urn:btih:m3", "m3", 0, "[]")
+            .await
+            .unwrap();
+        assert_eq!(
+            anime_seed::get_unresolved_magnet_seeds(db_connection)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        anime_seed::update_torrent_meta(db_connection, "magnet:?xt=urn:btih:m3", "m3", 512, "[]")
+            .await
+            .unwrap();
+        assert!(anime_seed::get_unresolved_magnet_seeds(db_connection)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            anime_seed::add_bulk(
+                db_connection,
+                vec![
+                    anime_seed_json(3143, 583, 1, "a1"),
+                    anime_seed_json(3143, 583, 2, "a2")
+                ]
+            )
+            .await
+            .unwrap(),
+            1
+        );
+        let response = anime_seed::add_bulk_with_response(
+            db_connection,
+            vec![
+                anime_seed_json(3143, 583, 2, "a2"),
+                anime_seed_json(3143, 382, 2, "b2"),
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.success_vec.len(), 1);
+        assert_eq!(response.failed_vec.len(), 1);
+
+        let seed_url = "/Download/20231006/a1.torrent";
+        anime_seed::update_anime_seed_status(db_connection, seed_url)
+            .await
+            .unwrap();
+        assert_eq!(
+            anime_seed::get_anime_seed_by_seed_url(db_connection, seed_url)
+                .await
+                .unwrap()
+                .seed_status,
+            SeedStatus::Used
+        );
+        anime_seed::update_seedstatus_by_seedurl(db_connection, seed_url, SeedStatus::Unused)
+            .await
+            .unwrap();
+        let response = anime_seed::update_seedstatus_by_seedurl_with_response(
+            db_connection,
+            seed_url,
+            SeedStatus::Used,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.success_vec.len(), 1);
+        anime_seed::update_seedstatus_by_mikanid_episode(db_connection, 3143, 2, SeedStatus::Used)
+            .await
+            .unwrap();
+        assert!(
+            anime_seed::get_by_mikanid_and_episode(db_connection, 3143, 2)
+                .await
+                .unwrap()
+                .iter()
+                .all(|s| s.seed_status == SeedStatus::Used)
+        );
+
+        assert_eq!(
+            anime_seed::get_anime_seed_by_mikan_id(db_connection, 3143)
+                .await
+                .unwrap()
+                .len(),
+            3
+        );
+        assert_eq!(
+            anime_seed::get_by_mikanid_subgeoupid(db_connection, 3143, 382)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        anime_seed::delete_anime_seed_by_seed_url(
+            db_connection,
+            "/Download/20231006/b2.torrent".to_string(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            anime_seed::get_anime_seed_by_mikan_id(db_connection, 3143)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+        anime_seed::delete_anime_seed_by_mikan_id(db_connection, 3143)
+            .await
+            .unwrap();
+        anime_seed::add(db_connection, anime_seed_json(3144, 583, 1, "c1"))
+            .await
+            .unwrap();
+        anime_seed::delete_all(db_connection).await.unwrap();
+        assert!(anime_seed::get_anime_seed_by_mikan_id(db_connection, 3144)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_anime_task() {
+        let pool = memory_pool();
+        let db_connection = &mut pool.get().unwrap();
+
+        anime_task::add(db_connection, &anime_task_json(3143, 1, "a1.torrent"))
+            .await
+            .unwrap();
+        let num = anime_task::add_bulk(
+            db_connection,
+            &vec![
+                anime_task_json(3143, 1, "a1.torrent"),
+                anime_task_json(3143, 2, "b.torrent#2"),
+                anime_task_json(3143, 3, "b.torrent#3"),
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(num, 2);
+        assert_eq!(anime_task::get_all(db_connection).await.unwrap().len(), 3);
+
+        let mut invalid = anime_task_json(3143, 4, "c.torrent");
+        invalid.rename_status = RenameStatus::Renamed;
+        assert!(anime_task::add(db_connection, &invalid).await.is_err());
+        assert!(anime_task::add_bulk(db_connection, &vec![invalid])
+            .await
+            .is_err());
+
+        // the base name of a batch torrent reaches the task of every episode
+        anime_task::update_qb_task_status(db_connection, "b.torrent".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            anime_task::get_by_qbtaskstatus(db_connection, QbTaskStatus::Finished)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            anime_task::get_by_torrent_name_with_batch(db_connection, "b.torrent")
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+
+        // a task is renamed only after it is downloaded
+        assert!(anime_task::update_task_status(
+            db_connection,
+            "a1.torrent",
+            TaskState::Renamed,
+            "a1.mp4",
+            NewStatus::New
+        )
+        .await
+        .is_err());
+        anime_task::update_qb_task_status(db_connection, "a1.torrent".to_string())
+            .await
+            .unwrap();
+        anime_task::update_task_status(
+            db_connection,
+            "a1.torrent",
+            TaskState::Renamed,
+            "a1.mp4",
+            NewStatus::New,
+        )
+        .await
+        .unwrap();
+        assert!(anime_task::update_task_status(
+            db_connection,
+            "a1.torrent",
+            TaskState::Downloading,
+            "",
+            NewStatus::Watched
+        )
+        .await
+        .is_err());
+        assert_eq!(
+            anime_task::get_by_task_status(db_connection, TaskState::Renamed)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        anime_task::update_isnew_status(db_connection, "a1.torrent", NewStatus::Watched)
+            .await
+            .unwrap();
+        let task = anime_task::update_anime_task(
+            db_connection,
+            "a1.torrent",
+            UpdateAnimeTask {
+                mikan_id: None,
+                episode: None,
+                qb_task_status: None,
+                rename_status: None,
+                filename: Some("a1.mkv".to_string()),
+                is_new: None,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(task.filename, "a1.mkv");
+        assert!(anime_task::update_anime_task(
+            db_connection,
+            "a1.torrent",
+            UpdateAnimeTask {
+                qb_task_status: Some(QbTaskStatus::Downloading),
+                ..Default::default()
+            },
+        )
+        .await
+        .is_err());
+        assert_eq!(task.is_new, NewStatus::Watched);
+
+        anime_task::update_torrent_name(db_connection, "a1.torrent", "a2.torrent", "a2", "a2.mkv")
+            .await
+            .unwrap();
+        let task = anime_task::get_by_torrent_name(db_connection, "a2.torrent")
+            .await
+            .unwrap();
+        assert_eq!((task.episode, task.is_new), (1, NewStatus::New));
+        assert_eq!(task.info_hash, "a2");
+        assert_eq!(
+            anime_task::get_exist_anime_task_by_torrent_name(
+                db_connection,
+                "a2.torrent".to_string()
+            )
+            .await
+            .unwrap()
+            .len(),
+            1
+        );
+        assert!(anime_task::get_exist_anime_task_set(db_connection)
+            .await
+            .unwrap()
+            .contains(&(3143, 3)));
+        assert_eq!(
+            anime_task::get_exist_anime_task_set_by_mikanid(db_connection, 3143)
+                .await
+                .unwrap()
+                .len(),
+            3
+        );
+
+        anime_task::delete_anime_task_by_mikan_id_and_episode(db_connection, 3143, 1)
+            .await
+            .unwrap();
+        anime_task::delete_anime_task_by_torrent_name(db_connection, "b.torrent")
+            .await
+            .unwrap();
+        assert!(
+            anime_task::get_exist_anime_task_by_mikan_id(db_connection, 3143)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        anime_task::add(db_connection, &anime_task_json(3144, 1, "c1.torrent"))
+            .await
+            .unwrap();
+        assert_eq!(
+            anime_task::update_qb_task_status_by_info_hash(db_connection, "c1")
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            anime_task::get_by_torrent_name(db_connection, "c1.torrent")
+                .await
+                .unwrap()
+                .qb_task_status,
+            QbTaskStatus::Finished
+        );
+        // a finished task is not downloading again
+        assert_eq!(
+            anime_task::update_qb_task_status_by_info_hash(db_connection, "c1")
+                .await
+                .unwrap(),
+            0
+        );
+        anime_task::update_task_status(
+            db_connection,
+            "c1.torrent",
+            TaskState::Seeding,
+            "c1.mkv",
+            NewStatus::New,
+        )
+        .await
+        .unwrap();
+        let tasks = anime_task::get_by_info_hash(db_connection, "c1")
+            .await
+            .unwrap();
+        assert_eq!(tasks[0].state().unwrap(), TaskState::Seeding);
+        assert_eq!(
+            anime_task::finish_seeding(db_connection, "c1")
+                .await
+                .unwrap(),
+            1
+        );
+        let tasks = anime_task::get_by_info_hash(db_connection, "c1")
+            .await
+            .unwrap();
+        assert_eq!(tasks[0].state().unwrap(), TaskState::Renamed);
+        anime_task::delete_anime_task_by_mikan_id(db_connection, 3144)
+            .await
+            .unwrap();
+        anime_task::add(db_connection, &anime_task_json(3145, 1, "d1.torrent"))
+            .await
+            .unwrap();
+        anime_task::delete_all(db_connection).await.unwrap();
+        assert!(anime_task::get_all(db_connection).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_anime_progress() {
+        let pool = memory_pool();
+        let db_connection = &mut pool.get().unwrap();
+
+        let mut item = AnimeProgressJson {
+            progress_id: "user".to_string(),
+            mikan_id: 3143,
+            episode: 1,
+            torrent_name: "a1.torrent".to_string(),
+            progress_status: 10,
+        };
+        anime_progress::add_with_mikan_id_and_episode(&item, db_connection)
+            .await
+            .unwrap();
+        item.progress_status = 20;
+        anime_progress::add_with_torrent_name(&item, db_connection)
+            .await
+            .unwrap();
+        assert_eq!(
+            anime_progress::get_by_mikan_id_and_episode("user", &3143, &1, db_connection)
+                .await
+                .unwrap()
+                .progress_status,
+            20
+        );
+
+        anime_progress::update_torrent_name("a1.torrent", "a2.torrent", db_connection)
+            .await
+            .unwrap();
+        assert!(
+            anime_progress::get_by_torrent_name("user", "a2.torrent", db_connection)
+                .await
+                .is_ok()
+        );
+
+        anime_progress::delete_by_torrent_name("user", "a2.torrent", db_connection)
+            .await
+            .unwrap();
+        assert!(
+            anime_progress::get_by_mikan_id_and_episode("user", &3143, &1, db_connection)
+                .await
+                .is_err()
+        );
+
+        anime_progress::add_with_mikan_id_and_episode(&item, db_connection)
+            .await
+            .unwrap();
+        anime_progress::delete_by_mikan_id_and_episode("user", &3143, &1, db_connection)
+            .await
+            .unwrap();
+        anime_progress::add_with_torrent_name(&item, db_connection)
+            .await
+            .unwrap();
+        anime_progress::delete_by_progress_id("user", db_connection)
+            .await
+            .unwrap();
+        assert!(
+            anime_progress::get_by_torrent_name("user", "a1.torrent", db_connection)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_anime_filter() {
+        let pool = memory_pool();
+        let db_connection = &mut pool.get().unwrap();
+
+        anime_filter::add_global_subgroup_filter(&583, db_connection)
+            .await
+            .unwrap();
+        anime_filter::add_global_subgroup_filter(&-382, db_connection)
+            .await
+            .unwrap();
+        let (preference, avoid) = anime_filter::get_global_subgroup_filter_set(db_connection).await;
+        assert!(preference.contains(&583));
+        assert!(avoid.contains(&-382));
+        assert_eq!(
+            anime_filter::delete_global_subgroup_filter_by_subgroup_id(-382, db_connection)
+                .await
+                .unwrap(),
+            1
+        );
+
+        anime_filter::add_local_subgroup_filter_by_mikan_id(3143, 382, db_connection)
+            .await
+            .unwrap();
+        anime_filter::add_local_subgroup_filter_by_mikan_id(3143, -583, db_connection)
+            .await
+            .unwrap();
+        let (preference, avoid) =
+            anime_filter::get_local_subgroup_filter_set_by_mikan_id(&3143, db_connection)
+                .await
+                .unwrap();
+        assert!(preference.contains(&382));
+        assert!(avoid.contains(&-583));
+        assert_eq!(
+            anime_filter::delete_local_subgroup_filter_by_mikan_id(3143, 382, db_connection)
+                .await
+                .unwrap(),
+            1
+        );
+
+        anime_filter::add_local_episode_filter_by_mikan_id(3143, 5, db_connection)
+            .await
+            .unwrap();
+        anime_filter::add_local_episode_filter_by_mikan_id(3143, 7, db_connection)
+            .await
+            .unwrap();
+        assert_eq!(
+            anime_filter::get_local_episode_filter_by_mikan_id(&3143, db_connection)
+                .await
+                .unwrap(),
+            7
+        );
+        assert_eq!(
+            anime_filter::delete_local_episode_filter_by_mikan_id(3143, db_connection)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            anime_filter::get_local_episode_filter_by_mikan_id(&3143, db_connection)
+                .await
+                .unwrap(),
+            0
+        );
+
+        anime_filter::add_quality_filter(0, "resolution", 1080, db_connection)
+            .await
+            .unwrap();
+        let local = anime_filter::add_quality_filter(3143, "codec", -2, db_connection)
+            .await
+            .unwrap();
+        assert_eq!(local.object, 0);
+        anime_filter::add_quality_filter(3144, "codec", 1, db_connection)
+            .await
+            .unwrap();
+        let rules = anime_filter::get_quality_filter_by_mikan_id(
+            &3143,
+            &["resolution", "codec"],
+            db_connection,
+        )
+        .await
+        .unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(
+            anime_filter::delete_quality_filter(0, "resolution", 1080, db_connection)
+                .await
+                .unwrap(),
+            1
+        );
+
+        // a second replace drops the global rules of the first one, local rules are kept
+        for quality_vec in [
+            vec![("resolution", 1080), ("codec", 1)],
+            vec![("resolution", 720)],
+        ] {
+            anime_filter::replace_global_quality_filter(
+                &["resolution", "codec"],
+                &quality_vec,
+                db_connection,
+            )
+            .await
+            .unwrap();
+        }
+        let rules = anime_filter::get_quality_filter_by_mikan_id(
+            &3143,
+            &["resolution", "codec"],
+            db_connection,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            rules
+                .iter()
+                .map(|r| (r.mikan_id, r.filter_val))
+                .collect::<Vec<_>>(),
+            [(3143, -2), (0, 720)]
+        );
+
+        anime_filter::set_speed_limit(3143, anime_filter::LIMIT_DOWNLOAD, 1024, db_connection)
+            .await
+            .unwrap();
+        anime_filter::set_speed_limit(3143, anime_filter::LIMIT_DOWNLOAD, 2048, db_connection)
+            .await
+            .unwrap();
+        anime_filter::set_speed_limit(3143, anime_filter::LIMIT_UPLOAD, 256, db_connection)
+            .await
+            .unwrap();
+        let speed_limit = anime_filter::get_speed_limit(3143, db_connection)
+            .await
+            .unwrap();
+        assert_eq!((speed_limit.download, speed_limit.upload), (2048, 256));
+        anime_filter::set_speed_limit(3143, anime_filter::LIMIT_UPLOAD, 0, db_connection)
+            .await
+            .unwrap();
+        let speed_limit = anime_filter::get_speed_limit(3143, db_connection)
+            .await
+            .unwrap();
+        assert_eq!((speed_limit.download, speed_limit.upload), (2048, 0));
+
+        // the grace period of an anime replaces the global one
+        for (query_mikan_id, hours, expected) in [(0, 12, 12), (3143, 0, 0), (3143, -1, 12)] {
+            anime_filter::set_grace_hours(query_mikan_id, hours, db_connection)
+                .await
+                .unwrap();
+            let grace_hours = anime_filter::get_grace_hours(3143, db_connection)
+                .await
+                .unwrap();
+            assert_eq!(grace_hours, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_anime_task_upgrade() {
+        let pool = memory_pool();
+        let db_connection = &mut pool.get().unwrap();
+
+        let upgrade = anime_task_upgrade::add(
+            db_connection,
+            &AnimeTaskUpgradeJson {
+                mikan_id: 3143,
+                episode: 1,
+                old_torrent_name: "a1.torrent".to_string(),
+                new_torrent_name: "a2.torrent".to_string(),
+                old_filename: "a1.mp4".to_string(),
+                reason: "1080p HEVC".to_string(),
+                new_info_hash: "a2".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(upgrade.upgrade_status, 0);
+        assert_eq!(
+            anime_task_upgrade::get_by_upgrade_status(db_connection, 0)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        anime_task_upgrade::update_upgrade_status(db_connection, upgrade.id.unwrap(), 1, "a2.mkv")
+            .await
+            .unwrap();
+        let upgrade = &anime_task_upgrade::get_by_mikan_id(db_connection, 3143)
+            .await
+            .unwrap()[0];
+        assert_eq!(upgrade.upgrade_status, 1);
+        assert_eq!(upgrade.new_filename, "a2.mkv");
+        assert!(!upgrade.finished_at.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_job() {
+        let pool = memory_pool();
+        let db_connection = &mut pool.get().unwrap();
+
+        let item = SchedulerJobJson {
+            job_name: "seed_refresh".to_string(),
+            schedule: "@every 2m".to_string(),
+            enabled: false,
+        };
+        let job = scheduler_job::add_if_missing(db_connection, &item, "2026-10-17 10:00:00")
+            .await
+            .unwrap();
+        assert!(!job.enabled);
+        assert_eq!(job.next_run, "2026-10-17 10:00:00");
+        assert!(job.last_run.is_empty());
+
+        // a saved schedule is kept on the next start
+        scheduler_job::update_schedule(
+            db_connection,
+            "seed_refresh",
+            "@every 10m",
+            true,
+            "2026-10-17 10:10:00",
+        )
+        .await
+        .unwrap();
+        let job = scheduler_job::add_if_missing(db_connection, &item, "2026-10-17 11:00:00")
+            .await
+            .unwrap();
+        assert_eq!(job.schedule, "@every 10m");
+        assert!(job.enabled);
+        assert_eq!(job.next_run, "2026-10-17 10:10:00");
+
+        scheduler_job::update_run_result(
+            db_connection,
+            "seed_refresh",
+            "2026-10-17 10:10:00",
+            "2026-10-17 10:20:00",
+            "qbittorrent client not started",
+        )
+        .await
+        .unwrap();
+        scheduler_job::update_next_run(db_connection, "seed_refresh", "2026-10-17 10:12:00")
+            .await
+            .unwrap();
+        let job = scheduler_job::get_by_job_name(db_connection, "seed_refresh")
+            .await
+            .unwrap();
+        assert_eq!(job.last_run, "2026-10-17 10:10:00");
+        assert_eq!(job.next_run, "2026-10-17 10:12:00");
+        assert_eq!(job.last_error, "qbittorrent client not started");
+
+        assert_eq!(
+            scheduler_job::get_all(db_connection).await.unwrap().len(),
+            1
+        );
+        assert!(scheduler_job::get_by_job_name(db_connection, "unknown")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_download_job() {
+        let pool = memory_pool();
+        let db_connection = &mut pool.get().unwrap();
+
+        let seed = anime_seed::add(db_connection, anime_seed_json(3143, 583, 1, "a1"))
+            .await
+            .unwrap();
+        download_job::enqueue(db_connection, &seed, "2026-10-17 10:00:00")
+            .await
+            .unwrap();
+        // queueing a seed again keeps its job
+        download_job::enqueue(db_connection, &seed, "2026-10-17 10:05:00")
+            .await
+            .unwrap();
+        assert!(download_job::get_due(db_connection, "2026-10-17 09:59:59")
+            .await
+            .unwrap()
+            .is_empty());
+        let job_vec = download_job::get_due(db_connection, "2026-10-17 10:00:00")
+            .await
+            .unwrap();
+        assert_eq!(job_vec.len(), 1);
+        let job_id = job_vec[0].id.unwrap();
+        assert_eq!((job_vec[0].mikan_id, job_vec[0].episode), (3143, 1));
+        assert_eq!(
+            download_job::get_by_mikan_id(db_connection, 3143)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // a claimed job is not taken by another worker
+        assert!(
+            download_job::claim(db_connection, job_id, "2026-10-17 10:00:00")
+                .await
+                .unwrap()
+        );
+        assert!(
+            !download_job::claim(db_connection, job_id, "2026-10-17 10:00:00")
+                .await
+                .unwrap()
+        );
+
+        download_job::mark_failed(
+            db_connection,
+            job_id,
+            "timeout",
+            Some("2026-10-17 10:01:00"),
+            "2026-10-17 10:00:00",
+        )
+        .await
+        .unwrap();
+        let job = &download_job::get_by_status(db_connection, JobStatus::Pending)
+            .await
+            .unwrap()[0];
+        assert_eq!(
+            (job.attempts, job.next_attempt.as_str()),
+            (1, "2026-10-17 10:01:00")
+        );
+        assert_eq!(job.last_error, "timeout");
+
+        // no attempt left
+        download_job::claim(db_connection, job_id, "2026-10-17 10:01:00")
+            .await
+            .unwrap();
+        download_job::mark_failed(db_connection, job_id, "404", None, "2026-10-17 10:01:00")
+            .await
+            .unwrap();
+        let dead_vec = download_job::get_by_status(db_connection, JobStatus::Dead)
+            .await
+            .unwrap();
+        assert_eq!((dead_vec.len(), dead_vec[0].attempts), (1, 2));
+        assert_eq!(
+            download_job::get_unfinished(db_connection)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        assert!(
+            download_job::retry(db_connection, job_id, "2026-10-17 11:00:00")
+                .await
+                .unwrap()
+        );
+        assert!(
+            !download_job::retry(db_connection, job_id, "2026-10-17 11:00:00")
+                .await
+                .unwrap()
+        );
+        download_job::claim(db_connection, job_id, "2026-10-17 11:00:00")
+            .await
+            .unwrap();
+        // a running job of a stopped process is queued again
+        assert_eq!(
+            download_job::reset_running(db_connection, "2026-10-17 12:00:00")
+                .await
+                .unwrap(),
+            1
+        );
+        download_job::mark_done(db_connection, job_id, "2026-10-17 12:00:00")
+            .await
+            .unwrap();
+        assert!(download_job::get_unfinished(db_connection)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_anime_episode() {
+        let pool = memory_pool();
+        let db_connection = &mut pool.get().unwrap();
+
+        let episode_json =
+            |episode: i32, episode_type: EpisodeType, title: &str| AnimeEpisodeJson {
+                mikan_id: 3143,
+                episode,
+                episode_type,
+                title: title.to_string(),
+                air_date: "2023-09-29".to_string(),
+                duration: 1440,
+            };
+        anime_episode::add_vec(
+            db_connection,
+            vec![
+                episode_json(2, EpisodeType::Main, ""),
+                episode_json(1, EpisodeType::Main, "冒险的终点"),
+                episode_json(1, EpisodeType::Opening, "勇者"),
+            ],
+        )
+        .await
+        .unwrap();
+        // a refresh replaces the stored episode
+        anime_episode::add_vec(
+            db_connection,
+            vec![episode_json(2, EpisodeType::Main, "魔法")],
+        )
+        .await
+        .unwrap();
+
+        let episode_vec = anime_episode::get_by_mikan_id(db_connection, 3143)
+            .await
+            .unwrap();
+        assert_eq!(episode_vec.len(), 3);
+        assert_eq!(episode_vec[2].episode_type, EpisodeType::Opening);
+        let main_vec = anime_episode::get_main_by_mikan_id(db_connection, 3143)
+            .await
+            .unwrap();
+        assert_eq!(
+            main_vec
+                .iter()
+                .map(|ep| (ep.episode, ep.title.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(1, "冒险的终点"), (2, "魔法")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pending_decision() {
+        let pool = memory_pool();
+        let db_connection = &mut pool.get().unwrap();
+
+        let decision_json = |episode: i32, deadline: &str, subgroup_id: i32| PendingDecisionJson {
+            mikan_id: 3143,
+            episode,
+            first_seen: "2023-10-06 17:00:00".to_string(),
+            deadline: deadline.to_string(),
+            seed_url: format!("/Download/{}-{}.torrent", subgroup_id, episode),
+            seed_name: "".to_string(),
+            subgroup_id,
+            updated_at: "2023-10-06 18:00:00".to_string(),
+        };
+        pending_decision::add_vec(
+            db_connection,
+            &[
+                decision_json(5, "2023-10-07 05:00:00", 615),
+                decision_json(6, "2023-10-07 04:00:00", 615),
+            ],
+        )
+        .await
+        .unwrap();
+        // a held episode is replaced by its new fallback seed
+        pending_decision::add_vec(
+            db_connection,
+            &[decision_json(5, "2023-10-07 05:00:00", 202)],
+        )
+        .await
+        .unwrap();
+
+        let decision_vec = pending_decision::get_all(db_connection).await.unwrap();
+        assert_eq!(
+            decision_vec
+                .iter()
+                .map(|d| (d.episode, d.subgroup_id))
+                .collect::<Vec<_>>(),
+            vec![(6, 615), (5, 202)]
+        );
+
+        pending_decision::delete_by_episodes(db_connection, 3143, &[6, 7])
+            .await
+            .unwrap();
+        let decision_vec = pending_decision::get_by_mikan_id(db_connection, 3143)
+            .await
+            .unwrap();
+        assert_eq!(decision_vec.len(), 1);
+        assert_eq!(decision_vec[0].episode, 5);
+    }
+
+    #[tokio::test]
+    async fn test_subgroup_release() {
+        let pool = memory_pool();
+        let db_connection = &mut pool.get().unwrap();
+
+        let release_json =
+            |episode: i32, first_seen: &str, resolution: i32, size: i64| SubgroupReleaseJson {
+                subgroup_id: 370,
+                mikan_id: 3143,
+                episode,
+                first_seen: first_seen.to_string(),
+                resolution,
+                size,
+            };
+        let new_num = subgroup_release::add_vec(
+            db_connection,
+            vec![
+                release_json(2, "2023-10-06 20:00:00", 720, 0),
+                release_json(1, "2023-09-29 20:00:00", 1080, 1 << 20),
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(new_num, 2);
+        // a later seed of an episode keeps its first seen time
+        let new_num = subgroup_release::add_vec(
+            db_connection,
+            vec![
+                release_json(2, "2023-10-07 20:00:00", 1080, 2 << 20),
+                release_json(1, "2023-09-29 19:00:00", 720, 3 << 20),
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(new_num, 0);
+
+        let release_vec = subgroup_release::get_by_mikan_id(db_connection, 3143)
+            .await
+            .unwrap();
+        assert_eq!(
+            release_vec
+                .iter()
+                .map(|r| (r.episode, r.first_seen.as_str(), r.resolution, r.size))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, "2023-09-29 19:00:00", 1080, 1 << 20),
+                (2, "2023-10-06 20:00:00", 1080, 2 << 20),
+            ]
+        );
+        let release_vec = subgroup_release::get_all(db_connection).await.unwrap();
+        assert_eq!(release_vec.len(), 2);
+    }
+}
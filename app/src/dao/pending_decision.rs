@@ -0,0 +1,61 @@
+use crate::models::pending_decision::*;
+use crate::schema::pending_decision::dsl::*;
+use diesel::dsl::replace_into;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::{delete, RunQueryDsl};
+
+// an episode already held is replaced, the fallback seed may change
+pub async fn add_vec(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    item_vec: &[PendingDecisionJson],
+) -> Result<usize, diesel::result::Error> {
+    let new_decision_vec: Vec<PostPendingDecision> = item_vec
+        .iter()
+        .map(|item| PostPendingDecision {
+            mikan_id: &item.mikan_id,
+            episode: &item.episode,
+            first_seen: &item.first_seen,
+            deadline: &item.deadline,
+            seed_url: &item.seed_url,
+            seed_name: &item.seed_name,
+            subgroup_id: &item.subgroup_id,
+            updated_at: &item.updated_at,
+        })
+        .collect();
+    replace_into(pending_decision)
+        .values(&new_decision_vec)
+        .execute(db_connection)
+}
+
+pub async fn delete_by_episodes(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    query_mikan_id: i32,
+    query_episode_vec: &[i32],
+) -> Result<usize, diesel::result::Error> {
+    delete(
+        pending_decision
+            .filter(mikan_id.eq(query_mikan_id))
+            .filter(episode.eq_any(query_episode_vec)),
+    )
+    .execute(db_connection)
+}
+
+// the earliest deadline first
+pub async fn get_all(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+) -> Result<Vec<PendingDecision>, diesel::result::Error> {
+    pending_decision
+        .order((deadline.asc(), mikan_id.asc(), episode.asc()))
+        .load::<PendingDecision>(db_connection)
+}
+
+pub async fn get_by_mikan_id(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    query_mikan_id: i32,
+) -> Result<Vec<PendingDecision>, diesel::result::Error> {
+    pending_decision
+        .filter(mikan_id.eq(query_mikan_id))
+        .order(episode.asc())
+        .load::<PendingDecision>(db_connection)
+}
@@ -0,0 +1,99 @@
+use crate::models::scheduler_job::*;
+use crate::schema::scheduler_job::dsl::*;
+use diesel::dsl::{insert_into, update};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::RunQueryDsl;
+
+// insert a job on first start, an existing job keeps the schedule saved by the user
+pub async fn add_if_missing(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    item: &SchedulerJobJson,
+    first_run: &str,
+) -> Result<SchedulerJob, diesel::result::Error> {
+    if let Some(result) = scheduler_job
+        .filter(job_name.eq(&item.job_name))
+        .first::<SchedulerJob>(db_connection)
+        .optional()?
+    {
+        return Ok(result);
+    }
+    let new_job = PostSchedulerJob {
+        job_name: &item.job_name,
+        schedule: &item.schedule,
+        enabled: &item.enabled,
+        next_run: first_run,
+    };
+    insert_into(scheduler_job)
+        .values(&new_job)
+        .execute(db_connection)?;
+    let result = scheduler_job
+        .filter(job_name.eq(&item.job_name))
+        .first::<SchedulerJob>(db_connection)?;
+    Ok(result)
+}
+
+pub async fn get_all(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+) -> Result<Vec<SchedulerJob>, diesel::result::Error> {
+    let result: Vec<SchedulerJob> = scheduler_job
+        .order(id.asc())
+        .load::<SchedulerJob>(db_connection)?;
+    Ok(result)
+}
+
+pub async fn get_by_job_name(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    query_job_name: &str,
+) -> Result<SchedulerJob, diesel::result::Error> {
+    let result: SchedulerJob = scheduler_job
+        .filter(job_name.eq(query_job_name))
+        .first::<SchedulerJob>(db_connection)?;
+    Ok(result)
+}
+
+pub async fn update_schedule(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    query_job_name: &str,
+    new_schedule: &str,
+    new_enabled: bool,
+    new_next_run: &str,
+) -> Result<(), diesel::result::Error> {
+    update(scheduler_job.filter(job_name.eq(query_job_name)))
+        .set((
+            schedule.eq(new_schedule),
+            enabled.eq(new_enabled),
+            next_run.eq(new_next_run),
+        ))
+        .execute(db_connection)?;
+    Ok(())
+}
+
+pub async fn update_next_run(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    query_job_name: &str,
+    new_next_run: &str,
+) -> Result<(), diesel::result::Error> {
+    update(scheduler_job.filter(job_name.eq(query_job_name)))
+        .set(next_run.eq(new_next_run))
+        .execute(db_connection)?;
+    Ok(())
+}
+
+// record a finished run, last_error is empty when the run succeeded
+pub async fn update_run_result(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    query_job_name: &str,
+    new_last_run: &str,
+    new_next_run: &str,
+    new_last_error: &str,
+) -> Result<(), diesel::result::Error> {
+    update(scheduler_job.filter(job_name.eq(query_job_name)))
+        .set((
+            last_run.eq(new_last_run),
+            next_run.eq(new_next_run),
+            last_error.eq(new_last_error),
+        ))
+        .execute(db_connection)?;
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use crate::models::subgroup_release::*;
+use crate::schema::subgroup_release::dsl::*;
+use diesel::dsl::{insert_into, update};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::RunQueryDsl;
+
+// a release already stored keeps the earliest first_seen, the highest resolution
+// and the first known size; returns the number of new releases
+pub async fn add_vec(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    item_vec: Vec<SubgroupReleaseJson>,
+) -> Result<usize, diesel::result::Error> {
+    let mut success_num: usize = 0;
+    for item in &item_vec {
+        let existing = subgroup_release
+            .filter(subgroup_id.eq(item.subgroup_id))
+            .filter(mikan_id.eq(item.mikan_id))
+            .filter(episode.eq(item.episode))
+            .first::<SubgroupRelease>(db_connection)
+            .optional()?;
+        match existing {
+            Some(release) => {
+                let new_first_seen = release.first_seen.min(item.first_seen.clone());
+                let new_resolution = release.resolution.max(item.resolution);
+                let new_size = if release.size > 0 {
+                    release.size
+                } else {
+                    item.size
+                };
+                update(subgroup_release.filter(id.eq(release.id)))
+                    .set((
+                        first_seen.eq(new_first_seen),
+                        resolution.eq(new_resolution),
+                        size.eq(new_size),
+                    ))
+                    .execute(db_connection)?;
+            }
+            None => {
+                insert_into(subgroup_release)
+                    .values(&PostSubgroupRelease {
+                        subgroup_id: &item.subgroup_id,
+                        mikan_id: &item.mikan_id,
+                        episode: &item.episode,
+                        first_seen: &item.first_seen,
+                        resolution: &item.resolution,
+                        size: &item.size,
+                    })
+                    .execute(db_connection)?;
+                success_num += 1;
+            }
+        }
+    }
+    Ok(success_num)
+}
+
+pub async fn get_all(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+) -> Result<Vec<SubgroupRelease>, diesel::result::Error> {
+    subgroup_release
+        .order((mikan_id.asc(), episode.asc()))
+        .load::<SubgroupRelease>(db_connection)
+}
+
+pub async fn get_by_mikan_id(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    query_mikan_id: i32,
+) -> Result<Vec<SubgroupRelease>, diesel::result::Error> {
+    subgroup_release
+        .filter(mikan_id.eq(query_mikan_id))
+        .order(episode.asc())
+        .load::<SubgroupRelease>(db_connection)
+}
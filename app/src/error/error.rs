@@ -1,24 +1,287 @@
+use crate::models::enums::InvalidTaskTransition;
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use diesel::result::DatabaseErrorKind;
+use serde::Serialize;
 use std::error::Error;
 use std::fmt;
 
-#[derive(Debug)]
-pub struct AnimeError {
-    message: String,
+/*
+    One error type for the whole app, the variant decides the http status of a
+    failed v2 request, the body is always {"code": "...", "message": "..."}.
+*/
+#[derive(Debug, Clone)]
+pub enum AnimeError {
+    Db(String),
+    Qbittorrent(String),
+    Downloader(String), // transmission, aria2
+    Scraper(String),
+    Ffmpeg(String),
+    Fs(String),
+    NotFound(String),
+    Conflict(String),
+    BadRequest(String),
+    Internal(String),
 }
+
+#[derive(Debug, Serialize)]
+struct AnimeErrorBody<'a> {
+    code: &'a str,
+    message: &'a str,
+}
+
 impl AnimeError {
-    pub fn new(mesg: String) -> Self 
-    {
-        Self {
-            message: mesg
+    pub fn code(&self) -> &'static str {
+        match self {
+            AnimeError::Db(_) => "db",
+            AnimeError::Qbittorrent(_) => "qbittorrent",
+            AnimeError::Downloader(_) => "downloader",
+            AnimeError::Scraper(_) => "scraper",
+            AnimeError::Ffmpeg(_) => "ffmpeg",
+            AnimeError::Fs(_) => "fs",
+            AnimeError::NotFound(_) => "not_found",
+            AnimeError::Conflict(_) => "conflict",
+            AnimeError::BadRequest(_) => "bad_request",
+            AnimeError::Internal(_) => "internal",
         }
-    } 
+    }
 
+    pub fn message(&self) -> &str {
+        match self {
+            AnimeError::Db(message)
+            | AnimeError::Qbittorrent(message)
+            | AnimeError::Downloader(message)
+            | AnimeError::Scraper(message)
+            | AnimeError::Ffmpeg(message)
+            | AnimeError::Fs(message)
+            | AnimeError::NotFound(message)
+            | AnimeError::Conflict(message)
+            | AnimeError::BadRequest(message)
+            | AnimeError::Internal(message) => message,
+        }
+    }
+
+    // prefix the message with what was being done, keeping the variant
+    pub fn context(self, context: &str) -> Self {
+        let message = format!("{}: {}", context, self.message());
+        match self {
+            AnimeError::Db(_) => AnimeError::Db(message),
+            AnimeError::Qbittorrent(_) => AnimeError::Qbittorrent(message),
+            AnimeError::Downloader(_) => AnimeError::Downloader(message),
+            AnimeError::Scraper(_) => AnimeError::Scraper(message),
+            AnimeError::Ffmpeg(_) => AnimeError::Ffmpeg(message),
+            AnimeError::Fs(_) => AnimeError::Fs(message),
+            AnimeError::NotFound(_) => AnimeError::NotFound(message),
+            AnimeError::Conflict(_) => AnimeError::Conflict(message),
+            AnimeError::BadRequest(_) => AnimeError::BadRequest(message),
+            AnimeError::Internal(_) => AnimeError::Internal(message),
+        }
+    }
 }
 
 impl Error for AnimeError {}
 
 impl fmt::Display for AnimeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.message)
+        write!(f, "{}", self.message())
+    }
+}
+
+impl ResponseError for AnimeError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AnimeError::NotFound(_) => StatusCode::NOT_FOUND,
+            AnimeError::Conflict(_) => StatusCode::CONFLICT,
+            AnimeError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AnimeError::Qbittorrent(_) | AnimeError::Downloader(_) | AnimeError::Scraper(_) => {
+                StatusCode::BAD_GATEWAY
+            }
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(AnimeErrorBody {
+            code: self.code(),
+            message: self.message(),
+        })
+    }
+}
+
+// log the error and classify it, message tells what was being done
+pub fn handle_error<E: Into<AnimeError> + fmt::Debug>(e: E, message: &str) -> AnimeError {
+    log::error!("{}, error: {:?}", message, e);
+    e.into().context(message)
+}
+
+impl From<diesel::result::Error> for AnimeError {
+    fn from(e: diesel::result::Error) -> Self {
+        match e {
+            diesel::result::Error::NotFound => AnimeError::NotFound(e.to_string()),
+            diesel::result::Error::SerializationError(ref inner)
+                if inner.is::<InvalidTaskTransition>() =>
+            {
+                AnimeError::Conflict(e.to_string())
+            }
+            diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                AnimeError::Conflict(e.to_string())
+            }
+            _ => AnimeError::Db(e.to_string()),
+        }
+    }
+}
+
+// an error already turned into an actix error by a nested v2 handler
+impl From<actix_web::Error> for AnimeError {
+    fn from(e: actix_web::Error) -> Self {
+        if let Some(anime_error) = e.as_error::<AnimeError>() {
+            return anime_error.clone();
+        }
+        let message = e.to_string();
+        match e.as_response_error().status_code() {
+            StatusCode::NOT_FOUND => AnimeError::NotFound(message),
+            StatusCode::CONFLICT => AnimeError::Conflict(message),
+            StatusCode::BAD_REQUEST => AnimeError::BadRequest(message),
+            _ => AnimeError::Internal(message),
+        }
+    }
+}
+
+impl From<diesel::r2d2::PoolError> for AnimeError {
+    fn from(e: diesel::r2d2::PoolError) -> Self {
+        AnimeError::Db(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for AnimeError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => AnimeError::NotFound(e.to_string()),
+            _ => AnimeError::Fs(e.to_string()),
+        }
+    }
+}
+
+impl From<reqwest::Error> for AnimeError {
+    fn from(e: reqwest::Error) -> Self {
+        AnimeError::Scraper(e.to_string())
+    }
+}
+
+impl From<ffmpeg_next::Error> for AnimeError {
+    fn from(e: ffmpeg_next::Error) -> Self {
+        AnimeError::Ffmpeg(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for AnimeError {
+    fn from(e: serde_json::Error) -> Self {
+        AnimeError::Internal(e.to_string())
+    }
+}
+
+impl From<serde_yml::Error> for AnimeError {
+    fn from(e: serde_yml::Error) -> Self {
+        AnimeError::Internal(e.to_string())
+    }
+}
+
+// errors boxed on the way up keep their kind when it is one of ours
+fn from_boxed(e: Box<dyn Error + Send + Sync + 'static>) -> AnimeError {
+    let e = match e.downcast::<AnimeError>() {
+        Ok(e) => return *e,
+        Err(e) => e,
+    };
+    let e = match e.downcast::<diesel::result::Error>() {
+        Ok(e) => return (*e).into(),
+        Err(e) => e,
+    };
+    let e = match e.downcast::<std::io::Error>() {
+        Ok(e) => return (*e).into(),
+        Err(e) => e,
+    };
+    match e.downcast::<reqwest::Error>() {
+        Ok(e) => (*e).into(),
+        Err(e) => AnimeError::Internal(e.to_string()),
+    }
+}
+
+impl From<anyhow::Error> for AnimeError {
+    fn from(e: anyhow::Error) -> Self {
+        let e = match e.downcast::<AnimeError>() {
+            Ok(e) => return e,
+            Err(e) => e,
+        };
+        let e = match e.downcast::<diesel::result::Error>() {
+            Ok(e) => return e.into(),
+            Err(e) => e,
+        };
+        let e = match e.downcast::<std::io::Error>() {
+            Ok(e) => return e.into(),
+            Err(e) => e,
+        };
+        match e.downcast::<reqwest::Error>() {
+            Ok(e) => e.into(),
+            Err(e) => AnimeError::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<Box<dyn Error + Send + Sync>> for AnimeError {
+    fn from(e: Box<dyn Error + Send + Sync>) -> Self {
+        from_boxed(e)
+    }
+}
+
+impl From<Box<dyn Error>> for AnimeError {
+    fn from(e: Box<dyn Error>) -> Self {
+        match e.downcast::<reqwest::Error>() {
+            Ok(e) => (*e).into(),
+            Err(e) => AnimeError::Internal(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::models::enums::{QbTaskStatus, RenameStatus, TaskState};
+    use actix_web::body::MessageBody;
+
+    #[test]
+    fn test_status_code() {
+        let not_found: AnimeError = diesel::result::Error::NotFound.into();
+        assert_eq!(not_found.status_code(), StatusCode::NOT_FOUND);
+
+        let transition =
+            TaskState::from_status(QbTaskStatus::Downloading, RenameStatus::Renamed).unwrap_err();
+        let conflict: AnimeError = diesel::result::Error::from(transition).into();
+        assert_eq!(conflict.status_code(), StatusCode::CONFLICT);
+
+        let qb = AnimeError::Qbittorrent("login failed".to_string());
+        assert_eq!(qb.status_code(), StatusCode::BAD_GATEWAY);
+
+        let downloader = AnimeError::Downloader("session expired".to_string());
+        assert_eq!(downloader.status_code(), StatusCode::BAD_GATEWAY);
+
+        // the kind survives a round trip through anyhow
+        let anyhow_err = anyhow::Error::new(AnimeError::Scraper("timeout".to_string()));
+        let scraper: AnimeError = anyhow_err.into();
+        assert_eq!(scraper.status_code(), StatusCode::BAD_GATEWAY);
+
+        let other: AnimeError = anyhow::Error::msg("boom").into();
+        assert_eq!(other.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn test_error_response() {
+        let e = handle_error(diesel::result::Error::NotFound, "get anime failed");
+        let resp = e.error_response();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let body = resp.into_body().try_into_bytes().unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "not_found");
+        assert_eq!(json["message"], "get anime failed: Record not found");
     }
 }
@@ -1,10 +1,13 @@
 use actix_files::Files;
 use actix_web::{web, App, HttpServer};
 use api::do_anime_task;
+use api::scheduler::{Scheduler, TIME_FORMAT};
+use chrono::Local;
 use diesel::connection::SimpleConnection;
 use diesel::r2d2::{self, ConnectionManager, PooledConnection};
 use diesel::SqliteConnection;
-use mods::{config::Config, qb_api::QbitTaskExecutor};
+use mods::config::Config;
+use mods::torrent_client::{self, TorrentClient};
 use routers::*;
 use std::fs;
 
@@ -25,16 +28,16 @@ extern crate diesel;
 
 pub type Pool = r2d2::Pool<ConnectionManager<SqliteConnection>>;
 pub type DB = PooledConnection<ConnectionManager<SqliteConnection>>;
-pub type QB = web::Data<Arc<TokioRwLock<QbitTaskExecutor>>>;
+pub type QB = web::Data<Arc<TokioRwLock<Box<dyn TorrentClient>>>>;
 pub type CONFIG = web::Data<Arc<TokioRwLock<Config>>>;
 pub type RWLOCK = web::Data<Arc<TokioRwLock<bool>>>;
 
 pub struct WebData {
     pub pool: Pool,
     pub qb: QB,
-    pub task_status: RWLOCK,
     pub video_file_lock: RWLOCK,
     pub config: CONFIG,
+    pub scheduler: Scheduler,
 }
 
 #[actix_web::main]
@@ -42,9 +45,7 @@ async fn main() -> std::io::Result<()> {
     dotenv::dotenv().ok();
     log4rs::init_file("./config/log4rs.yaml", Default::default()).unwrap();
 
-    let config = Arc::new(TokioRwLock::new(
-        Config::load_config("./config/config.yaml").await.unwrap(),
-    ));
+    let migrate_only = std::env::args().any(|arg| arg == "--migrate-only");
 
     let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
     let database_pool = Pool::builder()
@@ -57,18 +58,32 @@ async fn main() -> std::io::Result<()> {
             .expect("Failed to get a connection from the pool");
         conn.batch_execute("PRAGMA journal_mode=WAL;")
             .expect("Failed to set WAL mode");
+        let versions = dao::run_migrations(&mut conn).expect("Failed to run migrations");
+        for version in &versions {
+            log::info!("applied migration: {}", version);
+        }
     }
 
+    if migrate_only {
+        log::info!("database is up to date, exit");
+        return Ok(());
+    }
+
+    let config = Arc::new(TokioRwLock::new(
+        Config::load_config("./config/config.yaml").await.unwrap(),
+    ));
+
     let conf = config.read().await;
     let download_path = conf.download_path.clone();
+    // path_mapping is validated against the download directory
+    fs::create_dir_all(&download_path).expect("Failed to create download directory");
     let qb = Arc::new(TokioRwLock::new(
-        QbitTaskExecutor::new_with_config(&conf)
+        torrent_client::new_torrent_client(&conf)
             .await
-            .expect("Failed to create qb client"),
+            .expect("Failed to create torrent client"),
     ));
     drop(conf);
 
-    let task_status = Arc::new(TokioRwLock::new(false));
     let video_file_lock = Arc::new(TokioRwLock::new(false));
 
     {
@@ -76,21 +91,30 @@ async fn main() -> std::io::Result<()> {
         do_anime_task::add_default_filter(&config, &mut db_connection)
             .await
             .unwrap();
+        // seeds which were being fetched when the last process stopped
+        let now = Local::now().format(TIME_FORMAT).to_string();
+        dao::download_job::reset_running(&mut db_connection, &now)
+            .await
+            .unwrap();
     }
 
-    let qb_for_task = Arc::clone(&qb);
-    let video_file_lock_for_task = Arc::clone(&video_file_lock);
-    let database_pool_for_task = database_pool.clone();
-    let config_for_task = Arc::clone(&config);
-
-    fs::create_dir_all(&download_path).expect("Failed to create download directory");
+    let scheduler = Scheduler::new(
+        database_pool.clone(),
+        Arc::clone(&qb),
+        Arc::clone(&config),
+        Arc::clone(&video_file_lock),
+    );
+    scheduler
+        .init_jobs()
+        .await
+        .expect("Failed to init scheduler jobs");
 
     let web_data = web::Data::new(WebData {
         pool: database_pool.clone(),
         qb: web::Data::new(qb.clone()),
-        task_status: web::Data::new(task_status.clone()),
         video_file_lock: web::Data::new(video_file_lock.clone()),
         config: web::Data::new(config.clone()),
+        scheduler: scheduler.clone(),
     });
 
     let file_server = HttpServer::new(move || {
@@ -104,7 +128,6 @@ async fn main() -> std::io::Result<()> {
         App::new()
             .app_data(web::Data::new(database_pool.clone()))
             .app_data(web::Data::new(qb.clone()))
-            .app_data(web::Data::new(task_status.clone()))
             .app_data(web::Data::new(video_file_lock.clone()))
             .app_data(web::Data::new(config.clone()))
             .app_data(web_data.clone())
@@ -117,13 +140,7 @@ async fn main() -> std::io::Result<()> {
     .run();
 
     tokio::spawn(async move {
-        let _ = do_anime_task::auto_update_rename_extract(
-            &video_file_lock_for_task,
-            &database_pool_for_task,
-            &qb_for_task,
-            &config_for_task,
-        )
-        .await;
+        scheduler.run().await;
     });
 
     let (http_result, file_result) = tokio::join!(http_server, file_server);
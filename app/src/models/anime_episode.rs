@@ -0,0 +1,36 @@
+use crate::models::enums::EpisodeType;
+use crate::schema::*;
+use serde::{Deserialize, Serialize};
+
+// an episode of the anime on bangumi, air_date is "%Y-%m-%d" or empty when unknown
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, PartialEq)]
+pub struct AnimeEpisode {
+    pub id: Option<i32>,
+    pub mikan_id: i32,
+    pub episode: i32,
+    pub episode_type: EpisodeType,
+    pub title: String,
+    pub air_date: String,
+    pub duration: i32, // seconds, 0 when unknown
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = anime_episode)]
+pub struct PostAnimeEpisode<'a> {
+    pub mikan_id: &'a i32,
+    pub episode: &'a i32,
+    pub episode_type: &'a EpisodeType,
+    pub title: &'a str,
+    pub air_date: &'a str,
+    pub duration: &'a i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnimeEpisodeJson {
+    pub mikan_id: i32,
+    pub episode: i32,
+    pub episode_type: EpisodeType,
+    pub title: String,
+    pub air_date: String,
+    pub duration: i32,
+}
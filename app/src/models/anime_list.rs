@@ -1,3 +1,4 @@
+use crate::models::enums::{AnimeStatus, AnimeType, SubscribeStatus, UpdateDay};
 use crate::schema::*;
 use serde::{Deserialize, Serialize};
 
@@ -6,25 +7,33 @@ pub struct AnimeList {
     pub id: Option<i32>,
     pub mikan_id: i32,
     pub anime_name: String,
-    pub update_day: i32,
+    pub update_day: UpdateDay,
     pub img_url: String,
-    pub anime_type: i32,
-    pub subscribe_status: i32,
+    pub anime_type: AnimeType,
+    pub subscribe_status: SubscribeStatus,
     pub bangumi_id: i32,
-    pub bangumi_rank: String,
+    pub bangumi_rank: String, // bangumi 评分
     pub bangumi_summary: String,
     pub website: String,
-    pub anime_status: i32,   // 默认为-1, 0表示更新中, 1表示完结
+    pub anime_status: AnimeStatus, // 默认为-1, 0表示更新中, 1表示完结
     pub total_episodes: i32, // 默认为-1
     pub new_finished_episode: i32,
+    pub seed_mode: i32, // 0表示网页抓取种子, 1表示rss订阅
+    pub source: String, // 种子来源, mikan / torznab
+    pub air_date: String, // 开播日期, e.g. 2023-10-07
+    pub rating_rank: i32, // bangumi 排名, 默认为-1
+    pub rating_votes: i32,
+    pub tags: String, // json, e.g. ["原创", "搞笑"]
+    pub studio: String, // 动画制作
+    pub alt_names: String, // json, 别名
 }
 
 impl Ord for AnimeList {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         if self.subscribe_status != other.subscribe_status {
-            other.subscribe_status.cmp(&self.subscribe_status)
+            i32::from(other.subscribe_status).cmp(&i32::from(self.subscribe_status))
         } else {
-            self.update_day.cmp(&other.update_day)
+            i32::from(self.update_day).cmp(&i32::from(other.update_day))
         }
     }
 }
@@ -40,34 +49,44 @@ impl PartialOrd for AnimeList {
 pub struct PostAnimeList<'a> {
     pub mikan_id: &'a i32,
     pub anime_name: &'a str,
-    pub update_day: &'a i32,
+    pub update_day: &'a UpdateDay,
     pub img_url: &'a str,
-    pub anime_type: &'a i32,
-    pub subscribe_status: &'a i32,
+    pub anime_type: &'a AnimeType,
+    pub subscribe_status: &'a SubscribeStatus,
     pub bangumi_id: &'a i32,
     pub bangumi_rank: &'a str,
     pub bangumi_summary: &'a str,
     pub website: &'a str,
-    pub anime_status: &'a i32,
+    pub anime_status: &'a AnimeStatus,
     pub total_episodes: &'a i32,
     pub new_finished_episode: &'a i32,
+    pub seed_mode: &'a i32,
+    pub source: &'a str,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AnimeListJson {
     pub mikan_id: i32,
     pub anime_name: String,
-    pub update_day: i32,
+    pub update_day: UpdateDay,
     pub img_url: String,
-    pub anime_type: i32,
-    pub subscribe_status: i32,
+    pub anime_type: AnimeType,
+    pub subscribe_status: SubscribeStatus,
     pub bangumi_id: i32,
     pub bangumi_rank: String,
     pub bangumi_summary: String,
     pub website: String,
-    pub anime_status: i32,
+    pub anime_status: AnimeStatus,
     pub total_episodes: i32,
     pub new_finished_episode: i32,
+    #[serde(default)]
+    pub seed_mode: i32,
+    #[serde(default = "default_source")]
+    pub source: String,
+}
+
+fn default_source() -> String {
+    "mikan".to_string()
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -77,4 +96,10 @@ pub struct BangumiInfoJson {
     pub bangumi_summary: String,
     pub website: String,
     pub total_episodes: i32,
+    pub air_date: String,
+    pub rating_rank: i32,
+    pub rating_votes: i32,
+    pub tags: String,
+    pub studio: String,
+    pub alt_names: String,
 }
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use crate::models::enums::SeedStatus;
 use crate::schema::*;
 
 #[derive(Debug, Serialize, Deserialize, Queryable, Clone)]
@@ -9,8 +10,14 @@ pub struct AnimeSeed {
     pub episode: i32,
     pub seed_name: String,
     pub seed_url: String,
-    pub seed_status: i32,
-    pub seed_size: String
+    pub seed_status: SeedStatus,
+    pub seed_size: String,
+    pub publish_date: String,
+    pub episode_end: i32, // last episode of a batch seed, -1 for single episode seeds
+    pub info_hash: String, // parsed from the downloaded seed, empty until then
+    pub total_size: i64,
+    pub file_list: String, // json of Vec<TorrentFile>
+    pub is_special: bool   // a special like "12.5", episode is the one it follows
 }
 
 #[derive(Debug, Insertable)]
@@ -21,8 +28,11 @@ pub struct PostAnimeSeed<'a> {
     pub episode: &'a i32,
     pub seed_name: &'a str,
     pub seed_url: &'a str,
-    pub seed_status: &'a i32,
-    pub seed_size: &'a str
+    pub seed_status: &'a SeedStatus,
+    pub seed_size: &'a str,
+    pub publish_date: &'a str,
+    pub episode_end: &'a i32,
+    pub is_special: &'a bool
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -32,6 +42,10 @@ pub struct AnimeSeedJson {
     pub episode: i32,
     pub seed_name: String,
     pub seed_url: String,
-    pub seed_status: i32,
-    pub seed_size: String
+    pub seed_status: SeedStatus,
+    pub seed_size: String,
+    pub publish_date: String,
+    pub episode_end: i32,
+    #[serde(default)]
+    pub is_special: bool
 }
\ No newline at end of file
@@ -1,4 +1,7 @@
 use serde::{Deserialize, Serialize};
+use crate::models::enums::{
+    InvalidTaskTransition, NewStatus, QbTaskStatus, RenameStatus, TaskState,
+};
 use crate::schema::*;
 
 #[derive(Debug, Serialize, Deserialize, Queryable)]
@@ -7,10 +10,17 @@ pub struct AnimeTask {
     pub mikan_id: i32,
     pub episode: i32,
     pub torrent_name: String,
-    pub qb_task_status: i32,
-    pub rename_status: i32,
+    pub qb_task_status: QbTaskStatus,
+    pub rename_status: RenameStatus,
     pub filename: String,
-    pub is_new: i32
+    pub is_new: NewStatus,
+    pub info_hash: String
+}
+
+impl AnimeTask {
+    pub fn state(&self) -> Result<TaskState, InvalidTaskTransition> {
+        TaskState::from_status(self.qb_task_status, self.rename_status)
+    }
 }
 
 #[derive(Debug, Insertable)]
@@ -19,10 +29,11 @@ pub struct PostAnimeTask<'a> {
     pub mikan_id: &'a i32,
     pub episode: &'a i32,
     pub torrent_name: &'a str,
-    pub qb_task_status: &'a i32,
-    pub rename_status: &'a i32,
+    pub qb_task_status: &'a QbTaskStatus,
+    pub rename_status: &'a RenameStatus,
     pub filename: &'a String,
-    pub is_new: &'a i32
+    pub is_new: &'a NewStatus,
+    pub info_hash: &'a str
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -30,10 +41,17 @@ pub struct AnimeTaskJson {
     pub mikan_id: i32,
     pub episode: i32,
     pub torrent_name: String,
-    pub qb_task_status: i32,
-    pub rename_status: i32,
+    pub qb_task_status: QbTaskStatus,
+    pub rename_status: RenameStatus,
     pub filename: String,
-    pub is_new: i32
+    pub is_new: NewStatus,
+    pub info_hash: String
+}
+
+impl AnimeTaskJson {
+    pub fn state(&self) -> Result<TaskState, InvalidTaskTransition> {
+        TaskState::from_status(self.qb_task_status, self.rename_status)
+    }
 }
 
 #[derive(AsChangeset, Default)]
@@ -41,8 +59,8 @@ pub struct AnimeTaskJson {
 pub struct UpdateAnimeTask {
     pub mikan_id: Option<i32>,
     pub episode: Option<i32>,
-    pub qb_task_status: Option<i32>,
-    pub rename_status: Option<i32>,
+    pub qb_task_status: Option<QbTaskStatus>,
+    pub rename_status: Option<RenameStatus>,
     pub filename: Option<String>,
-    pub is_new: Option<i32>,
+    pub is_new: Option<NewStatus>,
 }
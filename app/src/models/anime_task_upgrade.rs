@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+use crate::schema::*;
+
+// upgrade_status: 0 downloading, 1 replaced, -1 failed
+#[derive(Debug, Serialize, Deserialize, Queryable)]
+pub struct AnimeTaskUpgrade {
+    pub id: Option<i32>,
+    pub mikan_id: i32,
+    pub episode: i32,
+    pub old_torrent_name: String,
+    pub new_torrent_name: String,
+    pub old_filename: String,
+    pub new_filename: String,
+    pub upgrade_status: i32,
+    pub reason: String,
+    pub created_at: String,
+    pub finished_at: String,
+    pub new_info_hash: String,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = anime_task_upgrade)]
+pub struct PostAnimeTaskUpgrade<'a> {
+    pub mikan_id: &'a i32,
+    pub episode: &'a i32,
+    pub old_torrent_name: &'a str,
+    pub new_torrent_name: &'a str,
+    pub old_filename: &'a str,
+    pub new_filename: &'a str,
+    pub upgrade_status: &'a i32,
+    pub reason: &'a str,
+    pub created_at: &'a str,
+    pub finished_at: &'a str,
+    pub new_info_hash: &'a str,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnimeTaskUpgradeJson {
+    pub mikan_id: i32,
+    pub episode: i32,
+    pub old_torrent_name: String,
+    pub new_torrent_name: String,
+    pub old_filename: String,
+    pub reason: String,
+    pub new_info_hash: String,
+}
@@ -0,0 +1,28 @@
+use crate::models::enums::JobStatus;
+use crate::schema::*;
+use serde::{Deserialize, Serialize};
+
+// a seed file waiting to be fetched, times are local "%Y-%m-%d %H:%M:%S"
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable)]
+pub struct DownloadJob {
+    pub id: Option<i32>,
+    pub seed_url: String,
+    pub mikan_id: i32,
+    pub episode: i32,
+    pub job_status: JobStatus,
+    pub attempts: i32,
+    pub next_attempt: String,
+    pub last_error: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = download_job)]
+pub struct PostDownloadJob<'a> {
+    pub seed_url: &'a str,
+    pub mikan_id: &'a i32,
+    pub episode: &'a i32,
+    pub job_status: &'a JobStatus,
+    pub next_attempt: &'a str,
+    pub updated_at: &'a str,
+}
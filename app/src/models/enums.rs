@@ -0,0 +1,366 @@
+use diesel::backend::Backend;
+use diesel::deserialize::{self, FromSql};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::Integer;
+use diesel::sqlite::Sqlite;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug)]
+pub struct InvalidEnumValue {
+    enum_name: &'static str,
+    value: i32,
+}
+
+impl Error for InvalidEnumValue {}
+
+impl fmt::Display for InvalidEnumValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid {} value: {}", self.enum_name, self.value)
+    }
+}
+
+/*
+    Integer backed enums, stored as INTEGER in sqlite and (de)serialized as the
+    plain integer, so the tables and the json api keep their old values.
+*/
+macro_rules! int_enum {
+    ($(#[$meta:meta])* $name:ident { $($(#[$vmeta:meta])* $variant:ident = $value:expr),+ $(,)? }) => {
+        $(#[$meta])*
+        #[derive(
+            Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, AsExpression, FromSqlRow,
+        )]
+        #[diesel(sql_type = Integer)]
+        #[serde(into = "i32", try_from = "i32")]
+        pub enum $name {
+            $($(#[$vmeta])* $variant = $value),+
+        }
+
+        impl From<$name> for i32 {
+            fn from(value: $name) -> i32 {
+                value as i32
+            }
+        }
+
+        impl TryFrom<i32> for $name {
+            type Error = InvalidEnumValue;
+
+            fn try_from(value: i32) -> Result<Self, Self::Error> {
+                match value {
+                    $($value => Ok($name::$variant),)+
+                    _ => Err(InvalidEnumValue {
+                        enum_name: stringify!($name),
+                        value,
+                    }),
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}", *self as i32)
+            }
+        }
+
+        impl ToSql<Integer, Sqlite> for $name {
+            fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+                out.set_value(*self as i32);
+                Ok(IsNull::No)
+            }
+        }
+
+        impl FromSql<Integer, Sqlite> for $name {
+            fn from_sql(bytes: <Sqlite as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+                let value = <i32 as FromSql<Integer, Sqlite>>::from_sql(bytes)?;
+                Ok($name::try_from(value)?)
+            }
+        }
+    };
+}
+
+int_enum!(
+    // anime_task.qb_task_status
+    QbTaskStatus {
+        Downloading = 0,
+        Finished = 1,
+        Seeding = 2, // renamed, the torrent is kept in the client by the seeding policy
+    }
+);
+
+int_enum!(
+    // anime_task.rename_status
+    RenameStatus {
+        Pending = 0,
+        Renamed = 1,
+    }
+);
+
+int_enum!(
+    // anime_task.is_new, an episode is new after it is renamed, until it is played
+    NewStatus {
+        Watched = 0,
+        New = 1,
+    }
+);
+
+int_enum!(
+    // download_job.job_status, a job is dead after max_attempts failures until it is retried
+    JobStatus {
+        Pending = 0,
+        Running = 1,
+        Done = 2,
+        Dead = 3,
+    }
+);
+
+int_enum!(
+    // anime_episode.episode_type, the episode types of bangumi
+    EpisodeType {
+        Main = 0,
+        Special = 1,
+        Opening = 2,
+        Ending = 3,
+    }
+);
+
+int_enum!(
+    // anime_seed.seed_status
+    SeedStatus {
+        Unused = 0,
+        Used = 1,
+        Unknown = -1, // placeholder of a seed not found in db
+    }
+);
+
+int_enum!(
+    // anime_list.subscribe_status
+    SubscribeStatus {
+        Unsubscribed = 0,
+        Subscribed = 1,
+    }
+);
+
+int_enum!(
+    // anime_list.anime_status
+    AnimeStatus {
+        Unknown = -1,
+        Airing = 0,
+        Finished = 1,
+    }
+);
+
+int_enum!(
+    // anime_list.anime_type
+    AnimeType {
+        Tv = 0,
+        Movie = 1,
+        Ova = 2,
+    }
+);
+
+int_enum!(
+    // anime_list.update_day, 1-7 for monday to sunday, movies and ovas have no update day
+    UpdateDay {
+        Monday = 1,
+        Tuesday = 2,
+        Wednesday = 3,
+        Thursday = 4,
+        Friday = 5,
+        Saturday = 6,
+        Sunday = 7,
+        Movie = 8,
+        Ova = 9,
+    }
+);
+
+impl UpdateDay {
+    // mikan numbers the days 0(sunday)-6, with 7 for movies and 8 for ovas
+    pub fn from_mikan(day_of_week: i32) -> UpdateDay {
+        match day_of_week {
+            1 => UpdateDay::Monday,
+            2 => UpdateDay::Tuesday,
+            3 => UpdateDay::Wednesday,
+            4 => UpdateDay::Thursday,
+            5 => UpdateDay::Friday,
+            6 => UpdateDay::Saturday,
+            7 => UpdateDay::Movie,
+            8 => UpdateDay::Ova,
+            _ => UpdateDay::Sunday,
+        }
+    }
+
+    pub fn anime_type(&self) -> AnimeType {
+        match self {
+            UpdateDay::Movie => AnimeType::Movie,
+            UpdateDay::Ova => AnimeType::Ova,
+            _ => AnimeType::Tv,
+        }
+    }
+}
+
+/*
+    Lifecycle of an anime_task, derived from (qb_task_status, rename_status):
+    Downloading (0, 0) -> Downloaded (1, 0) -> Renamed (1, 1)
+                                          \-> Seeding (2, 1) -> Renamed (1, 1)
+    A task never goes back, (0, 1) and (2, 0) are not valid combinations.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    Downloading,
+    Downloaded,
+    Renamed,
+    Seeding,
+}
+
+impl TaskState {
+    pub fn from_status(
+        qb_task_status: QbTaskStatus,
+        rename_status: RenameStatus,
+    ) -> Result<TaskState, InvalidTaskTransition> {
+        match (qb_task_status, rename_status) {
+            (QbTaskStatus::Downloading, RenameStatus::Pending) => Ok(TaskState::Downloading),
+            (QbTaskStatus::Finished, RenameStatus::Pending) => Ok(TaskState::Downloaded),
+            (QbTaskStatus::Finished, RenameStatus::Renamed) => Ok(TaskState::Renamed),
+            (QbTaskStatus::Seeding, RenameStatus::Renamed) => Ok(TaskState::Seeding),
+            (QbTaskStatus::Downloading, RenameStatus::Renamed)
+            | (QbTaskStatus::Seeding, RenameStatus::Pending) => Err(InvalidTaskTransition {
+                from: None,
+                to: (qb_task_status, rename_status),
+            }),
+        }
+    }
+
+    pub fn qb_task_status(&self) -> QbTaskStatus {
+        match self {
+            TaskState::Downloading => QbTaskStatus::Downloading,
+            TaskState::Seeding => QbTaskStatus::Seeding,
+            _ => QbTaskStatus::Finished,
+        }
+    }
+
+    pub fn rename_status(&self) -> RenameStatus {
+        match self {
+            TaskState::Renamed | TaskState::Seeding => RenameStatus::Renamed,
+            _ => RenameStatus::Pending,
+        }
+    }
+
+    pub fn can_transition_to(&self, next: TaskState) -> bool {
+        matches!(
+            (self, next),
+            (TaskState::Downloading, TaskState::Downloading)
+                | (TaskState::Downloading, TaskState::Downloaded)
+                | (TaskState::Downloaded, TaskState::Downloaded)
+                | (TaskState::Downloaded, TaskState::Renamed)
+                | (TaskState::Downloaded, TaskState::Seeding)
+                | (TaskState::Seeding, TaskState::Seeding)
+                | (TaskState::Seeding, TaskState::Renamed)
+                | (TaskState::Renamed, TaskState::Renamed)
+        )
+    }
+
+    pub fn transition_to(&self, next: TaskState) -> Result<TaskState, InvalidTaskTransition> {
+        if self.can_transition_to(next) {
+            Ok(next)
+        } else {
+            Err(InvalidTaskTransition {
+                from: Some(*self),
+                to: (next.qb_task_status(), next.rename_status()),
+            })
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct InvalidTaskTransition {
+    from: Option<TaskState>,
+    to: (QbTaskStatus, RenameStatus),
+}
+
+impl Error for InvalidTaskTransition {}
+
+impl fmt::Display for InvalidTaskTransition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.from {
+            Some(from) => write!(
+                f,
+                "invalid anime_task transition: {:?} -> ({}, {})",
+                from, self.to.0, self.to.1
+            ),
+            None => write!(
+                f,
+                "invalid anime_task status: ({}, {})",
+                self.to.0, self.to.1
+            ),
+        }
+    }
+}
+
+// reject an invalid task status in the dao layer with a diesel error
+impl From<InvalidTaskTransition> for diesel::result::Error {
+    fn from(e: InvalidTaskTransition) -> Self {
+        diesel::result::Error::SerializationError(Box::new(e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_int_enum() {
+        assert_eq!(i32::from(AnimeStatus::Unknown), -1);
+        assert_eq!(AnimeType::try_from(2).unwrap(), AnimeType::Ova);
+        assert!(SubscribeStatus::try_from(2).is_err());
+        assert_eq!(serde_json::to_string(&UpdateDay::Sunday).unwrap(), "7");
+        assert_eq!(
+            serde_json::from_str::<QbTaskStatus>("1").unwrap(),
+            QbTaskStatus::Finished
+        );
+        assert!(serde_json::from_str::<RenameStatus>("3").is_err());
+    }
+
+    #[test]
+    fn test_update_day_from_mikan() {
+        assert_eq!(UpdateDay::from_mikan(0), UpdateDay::Sunday);
+        assert_eq!(UpdateDay::from_mikan(3), UpdateDay::Wednesday);
+        assert_eq!(UpdateDay::from_mikan(7).anime_type(), AnimeType::Movie);
+        assert_eq!(UpdateDay::from_mikan(8).anime_type(), AnimeType::Ova);
+        assert_eq!(UpdateDay::from_mikan(6).anime_type(), AnimeType::Tv);
+    }
+
+    #[test]
+    fn test_task_state() {
+        assert_eq!(
+            TaskState::from_status(QbTaskStatus::Finished, RenameStatus::Pending).unwrap(),
+            TaskState::Downloaded
+        );
+        assert!(TaskState::from_status(QbTaskStatus::Downloading, RenameStatus::Renamed).is_err());
+
+        assert!(TaskState::Downloading
+            .transition_to(TaskState::Downloaded)
+            .is_ok());
+        assert!(TaskState::Downloaded
+            .transition_to(TaskState::Renamed)
+            .is_ok());
+        assert!(TaskState::Downloading
+            .transition_to(TaskState::Renamed)
+            .is_err());
+        assert!(TaskState::Renamed
+            .transition_to(TaskState::Downloading)
+            .is_err());
+
+        // a seeding task is renamed already and ends as renamed once it is removed
+        assert_eq!(
+            TaskState::from_status(QbTaskStatus::Seeding, RenameStatus::Renamed).unwrap(),
+            TaskState::Seeding
+        );
+        assert!(TaskState::from_status(QbTaskStatus::Seeding, RenameStatus::Pending).is_err());
+        assert!(TaskState::Downloaded
+            .transition_to(TaskState::Seeding)
+            .is_ok());
+        assert!(TaskState::Seeding.transition_to(TaskState::Renamed).is_ok());
+        assert!(TaskState::Renamed.transition_to(TaskState::Seeding).is_err());
+    }
+}
@@ -4,4 +4,11 @@ pub mod anime_seed;
 pub mod anime_task;
 pub mod anime_subgroup;
 pub mod anime_filter;
-pub mod anime_progess;
\ No newline at end of file
+pub mod anime_progess;
+pub mod anime_task_upgrade;
+pub mod anime_episode;
+pub mod download_job;
+pub mod pending_decision;
+pub mod enums;
+pub mod scheduler_job;
+pub mod subgroup_release;
\ No newline at end of file
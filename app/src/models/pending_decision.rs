@@ -0,0 +1,42 @@
+use crate::schema::*;
+use serde::{Deserialize, Serialize};
+
+// an episode held for a preferred subgroup, the seed is accepted after the deadline,
+// times are local "%Y-%m-%d %H:%M:%S"
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable)]
+pub struct PendingDecision {
+    pub id: Option<i32>,
+    pub mikan_id: i32,
+    pub episode: i32,
+    pub first_seen: String,
+    pub deadline: String,
+    pub seed_url: String,
+    pub seed_name: String,
+    pub subgroup_id: i32,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = pending_decision)]
+pub struct PostPendingDecision<'a> {
+    pub mikan_id: &'a i32,
+    pub episode: &'a i32,
+    pub first_seen: &'a str,
+    pub deadline: &'a str,
+    pub seed_url: &'a str,
+    pub seed_name: &'a str,
+    pub subgroup_id: &'a i32,
+    pub updated_at: &'a str,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PendingDecisionJson {
+    pub mikan_id: i32,
+    pub episode: i32,
+    pub first_seen: String,
+    pub deadline: String,
+    pub seed_url: String,
+    pub seed_name: String,
+    pub subgroup_id: i32,
+    pub updated_at: String,
+}
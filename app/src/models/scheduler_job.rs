@@ -0,0 +1,30 @@
+use crate::schema::*;
+use serde::{Deserialize, Serialize};
+
+// schedule is either "@every <n><s|m|h|d>" or a cron expression, times are local "%Y-%m-%d %H:%M:%S"
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable)]
+pub struct SchedulerJob {
+    pub id: Option<i32>,
+    pub job_name: String,
+    pub schedule: String,
+    pub enabled: bool,
+    pub last_run: String,
+    pub next_run: String,
+    pub last_error: String,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = scheduler_job)]
+pub struct PostSchedulerJob<'a> {
+    pub job_name: &'a str,
+    pub schedule: &'a str,
+    pub enabled: &'a bool,
+    pub next_run: &'a str,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchedulerJobJson {
+    pub job_name: String,
+    pub schedule: String,
+    pub enabled: bool,
+}
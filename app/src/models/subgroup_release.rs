@@ -0,0 +1,35 @@
+use crate::schema::*;
+use serde::{Deserialize, Serialize};
+
+// the first seed of an episode released by a subgroup, first_seen is local "%Y-%m-%d %H:%M:%S"
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable)]
+pub struct SubgroupRelease {
+    pub id: Option<i32>,
+    pub subgroup_id: i32,
+    pub mikan_id: i32,
+    pub episode: i32,
+    pub first_seen: String,
+    pub resolution: i32, // height, 0 when unknown
+    pub size: i64,       // bytes, 0 when unknown
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = subgroup_release)]
+pub struct PostSubgroupRelease<'a> {
+    pub subgroup_id: &'a i32,
+    pub mikan_id: &'a i32,
+    pub episode: &'a i32,
+    pub first_seen: &'a str,
+    pub resolution: &'a i32,
+    pub size: &'a i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubgroupReleaseJson {
+    pub subgroup_id: i32,
+    pub mikan_id: i32,
+    pub episode: i32,
+    pub first_seen: String,
+    pub resolution: i32,
+    pub size: i64,
+}
@@ -1,11 +1,279 @@
+use crate::api::scheduler::TIME_FORMAT;
 use crate::dao;
+use crate::models::anime_filter::AnimeFilter;
 use crate::models::anime_seed::AnimeSeed;
+use crate::models::anime_task::AnimeTask;
+use crate::models::enums::{SeedStatus, TaskState};
+use crate::models::pending_decision::PendingDecisionJson;
+use crate::mods::spider;
+use crate::mods::title_parser::{self, ParsedTitle};
 use anyhow::Error;
+use chrono::{Duration, Local, NaiveDateTime};
 use diesel::r2d2::ConnectionManager;
 use diesel::r2d2::PooledConnection;
 use diesel::SqliteConnection;
 use std::collections::{HashMap, HashSet};
 
+/*
+    Quality rules are stored in anime_filter next to the subgroup rules:
+        filter_type  resolution | codec | subtitle | container
+        filter_val   code of the value, negative to avoid it
+        object       1 global (mikan_id 0), 0 local
+    Local rules of a filter_type replace the global rules of the same filter_type.
+    resolution: positive values are the allowed resolutions, the highest one wins
+    codec / subtitle / container: positive values are preferred
+*/
+pub const FILTER_RESOLUTION: &str = "resolution";
+pub const FILTER_CODEC: &str = "codec";
+pub const FILTER_SUBTITLE: &str = "subtitle";
+pub const FILTER_CONTAINER: &str = "container";
+pub const QUALITY_FILTER_TYPES: [&str; 4] = [
+    FILTER_RESOLUTION,
+    FILTER_CODEC,
+    FILTER_SUBTITLE,
+    FILTER_CONTAINER,
+];
+
+// "1080" / "1080p" -> 1080, "HEVC" -> 2, "简" -> 1, "mkv" -> 1
+pub fn quality_filter_val(quality_type: &str, value: &str) -> Option<i32> {
+    let value = value.trim();
+    let code = match quality_type {
+        FILTER_RESOLUTION => match value.to_lowercase().trim_end_matches('p') {
+            "4k" => 2160,
+            height => height.parse::<i32>().ok().filter(|h| *h > 0)?,
+        },
+        FILTER_CODEC => match value.to_uppercase().as_str() {
+            "AVC" | "H264" | "X264" => 1,
+            "HEVC" | "H265" | "X265" => 2,
+            "AV1" => 3,
+            _ => return None,
+        },
+        FILTER_SUBTITLE => match value.to_uppercase().as_str() {
+            "CHS" | "简" | "简体" => 1,
+            "CHT" | "繁" | "繁体" => 2,
+            "EMBEDDED" | "内封" => 3,
+            _ => return None,
+        },
+        FILTER_CONTAINER => match value.to_uppercase().as_str() {
+            "MKV" => 1,
+            "MP4" => 2,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(code)
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct QualityRule {
+    pub preference: HashSet<i32>,
+    pub avoid: HashSet<i32>, // stored as positive codes
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct QualityRules {
+    pub resolution: QualityRule,
+    pub codec: QualityRule,
+    pub subtitle: QualityRule,
+    pub container: QualityRule,
+}
+
+impl QualityRules {
+    pub fn from_filters(filters: &[AnimeFilter]) -> QualityRules {
+        let mut rules = QualityRules::default();
+        for quality_type in QUALITY_FILTER_TYPES {
+            let has_local = filters
+                .iter()
+                .any(|f| f.fiter_type == quality_type && f.object == 0);
+            let rule = match quality_type {
+                FILTER_RESOLUTION => &mut rules.resolution,
+                FILTER_CODEC => &mut rules.codec,
+                FILTER_SUBTITLE => &mut rules.subtitle,
+                _ => &mut rules.container,
+            };
+            for filter in filters.iter().filter(|f| {
+                f.fiter_type == quality_type && (f.object == 0) == has_local
+            }) {
+                if filter.filter_val > 0 {
+                    rule.preference.insert(filter.filter_val);
+                } else {
+                    rule.avoid.insert(-filter.filter_val);
+                }
+            }
+        }
+        rules
+    }
+
+    // None if the seed is rejected by the rules, otherwise (resolution, number of preferences matched)
+    pub fn score(&self, parsed: &ParsedTitle) -> Option<(i32, i32)> {
+        let resolution = parsed.resolution_height();
+        if let Some(height) = resolution {
+            if self.resolution.avoid.contains(&height)
+                || (!self.resolution.preference.is_empty()
+                    && !self.resolution.preference.contains(&height))
+            {
+                return None;
+            }
+        }
+
+        let codec: Vec<i32> = parsed
+            .codec
+            .iter()
+            .filter_map(|c| quality_filter_val(FILTER_CODEC, c))
+            .collect();
+        let mut subtitle: Vec<i32> = parsed
+            .subtitle_language
+            .iter()
+            .filter_map(|l| quality_filter_val(FILTER_SUBTITLE, l))
+            .collect();
+        if parsed.subtitle_embedded {
+            subtitle.extend(quality_filter_val(FILTER_SUBTITLE, "EMBEDDED"));
+        }
+        let container: Vec<i32> = parsed
+            .container
+            .iter()
+            .filter_map(|c| quality_filter_val(FILTER_CONTAINER, c))
+            .collect();
+
+        let mut matched = 0;
+        for (rule, codes) in [
+            (&self.codec, &codec),
+            (&self.subtitle, &subtitle),
+            (&self.container, &container),
+        ] {
+            if codes.iter().any(|c| rule.avoid.contains(c)) {
+                return None;
+            }
+            matched += codes.iter().filter(|c| rule.preference.contains(c)).count() as i32;
+        }
+        Some((resolution.unwrap_or(0), matched))
+    }
+}
+
+// smaller is better: preferred subgroups first, then higher resolution, then matched quality
+// preferences, then the re-released version (v2, v3 ...) of the same episode
+pub type SeedRank = (bool, i32, i32, i32);
+
+// None if the seed is rejected by the quality rules
+pub fn seed_rank(
+    anime_seed: &AnimeSeed,
+    priority_ids: &[i32],
+    quality_rules: &QualityRules,
+) -> Option<SeedRank> {
+    let parsed = title_parser::parse_title(&anime_seed.seed_name);
+    let (resolution, matched) = quality_rules.score(&parsed)?;
+    Some((
+        !priority_ids.contains(&anime_seed.subgroup_id),
+        -resolution,
+        -matched,
+        -parsed.version,
+    ))
+}
+
+// the best candidate which ranks strictly better than the downloaded seed
+// and was published within window_hours after it
+pub fn pick_upgrade<'a>(
+    current: &AnimeSeed,
+    candidates: &'a [AnimeSeed],
+    priority_ids: &[i32],
+    quality_rules: &QualityRules,
+    window_hours: i64,
+) -> Option<&'a AnimeSeed> {
+    let parse_date = |date: &str| NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S").ok();
+    let current_date = parse_date(&current.publish_date)?;
+    let current_rank =
+        seed_rank(current, priority_ids, quality_rules).unwrap_or((true, i32::MAX, i32::MAX, i32::MAX));
+
+    candidates
+        .iter()
+        .filter(|s| {
+            s.episode == current.episode && s.episode_end == -1 && s.seed_url != current.seed_url
+        })
+        .filter(|s| match parse_date(&s.publish_date) {
+            Some(date) => {
+                let hours = (date - current_date).num_hours();
+                date >= current_date && hours <= window_hours
+            }
+            None => false,
+        })
+        .filter_map(|s| seed_rank(s, priority_ids, quality_rules).map(|rank| (s, rank)))
+        .filter(|(_, rank)| *rank < current_rank)
+        .min_by_key(|(_, rank)| *rank)
+        .map(|(s, _)| s)
+}
+
+// the best seed for each missing episode, among the seeds never tried before;
+// a batch is only taken when all of its episodes are missing
+pub fn pick_gap_seeds(
+    anime_seed_vec: Vec<AnimeSeed>,
+    gap_set: &HashSet<i32>,
+    tried_set: &HashSet<String>,
+    avoid_sub_set: &HashSet<i32>,
+    priority_ids: &[i32],
+    quality_rules: &QualityRules,
+) -> Vec<AnimeSeed> {
+    let mut ranked_seed_vec: Vec<(AnimeSeed, SeedRank)> = anime_seed_vec
+        .into_iter()
+        .filter(|s| {
+            s.seed_status == SeedStatus::Unused
+                && !tried_set.contains(&s.seed_url)
+                && !avoid_sub_set.contains(&-s.subgroup_id)
+        })
+        .filter_map(|s| seed_rank(&s, priority_ids, quality_rules).map(|rank| (s, rank)))
+        .collect();
+    ranked_seed_vec.sort_by_key(|(s, rank)| (*rank, s.episode_end == -1));
+
+    let mut left_set = gap_set.clone();
+    let mut picked_vec: Vec<AnimeSeed> = Vec::new();
+    for (anime_seed, _) in ranked_seed_vec {
+        let episodes = anime_seed.episode..=anime_seed.episode.max(anime_seed.episode_end);
+        if !episodes.clone().all(|ep| left_set.contains(&ep)) {
+            continue;
+        }
+        for ep in episodes {
+            left_set.remove(&ep);
+        }
+        picked_vec.push(anime_seed);
+    }
+    picked_vec
+}
+
+// the end of the grace period of an episode first seen at first_seen, None when
+// it is over and the seed of another subgroup can be taken
+pub fn grace_deadline(
+    first_seen: &str,
+    grace_hours: i32,
+    now: NaiveDateTime,
+) -> Option<NaiveDateTime> {
+    if grace_hours <= 0 {
+        return None;
+    }
+    let first_seen = NaiveDateTime::parse_from_str(first_seen, TIME_FORMAT).ok()?;
+    let deadline = first_seen + Duration::hours(grace_hours as i64);
+    (now < deadline).then_some(deadline)
+}
+
+// the earliest publish date of the single episode seeds, by episode
+fn first_seen_by_episode(anime_seed_vec: &[AnimeSeed]) -> HashMap<i32, String> {
+    let mut first_seen_map: HashMap<i32, String> = HashMap::new();
+    for anime_seed in anime_seed_vec {
+        if anime_seed.episode_end != -1
+            || NaiveDateTime::parse_from_str(&anime_seed.publish_date, TIME_FORMAT).is_err()
+        {
+            continue;
+        }
+        first_seen_map
+            .entry(anime_seed.episode)
+            .and_modify(|date| {
+                if anime_seed.publish_date < *date {
+                    *date = anime_seed.publish_date.clone();
+                }
+            })
+            .or_insert_with(|| anime_seed.publish_date.clone());
+    }
+    first_seen_map
+}
+
 #[allow(dead_code)]
 pub async fn filter_anime_bulk(
     anime_seed_vec: Vec<AnimeSeed>,
@@ -14,7 +282,7 @@ pub async fn filter_anime_bulk(
     let mut new_anime_seed_vec: Vec<AnimeSeed> = Vec::new();
 
     for anime_seed in anime_seed_vec {
-        if anime_seed.seed_status == 1
+        if anime_seed.seed_status == SeedStatus::Used
             || anime_task_set.contains(&(anime_seed.mikan_id, anime_seed.episode))
         {
             continue;
@@ -41,16 +309,14 @@ pub async fn filter_anime_bulk_with_anime_filter(
                 &anime_seed.mikan_id,
                 db_connection,
             )
-            .await
-            .unwrap();
+            .await?;
         let local_episode_filter = dao::anime_filter::get_local_episode_filter_by_mikan_id(
             &anime_seed.mikan_id,
             db_connection,
         )
-        .await
-        .unwrap();
+        .await?;
 
-        if anime_seed.seed_status == 1
+        if anime_seed.seed_status == SeedStatus::Used
             || anime_task_set.contains(&(anime_seed.mikan_id, anime_seed.episode))
             || global_avoid_sub_set.contains(&-anime_seed.subgroup_id)
             || local_avoid_sub_set.contains(&-anime_seed.subgroup_id)
@@ -74,32 +340,74 @@ pub async fn filter_v3(
     let mut new_anime_seed_vec: Vec<AnimeSeed> = Vec::new();
     let (global_perference_sub_set, global_avoid_sub_set) =
         dao::anime_filter::get_global_subgroup_filter_set(db_connection).await;
+    let now = Local::now().naive_local();
+    let now_str = now.format(TIME_FORMAT).to_string();
 
-    for (mikan_id, mut anime_seed_vec) in anime_seed_map.into_iter() {
+    for (mikan_id, anime_seed_vec) in anime_seed_map.into_iter() {
         let (local_perference_sub_set, local_avoid_sub_set) =
             dao::anime_filter::get_local_subgroup_filter_set_by_mikan_id(&mikan_id, db_connection)
-                .await
-                .unwrap();
+                .await?;
         let local_episode_filter =
             dao::anime_filter::get_local_episode_filter_by_mikan_id(&mikan_id, db_connection)
-                .await
-                .unwrap();
+                .await?;
+        let grace_hours = dao::anime_filter::get_grace_hours(mikan_id, db_connection).await?;
+
+        // a held episode keeps the first seen time it was held with
+        let mut first_seen_map = first_seen_by_episode(&anime_seed_vec);
+        let pending_vec = dao::pending_decision::get_by_mikan_id(db_connection, mikan_id).await?;
+        for pending in &pending_vec {
+            first_seen_map.insert(pending.episode, pending.first_seen.clone());
+        }
+        let seen_episode_set: HashSet<i32> = anime_seed_vec
+            .iter()
+            .flat_map(|s| s.episode..=s.episode.max(s.episode_end))
+            .collect();
+        let mut held_vec: Vec<PendingDecisionJson> = Vec::new();
         
+        let quality_rules = QualityRules::from_filters(
+            &dao::anime_filter::get_quality_filter_by_mikan_id(
+                &mikan_id,
+                &QUALITY_FILTER_TYPES,
+                db_connection,
+            )
+            .await?,
+        );
+
         let priority_ids: Vec<i32> = global_perference_sub_set.union(&local_perference_sub_set).cloned().collect();
 
-        anime_seed_vec.sort_by(|a, b| {
-            let a_priority = priority_ids.contains(&a.subgroup_id);
-            let b_priority = priority_ids.contains(&b.subgroup_id);
+        let mut ranked_seed_vec: Vec<(AnimeSeed, SeedRank)> = Vec::new();
+        for anime_seed in anime_seed_vec {
+            match seed_rank(&anime_seed, &priority_ids, &quality_rules) {
+                Some(rank) => ranked_seed_vec.push((anime_seed, rank)),
+                None => log::debug!("skip torrent by quality rules: {}", anime_seed.seed_name),
+            }
+        }
+        // a batch is taken before single episodes of the same rank
+        ranked_seed_vec.sort_by_key(|(s, rank)| (*rank, s.episode_end == -1));
 
-            match (a_priority, b_priority) {
-                (true, false) => std::cmp::Ordering::Less,
-                (false, true) => std::cmp::Ordering::Greater,
-                _ => std::cmp::Ordering::Equal,
+        for (anime_seed, _) in ranked_seed_vec {
+            // a batch is only taken when none of its episodes has been downloaded
+            if anime_seed.episode_end != -1 {
+                let episodes = anime_seed.episode..=anime_seed.episode_end;
+                if anime_seed.seed_status == SeedStatus::Used
+                    || episodes
+                        .clone()
+                        .any(|ep| anime_task_set.contains(&(anime_seed.mikan_id, ep)))
+                    || global_avoid_sub_set.contains(&-anime_seed.subgroup_id)
+                    || local_avoid_sub_set.contains(&-anime_seed.subgroup_id)
+                    || anime_seed.episode < local_episode_filter
+                {
+                    log::debug!("skip batch torrent: {}", anime_seed.seed_name);
+                    continue;
+                }
+                for ep in episodes {
+                    anime_task_set.insert((anime_seed.mikan_id, ep));
+                }
+                new_anime_seed_vec.push(anime_seed);
+                continue;
             }
-        });
 
-        for anime_seed in anime_seed_vec {
-            if anime_seed.seed_status == 1
+            if anime_seed.seed_status == SeedStatus::Used
                 || anime_task_set.contains(&(anime_seed.mikan_id, anime_seed.episode))
                 || global_avoid_sub_set.contains(&-anime_seed.subgroup_id)
                 || local_avoid_sub_set.contains(&-anime_seed.subgroup_id)
@@ -117,9 +425,385 @@ pub async fn filter_v3(
                 continue;
             }
 
+            // no seed of a preferred subgroup yet, the best other seed waits for the deadline
+            if !priority_ids.is_empty() {
+                let first_seen = first_seen_map
+                    .get(&anime_seed.episode)
+                    .cloned()
+                    .unwrap_or_else(|| now_str.clone());
+                if let Some(deadline) = grace_deadline(&first_seen, grace_hours, now) {
+                    log::debug!("hold torrent until {}: {}", deadline, anime_seed.seed_name);
+                    anime_task_set.insert((anime_seed.mikan_id, anime_seed.episode));
+                    held_vec.push(PendingDecisionJson {
+                        mikan_id: anime_seed.mikan_id,
+                        episode: anime_seed.episode,
+                        first_seen,
+                        deadline: deadline.format(TIME_FORMAT).to_string(),
+                        seed_url: anime_seed.seed_url,
+                        seed_name: anime_seed.seed_name,
+                        subgroup_id: anime_seed.subgroup_id,
+                        updated_at: now_str.clone(),
+                    });
+                    continue;
+                }
+            }
+
             anime_task_set.insert((anime_seed.mikan_id, anime_seed.episode));
             new_anime_seed_vec.push(anime_seed);
         }
+
+        // the episodes which are not held anymore are decided
+        let decided_vec: Vec<i32> = seen_episode_set
+            .into_iter()
+            .filter(|ep| !held_vec.iter().any(|held| held.episode == *ep))
+            .collect();
+        if !decided_vec.is_empty() {
+            dao::pending_decision::delete_by_episodes(db_connection, mikan_id, &decided_vec)
+                .await?;
+        }
+        if !held_vec.is_empty() {
+            dao::pending_decision::add_vec(db_connection, &held_vec).await?;
+        }
     }
     Ok(new_anime_seed_vec)
-}
\ No newline at end of file
+}
+
+// downloaded and renamed tasks which can be replaced by a better seed
+#[allow(dead_code)]
+pub async fn filter_upgrade(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    anime_seed_map: HashMap<i32, Vec<AnimeSeed>>,
+    window_hours: i64,
+) -> Result<Vec<(AnimeTask, AnimeSeed)>, Error> {
+    let mut upgrade_vec: Vec<(AnimeTask, AnimeSeed)> = Vec::new();
+    let upgrading_set: HashSet<String> =
+        dao::anime_task_upgrade::get_by_upgrade_status(db_connection, 0)
+            .await?
+            .into_iter()
+            .map(|u| u.old_torrent_name)
+            .collect();
+    let (global_perference_sub_set, global_avoid_sub_set) =
+        dao::anime_filter::get_global_subgroup_filter_set(db_connection).await;
+
+    for (mikan_id, anime_seed_vec) in anime_seed_map.into_iter() {
+        let (local_perference_sub_set, local_avoid_sub_set) =
+            dao::anime_filter::get_local_subgroup_filter_set_by_mikan_id(&mikan_id, db_connection)
+                .await?;
+        let quality_rules = QualityRules::from_filters(
+            &dao::anime_filter::get_quality_filter_by_mikan_id(
+                &mikan_id,
+                &QUALITY_FILTER_TYPES,
+                db_connection,
+            )
+            .await?,
+        );
+        let priority_ids: Vec<i32> = global_perference_sub_set
+            .union(&local_perference_sub_set)
+            .cloned()
+            .collect();
+
+        let candidates: Vec<AnimeSeed> = anime_seed_vec
+            .iter()
+            .filter(|s| {
+                s.seed_status == SeedStatus::Unused
+                    && !global_avoid_sub_set.contains(&-s.subgroup_id)
+                    && !local_avoid_sub_set.contains(&-s.subgroup_id)
+            })
+            .cloned()
+            .collect();
+
+        let task_vec = dao::anime_task::get_exist_anime_task_by_mikan_id(db_connection, mikan_id)
+            .await?;
+        for task in task_vec {
+            if !matches!(task.state(), Ok(TaskState::Renamed))
+                || upgrading_set.contains(&task.torrent_name)
+            {
+                continue;
+            }
+            // a batch task is compared as the single episode it holds
+            let torrent_name = spider::get_task_torrent_name(&task.torrent_name);
+            let current = match anime_seed_vec
+                .iter()
+                .find(|s| spider::get_torrent_name(&s.seed_url) == torrent_name)
+            {
+                Some(current) => AnimeSeed {
+                    episode: task.episode,
+                    episode_end: -1,
+                    ..current.clone()
+                },
+                None => continue,
+            };
+            if let Some(better) = pick_upgrade(
+                &current,
+                &candidates,
+                &priority_ids,
+                &quality_rules,
+                window_hours,
+            ) {
+                log::info!(
+                    "upgrade [{}] -> [{}]",
+                    current.seed_name,
+                    better.seed_name
+                );
+                upgrade_vec.push((task, better.clone()));
+            }
+        }
+    }
+    Ok(upgrade_vec)
+}
+
+// seeds of other subgroups for the missing episodes of an anime, see pick_gap_seeds
+pub async fn filter_gap(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    mikan_id: i32,
+    anime_seed_vec: Vec<AnimeSeed>,
+    gap_set: &HashSet<i32>,
+    tried_set: &HashSet<String>,
+) -> Result<Vec<AnimeSeed>, Error> {
+    let (global_perference_sub_set, global_avoid_sub_set) =
+        dao::anime_filter::get_global_subgroup_filter_set(db_connection).await;
+    let (local_perference_sub_set, local_avoid_sub_set) =
+        dao::anime_filter::get_local_subgroup_filter_set_by_mikan_id(&mikan_id, db_connection)
+            .await?;
+    let quality_rules = QualityRules::from_filters(
+        &dao::anime_filter::get_quality_filter_by_mikan_id(
+            &mikan_id,
+            &QUALITY_FILTER_TYPES,
+            db_connection,
+        )
+        .await?,
+    );
+    let priority_ids: Vec<i32> = global_perference_sub_set
+        .union(&local_perference_sub_set)
+        .cloned()
+        .collect();
+    let avoid_sub_set: HashSet<i32> = global_avoid_sub_set
+        .union(&local_avoid_sub_set)
+        .cloned()
+        .collect();
+
+    Ok(pick_gap_seeds(
+        anime_seed_vec,
+        gap_set,
+        tried_set,
+        &avoid_sub_set,
+        &priority_ids,
+        &quality_rules,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn filter(mikan_id: i32, filter_type: &str, filter_val: i32) -> AnimeFilter {
+        AnimeFilter {
+            id: None,
+            mikan_id,
+            fiter_type: filter_type.to_string(),
+            filter_val,
+            object: if mikan_id == 0 { 1 } else { 0 },
+        }
+    }
+
+    #[test]
+    fn test_quality_filter_val() {
+        assert_eq!(quality_filter_val(FILTER_RESOLUTION, "1080p"), Some(1080));
+        assert_eq!(quality_filter_val(FILTER_RESOLUTION, "4K"), Some(2160));
+        assert_eq!(quality_filter_val(FILTER_CODEC, "hevc"), Some(2));
+        assert_eq!(quality_filter_val(FILTER_SUBTITLE, "内封"), Some(3));
+        assert_eq!(quality_filter_val(FILTER_CONTAINER, "MP4"), Some(2));
+        assert_eq!(quality_filter_val(FILTER_CODEC, "VP9"), None);
+        assert_eq!(quality_filter_val("subgroup", "370"), None);
+    }
+
+    #[test]
+    fn test_local_rules_replace_global() {
+        let rules = QualityRules::from_filters(&[
+            filter(0, FILTER_RESOLUTION, 1080),
+            filter(0, FILTER_RESOLUTION, 720),
+            filter(0, FILTER_CODEC, 2),
+            filter(3143, FILTER_RESOLUTION, 2160),
+        ]);
+        assert_eq!(rules.resolution.preference, HashSet::from([2160]));
+        assert_eq!(rules.codec.preference, HashSet::from([2]));
+    }
+
+    #[test]
+    fn test_quality_score() {
+        let rules = QualityRules::from_filters(&[
+            filter(0, FILTER_RESOLUTION, 1080),
+            filter(0, FILTER_RESOLUTION, 720),
+            filter(0, FILTER_CODEC, 2),
+            filter(0, FILTER_SUBTITLE, 3),
+            filter(0, FILTER_CONTAINER, -2),
+        ]);
+        let score = |title: &str| rules.score(&title_parser::parse_title(title));
+
+        assert_eq!(
+            score("[LoliHouse] Frieren - 05 [WebRip 1080p HEVC-10bit AAC][简繁内封字幕]"),
+            Some((1080, 2))
+        );
+        assert_eq!(score("[SubsPlease] Frieren - 05 (720p) [0B7A9F22].mkv"), Some((720, 0)));
+        assert_eq!(score("[Breeze] Frieren - 05 [4K AV1 Opus]"), None);
+        assert_eq!(score("[HYSUB]Sousou no Frieren[05][GB_MP4][1280X720]"), None);
+        assert_eq!(score("[Lilith-Raws] Frieren - 05 [Baha][WEB-DL][AVC AAC][CHT]"), Some((0, 0)));
+    }
+
+    fn seed(subgroup_id: i32, seed_name: &str, publish_date: &str) -> AnimeSeed {
+        AnimeSeed {
+            id: None,
+            mikan_id: 3143,
+            subgroup_id,
+            episode: 5,
+            seed_name: seed_name.to_string(),
+            seed_url: format!("/Download/{}.torrent", seed_name),
+            seed_status: SeedStatus::Unused,
+            seed_size: "".to_string(),
+            publish_date: publish_date.to_string(),
+            episode_end: -1,
+            info_hash: "".to_string(),
+            total_size: 0,
+            file_list: "[]".to_string(),
+            is_special: false,
+        }
+    }
+
+    #[test]
+    fn test_pick_upgrade() {
+        let rules = QualityRules::from_filters(&[
+            filter(0, FILTER_RESOLUTION, 1080),
+            filter(0, FILTER_RESOLUTION, 720),
+        ]);
+        let current = seed(1, "[ANi] Frieren - 05 [1080P]", "2023-10-06 17:00:00");
+        let v2 = seed(1, "[ANi] Frieren - 05v2 [1080P]", "2023-10-07 12:00:00");
+        let late_v2 = seed(1, "[ANi] Frieren - 05v2 [1080P]", "2023-10-12 12:00:00");
+        let preferred = seed(2, "[LoliHouse] Frieren - 05 [1080p]", "2023-10-06 20:00:00");
+        let worse = seed(3, "[Other] Frieren - 05 [720p]", "2023-10-06 18:00:00");
+
+        // same rank is not an upgrade
+        assert!(pick_upgrade(&current, &[current.clone(), worse.clone()], &[], &rules, 72).is_none());
+        assert_eq!(
+            pick_upgrade(&current, &[v2.clone(), worse.clone()], &[], &rules, 72)
+                .map(|s| &s.seed_name),
+            Some(&v2.seed_name)
+        );
+        assert!(pick_upgrade(&current, &[late_v2], &[], &rules, 72).is_none());
+        assert_eq!(
+            pick_upgrade(&current, &[v2, preferred.clone()], &[2], &rules, 72)
+                .map(|s| s.subgroup_id),
+            Some(2)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_filter_upgrade_batch_task() {
+        use crate::models::anime_task::AnimeTaskJson;
+        use crate::models::enums::{NewStatus, QbTaskStatus, RenameStatus};
+
+        let pool = crate::Pool::builder()
+            .max_size(1)
+            .build(ConnectionManager::<SqliteConnection>::new(":memory:"))
+            .unwrap();
+        let db_connection = &mut pool.get().unwrap();
+        dao::run_migrations(db_connection).unwrap();
+        for resolution in [1080, 720] {
+            dao::anime_filter::add_quality_filter(0, FILTER_RESOLUTION, resolution, db_connection)
+                .await
+                .unwrap();
+        }
+        dao::anime_task::add(
+            db_connection,
+            &AnimeTaskJson {
+                mikan_id: 3143,
+                episode: 5,
+                torrent_name: spider::get_batch_task_name("batch.torrent", 5),
+                qb_task_status: QbTaskStatus::Finished,
+                rename_status: RenameStatus::Renamed,
+                filename: "".to_string(),
+                is_new: NewStatus::Watched,
+                info_hash: "".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let batch = AnimeSeed {
+            episode: 1,
+            episode_end: 12,
+            seed_url: "/Download/batch.torrent".to_string(),
+            ..seed(583, "[Sub] Frieren 01-12 [720p]", "2023-10-06 17:00:00")
+        };
+        let better = seed(370, "[ANi] Frieren - 05 [1080P]", "2023-10-07 12:00:00");
+        let anime_seed_map = HashMap::from([(3143, vec![batch, better.clone()])]);
+
+        let upgrade_vec = filter_upgrade(db_connection, anime_seed_map, 72)
+            .await
+            .unwrap();
+        assert_eq!(upgrade_vec.len(), 1);
+        assert_eq!(upgrade_vec[0].0.torrent_name, "batch.torrent#5");
+        assert_eq!(upgrade_vec[0].1.seed_url, better.seed_url);
+    }
+
+    #[test]
+    fn test_pick_gap_seeds() {
+        let rules = QualityRules::from_filters(&[filter(0, FILTER_RESOLUTION, 1080)]);
+        let episode_seed = |subgroup_id: i32, episode: i32, episode_end: i32| AnimeSeed {
+            episode,
+            episode_end,
+            ..seed(
+                subgroup_id,
+                &format!(
+                    "[{}] Frieren {}-{} [1080p]",
+                    subgroup_id, episode, episode_end
+                ),
+                "2023-10-06 17:00:00",
+            )
+        };
+        let failed = episode_seed(583, 5, -1);
+        let avoided = episode_seed(202, 5, -1);
+        let fallback = episode_seed(370, 5, -1);
+        let preferred = episode_seed(382, 7, -1);
+        let other = episode_seed(370, 7, -1);
+        // episode 6 is downloaded already
+        let batch = episode_seed(370, 5, 7);
+
+        let picked = pick_gap_seeds(
+            vec![failed.clone(), avoided, fallback, preferred, other, batch],
+            &HashSet::from([5, 7]),
+            &HashSet::from([failed.seed_url]),
+            &HashSet::from([-202]),
+            &[382],
+            &rules,
+        );
+        assert_eq!(
+            picked
+                .iter()
+                .map(|s| (s.subgroup_id, s.episode))
+                .collect::<Vec<_>>(),
+            vec![(382, 7), (370, 5)]
+        );
+    }
+
+    #[test]
+    fn test_grace_deadline() {
+        let now = |date: &str| NaiveDateTime::parse_from_str(date, TIME_FORMAT).unwrap();
+        assert_eq!(
+            grace_deadline("2023-10-06 17:00:00", 12, now("2023-10-07 04:59:59")),
+            Some(now("2023-10-07 05:00:00"))
+        );
+        assert!(grace_deadline("2023-10-06 17:00:00", 12, now("2023-10-07 05:00:00")).is_none());
+        assert!(grace_deadline("2023-10-06 17:00:00", 0, now("2023-10-06 17:00:00")).is_none());
+        assert!(grace_deadline("", 12, now("2023-10-06 17:00:00")).is_none());
+
+        let first_seen_map = first_seen_by_episode(&[
+            seed(615, "[Kirara] Frieren - 05", "2023-10-06 19:00:00"),
+            seed(583, "[ANi] Frieren - 05", "2023-10-06 17:00:00"),
+            seed(202, "[Raw] Frieren - 05", ""),
+        ]);
+        assert_eq!(
+            first_seen_map.get(&5).map(|date| date.as_str()),
+            Some("2023-10-06 17:00:00")
+        );
+    }
+}
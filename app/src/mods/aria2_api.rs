@@ -0,0 +1,533 @@
+use crate::error::error::{handle_error, AnimeError};
+use crate::models::anime_seed::AnimeSeed;
+use crate::mods::config::{Config, SpeedLimit};
+use crate::mods::magnet;
+use crate::mods::path_mapping::PathMapper;
+use crate::mods::torrent_client::{
+    format_eta, format_progress, format_size, format_speed, get_save_path, read_seed_file,
+    SeedingStats, TorrentClient, TorrentFile, TorrentInfo,
+};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::header::CONTENT_TYPE;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// aria2 lists the queues page by page, this is far more than we ever add
+const MAX_LIST: i64 = 1000;
+
+const STATUS_KEYS: [&str; 13] = [
+    "gid",
+    "infoHash",
+    "status",
+    "totalLength",
+    "completedLength",
+    "uploadLength",
+    "downloadSpeed",
+    "connections",
+    "numSeeders",
+    "seeder",
+    "dir",
+    "bittorrent",
+    "followedBy",
+];
+
+/*
+    aria2 json-rpc client, see https://aria2.github.io/manual/en/html/aria2c.html#rpc-interface
+    aria2 addresses a download by its gid, the gid of a torrent is looked up
+    by the info hash in the active, waiting and stopped queues.
+*/
+#[derive(Debug)]
+pub struct Aria2Client {
+    is_login: AtomicBool,
+    client: reqwest::Client,
+    url: String,
+    secret: String,
+    download_path: String,
+    speed_limit: SpeedLimit,
+    path_mapper: PathMapper,
+}
+
+impl Aria2Client {
+    pub async fn new_with_config(config: &Config) -> Self {
+        let client = Self {
+            is_login: AtomicBool::new(false),
+            client: reqwest::Client::new(),
+            url: config.downloader.url.clone(),
+            secret: config.downloader.password.clone(),
+            download_path: config.download_path.clone(),
+            speed_limit: config.speed_limit,
+            path_mapper: PathMapper::new(config.path_mapping_rules()),
+        };
+        if let Err(e) = client.login().await {
+            log::error!("[ARIA2 API] Login error, {}", e);
+        }
+        client
+    }
+
+    async fn login(&self) -> Result<(), AnimeError> {
+        self.rpc("aria2.getVersion", Vec::new()).await?;
+        if !self.is_login.swap(true, Ordering::SeqCst) {
+            log::info!("[ARIA2 API] Successfully Connect to aria2 rpc");
+        }
+        Ok(())
+    }
+
+    async fn rpc(&self, method: &str, mut params: Vec<Value>) -> Result<Value, AnimeError> {
+        if !self.secret.is_empty() {
+            params.insert(0, json!(format!("token:{}", self.secret)));
+        }
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": "autoAnime",
+            "method": method,
+            "params": params,
+        });
+
+        // aria2 answers errors with a 400 and a json body
+        let resp_text = self
+            .client
+            .post(&self.url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .send()
+            .await
+            .map_err(|e| {
+                self.is_login.store(false, Ordering::SeqCst);
+                handle_error(
+                    AnimeError::Downloader(format!("{:?}", e)),
+                    "[ARIA2 API] Unable to access aria2 rpc",
+                )
+            })?
+            .text()
+            .await
+            .map_err(|e| {
+                handle_error(
+                    AnimeError::Downloader(format!("{:?}", e)),
+                    "[ARIA2 API] Failed to read rpc response",
+                )
+            })?;
+        let json: Value = serde_json::from_str(&resp_text).map_err(|e| {
+            handle_error(
+                AnimeError::Downloader(format!("{:?}", e)),
+                &format!("Failed to serialize {:?}", resp_text),
+            )
+        })?;
+        parse_response(method, json)
+    }
+
+    async fn ensure_login(&self) -> Result<(), AnimeError> {
+        if self.is_login.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        self.login().await.map_err(|e| {
+            AnimeError::Downloader(format!("[ARIA2 API] aria2 client not started, {}", e))
+        })
+    }
+
+    // every download aria2 knows, the stopped ones included
+    async fn tell_all(&self) -> Result<Vec<Value>, AnimeError> {
+        self.ensure_login().await?;
+        let mut downloads = Vec::new();
+        let queues = [
+            ("aria2.tellActive", vec![json!(STATUS_KEYS)]),
+            (
+                "aria2.tellWaiting",
+                vec![json!(0), json!(MAX_LIST), json!(STATUS_KEYS)],
+            ),
+            (
+                "aria2.tellStopped",
+                vec![json!(0), json!(MAX_LIST), json!(STATUS_KEYS)],
+            ),
+        ];
+        for (method, params) in queues {
+            if let Value::Array(items) = self.rpc(method, params).await? {
+                downloads.extend(items);
+            }
+        }
+        downloads.retain(|download| !is_magnet_metadata(download));
+        Ok(downloads)
+    }
+
+    async fn get_download(&self, info_hash: &str) -> Result<Value, AnimeError> {
+        self.tell_all()
+            .await?
+            .into_iter()
+            .find(|download| download["infoHash"].as_str() == Some(info_hash))
+            .ok_or_else(|| {
+                AnimeError::NotFound(format!("[ARIA2 API] torrent not found: {}", info_hash))
+            })
+    }
+}
+
+#[async_trait]
+impl TorrentClient for Aria2Client {
+    fn is_login(&self) -> bool {
+        self.is_login.load(Ordering::SeqCst)
+    }
+
+    async fn reconnect(&self) -> Result<(), AnimeError> {
+        self.login().await
+    }
+
+    async fn add_torrent(
+        &self,
+        anime_name: &str,
+        anime_seed_info: &AnimeSeed,
+        speed_limit: SpeedLimit,
+    ) -> Result<(), AnimeError> {
+        self.ensure_login().await?;
+        let save_path = get_save_path(
+            &self.path_mapper,
+            &self.download_path,
+            anime_name,
+            anime_seed_info.mikan_id,
+        )?;
+        let options = add_options(&save_path, speed_limit.or(self.speed_limit));
+
+        if magnet::is_magnet(&anime_seed_info.seed_url) {
+            self.rpc(
+                "aria2.addUri",
+                vec![json!([anime_seed_info.seed_url]), options],
+            )
+            .await?;
+        } else {
+            let (_, file_byte) = read_seed_file(anime_seed_info)?;
+            self.rpc(
+                "aria2.addTorrent",
+                vec![json!(STANDARD.encode(file_byte)), json!([]), options],
+            )
+            .await?;
+        }
+        log::info!(
+            "[ARIA2 API] Successfully added seeds: {:?}",
+            anime_seed_info
+        );
+        Ok(())
+    }
+
+    async fn torrent_info(&self, info_hash: &str) -> Result<TorrentInfo, AnimeError> {
+        let download = self.get_download(info_hash).await?;
+        Ok(torrent_info_from_json(&download))
+    }
+
+    async fn torrent_files(&self, info_hash: &str) -> Result<Vec<TorrentFile>, AnimeError> {
+        let download = self.get_download(info_hash).await?;
+        let files = self
+            .rpc("aria2.getFiles", vec![download["gid"].clone()])
+            .await?;
+        Ok(torrent_files_from_json(
+            download["dir"].as_str().unwrap_or_default(),
+            &files,
+        ))
+    }
+
+    // aria2 never deletes the downloaded files itself
+    async fn del_torrent(&self, info_hash: &str, delete_files: bool) -> Result<(), AnimeError> {
+        let download = self.get_download(info_hash).await?;
+        let gid = download["gid"].clone();
+        let files = self.rpc("aria2.getFiles", vec![gid.clone()]).await?;
+
+        if !matches!(
+            download["status"].as_str(),
+            Some("complete" | "error" | "removed")
+        ) {
+            self.rpc("aria2.forceRemove", vec![gid.clone()]).await?;
+        }
+        // a download removed just now may still be stopping, its result is then left behind
+        if let Err(e) = self.rpc("aria2.removeDownloadResult", vec![gid]).await {
+            log::debug!("[ARIA2 API] Failed to remove download result, err: {}", e);
+        }
+
+        if !delete_files {
+            return Ok(());
+        }
+        for file in files.as_array().into_iter().flatten() {
+            if let Some(path) = file["path"].as_str() {
+                let path = self.path_mapper.to_local(path);
+                if let Err(e) = std::fs::remove_file(&path) {
+                    log::warn!("[ARIA2 API] Failed to delete {}, err: {}", path, e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // aria2 has no way to move a file of a running download
+    async fn rename_file(
+        &self,
+        info_hash: &str,
+        old_path: &str,
+        _new_path: &str,
+    ) -> Result<(), AnimeError> {
+        Err(AnimeError::Downloader(format!(
+            "[ARIA2 API] can not rename {} of {}",
+            old_path, info_hash
+        )))
+    }
+
+    async fn pause_torrent(&self, info_hash: &str) -> Result<(), AnimeError> {
+        let download = self.get_download(info_hash).await?;
+        self.rpc("aria2.pause", vec![download["gid"].clone()])
+            .await?;
+        log::info!("[ARIA2 API] Successfully pause seed: {}", info_hash);
+        Ok(())
+    }
+
+    async fn resume_torrent(&self, info_hash: &str) -> Result<(), AnimeError> {
+        let download = self.get_download(info_hash).await?;
+        self.rpc("aria2.unpause", vec![download["gid"].clone()])
+            .await?;
+        log::info!("[ARIA2 API] Successfully resume seed: {}", info_hash);
+        Ok(())
+    }
+
+    async fn completed_torrent_set(&self) -> Result<HashSet<String>, AnimeError> {
+        Ok(completed_torrent_set_from_json(&self.tell_all().await?))
+    }
+
+    async fn seeding_stats(&self) -> Result<HashMap<String, SeedingStats>, AnimeError> {
+        Ok(seeding_stats_from_json(&self.tell_all().await?))
+    }
+}
+
+fn parse_response(method: &str, json: Value) -> Result<Value, AnimeError> {
+    match json.get("error") {
+        Some(error) => Err(AnimeError::Downloader(format!(
+            "[ARIA2 API] {} failed: {}",
+            method,
+            error["message"].as_str().unwrap_or("unknown error")
+        ))),
+        None => Ok(json["result"].clone()),
+    }
+}
+
+// aria2 takes every option as a string, 0 is unlimited
+fn add_options(save_path: &str, speed_limit: SpeedLimit) -> Value {
+    json!({
+        "dir": save_path,
+        "max-download-limit": format!("{}K", speed_limit.download),
+        "max-upload-limit": format!("{}K", speed_limit.upload),
+    })
+}
+
+// aria2 sends every number as a string
+fn get_number(value: &Value) -> i64 {
+    value
+        .as_str()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or_default()
+}
+
+// a seeding torrent stays active, it is finished once every byte is there
+// a magnet starts as a download of its metadata, which is followed by the real download
+// under the same info hash once it completes
+fn is_magnet_metadata(download: &Value) -> bool {
+    download.get("followedBy").is_some()
+}
+
+fn is_completed(download: &Value) -> bool {
+    let total_length = get_number(&download["totalLength"]);
+    download["status"].as_str() == Some("complete")
+        || (total_length > 0 && get_number(&download["completedLength"]) == total_length)
+}
+
+fn torrent_info_from_json(download: &Value) -> TorrentInfo {
+    let total_length = get_number(&download["totalLength"]);
+    let completed_length = get_number(&download["completedLength"]);
+    let download_speed = get_number(&download["downloadSpeed"]);
+
+    let progress = if total_length > 0 {
+        completed_length as f64 / total_length as f64
+    } else {
+        0.0
+    };
+    let eta = if download_speed > 0 {
+        (total_length - completed_length) / download_speed
+    } else {
+        0
+    };
+    let state = if download["seeder"].as_str() == Some("true") {
+        "seeding"
+    } else {
+        download["status"].as_str().unwrap_or("unknown")
+    };
+
+    TorrentInfo {
+        name: download["bittorrent"]["info"]["name"]
+            .as_str()
+            .unwrap_or_default()
+            .to_owned(),
+        size: format_size(total_length),
+        done: format_progress(progress),
+        peers: get_number(&download["connections"]).to_string(),
+        seeds: get_number(&download["numSeeders"]).to_string(),
+        download_speed: format_speed(download_speed),
+        eta: format_eta(eta),
+        hash: download["infoHash"].as_str().unwrap_or_default().to_owned(),
+        state: state.to_string(),
+    }
+}
+
+// aria2 gives absolute paths and 1-based indexes, make them look like qbittorrent's
+fn torrent_files_from_json(dir: &str, files: &Value) -> Vec<TorrentFile> {
+    let prefix = format!("{}/", dir.trim_end_matches('/'));
+    files
+        .as_array()
+        .map(|files| {
+            files
+                .iter()
+                .filter(|file| {
+                    // the metadata of a magnet which is not resolved yet
+                    !file["path"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .starts_with("[METADATA]")
+                })
+                .map(|file| {
+                    let path = file["path"].as_str().unwrap_or_default();
+                    TorrentFile {
+                        index: get_number(&file["index"]) - 1,
+                        name: path.strip_prefix(&prefix).unwrap_or(path).to_owned(),
+                        size: get_number(&file["length"]),
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn completed_torrent_set_from_json(downloads: &[Value]) -> HashSet<String> {
+    downloads
+        .iter()
+        .filter(|download| is_completed(download))
+        .filter_map(|download| download["infoHash"].as_str())
+        .map(|hash| hash.to_string())
+        .collect()
+}
+
+// aria2 does not report how long a torrent has been seeding, only the ratio is known
+fn seeding_stats_from_json(downloads: &[Value]) -> HashMap<String, SeedingStats> {
+    downloads
+        .iter()
+        .filter_map(|download| {
+            let hash = download["infoHash"].as_str()?.to_string();
+            let completed_length = get_number(&download["completedLength"]);
+            let ratio = if completed_length > 0 {
+                get_number(&download["uploadLength"]) as f64 / completed_length as f64
+            } else {
+                0.0
+            };
+            Some((
+                hash,
+                SeedingStats {
+                    ratio,
+                    seeding_time: 0,
+                },
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_tell_status() {
+        let downloads: Vec<Value> = serde_json::from_str(
+            r#"[
+                {
+                    "gid": "2089b05ecca3d829",
+                    "infoHash": "bdd2f547cdfd8a38011a5ea451d65379c9572305",
+                    "status": "active",
+                    "totalLength": "366372454",
+                    "completedLength": "366372454",
+                    "uploadLength": "183186227",
+                    "downloadSpeed": "0",
+                    "connections": "3",
+                    "numSeeders": "0",
+                    "seeder": "true",
+                    "dir": "/app/downloads/Migi to Dali(3143)",
+                    "bittorrent": {"info": {"name": "[LoliHouse] Migi to Dali - 03"}}
+                },
+                {
+                    "gid": "cca3d8292089b05e",
+                    "infoHash": "0123456789abcdef0123456789abcdef01234567",
+                    "status": "active",
+                    "totalLength": "1000",
+                    "completedLength": "250",
+                    "downloadSpeed": "25"
+                },
+                {
+                    "gid": "5e2089b0cca3d829",
+                    "infoHash": "0123456789abcdef0123456789abcdef01234567",
+                    "status": "complete",
+                    "totalLength": "1024",
+                    "completedLength": "1024",
+                    "followedBy": ["cca3d8292089b05e"]
+                }
+            ]"#,
+        )
+        .unwrap();
+
+        let info = torrent_info_from_json(&downloads[0]);
+        assert_eq!(info.name, "[LoliHouse] Migi to Dali - 03");
+        assert_eq!(info.done, "100.00 %");
+        assert_eq!(info.peers, "3");
+        assert_eq!(info.state, "seeding");
+
+        let info = torrent_info_from_json(&downloads[1]);
+        assert_eq!(info.done, "25.00 %");
+        assert_eq!(info.eta, "00:00:30");
+
+        // the resolved metadata of a magnet is not the finished download
+        assert!(is_magnet_metadata(&downloads[2]));
+        let downloads: Vec<Value> = downloads
+            .into_iter()
+            .filter(|download| !is_magnet_metadata(download))
+            .collect();
+        let completed = completed_torrent_set_from_json(&downloads);
+        assert_eq!(completed.len(), 1);
+        assert!(completed.contains("bdd2f547cdfd8a38011a5ea451d65379c9572305"));
+
+        let seeding_stats = seeding_stats_from_json(&downloads);
+        assert_eq!(
+            seeding_stats["bdd2f547cdfd8a38011a5ea451d65379c9572305"].ratio,
+            0.5
+        );
+        assert_eq!(
+            seeding_stats["0123456789abcdef0123456789abcdef01234567"].ratio,
+            0.0
+        );
+
+        let files = serde_json::from_str(
+            r#"[
+                {"index": "1", "path": "/app/downloads/Migi to Dali(3143)/Migi to Dali/03.mkv", "length": "366000000"},
+                {"index": "2", "path": "[METADATA]0123456789abcdef0123456789abcdef01234567", "length": "0"}
+            ]"#,
+        )
+        .unwrap();
+        let files = torrent_files_from_json("/app/downloads/Migi to Dali(3143)", &files);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].index, 0);
+        assert_eq!(files[0].name, "Migi to Dali/03.mkv");
+
+        let options = add_options(
+            "/app/downloads/Migi to Dali(3143)",
+            SpeedLimit {
+                download: 1024,
+                upload: 0,
+            },
+        );
+        assert_eq!(options["max-download-limit"], "1024K");
+        assert_eq!(options["max-upload-limit"], "0K");
+
+        let failed = parse_response(
+            "aria2.addTorrent",
+            json!({ "id": "autoAnime", "error": { "code": 1, "message": "Unauthorized" } }),
+        );
+        assert_eq!(failed.unwrap_err().code(), "downloader");
+    }
+}
@@ -0,0 +1,248 @@
+use crate::error::error::AnimeError;
+use crate::mods::torrent_client::TorrentFile;
+use sha1::{Digest, Sha1};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/*
+    Bencode decoder for .torrent files, see
+    https://www.bittorrent.org/beps/bep_0003.html
+    The info hash is the sha1 of the raw `info` dict, so the decoder remembers
+    the byte span of every value it reads instead of encoding it again.
+*/
+#[derive(Debug, PartialEq)]
+pub enum Bencode<'a> {
+    Int(i64),
+    Bytes(&'a [u8]),
+    List(Vec<Bencode<'a>>),
+    Dict(BTreeMap<&'a [u8], (Bencode<'a>, &'a [u8])>),
+}
+
+impl<'a> Bencode<'a> {
+    pub fn get(&self, key: &str) -> Option<&Bencode<'a>> {
+        self.get_raw(key).map(|(value, _)| value)
+    }
+
+    // the value and the bytes it was decoded from
+    pub fn get_raw(&self, key: &str) -> Option<(&Bencode<'a>, &'a [u8])> {
+        match self {
+            Bencode::Dict(dict) => dict.get(key.as_bytes()).map(|(value, raw)| (value, *raw)),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Bencode::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<String> {
+        match self {
+            Bencode::Bytes(bytes) => Some(String::from_utf8_lossy(bytes).to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&Vec<Bencode<'a>>> {
+        match self {
+            Bencode::List(list) => Some(list),
+            _ => None,
+        }
+    }
+}
+
+fn bencode_error(message: &str, pos: usize) -> AnimeError {
+    AnimeError::BadRequest(format!("invalid torrent: {} at byte {}", message, pos))
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn peek(&self) -> Result<u8, AnimeError> {
+        self.data
+            .get(self.pos)
+            .copied()
+            .ok_or_else(|| bencode_error("unexpected end", self.pos))
+    }
+
+    // reads up to the terminator and skips it
+    fn read_until(&mut self, terminator: u8) -> Result<&'a str, AnimeError> {
+        let start = self.pos;
+        let len = self.data[start..]
+            .iter()
+            .position(|b| *b == terminator)
+            .ok_or_else(|| bencode_error("unexpected end", start))?;
+        self.pos = start + len + 1;
+        std::str::from_utf8(&self.data[start..start + len])
+            .map_err(|_| bencode_error("invalid number", start))
+    }
+
+    fn decode(&mut self) -> Result<(Bencode<'a>, &'a [u8]), AnimeError> {
+        let start = self.pos;
+        let value = match self.peek()? {
+            b'i' => {
+                self.pos += 1;
+                let number = self.read_until(b'e')?;
+                Bencode::Int(
+                    number
+                        .parse()
+                        .map_err(|_| bencode_error("invalid integer", start))?,
+                )
+            }
+            b'l' => {
+                self.pos += 1;
+                let mut list = Vec::new();
+                while self.peek()? != b'e' {
+                    list.push(self.decode()?.0);
+                }
+                self.pos += 1;
+                Bencode::List(list)
+            }
+            b'd' => {
+                self.pos += 1;
+                let mut dict = BTreeMap::new();
+                while self.peek()? != b'e' {
+                    let key = match self.decode()?.0 {
+                        Bencode::Bytes(key) => key,
+                        _ => return Err(bencode_error("dict key is not a string", self.pos)),
+                    };
+                    dict.insert(key, self.decode()?);
+                }
+                self.pos += 1;
+                Bencode::Dict(dict)
+            }
+            b'0'..=b'9' => {
+                let len: usize = self
+                    .read_until(b':')?
+                    .parse()
+                    .map_err(|_| bencode_error("invalid string length", start))?;
+                let end = self
+                    .pos
+                    .checked_add(len)
+                    .filter(|end| *end <= self.data.len())
+                    .ok_or_else(|| bencode_error("unexpected end", self.pos))?;
+                let bytes = &self.data[self.pos..end];
+                self.pos = end;
+                Bencode::Bytes(bytes)
+            }
+            _ => return Err(bencode_error("unknown type", start)),
+        };
+        Ok((value, &self.data[start..self.pos]))
+    }
+}
+
+pub fn decode(data: &[u8]) -> Result<Bencode<'_>, AnimeError> {
+    let mut decoder = Decoder { data, pos: 0 };
+    let (value, _) = decoder.decode()?;
+    Ok(value)
+}
+
+#[derive(Debug, Default)]
+pub struct TorrentMeta {
+    pub info_hash: String,
+    pub files: Vec<TorrentFile>,
+    pub total_size: i64,
+}
+
+// v1 info hash, file list and total size of a .torrent file
+pub fn parse_torrent(data: &[u8]) -> Result<TorrentMeta, AnimeError> {
+    let torrent = decode(data)?;
+    let (info, info_raw) = torrent
+        .get_raw("info")
+        .ok_or_else(|| AnimeError::BadRequest("invalid torrent: no info dict".to_string()))?;
+
+    let name = info
+        .get("name.utf-8")
+        .or_else(|| info.get("name"))
+        .and_then(|n| n.as_str())
+        .unwrap_or_default();
+
+    // a single file torrent names the file, a multi file torrent names the folder
+    let files = match info.get("files").and_then(|f| f.as_list()) {
+        Some(files) => files
+            .iter()
+            .enumerate()
+            .map(|(index, file)| {
+                let path = file
+                    .get("path.utf-8")
+                    .or_else(|| file.get("path"))
+                    .and_then(|p| p.as_list())
+                    .map(|p| p.iter().filter_map(|s| s.as_str()).collect::<Vec<_>>())
+                    .unwrap_or_default();
+                TorrentFile {
+                    index: index as i64,
+                    name: format!("{}/{}", name, path.join("/")),
+                    size: file
+                        .get("length")
+                        .and_then(|l| l.as_int())
+                        .unwrap_or_default(),
+                }
+            })
+            .collect(),
+        None => vec![TorrentFile {
+            index: 0,
+            name,
+            size: info
+                .get("length")
+                .and_then(|l| l.as_int())
+                .unwrap_or_default(),
+        }],
+    };
+
+    let mut info_hash = String::with_capacity(40);
+    for b in Sha1::digest(info_raw).iter() {
+        let _ = write!(info_hash, "{:02x}", b);
+    }
+
+    Ok(TorrentMeta {
+        info_hash,
+        total_size: files.iter().map(|f| f.size).sum(),
+        files,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let value = decode(b"d3:agei18e4:listl1:a1:be4:name5:mikane").unwrap();
+        assert_eq!(value.get("age"), Some(&Bencode::Int(18)));
+        assert_eq!(value.get("name").unwrap().as_str().unwrap(), "mikan");
+        assert_eq!(value.get("list").unwrap().as_list().unwrap().len(), 2);
+
+        assert!(decode(b"d3:agei18e").is_err());
+        assert!(decode(b"5:mikan").is_ok());
+        assert!(decode(b"9:mikan").is_err());
+        assert!(decode(b"x").is_err());
+    }
+
+    #[test]
+    fn test_parse_torrent() {
+        let info: &[u8] = b"d5:filesld6:lengthi300e4:pathl2:Sx6:01.mkveed6:lengthi20e4:pathl6:01.asseee4:name5:Anime12:piece lengthi16384e6:pieces0:e";
+        let mut torrent = b"d8:announce23:http://tracker/announce4:info".to_vec();
+        torrent.extend_from_slice(info);
+        torrent.push(b'e');
+
+        let meta = parse_torrent(&torrent).unwrap();
+        assert_eq!(meta.info_hash, "542649544fed997bb3ee735b1b01fb8103a408ff");
+        assert_eq!(meta.total_size, 320);
+        assert_eq!(meta.files[0].name, "Anime/Sx/01.mkv");
+        assert_eq!(meta.files[1].index, 1);
+        assert_eq!(meta.files[1].name, "Anime/01.ass");
+
+        let single = b"d4:infod6:lengthi42e4:name6:01.mkv12:piece lengthi16384e6:pieces0:ee";
+        let meta = parse_torrent(single).unwrap();
+        assert_eq!(meta.files.len(), 1);
+        assert_eq!(meta.files[0].name, "01.mkv");
+        assert_eq!(meta.total_size, 42);
+
+        assert!(parse_torrent(b"d8:announce3:urle").is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::api::do_anime_task::handle_error;
+use crate::error::error::handle_error;
 use anyhow::Error;
 use serde::{Deserialize, Serialize};
 use serde_yml;
@@ -11,6 +11,109 @@ pub struct QbConfig {
     pub qb_url: String,
     pub username: String,
     pub password: String,
+    #[serde(default)]
+    pub category: String, // empty adds the torrents without category
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TorznabConfig {
+    pub url: String, // e.g. http://127.0.0.1:9117/api/v2.0/indexers/nyaasi/results/torznab/api
+    pub apikey: String,
+    pub category: String,
+}
+
+// the torrent client which downloads the seeds, qbittorrent keeps using qb_config
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DownloaderConfig {
+    pub kind: String, // qbittorrent (default), transmission or aria2
+    pub url: String,  // e.g. http://127.0.0.1:9091/transmission/rpc, http://127.0.0.1:6800/jsonrpc
+    pub username: String,
+    pub password: String, // the rpc secret of aria2
+}
+
+// a directory of the torrent client (remote) and where autoAnime sees it (local),
+// e.g. remote /downloads, local /app/downloads, see mods::path_mapping
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PathMapping {
+    pub remote: String,
+    pub local: String,
+}
+
+// seconds a scraped page is served from the cache before it is revalidated
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheTtl {
+    pub season: u64,
+    pub anime: u64,
+    pub episode_table: u64,
+    pub rss: u64,
+    pub subject: u64, // bangumi
+    pub other: u64,   // torznab
+}
+
+impl Default for CacheTtl {
+    fn default() -> Self {
+        CacheTtl {
+            season: 21600,
+            anime: 3600,
+            episode_table: 300,
+            rss: 300,
+            subject: 86400,
+            other: 0,
+        }
+    }
+}
+
+// on-disk cache of the scraped pages, replay serves only from it, see mods::http_cache
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpCacheConfig {
+    pub enable: bool,
+    pub replay: bool,
+    pub dir: String,
+    pub ttl: CacheTtl,
+}
+
+impl Default for HttpCacheConfig {
+    fn default() -> Self {
+        HttpCacheConfig {
+            enable: true,
+            replay: false,
+            dir: "downloads/cache".to_string(),
+            ttl: CacheTtl::default(),
+        }
+    }
+}
+
+// http of the scrapers, see mods::http_client
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScraperConfig {
+    pub proxy: String, // e.g. http://127.0.0.1:7890, socks5://127.0.0.1:1080, empty for none
+    pub user_agent: String,
+    pub mikan_url: String, // a mirror such as https://mikanime.tv
+    pub bangumi_url: String,
+    pub bangumi_api_url: String, // bangumi v0 json api, the subject page is scraped when it fails
+    pub timeout_seconds: u64,
+    pub rate_limit_ms: u64, // the least time between two requests to a host
+    pub max_retries: u32,
+    pub cache: HttpCacheConfig,
+}
+
+impl Default for ScraperConfig {
+    fn default() -> Self {
+        ScraperConfig {
+            proxy: String::new(),
+            user_agent: "Mozilla/5.0 (compatible; autoAnime)".to_string(),
+            mikan_url: "https://mikanani.me".to_string(),
+            bangumi_url: "https://bgm.tv".to_string(),
+            bangumi_api_url: "https://api.bgm.tv".to_string(),
+            timeout_seconds: 10,
+            rate_limit_ms: 500,
+            max_retries: 3,
+            cache: HttpCacheConfig::default(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,9 +122,127 @@ pub struct SubgroupFilter {
     pub avoid: Vec<i32>,
 }
 
+// global quality rules, see mods::anime_filter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QualityFilter {
+    pub resolution: Vec<i32>,   // allowed resolutions, e.g. 1080, 720
+    pub codec: Vec<String>,     // preferred codecs: HEVC, AVC, AV1
+    pub subtitle: Vec<String>,  // preferred subtitles: 简, 繁, 内封
+    pub container: Vec<String>, // preferred containers: MKV, MP4
+}
+
+impl Default for QualityFilter {
+    fn default() -> Self {
+        QualityFilter {
+            resolution: vec![1080, 720],
+            codec: Vec::new(),
+            subtitle: Vec::new(),
+            container: Vec::new(),
+        }
+    }
+}
+
+// replace a downloaded episode when a strictly better seed is published
+// within window_hours after it, e.g. a fixed v2 or a release of a preferred subgroup
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpgradePolicy {
+    #[serde(default)]
+    pub enable: bool,
+    #[serde(default)]
+    pub window_hours: i64, // 0 keeps the current policy when modifying config
+}
+
+// an episode without a seed of a preferred subgroup is held for hours after its
+// first seen seed before the best other seed is accepted, an anime may have its
+// own period, see dao::anime_filter::get_grace_hours
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GracePolicy {
+    #[serde(default)]
+    pub enable: bool,
+    #[serde(default)]
+    pub hours: i64, // 0 keeps the current policy when modifying config
+}
+
+// keep finished torrents seeding until a target is reached, a torrent is removed once
+// it reached ratio_limit or seeding_minutes (0 disables a target) and the oldest
+// seeds are removed beyond max_active_seeds (0 is unlimited)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SeedingPolicy {
+    #[serde(default)]
+    pub enable: bool,
+    #[serde(default)]
+    pub ratio_limit: f64,
+    #[serde(default)]
+    pub seeding_minutes: i64,
+    #[serde(default)]
+    pub max_active_seeds: i64,
+}
+
+// KiB/s of every torrent, 0 is unlimited. The limit of an anime is stored in
+// anime_filter and replaces this one, see dao::anime_filter::get_speed_limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct SpeedLimit {
+    #[serde(default)]
+    pub download: i64,
+    #[serde(default)]
+    pub upload: i64,
+}
+
+impl SpeedLimit {
+    pub fn or(self, global: SpeedLimit) -> SpeedLimit {
+        let pick = |local: i64, global: i64| if local > 0 { local } else { global };
+        SpeedLimit {
+            download: pick(self.download, global.download),
+            upload: pick(self.upload, global.upload),
+        }
+    }
+}
+
+// seed files are fetched by at most workers at a time, a failed fetch is retried after
+// backoff_seconds, doubled on every attempt, and the job is dead after max_attempts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DownloadQueueConfig {
+    pub workers: usize,
+    pub max_attempts: i32,
+    pub backoff_seconds: i64,
+}
+
+impl Default for DownloadQueueConfig {
+    fn default() -> Self {
+        DownloadQueueConfig {
+            workers: 4,
+            max_attempts: 5,
+            backoff_seconds: 60,
+        }
+    }
+}
+
+impl DownloadQueueConfig {
+    // the delay after the attempts-th failure, None when the job is dead
+    pub fn backoff(&self, attempts: i32) -> Option<i64> {
+        if attempts >= self.max_attempts {
+            return None;
+        }
+        let exp = (attempts - 1).clamp(0, 16) as u32;
+        Some(
+            self.backoff_seconds
+                .max(1)
+                .saturating_mul(1 << exp)
+                .min(86400),
+        )
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnimeConfig {
     pub subgroup_filter: SubgroupFilter,
+    #[serde(default)]
+    pub quality_filter: QualityFilter,
+    #[serde(default)]
+    pub upgrade_policy: UpgradePolicy,
+    #[serde(default)]
+    pub grace_policy: GracePolicy,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,6 +253,21 @@ pub struct Config {
     pub ui_url: String,
     pub qb_config: QbConfig,
     pub anime_config: AnimeConfig,
+    #[serde(default)]
+    pub torznab_config: TorznabConfig,
+    #[serde(default)]
+    pub downloader: DownloaderConfig,
+    #[serde(default)]
+    pub seeding_policy: SeedingPolicy,
+    #[serde(default)]
+    pub speed_limit: SpeedLimit,
+    // None when absent, so modify_config can tell "keep the rules" from an empty list
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_mapping: Option<Vec<PathMapping>>,
+    #[serde(default)]
+    pub download_queue: DownloadQueueConfig,
+    #[serde(default)]
+    pub scraper: ScraperConfig,
 }
 
 trait Updatable {
@@ -49,6 +285,99 @@ impl Updatable for QbConfig {
         if other.password != "" {
             self.password = std::mem::take(&mut other.password);
         }
+        if !other.category.is_empty() {
+            self.category = std::mem::take(&mut other.category);
+        }
+    }
+}
+
+impl Updatable for TorznabConfig {
+    fn update_from(&mut self, other: &mut Self) {
+        if !other.url.is_empty() {
+            self.url = std::mem::take(&mut other.url);
+        }
+        if !other.apikey.is_empty() {
+            self.apikey = std::mem::take(&mut other.apikey);
+        }
+        if !other.category.is_empty() {
+            self.category = std::mem::take(&mut other.category);
+        }
+    }
+}
+
+impl Updatable for DownloaderConfig {
+    fn update_from(&mut self, other: &mut Self) {
+        if !other.kind.is_empty() {
+            self.kind = std::mem::take(&mut other.kind);
+        }
+        if !other.url.is_empty() {
+            self.url = std::mem::take(&mut other.url);
+        }
+        if !other.username.is_empty() {
+            self.username = std::mem::take(&mut other.username);
+        }
+        if !other.password.is_empty() {
+            self.password = std::mem::take(&mut other.password);
+        }
+    }
+}
+
+impl Updatable for ScraperConfig {
+    fn update_from(&mut self, other: &mut Self) {
+        if !other.proxy.is_empty() {
+            self.proxy = std::mem::take(&mut other.proxy);
+        }
+        if !other.user_agent.is_empty() {
+            self.user_agent = std::mem::take(&mut other.user_agent);
+        }
+        if !other.mikan_url.is_empty() {
+            self.mikan_url = std::mem::take(&mut other.mikan_url);
+        }
+        if !other.bangumi_url.is_empty() {
+            self.bangumi_url = std::mem::take(&mut other.bangumi_url);
+        }
+        if !other.bangumi_api_url.is_empty() {
+            self.bangumi_api_url = std::mem::take(&mut other.bangumi_api_url);
+        }
+        if other.timeout_seconds > 0 {
+            self.timeout_seconds = other.timeout_seconds;
+        }
+        if other.rate_limit_ms > 0 {
+            self.rate_limit_ms = other.rate_limit_ms;
+        }
+        if other.max_retries > 0 {
+            self.max_retries = other.max_retries;
+        }
+        self.cache.update_from(&mut other.cache);
+    }
+}
+
+impl Updatable for HttpCacheConfig {
+    fn update_from(&mut self, other: &mut Self) {
+        // an empty dir keeps the current cache settings when modifying config
+        if !other.dir.is_empty() {
+            self.enable = other.enable;
+            self.replay = other.replay;
+            self.dir = std::mem::take(&mut other.dir);
+        }
+        self.ttl.update_from(&mut other.ttl);
+    }
+}
+
+impl Updatable for CacheTtl {
+    fn update_from(&mut self, other: &mut Self) {
+        for (ttl, other_ttl) in [
+            (&mut self.season, other.season),
+            (&mut self.anime, other.anime),
+            (&mut self.episode_table, other.episode_table),
+            (&mut self.rss, other.rss),
+            (&mut self.subject, other.subject),
+            (&mut self.other, other.other),
+        ] {
+            if other_ttl > 0 {
+                *ttl = other_ttl;
+            }
+        }
     }
 }
 
@@ -63,9 +392,85 @@ impl Updatable for SubgroupFilter {
     }
 }
 
+impl Updatable for QualityFilter {
+    fn update_from(&mut self, other: &mut Self) {
+        if !other.resolution.is_empty() {
+            self.resolution = std::mem::take(&mut other.resolution);
+        }
+        if !other.codec.is_empty() {
+            self.codec = std::mem::take(&mut other.codec);
+        }
+        if !other.subtitle.is_empty() {
+            self.subtitle = std::mem::take(&mut other.subtitle);
+        }
+        if !other.container.is_empty() {
+            self.container = std::mem::take(&mut other.container);
+        }
+    }
+}
+
+impl Updatable for UpgradePolicy {
+    fn update_from(&mut self, other: &mut Self) {
+        if other.window_hours > 0 {
+            self.enable = other.enable;
+            self.window_hours = other.window_hours;
+        }
+    }
+}
+
+impl Updatable for GracePolicy {
+    fn update_from(&mut self, other: &mut Self) {
+        if other.hours > 0 {
+            self.enable = other.enable;
+            self.hours = other.hours;
+        }
+    }
+}
+
+impl Updatable for SeedingPolicy {
+    fn update_from(&mut self, other: &mut Self) {
+        // an empty policy keeps the current one when modifying config
+        if other.enable
+            || other.ratio_limit > 0.0
+            || other.seeding_minutes > 0
+            || other.max_active_seeds > 0
+        {
+            *self = other.clone();
+        }
+    }
+}
+
+impl Updatable for SpeedLimit {
+    fn update_from(&mut self, other: &mut Self) {
+        if other.download > 0 {
+            self.download = other.download;
+        }
+        if other.upload > 0 {
+            self.upload = other.upload;
+        }
+    }
+}
+
+impl Updatable for DownloadQueueConfig {
+    fn update_from(&mut self, other: &mut Self) {
+        if other.workers > 0 {
+            self.workers = other.workers;
+        }
+        if other.max_attempts > 0 {
+            self.max_attempts = other.max_attempts;
+        }
+        if other.backoff_seconds > 0 {
+            self.backoff_seconds = other.backoff_seconds;
+        }
+    }
+}
+
 impl Updatable for AnimeConfig {
     fn update_from(&mut self, other: &mut Self) {
         self.subgroup_filter.update_from(&mut other.subgroup_filter);
+        self.quality_filter.update_from(&mut other.quality_filter);
+        self.upgrade_policy.update_from(&mut other.upgrade_policy);
+        self.grace_policy.update_from(&mut other.grace_policy);
     }
 }
 
@@ -85,6 +490,16 @@ impl Updatable for Config {
         }
         self.qb_config.update_from(&mut other.qb_config);
         self.anime_config.update_from(&mut other.anime_config);
+        self.torznab_config.update_from(&mut other.torznab_config);
+        self.downloader.update_from(&mut other.downloader);
+        self.seeding_policy.update_from(&mut other.seeding_policy);
+        self.speed_limit.update_from(&mut other.speed_limit);
+        // an empty list clears the rules
+        if other.path_mapping.is_some() {
+            self.path_mapping = other.path_mapping.take();
+        }
+        self.download_queue.update_from(&mut other.download_queue);
+        self.scraper.update_from(&mut other.scraper);
     }
 }
 
@@ -102,6 +517,11 @@ async fn read_raw_config_file(path: &str) -> Result<String, Error> {
 }
 
 impl Config {
+    // the rules of path_mapping, there are none when it is absent
+    pub fn path_mapping_rules(&self) -> &[PathMapping] {
+        self.path_mapping.as_deref().unwrap_or_default()
+    }
+
     #[allow(dead_code)]
     pub async fn load_config(path: &str) -> Result<Config, Error> {
         match read_raw_config_file(path).await {
@@ -177,15 +597,115 @@ mod test {
                 qb_url: "".to_string(),
                 username: "".to_string(),
                 password: "".to_string(),
+                category: "".to_string(),
             },
             anime_config: AnimeConfig {
                 subgroup_filter: SubgroupFilter {
                     preference: vec![123],
                     avoid: vec![456],
                 },
+                quality_filter: QualityFilter {
+                    resolution: Vec::new(),
+                    codec: vec!["HEVC".to_string()],
+                    subtitle: Vec::new(),
+                    container: Vec::new(),
+                },
+                upgrade_policy: UpgradePolicy::default(),
+                grace_policy: GracePolicy::default(),
+            },
+            torznab_config: TorznabConfig::default(),
+            downloader: DownloaderConfig::default(),
+            seeding_policy: SeedingPolicy::default(),
+            speed_limit: SpeedLimit {
+                download: 0,
+                upload: 512,
+            },
+            path_mapping: None,
+            download_queue: DownloadQueueConfig {
+                workers: 0,
+                max_attempts: 0,
+                backoff_seconds: 0,
+            },
+            scraper: ScraperConfig {
+                proxy: "".to_string(),
+                user_agent: "".to_string(),
+                mikan_url: "".to_string(),
+                bangumi_url: "".to_string(),
+                bangumi_api_url: "".to_string(),
+                timeout_seconds: 0,
+                rate_limit_ms: 0,
+                max_retries: 0,
+                cache: HttpCacheConfig {
+                    enable: false,
+                    replay: false,
+                    dir: "".to_string(),
+                    ttl: CacheTtl {
+                        season: 0,
+                        anime: 0,
+                        episode_table: 0,
+                        rss: 0,
+                        subject: 0,
+                        other: 0,
+                    },
+                },
             },
         };
         config.modify_filed(&mut new_config_val).await.unwrap();
         println!("{:?}", config);
+
+        // the limit of an anime replaces the global one
+        let global = SpeedLimit {
+            download: 4096,
+            upload: 512,
+        };
+        let local = SpeedLimit {
+            download: 1024,
+            upload: 0,
+        };
+        assert_eq!(
+            local.or(global),
+            SpeedLimit {
+                download: 1024,
+                upload: 512
+            }
+        );
+
+        // 60s, 120s, 240s, then dead after the 4th failure
+        let queue = DownloadQueueConfig {
+            workers: 2,
+            max_attempts: 4,
+            backoff_seconds: 60,
+        };
+        assert_eq!(queue.backoff(1), Some(60));
+        assert_eq!(queue.backoff(3), Some(240));
+        assert_eq!(queue.backoff(4), None);
+    }
+
+    #[tokio::test]
+    async fn test_update_path_mapping() {
+        let mut config = Config::load_config("./config/config.yaml").await.unwrap();
+        let rules = vec![PathMapping {
+            remote: "/downloads".to_string(),
+            local: "/app/downloads".to_string(),
+        }];
+
+        let mut new_config_val = config.clone();
+        new_config_val.path_mapping = Some(rules.clone());
+        config.update_from(&mut new_config_val);
+        assert_eq!(config.path_mapping_rules(), rules.as_slice());
+
+        // an absent path_mapping keeps the rules, an empty list clears them
+        let mut new_config_val = config.clone();
+        new_config_val.path_mapping = None;
+        config.update_from(&mut new_config_val);
+        assert_eq!(config.path_mapping_rules(), rules.as_slice());
+
+        let mut new_config_val = config.clone();
+        new_config_val.path_mapping = Some(Vec::new());
+        config.update_from(&mut new_config_val);
+        assert!(config.path_mapping_rules().is_empty());
+        assert!(serde_yml::to_string(&config)
+            .unwrap()
+            .contains("path_mapping: []"));
     }
 }
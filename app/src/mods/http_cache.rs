@@ -0,0 +1,176 @@
+use crate::mods::config::{CacheTtl, HttpCacheConfig};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::error::Error;
+use std::fmt::{self, Write};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/*
+    On-disk cache of the pages fetched by the scrapers, one json file per url.
+    A page younger than the ttl of its endpoint is served as is, an older one is
+    revalidated with If-None-Match / If-Modified-Since. In replay mode only the
+    cache is read and a missing page is an error, so the parsers can be run offline.
+*/
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub url: String,
+    pub etag: String,
+    pub last_modified: String,
+    pub fetched_at: u64, // unix seconds
+    pub body: String,
+}
+
+#[derive(Debug)]
+pub struct NotCachedError {
+    pub url: String,
+}
+
+impl fmt::Display for NotCachedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} is not cached", self.url)
+    }
+}
+
+impl Error for NotCachedError {}
+
+#[derive(Debug, Clone)]
+pub struct HttpCache {
+    dir: PathBuf,
+    replay: bool,
+    ttl: CacheTtl,
+}
+
+impl HttpCache {
+    // None when the cache is disabled
+    pub fn new(config: &HttpCacheConfig) -> Option<HttpCache> {
+        if !config.enable && !config.replay {
+            return None;
+        }
+        Some(HttpCache {
+            dir: PathBuf::from(&config.dir),
+            replay: config.replay,
+            ttl: config.ttl.clone(),
+        })
+    }
+
+    pub fn is_replay(&self) -> bool {
+        self.replay
+    }
+
+    // a missing or unreadable entry is a miss
+    pub fn load(&self, url: &str) -> Option<CacheEntry> {
+        let contents = fs::read_to_string(self.path_of(url)).ok()?;
+        serde_json::from_str::<CacheEntry>(&contents)
+            .ok()
+            .filter(|entry| entry.url == url)
+    }
+
+    pub fn store(&self, entry: &CacheEntry) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.path_of(&entry.url), serde_json::to_string(entry)?)?;
+        Ok(())
+    }
+
+    pub fn is_fresh(&self, entry: &CacheEntry, now: u64) -> bool {
+        now.saturating_sub(entry.fetched_at) < ttl_of(&self.ttl, &entry.url)
+    }
+
+    fn path_of(&self, url: &str) -> PathBuf {
+        let mut name = String::new();
+        for b in Sha1::digest(url.as_bytes()).iter() {
+            let _ = write!(name, "{:02x}", b);
+        }
+        self.dir.join(format!("{}.json", name))
+    }
+}
+
+// seconds a page of the endpoint is served without asking the site
+pub fn ttl_of(ttl: &CacheTtl, url: &str) -> u64 {
+    if url.contains("/RSS/") {
+        ttl.rss
+    } else if url.contains("/Home/ExpandEpisodeTable") {
+        ttl.episode_table
+    } else if url.contains("/Home/BangumiCoverFlowByDayOfWeek") {
+        ttl.season
+    } else if url.contains("/Home/Bangumi/") {
+        ttl.anime
+    } else if url.contains("/subject/") || url.contains("/v0/") {
+        ttl.subject
+    } else {
+        ttl.other
+    }
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_http_cache() {
+        let dir = env::temp_dir().join("autoanime_http_cache");
+        let _ = fs::remove_dir_all(&dir);
+        let config = HttpCacheConfig {
+            enable: true,
+            replay: false,
+            dir: dir.to_string_lossy().to_string(),
+            ttl: CacheTtl::default(),
+        };
+        let cache = HttpCache::new(&config).unwrap();
+
+        let url = "https://mikanani.me/RSS/Bangumi?bangumiId=3143&subgroupid=382";
+        assert!(cache.load(url).is_none());
+        let entry = CacheEntry {
+            url: url.to_string(),
+            etag: "\"v1\"".to_string(),
+            last_modified: "".to_string(),
+            fetched_at: 1000,
+            body: "<rss/>".to_string(),
+        };
+        cache.store(&entry).unwrap();
+        assert_eq!(cache.load(url), Some(entry.clone()));
+
+        let rss_ttl = config.ttl.rss;
+        assert!(cache.is_fresh(&entry, 1000 + rss_ttl - 1));
+        assert!(!cache.is_fresh(&entry, 1000 + rss_ttl));
+
+        assert!(HttpCache::new(&HttpCacheConfig {
+            enable: false,
+            ..config
+        })
+        .is_none());
+    }
+
+    #[test]
+    fn test_ttl_of() {
+        let ttl = CacheTtl {
+            season: 1,
+            anime: 2,
+            episode_table: 3,
+            rss: 4,
+            subject: 5,
+            other: 6,
+        };
+        let urls = [
+            "https://mikanani.me/Home/BangumiCoverFlowByDayOfWeek?year=2023&seasonStr=%E7%A7%8B",
+            "https://mikanani.me/Home/Bangumi/3143",
+            "https://mikanani.me/Home/ExpandEpisodeTable?bangumiId=3143&subtitleGroupId=382&take=65",
+            "https://mikanani.me/RSS/Bangumi?bangumiId=3143&subgroupid=382",
+            "https://bgm.tv/subject/400602",
+            "https://api.bgm.tv/v0/episodes?subject_id=400602&limit=100&offset=0",
+            "http://127.0.0.1:9117/api/v2.0/indexers/nyaasi/results/torznab/api?t=search",
+        ];
+        let ttl_vec: Vec<u64> = urls.iter().map(|url| ttl_of(&ttl, url)).collect();
+        assert_eq!(ttl_vec, vec![1, 2, 3, 4, 5, 5, 6]);
+    }
+}
@@ -0,0 +1,431 @@
+use crate::mods::config::ScraperConfig;
+use crate::mods::http_cache::{self, CacheEntry, HttpCache, NotCachedError};
+use once_cell::sync::Lazy;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{Client, Proxy, Response, StatusCode, Url};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::time::sleep;
+
+/*
+    The http client shared by the scrapers (mikan, bangumi and torznab).
+    Requests to a host are spaced by rate_limit_ms, across every client of the
+    process, and a request which timed out, failed to connect or got a 429/5xx
+    is retried up to max_retries times with an exponential backoff plus jitter.
+    Pages read by get_text go through the http cache, see mods::http_cache.
+*/
+
+// the earliest time of the next request to each host
+static NEXT_REQUEST: Lazy<Mutex<HashMap<String, Instant>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+const RETRY_BASE: Duration = Duration::from_millis(500);
+
+#[derive(Debug)]
+pub struct StatusError {
+    pub url: String,
+    pub status: StatusCode,
+}
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "request {} failed with {}", self.url, self.status)
+    }
+}
+
+impl Error for StatusError {}
+
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    client: Client,
+    rate_limit: Duration,
+    max_retries: u32,
+    retry_base: Duration,
+    cache: Option<HttpCache>,
+}
+
+impl HttpClient {
+    pub fn new(config: &ScraperConfig) -> Result<HttpClient, Box<dyn Error>> {
+        let mut builder = Client::builder()
+            .timeout(Duration::from_secs(config.timeout_seconds.max(1)))
+            .user_agent(config.user_agent.as_str());
+        // http://, https://, socks5:// or socks5h:// (dns through the proxy)
+        if !config.proxy.is_empty() {
+            builder = builder.proxy(Proxy::all(&config.proxy)?);
+        }
+        Ok(HttpClient {
+            client: builder.build()?,
+            rate_limit: Duration::from_millis(config.rate_limit_ms),
+            max_retries: config.max_retries,
+            retry_base: RETRY_BASE,
+            cache: HttpCache::new(&config.cache),
+        })
+    }
+
+    // a response of another status than 2xx is returned as StatusError
+    pub async fn get(&self, url: &str) -> Result<Response, Box<dyn Error>> {
+        self.send(url, None).await
+    }
+
+    // a 304 is also accepted when the request is revalidating a cached page
+    async fn send(
+        &self,
+        url: &str,
+        cached: Option<&CacheEntry>,
+    ) -> Result<Response, Box<dyn Error>> {
+        let mut attempt = 0;
+        loop {
+            self.wait_turn(url).await;
+            let mut request = self.client.get(url);
+            if let Some(entry) = cached {
+                if !entry.etag.is_empty() {
+                    request = request.header(IF_NONE_MATCH, &entry.etag);
+                }
+                if !entry.last_modified.is_empty() {
+                    request = request.header(IF_MODIFIED_SINCE, &entry.last_modified);
+                }
+            }
+            let reason = match request.send().await {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response)
+                    if response.status() == StatusCode::NOT_MODIFIED && cached.is_some() =>
+                {
+                    return Ok(response)
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let retryable =
+                        status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS;
+                    if !retryable || attempt >= self.max_retries {
+                        return Err(Box::new(StatusError {
+                            url: url.to_string(),
+                            status,
+                        }));
+                    }
+                    status.to_string()
+                }
+                Err(e) => {
+                    let retryable = e.is_timeout() || e.is_connect() || e.is_request();
+                    if !retryable || attempt >= self.max_retries {
+                        return Err(Box::new(e));
+                    }
+                    e.to_string()
+                }
+            };
+            attempt += 1;
+            let delay = backoff(self.retry_base, attempt);
+            log::warn!(
+                "request {} failed: {}, retry {}/{} in {:?}",
+                url,
+                reason,
+                attempt,
+                self.max_retries,
+                delay
+            );
+            sleep(delay).await;
+        }
+    }
+
+    pub async fn get_text(&self, url: &str) -> Result<String, Box<dyn Error>> {
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => {
+                let response = self.get(url).await?;
+                return Ok(response.text().await?);
+            }
+        };
+        let cached = cache.load(url);
+        if cache.is_replay() {
+            return match cached {
+                Some(entry) => Ok(entry.body),
+                None => Err(Box::new(NotCachedError {
+                    url: url.to_string(),
+                })),
+            };
+        }
+
+        let now = http_cache::now_secs();
+        let cached = match cached {
+            Some(entry) if cache.is_fresh(&entry, now) => return Ok(entry.body),
+            cached => cached,
+        };
+        let response = self.send(url, cached.as_ref()).await?;
+        let entry = match cached {
+            Some(entry) if response.status() == StatusCode::NOT_MODIFIED => CacheEntry {
+                fetched_at: now,
+                ..entry
+            },
+            _ => {
+                let header = |name| {
+                    response
+                        .headers()
+                        .get(name)
+                        .and_then(|v| v.to_str().ok())
+                        .unwrap_or_default()
+                        .to_string()
+                };
+                CacheEntry {
+                    url: url.to_string(),
+                    etag: header(ETAG),
+                    last_modified: header(LAST_MODIFIED),
+                    fetched_at: now,
+                    body: response.text().await?,
+                }
+            }
+        };
+        if let Err(e) = cache.store(&entry) {
+            log::warn!("Failed to cache {}, {}", url, e);
+        }
+        Ok(entry.body)
+    }
+
+    // reserve the next slot of the host, then wait for it
+    async fn wait_turn(&self, url: &str) {
+        if self.rate_limit.is_zero() {
+            return;
+        }
+        let wait = {
+            let mut next_request = NEXT_REQUEST.lock().unwrap();
+            let now = Instant::now();
+            let slot = match next_request.get(&host_of(url)) {
+                Some(next) if *next > now => *next,
+                _ => now,
+            };
+            next_request.insert(host_of(url), slot + self.rate_limit);
+            slot - now
+        };
+        if !wait.is_zero() {
+            sleep(wait).await;
+        }
+    }
+}
+
+// host and port, the rate limit of a mirror is counted apart
+fn host_of(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(url) => format!(
+            "{}:{}",
+            url.host_str().unwrap_or_default(),
+            url.port_or_known_default().unwrap_or_default()
+        ),
+        Err(_) => String::new(),
+    }
+}
+
+// base * 2^(attempt - 1), plus a jitter below base so that retries do not line up
+fn backoff(base: Duration, attempt: u32) -> Duration {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    base * 2u32.pow(attempt.clamp(1, 7) - 1) + base.mul_f64(nanos as f64 / 1e9)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mods::config::HttpCacheConfig;
+    use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+    use std::env;
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    type MockState = web::Data<AtomicI32>;
+
+    // fails twice with 503 before it answers
+    async fn mock_flaky(state: MockState) -> HttpResponse {
+        if state.fetch_add(1, Ordering::SeqCst) < 2 {
+            return HttpResponse::ServiceUnavailable().finish();
+        }
+        HttpResponse::Ok().body("ok")
+    }
+
+    async fn mock_missing(state: MockState) -> HttpResponse {
+        state.fetch_add(1, Ordering::SeqCst);
+        HttpResponse::NotFound().finish()
+    }
+
+    async fn mock_user_agent(req: HttpRequest) -> HttpResponse {
+        let user_agent = req
+            .headers()
+            .get("user-agent")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        HttpResponse::Ok().body(user_agent.to_string())
+    }
+
+    // counts 1 for a page and 100 for a 304
+    async fn mock_etag(state: MockState, req: HttpRequest) -> HttpResponse {
+        if req
+            .headers()
+            .get("if-none-match")
+            .and_then(|v| v.to_str().ok())
+            == Some("\"v1\"")
+        {
+            state.fetch_add(100, Ordering::SeqCst);
+            return HttpResponse::NotModified().finish();
+        }
+        state.fetch_add(1, Ordering::SeqCst);
+        HttpResponse::Ok()
+            .insert_header(("ETag", "\"v1\""))
+            .body("page")
+    }
+
+    fn start_mock_server(state: MockState) -> String {
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(state.clone())
+                .route("/flaky", web::get().to(mock_flaky))
+                .route("/missing", web::get().to(mock_missing))
+                .route("/ua", web::get().to(mock_user_agent))
+                .route("/etag", web::get().to(mock_etag))
+                .route("/RSS/Bangumi", web::get().to(mock_etag))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let host = format!("http://127.0.0.1:{}", server.addrs()[0].port());
+        actix_web::rt::spawn(server.run());
+        host
+    }
+
+    fn test_client(rate_limit_ms: u64) -> HttpClient {
+        let config = ScraperConfig {
+            user_agent: "autoAnime-test".to_string(),
+            rate_limit_ms,
+            cache: HttpCacheConfig {
+                enable: false,
+                ..HttpCacheConfig::default()
+            },
+            ..ScraperConfig::default()
+        };
+        HttpClient {
+            retry_base: Duration::from_millis(10),
+            ..HttpClient::new(&config).unwrap()
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_retry() {
+        let state = web::Data::new(AtomicI32::new(0));
+        let host = start_mock_server(state.clone());
+        let client = test_client(0);
+
+        assert_eq!(
+            client.get_text(&format!("{}/flaky", host)).await.unwrap(),
+            "ok"
+        );
+        assert_eq!(state.load(Ordering::SeqCst), 3);
+
+        // a 404 is not retried
+        state.store(0, Ordering::SeqCst);
+        let e = client.get(&format!("{}/missing", host)).await.unwrap_err();
+        assert_eq!(
+            e.downcast_ref::<StatusError>().unwrap().status,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(state.load(Ordering::SeqCst), 1);
+
+        // give up after max_retries
+        state.store(-10, Ordering::SeqCst);
+        assert!(client.get(&format!("{}/flaky", host)).await.is_err());
+        assert_eq!(state.load(Ordering::SeqCst), -10 + 4);
+
+        // nothing listens on the port of a stopped server
+        let closed = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().port()
+        };
+        assert!(client
+            .get(&format!("http://127.0.0.1:{}/", closed))
+            .await
+            .is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_rate_limit_and_user_agent() {
+        let state = web::Data::new(AtomicI32::new(0));
+        let host = start_mock_server(state.clone());
+        let client = test_client(100);
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert_eq!(
+                client.get_text(&format!("{}/ua", host)).await.unwrap(),
+                "autoAnime-test"
+            );
+        }
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[actix_web::test]
+    async fn test_cache() {
+        let state = web::Data::new(AtomicI32::new(0));
+        let host = start_mock_server(state.clone());
+        let dir = env::temp_dir().join("autoanime_http_client_cache");
+        let _ = std::fs::remove_dir_all(&dir);
+        let cache_config = HttpCacheConfig {
+            dir: dir.to_string_lossy().to_string(),
+            ..HttpCacheConfig::default()
+        };
+        let client = HttpClient {
+            cache: HttpCache::new(&cache_config),
+            ..test_client(0)
+        };
+
+        // ttl 0, the page is revalidated by its etag
+        let url = format!("{}/etag", host);
+        assert_eq!(client.get_text(&url).await.unwrap(), "page");
+        assert_eq!(client.get_text(&url).await.unwrap(), "page");
+        assert_eq!(state.load(Ordering::SeqCst), 101);
+
+        // rss is served from the cache within its ttl
+        let rss_url = format!("{}/RSS/Bangumi?bangumiId=3143", host);
+        state.store(0, Ordering::SeqCst);
+        assert_eq!(client.get_text(&rss_url).await.unwrap(), "page");
+        assert_eq!(client.get_text(&rss_url).await.unwrap(), "page");
+        assert_eq!(state.load(Ordering::SeqCst), 1);
+
+        // replay never asks the site
+        let replay = HttpClient {
+            cache: HttpCache::new(&HttpCacheConfig {
+                replay: true,
+                ..cache_config
+            }),
+            ..test_client(0)
+        };
+        state.store(0, Ordering::SeqCst);
+        assert_eq!(replay.get_text(&url).await.unwrap(), "page");
+        let e = replay.get_text(&format!("{}/ua", host)).await.unwrap_err();
+        assert!(e.downcast_ref::<NotCachedError>().is_some());
+        assert_eq!(state.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let base = Duration::from_millis(100);
+        for attempt in 1..=3 {
+            let delay = backoff(base, attempt);
+            let min = base * 2u32.pow(attempt - 1);
+            assert!(delay >= min && delay < min + base);
+        }
+        assert_eq!(
+            host_of("https://mikanani.me/RSS/Bangumi"),
+            "mikanani.me:443"
+        );
+        assert_eq!(host_of("http://127.0.0.1:8080/"), "127.0.0.1:8080");
+
+        let socks = ScraperConfig {
+            proxy: "socks5h://127.0.0.1:1080".to_string(),
+            ..ScraperConfig::default()
+        };
+        assert!(HttpClient::new(&socks).is_ok());
+        let invalid = ScraperConfig {
+            proxy: "127.0.0.1 1080".to_string(),
+            ..ScraperConfig::default()
+        };
+        assert!(HttpClient::new(&invalid).is_err());
+    }
+}
@@ -0,0 +1,85 @@
+/*
+    Magnet links, see https://www.bittorrent.org/beps/bep_0009.html
+    A magnet seed has no .torrent file, it is identified by the v1 info hash
+    in its `xt=urn:btih:` parameter. The hash may be hex or base32 encoded,
+    it is always handed out as lowercase hex like bencode::parse_torrent.
+*/
+const MAGNET_PREFIX: &str = "magnet:?";
+const BTIH_PREFIX: &str = "urn:btih:";
+
+pub fn is_magnet(seed_url: &str) -> bool {
+    seed_url.starts_with(MAGNET_PREFIX)
+}
+
+// the raw btih value of a magnet link
+fn get_btih(seed_url: &str) -> Option<&str> {
+    seed_url
+        .strip_prefix(MAGNET_PREFIX)?
+        .split('&')
+        .filter_map(|param| param.strip_prefix("xt="))
+        .find_map(|xt| xt.strip_prefix(BTIH_PREFIX))
+}
+
+pub fn get_info_hash(seed_url: &str) -> Option<String> {
+    let btih = get_btih(seed_url)?;
+    match btih.len() {
+        40 if btih.chars().all(|c| c.is_ascii_hexdigit()) => Some(btih.to_lowercase()),
+        32 => base32_to_hex(btih),
+        _ => None,
+    }
+}
+
+// rewrite a base32 btih as hex, so the seed url contains the info hash it is tracked by
+pub fn normalize(seed_url: &str) -> Option<String> {
+    let btih = get_btih(seed_url)?;
+    let info_hash = get_info_hash(seed_url)?;
+    Some(seed_url.replacen(btih, &info_hash, 1))
+}
+
+fn base32_to_hex(value: &str) -> Option<String> {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+    let mut hex = String::with_capacity(40);
+    let mut buffer: u64 = 0;
+    let mut bits = 0;
+    for c in value.to_ascii_uppercase().bytes() {
+        let index = ALPHABET.iter().position(|a| *a == c)? as u64;
+        buffer = (buffer << 5) | index;
+        bits += 5;
+        while bits >= 4 {
+            bits -= 4;
+            hex.push_str(&format!("{:x}", (buffer >> bits) & 0xf));
+        }
+    }
+    Some(hex)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_info_hash() {
+        let hex = "magnet:?xt=urn:btih:BDD2F547CDFD8A38011A5EA451D65379C9572305&dn=%5BLoliHouse%5D&tr=http%3A%2F%2Ft.acg.rip%3A6699%2Fannounce";
+        assert!(is_magnet(hex));
+        assert_eq!(
+            get_info_hash(hex).unwrap(),
+            "bdd2f547cdfd8a38011a5ea451d65379c9572305"
+        );
+
+        let base32 = "magnet:?dn=Anime&xt=urn:btih:XXJPKR6N7WFDQAI2L2SFDVSTPHEVOIYF";
+        assert_eq!(
+            get_info_hash(base32).unwrap(),
+            "bdd2f547cdfd8a38011a5ea451d65379c9572305"
+        );
+        assert_eq!(
+            normalize(base32).unwrap(),
+            "magnet:?dn=Anime&xt=urn:btih:bdd2f547cdfd8a38011a5ea451d65379c9572305"
+        );
+
+        assert!(!is_magnet(
+            "/Download/20231021/bdd2f547cdfd8a38011a5ea451d65379c9572305.torrent"
+        ));
+        assert!(get_info_hash("magnet:?xt=urn:btih:1234").is_none());
+        assert!(get_info_hash("magnet:?dn=Anime").is_none());
+    }
+}
@@ -3,4 +3,17 @@ pub mod qb_api;
 pub mod anime_filter;
 pub mod web_socket;
 pub mod video_proccessor;
-pub mod config;
\ No newline at end of file
+pub mod config;
+pub mod rss_parser;
+pub mod seed_source;
+pub mod torznab;
+pub mod title_parser;
+pub mod torrent_client;
+pub mod transmission_api;
+pub mod aria2_api;
+pub mod bencode;
+pub mod magnet;
+pub mod path_mapping;
+pub mod http_cache;
+pub mod http_client;
+pub mod subgroup_stats;
\ No newline at end of file
@@ -0,0 +1,174 @@
+use crate::error::error::AnimeError;
+use crate::mods::config::PathMapping;
+use std::env;
+use std::path::Path;
+
+/*
+    Translates paths between the filesystem of the torrent client (remote) and
+    the filesystem of autoAnime (local), e.g. qbittorrent in another container
+    which mounts /app/downloads as /downloads. The longest matching prefix wins,
+    a path which matches no rule is used as it is on both sides.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct PathMapper {
+    rules: Vec<PathMapping>,
+}
+
+// "/a/b" is under "/a" but "/ab" is not
+fn replace_prefix(path: &str, from: &str, to: &str) -> Option<String> {
+    let rest = path.strip_prefix(from)?;
+    if rest.is_empty() || rest.starts_with('/') {
+        Some(format!("{}{}", to, rest))
+    } else {
+        None
+    }
+}
+
+fn normalize(path: &str) -> String {
+    match path.trim_end_matches('/') {
+        "" => "/".to_string(),
+        path => path.to_string(),
+    }
+}
+
+impl PathMapper {
+    // a relative local path is relative to the working directory, like download_path
+    pub fn new(rules: &[PathMapping]) -> Self {
+        let exec_path = env::current_dir().unwrap_or_default();
+        let rules = rules
+            .iter()
+            .map(|rule| PathMapping {
+                remote: normalize(&rule.remote),
+                local: normalize(&exec_path.join(&rule.local).to_string_lossy()),
+            })
+            .collect();
+        Self { rules }
+    }
+
+    fn map(&self, path: &str, to_remote: bool) -> String {
+        self.rules
+            .iter()
+            .map(|rule| {
+                if to_remote {
+                    (&rule.local, &rule.remote)
+                } else {
+                    (&rule.remote, &rule.local)
+                }
+            })
+            .filter_map(|(from, to)| Some((from.len(), replace_prefix(path, from, to)?)))
+            .max_by_key(|(len, _)| *len)
+            .map(|(_, path)| path)
+            .unwrap_or_else(|| path.to_string())
+    }
+
+    pub fn to_remote(&self, local_path: &str) -> String {
+        self.map(local_path, true)
+    }
+
+    pub fn to_local(&self, remote_path: &str) -> String {
+        self.map(remote_path, false)
+    }
+
+    // every rule needs an existing local directory, and the seeds must be saved under a rule
+    pub fn validate(&self, download_path: &str) -> Result<(), AnimeError> {
+        let invalid = |reason: String| AnimeError::BadRequest(format!("path_mapping: {}", reason));
+        for (i, rule) in self.rules.iter().enumerate() {
+            // an empty path is normalized to the root
+            if rule.remote == "/" || rule.local == "/" {
+                return Err(invalid(format!(
+                    "{} -> {} maps the root directory",
+                    rule.remote, rule.local
+                )));
+            }
+            if !Path::new(&rule.local).is_dir() {
+                return Err(invalid(format!("{} is not a directory", rule.local)));
+            }
+            if self.rules[..i].iter().any(|r| r.local == rule.local) {
+                return Err(invalid(format!("{} is mapped twice", rule.local)));
+            }
+        }
+        if self.rules.is_empty() {
+            return Ok(());
+        }
+
+        let download_path = env::current_dir()
+            .map_err(|e| AnimeError::from(e).context("Failed to get current dir"))?
+            .join(download_path);
+        let download_path = download_path.to_string_lossy();
+        if self.to_remote(&download_path) == download_path {
+            return Err(invalid(format!("{} is not mapped", download_path)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rule(remote: &str, local: &str) -> PathMapping {
+        PathMapping {
+            remote: remote.to_string(),
+            local: local.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_path_mapper() {
+        let mapper = PathMapper::new(&[
+            rule("/downloads/", "/app/downloads"),
+            rule("/mnt/anime", "/app/downloads/anime"),
+        ]);
+        assert_eq!(
+            mapper.to_remote("/app/downloads/Frieren(3143)"),
+            "/downloads/Frieren(3143)"
+        );
+        assert_eq!(
+            mapper.to_remote("/app/downloads/anime/Frieren(3143)"),
+            "/mnt/anime/Frieren(3143)"
+        );
+        assert_eq!(
+            mapper.to_remote("/app/downloads2/a.mkv"),
+            "/app/downloads2/a.mkv"
+        );
+        assert_eq!(
+            mapper.to_local("/downloads/Frieren(3143)/01.mkv"),
+            "/app/downloads/Frieren(3143)/01.mkv"
+        );
+        assert_eq!(mapper.to_local("/downloads"), "/app/downloads");
+
+        // no rules, both sides see the same path
+        let mapper = PathMapper::default();
+        assert_eq!(mapper.to_local("/downloads/01.mkv"), "/downloads/01.mkv");
+        assert!(mapper.validate("downloads").is_ok());
+    }
+
+    #[test]
+    fn test_validate() {
+        let local = env::temp_dir().join("autoanime_path_mapping");
+        std::fs::create_dir_all(local.join("downloads")).unwrap();
+        let local = local.to_string_lossy().to_string();
+        let download_path = format!("{}/downloads", local);
+
+        let mapper = PathMapper::new(&[rule("/data", &local)]);
+        assert!(mapper.validate(&download_path).is_ok());
+        assert_eq!(
+            mapper.to_remote(&format!("{}/Frieren(3143)", download_path)),
+            "/data/downloads/Frieren(3143)"
+        );
+
+        let e = mapper.validate("/elsewhere").unwrap_err();
+        assert_eq!(e.code(), "bad_request");
+        assert!(PathMapper::new(&[rule("", &local)])
+            .validate(&download_path)
+            .is_err());
+        assert!(PathMapper::new(&[rule("/data", "/no/such/dir")])
+            .validate(&download_path)
+            .is_err());
+        assert!(
+            PathMapper::new(&[rule("/data", &local), rule("/data2", &local)])
+                .validate(&download_path)
+                .is_err()
+        );
+    }
+}
@@ -1,497 +1,420 @@
-use crate::error::error::AnimeError;
+use crate::error::error::{handle_error, AnimeError};
 use crate::models::anime_seed::AnimeSeed;
-use crate::mods::config::Config;
-use chrono::DateTime;
+use crate::mods::config::{Config, SpeedLimit};
+use crate::mods::magnet;
+use crate::mods::path_mapping::PathMapper;
+use crate::mods::torrent_client::{
+    format_eta, format_progress, format_size, format_speed, get_save_path, get_torrent_tags,
+    read_seed_file, SeedingStats, TorrentClient, TorrentFile, TorrentInfo,
+};
+use async_trait::async_trait;
 use reqwest::multipart::{Form, Part};
-use serde::{Deserialize, Serialize};
+use reqwest::StatusCode;
 use serde_json;
 use std::collections::{HashMap, HashSet};
 use std::env;
-use std::time::{Duration, UNIX_EPOCH};
-
-pub fn handle_error<E: std::fmt::Debug>(e: E, _message: &str) -> AnimeError {
-    AnimeError::new(format!("{:?}", e))
-}
-
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+/*
+    is_login is the health of the connection: it is cleared when qbittorrent
+    can not be reached or refuses to log us in, and set again by the next
+    successful login. A 403 means qbittorrent forgot our session (e.g. it has
+    been restarted), the request is then retried once after logging in again.
+*/
 #[derive(Debug, Clone)]
 pub struct QbitTaskExecutor {
-    pub is_login: bool,
+    is_login: Arc<AtomicBool>,
     qbt_client: reqwest::Client,
-    cookie: String,
+    cookie: Arc<RwLock<String>>,
     host: String,
+    username: String,
+    password: String,
     download_path: String,
     deploy_mode: String,
+    category: String,
+    speed_limit: SpeedLimit,
+    path_mapper: PathMapper,
 }
 
 #[allow(dead_code)]
 impl QbitTaskExecutor {
-    pub async fn new_with_login(username: String, password: String) -> Result<Self, AnimeError> {
-        let qbt_client = reqwest::Client::new();
-        let host = "http://127.0.0.1:8081/".to_string();
-        let login_endpoint = host.clone() + "api/v2/auth/login";
-
-        if let Ok(resp) = qbt_client
-            .post(login_endpoint)
-            .header("Referer", &host)
-            .form(&[("username", &username), ("password", &password)])
-            .send()
-            .await
-        {
-            if resp.status().is_success() {
-                match resp.cookies().next() {
-                    Some(cookie) => Ok(Self {
-                        qbt_client,
-                        is_login: true,
-                        cookie: format!("{}={}", cookie.name(), cookie.value()),
-                        host,
-                        download_path: "downloads".to_string(),
-                        deploy_mode: "local".to_string(),
-                    }),
-                    None => panic!("[QB API] Login error, without cookies found"),
-                }
-            } else {
-                panic!("[QB API] Login error, response status is {}", resp.status());
-            }
-        } else {
-            panic!("[QB API] Login error, qbittorrent client error");
+    pub fn new(
+        host: &str,
+        username: &str,
+        password: &str,
+        download_path: &str,
+        deploy_mode: &str,
+    ) -> Self {
+        Self {
+            is_login: Arc::new(AtomicBool::new(false)),
+            qbt_client: reqwest::Client::new(),
+            cookie: Arc::new(RwLock::new(String::new())),
+            host: host.to_string(),
+            username: username.to_string(),
+            password: password.to_string(),
+            download_path: download_path.to_string(),
+            deploy_mode: deploy_mode.to_string(),
+            category: String::new(),
+            speed_limit: SpeedLimit::default(),
+            path_mapper: PathMapper::default(),
         }
     }
 
-    pub async fn new_with_config(config: &Config) -> Result<Self, AnimeError> {
-        let qbt_client = reqwest::Client::new();
-        let host = config.qb_config.qb_url.to_string();
-        let login_endpoint = host.clone() + "api/v2/auth/login";
+    pub async fn new_with_login(username: String, password: String) -> Result<Self, AnimeError> {
+        let qb = Self::new(
+            "http://127.0.0.1:8081/",
+            &username,
+            &password,
+            "downloads",
+            "local",
+        );
+        qb.login().await?;
+        Ok(qb)
+    }
 
-        if let Ok(resp) = qbt_client
-            .post(login_endpoint)
-            .header("Referer", &host)
-            .form(&[
-                ("username", &config.qb_config.username),
-                ("password", &config.qb_config.password),
-            ])
-            .send()
-            .await
-        {
-            if resp.status().is_success() {
-                match resp.cookies().next() {
-                    Some(cookie) => {
-                        log::info!("[QB API] Successfully Connect to qbittorrent web api");
-                        return Ok(Self {
-                            qbt_client,
-                            is_login: true,
-                            cookie: format!("{}={}", cookie.name(), cookie.value()),
-                            host,
-                            download_path: config.download_path.clone(),
-                            deploy_mode: config.deploy_mode.clone(),
-                        });
-                    }
-                    None => log::error!("[QB API] Login error, without cookies found"),
-                }
-            } else {
-                log::error!("[QB API] Login error, response status is {}", resp.status());
-            }
-        } else {
-            log::error!("[QB API] Login error, qbittorrent client error");
+    // the executor is returned even if qbittorrent is down, it logs in once qbittorrent is up
+    pub async fn new_with_config(config: &Config) -> Result<Self, AnimeError> {
+        let qb = Self {
+            category: config.qb_config.category.clone(),
+            speed_limit: config.speed_limit,
+            path_mapper: PathMapper::new(config.path_mapping_rules()),
+            ..Self::new(
+                &config.qb_config.qb_url,
+                &config.qb_config.username,
+                &config.qb_config.password,
+                &config.download_path,
+                &config.deploy_mode,
+            )
+        };
+        if let Err(e) = qb.login().await {
+            log::error!("{}", e);
         }
-
-        Ok(Self {
-            qbt_client,
-            is_login: false,
-            cookie: "".to_string(),
-            host,
-            download_path: config.download_path.clone(),
-            deploy_mode: config.deploy_mode.clone(),
-        })
+        Ok(qb)
     }
 
     pub async fn relogin(&mut self, config: &Config) -> Result<(), AnimeError> {
         self.deploy_mode = config.deploy_mode.clone();
         self.host = config.qb_config.qb_url.clone();
+        self.username = config.qb_config.username.clone();
+        self.password = config.qb_config.password.clone();
         self.download_path = config.download_path.clone();
+        self.category = config.qb_config.category.clone();
+        self.speed_limit = config.speed_limit;
+        self.path_mapper = PathMapper::new(config.path_mapping_rules());
+        self.login().await
+    }
 
+    pub async fn login(&self) -> Result<(), AnimeError> {
         let login_endpoint = self.host.clone() + "api/v2/auth/login";
 
-        if let Ok(resp) = self
+        let result = match self
             .qbt_client
             .post(login_endpoint)
             .header("Referer", &self.host)
-            .form(&[
-                ("username", config.qb_config.username.clone()),
-                ("password", config.qb_config.password.clone()),
-            ])
+            .form(&[("username", &self.username), ("password", &self.password)])
             .send()
             .await
         {
-            if resp.status().is_success() {
-                match resp.cookies().next() {
-                    Some(cookie) => {
-                        log::info!("[QB API] Successfully Connect to qbittorrent web api");
-                        self.cookie = format!("{}={}", cookie.name(), cookie.value());
-                        self.is_login = true;
-                        return Ok(());
-                    }
-                    None => log::error!("[QB API] Login error, without cookies found"),
+            Ok(resp) if resp.status().is_success() => match resp.cookies().next() {
+                Some(cookie) => {
+                    *self.cookie.write().unwrap() = format!("{}={}", cookie.name(), cookie.value());
+                    Ok(())
                 }
-            } else {
-                log::error!("[QB API] Login error, response status is {}", resp.status());
+                None => Err("[QB API] Login error, without cookies found".to_string()),
+            },
+            Ok(resp) => Err(format!(
+                "[QB API] Login error, response status is {}",
+                resp.status()
+            )),
+            Err(e) => Err(format!(
+                "[QB API] Login error, qbittorrent client error: {}",
+                e
+            )),
+        };
+
+        match result {
+            Ok(_) => {
+                if !self.is_login.swap(true, Ordering::SeqCst) {
+                    log::info!("[QB API] Successfully Connect to qbittorrent web api");
+                }
+                Ok(())
+            }
+            Err(message) => {
+                self.is_login.store(false, Ordering::SeqCst);
+                Err(AnimeError::Qbittorrent(message))
             }
-        } else {
-            log::error!("[QB API] Login error, qbittorrent client error");
         }
+    }
 
-        Err(AnimeError::new("[QB API]relogin failed".to_string()))
+    async fn ensure_login(&self) -> Result<(), AnimeError> {
+        if self.is_login.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        self.login().await.map_err(|e| {
+            AnimeError::Qbittorrent(format!("[QB API] qbittorrent client not started, {}", e))
+        })
     }
 
-    pub async fn qb_api_version(&self) -> Result<(), AnimeError> {
-        if !self.is_login {
-            return Err(AnimeError::new(
-                "[QB API] qbittorrent client not started".to_string(),
-            ));
+    // send a request with our session, request builds it again for the retry
+    async fn send<F>(&self, request: F) -> Result<reqwest::Response, AnimeError>
+    where
+        F: Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+    {
+        self.ensure_login().await?;
+
+        let mut retry = true;
+        loop {
+            let cookie = self.cookie.read().unwrap().clone();
+            let resp = match request(&self.qbt_client)
+                .header("Cookie", cookie)
+                .send()
+                .await
+            {
+                Ok(resp) => resp,
+                Err(e) => {
+                    self.is_login.store(false, Ordering::SeqCst);
+                    return Err(handle_error(
+                        AnimeError::Qbittorrent(format!("{:?}", e)),
+                        "[QB API] Unable to access qb web api",
+                    ));
+                }
+            };
+
+            if resp.status() == StatusCode::FORBIDDEN && retry {
+                log::info!("[QB API] Session expired, login again");
+                self.login().await?;
+                retry = false;
+                continue;
+            }
+            if resp.status().is_server_error() {
+                self.is_login.store(false, Ordering::SeqCst);
+            }
+            if !resp.status().is_success() {
+                return Err(AnimeError::Qbittorrent(format!(
+                    "[QB API] {} failed, response status is {}",
+                    resp.url().path(),
+                    resp.status()
+                )));
+            }
+            return Ok(resp);
         }
+    }
 
+    async fn send_for_json<F>(&self, request: F) -> Result<serde_json::Value, AnimeError>
+    where
+        F: Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+    {
+        let resp_text = self.send(request).await?.text().await.map_err(|e| {
+            handle_error(
+                AnimeError::Qbittorrent(format!("{:?}", e)),
+                "[QB API] Failed to read response",
+            )
+        })?;
+        serde_json::from_str(&resp_text).map_err(|e| {
+            handle_error(
+                AnimeError::Qbittorrent(format!("{:?}", e)),
+                format!("Failed to serialize {:?}", resp_text).as_str(),
+            )
+        })
+    }
+
+    pub async fn qb_api_version(&self) -> Result<String, AnimeError> {
         let webapiversion_endpoint = self.host.clone() + "api/v2/app/webapiVersion";
-        if let Ok(info_response) = self
-            .qbt_client
-            .get(webapiversion_endpoint.clone())
-            .header("Cookie", &self.cookie)
-            .send()
+        self.send(|client| client.get(&webapiversion_endpoint))
+            .await?
+            .text()
             .await
-        {
-            log::info!(
-                "[QB API] qb_api_version: {}",
-                info_response.text().await.unwrap()
-            );
-        } else {
-            log::info!(
-                "[QB API] Unable to access qb web api: {}",
-                webapiversion_endpoint
-            );
-        }
-        Ok(())
+            .map_err(|e| {
+                handle_error(
+                    AnimeError::Qbittorrent(format!("{:?}", e)),
+                    "[QB API] Failed to read response",
+                )
+            })
     }
 
-    pub async fn qb_api_torrent_info(&self, torrent_name: &str) -> Result<TorrentInfo, AnimeError> {
-        if !self.is_login {
-            return Err(AnimeError::new(
-                "[QB API] qbittorrent client not started".to_string(),
-            ));
-        }
-
+    pub async fn qb_api_torrent_info(&self, info_hash: &str) -> Result<TorrentInfo, AnimeError> {
         let torrent_info_endpoint = self.host.clone() + "api/v2/torrents/info";
-        let hashes = torrent_name.split('.').next().unwrap().to_owned();
 
-        if let Ok(torrent_info_response) = self
-            .qbt_client
-            .post(torrent_info_endpoint.clone())
-            .header("Cookie", &self.cookie)
-            .form(&[("hashes", &hashes)])
-            .send()
-            .await
-        {
-            let torrent_info_response_text = torrent_info_response.text().await.unwrap();
-            let json: serde_json::Value =
-                serde_json::from_str(&torrent_info_response_text).unwrap();
-            match TorrentInfo::new(&json[0]) {
-                Ok(torrent_info) => Ok(torrent_info),
-                Err(e) => Err(e),
-            }
-        } else {
-            log::info!(
-                "[QB API] Unable to access qb web api: {}",
-                torrent_info_endpoint
-            );
-            Err(AnimeError::new(format!(
-                "[QB API] Unable to access qb web api: {}",
-                torrent_info_endpoint,
-            )))
-        }
+        let json = self
+            .send_for_json(|client| {
+                client
+                    .post(&torrent_info_endpoint)
+                    .form(&[("hashes", info_hash)])
+            })
+            .await?;
+        torrent_info_from_json(&json[0])
+    }
+
+    // files of a torrent, name is the path relative to the save path of the torrent
+    pub async fn qb_api_torrent_files(
+        &self,
+        info_hash: &str,
+    ) -> Result<Vec<TorrentFile>, AnimeError> {
+        let torrent_files_endpoint = self.host.clone() + "api/v2/torrents/files";
+
+        let json = self
+            .send_for_json(|client| {
+                client
+                    .post(&torrent_files_endpoint)
+                    .form(&[("hash", info_hash)])
+            })
+            .await?;
+        serde_json::from_value::<Vec<TorrentFile>>(json).map_err(|e| {
+            handle_error(
+                AnimeError::Qbittorrent(format!("{:?}", e)),
+                "Failed to serialize torrent files",
+            )
+        })
     }
 
     pub async fn qb_api_add_torrent(
         &self,
         anime_name: &str,
         anime_seed_info: &AnimeSeed,
+        speed_limit: SpeedLimit,
     ) -> Result<(), AnimeError> {
-        if !self.is_login {
-            return Err(AnimeError::new(
-                "[QB API] qbittorrent client not started".to_string(),
-            ));
-        }
-
         let add_endpoint = self.host.clone() + "api/v2/torrents/add";
-        let file_name = anime_seed_info
-            .seed_url
-            .rsplit('/')
-            .next()
-            .unwrap_or(&anime_seed_info.seed_url)
-            .to_string();
-
-        println!("{}", anime_seed_info.seed_name);
-
-        let seed_path = format!("downloads/seed/{}/{}", anime_seed_info.mikan_id, file_name);
-        let file_byte = std::fs::read(seed_path).unwrap();
-
-        // {exec_path}/{download_path}/{anime_path}
-        let save_path = format!(
-            "{}/{}/{}",
-            env::current_dir()
-                .map_err(|e| handle_error(e, "message"))?
-                .to_str()
-                .unwrap(), // exec_path
-            self.download_path, // download_path
-            anime_name.to_string() + "(" + &anime_seed_info.mikan_id.to_string() + ")"  // anime_path
-        );
-
-        let form = Form::new()
-            .part("torrent", Part::bytes(file_byte).file_name(file_name))
-            .text("savepath", save_path);
-
-        if let Ok(_) = self
-            .qbt_client
-            .post(add_endpoint.clone())
-            .header("Cookie", &self.cookie)
-            .multipart(form)
-            .send()
-            .await
-        {
-            log::info!("[QB API] Successfully added seeds: {:?}", anime_seed_info);
+        let save_path = get_save_path(
+            &self.path_mapper,
+            &self.download_path,
+            anime_name,
+            anime_seed_info.mikan_id,
+        )?;
+        let speed_limit = speed_limit.or(self.speed_limit);
+
+        // a magnet is passed by urls, qbittorrent fetches the metadata itself
+        let seed_file = if magnet::is_magnet(&anime_seed_info.seed_url) {
+            None
         } else {
-            log::info!("[QB API] Unable to access qb web api: {}", add_endpoint);
-        }
+            Some(read_seed_file(anime_seed_info)?)
+        };
+
+        self.send(|client| {
+            let mut form = match &seed_file {
+                Some((file_name, file_byte)) => Form::new().part(
+                    "torrent",
+                    Part::bytes(file_byte.clone()).file_name(file_name.clone()),
+                ),
+                None => Form::new().text("urls", anime_seed_info.seed_url.clone()),
+            }
+            .text("savepath", save_path.clone())
+            .text("tags", get_torrent_tags(anime_seed_info).join(","));
+            // qbittorrent creates a category which does not exist yet
+            if !self.category.is_empty() {
+                form = form.text("category", self.category.clone());
+            }
+            // bytes/s
+            if speed_limit.download > 0 {
+                form = form.text("dlLimit", (speed_limit.download * 1024).to_string());
+            }
+            if speed_limit.upload > 0 {
+                form = form.text("upLimit", (speed_limit.upload * 1024).to_string());
+            }
+            client.post(&add_endpoint).multipart(form)
+        })
+        .await?;
+        log::info!("[QB API] Successfully added seeds: {:?}", anime_seed_info);
         Ok(())
     }
 
-    pub async fn qb_api_del_torrent(&self, torrent_name: &str) -> Result<(), AnimeError> {
-        if !self.is_login {
-            return Err(AnimeError::new(
-                "[QB API] qbittorrent client not started".to_string(),
-            ));
-        }
-
+    pub async fn qb_api_del_torrent(
+        &self,
+        info_hash: &str,
+        delete_files: bool,
+    ) -> Result<(), AnimeError> {
         let delete_endpoint = self.host.clone() + "api/v2/torrents/delete";
-        let hashes = torrent_name.split('.').next().unwrap().to_owned();
+        let delete_files = if delete_files { "true" } else { "false" };
 
-        if let Ok(_) = self
-            .qbt_client
-            .post(delete_endpoint.clone())
-            .header("Cookie", &self.cookie)
-            .form(&[("hashes", hashes), ("deleteFiles", String::from("true"))])
-            .send()
-            .await
-        {
-            log::info!("[QB API] Successfully delete seeds: {}", torrent_name);
-        } else {
-            log::info!("[QB API] Unable to access qb web api: {}", delete_endpoint);
-        }
+        self.send(|client| {
+            client
+                .post(&delete_endpoint)
+                .form(&[("hashes", info_hash), ("deleteFiles", delete_files)])
+        })
+        .await?;
+        log::info!("[QB API] Successfully delete seeds: {}", info_hash);
         Ok(())
     }
 
+    // paths are relative to the save path, qbittorrent creates missing folders of new_path
     pub async fn qb_api_torrent_rename_file(
         &self,
-        anime_name: &str,
-        subgroup_name: &str,
-        anime_seed_info: &AnimeSeed,
+        info_hash: &str,
+        old_path: &str,
+        new_path: &str,
     ) -> Result<(), AnimeError> {
-        if !self.is_login {
-            return Err(AnimeError::new(
-                "[QB API] qbittorrent client not started".to_string(),
-            ));
-        }
-
         let rename_file_endpoint = self.host.clone() + "api/v2/torrents/renameFile";
-        let torrent_name = anime_seed_info
-            .seed_url
-            .rsplit('/')
-            .next()
-            .unwrap_or(&anime_seed_info.seed_url)
-            .to_string();
-        let hashes = torrent_name.split('.').next().unwrap().to_owned();
-        let file_name = self.qb_api_torrent_info(&torrent_name).await.unwrap().name;
-        let extension = match file_name.rsplit('.').next() {
-            Some(ext) => ext,
-            None => "mp4",
-        };
-
-        let new_name = format!(
-            "{}{}{}{}{}{}{}",
-            anime_name, " - ", anime_seed_info.episode, " - ", subgroup_name, ".", extension
-        );
 
-        if let Ok(torrent_info_response) = self
-            .qbt_client
-            .post(rename_file_endpoint.clone())
-            .header("Cookie", &self.cookie)
-            .form(&[
-                ("hash", hashes),
-                ("oldPath", file_name.to_string()),
-                ("newPath", new_name.clone()),
+        self.send(|client| {
+            client.post(&rename_file_endpoint).form(&[
+                ("hash", info_hash),
+                ("oldPath", old_path),
+                ("newPath", new_path),
             ])
-            .send()
-            .await
-        {
-            if torrent_info_response.status().is_success() {
-                log::info!(
-                    "[QB API] Successfully rename seeds: {} with new name: {}",
-                    file_name,
-                    new_name
-                );
-            } else {
-                log::info!(
-                    "[QB API] Unalble to rename seed: {} with new name: {}, {}",
-                    file_name,
-                    new_name,
-                    torrent_info_response.text().await.unwrap()
-                )
-            }
-        } else {
-            log::info!(
-                "[QB API] Unable to access qb web api: {}",
-                rename_file_endpoint
-            );
-        }
+        })
+        .await?;
+        log::info!(
+            "[QB API] Successfully rename seeds: {} with new name: {}",
+            old_path,
+            new_path
+        );
         Ok(())
     }
 
-    pub async fn qb_api_resume_torrent(&self, torrent_name: &str) -> Result<(), AnimeError> {
-        if !self.is_login {
-            return Err(AnimeError::new(
-                "[QB API] qbittorrent client not started".to_string(),
-            ));
-        }
-
+    pub async fn qb_api_resume_torrent(&self, info_hash: &str) -> Result<(), AnimeError> {
         let resume_endpoint = self.host.clone() + "api/v2/torrents/resume";
-        let hashes = torrent_name.split('.').next().unwrap().to_owned();
 
-        if let Ok(_) = self
-            .qbt_client
-            .post(resume_endpoint.clone())
-            .header("Cookie", &self.cookie)
-            .form(&[("hashes", hashes)])
-            .send()
-            .await
-        {
-            log::info!("[QB API] Successfully resume seed: {}", torrent_name);
-        } else {
-            log::info!("[QB API] Unable to access qb web api: {}", resume_endpoint);
-        }
+        self.send(|client| client.post(&resume_endpoint).form(&[("hashes", info_hash)]))
+            .await?;
+        log::info!("[QB API] Successfully resume seed: {}", info_hash);
         Ok(())
     }
 
-    pub async fn qb_api_pause_torrent(&self, torrent_name: &str) -> Result<(), AnimeError> {
-        if !self.is_login {
-            return Err(AnimeError::new(
-                "[QB API] qbittorrent client not started".to_string(),
-            ));
-        }
-
+    pub async fn qb_api_pause_torrent(&self, info_hash: &str) -> Result<(), AnimeError> {
         let pause_endpoint = self.host.clone() + "api/v2/torrents/pause";
-        let hashes = torrent_name.split('.').next().unwrap().to_owned();
 
-        if let Ok(_) = self
-            .qbt_client
-            .post(pause_endpoint.clone())
-            .header("Cookie", &self.cookie)
-            .form(&[("hashes", hashes)])
-            .send()
-            .await
-        {
-            log::info!("[QB API] Successfully pause seed: {}", torrent_name);
-        } else {
-            log::info!("[QB API] Unable to access qb web api: {}", pause_endpoint);
-        }
+        self.send(|client| client.post(&pause_endpoint).form(&[("hashes", info_hash)]))
+            .await?;
+        log::info!("[QB API] Successfully pause seed: {}", info_hash);
         Ok(())
     }
 
-    pub async fn qb_api_completed_torrent_list(&self) -> Result<Vec<String>, AnimeError> {
-        if !self.is_login {
-            return Err(AnimeError::new(
-                "[QB API] qbittorrent client not started".to_string(),
-            ));
+    // torrents/info only lists our own torrents when there is a category
+    fn torrent_list_query<'a>(&'a self, filter: &'a str) -> Vec<(&'a str, &'a str)> {
+        let mut query = vec![("filter", filter)];
+        if !self.category.is_empty() {
+            query.push(("category", self.category.as_str()));
         }
+        query
+    }
 
+    pub async fn qb_api_completed_torrent_set(&self) -> Result<HashSet<String>, AnimeError> {
         let torrent_info_endpoint = self.host.clone() + "api/v2/torrents/info";
-        let mut torrent_hash_list: Vec<String> = Vec::new();
+        let query = self.torrent_list_query("completed");
 
-        if let Ok(completed_torrent_response) = self
-            .qbt_client
-            .post(torrent_info_endpoint.clone())
-            .header("Cookie", &self.cookie)
-            .form(&[("filter", "completed")])
-            .send()
-            .await
-        {
-            let completed_torrent_response_text = completed_torrent_response.text().await.unwrap();
-            let json: serde_json::Value = serde_json::from_str(&completed_torrent_response_text)
-                .map_err(|e| {
-                    handle_error(
-                        e,
-                        format!("Failed to serialize {:?}", completed_torrent_response_text)
-                            .as_str(),
-                    )
-                })?;
-
-            if let serde_json::Value::Array(torrents) = json {
-                for torrent in torrents {
-                    torrent_hash_list.push(
-                        torrent["hash"]
-                            .as_str()
-                            .ok_or("Field not found")
-                            .unwrap()
-                            .to_string()
-                            + ".torrent",
-                    );
+        let json = self
+            .send_for_json(|client| client.post(&torrent_info_endpoint).form(&query))
+            .await?;
+
+        let mut torrent_hash_set: HashSet<String> = HashSet::new();
+        if let serde_json::Value::Array(torrents) = json {
+            for torrent in torrents {
+                if let Some(hash) = torrent["hash"].as_str() {
+                    torrent_hash_set.insert(hash.to_string());
                 }
             }
-        } else {
-            log::info!(
-                "[QB API] Unable to access qb web api: {}",
-                torrent_info_endpoint
-            );
         }
-        Ok(torrent_hash_list)
+        Ok(torrent_hash_set)
     }
 
-    pub async fn qb_api_completed_torrent_set(&self) -> Result<HashSet<String>, AnimeError> {
-        if !self.is_login {
-            return Err(AnimeError::new(
-                "[QB API] qbittorrent client not started".to_string(),
-            ));
-        }
-
+    pub async fn qb_api_seeding_stats(&self) -> Result<HashMap<String, SeedingStats>, AnimeError> {
         let torrent_info_endpoint = self.host.clone() + "api/v2/torrents/info";
-        let mut torrent_hash_set: HashSet<String> = HashSet::new();
+        let query = self.torrent_list_query("all");
 
-        if let Ok(completed_torrent_response) = self
-            .qbt_client
-            .post(torrent_info_endpoint.clone())
-            .header("Cookie", &self.cookie)
-            .form(&[("filter", "completed")])
-            .send()
-            .await
-        {
-            let completed_torrent_response_text = completed_torrent_response.text().await.unwrap();
-            let json: serde_json::Value =
-                serde_json::from_str(&completed_torrent_response_text).unwrap();
-
-            if let serde_json::Value::Array(torrents) = json {
-                for torrent in torrents {
-                    torrent_hash_set.insert(
-                        torrent["hash"]
-                            .as_str()
-                            .ok_or("Field not found")
-                            .unwrap()
-                            .to_string()
-                            + ".torrent",
-                    );
-                }
-            }
-        } else {
-            log::info!(
-                "[QB API] Unable to access qb web api: {}",
-                torrent_info_endpoint
-            );
-        }
-        Ok(torrent_hash_set)
+        let json = self
+            .send_for_json(|client| client.post(&torrent_info_endpoint).form(&query))
+            .await?;
+        Ok(seeding_stats_from_json(&json))
     }
 
     /*
@@ -502,25 +425,10 @@ impl QbitTaskExecutor {
     */
     pub async fn qb_api_get_download_path(&self) -> Result<String, AnimeError> {
         let app_info_endpoint = self.host.clone() + "api/v2/app/preferences";
-        let mut download_path = String::from("");
-        if let Ok(app_info_response) = self
-            .qbt_client
-            .get(app_info_endpoint.clone())
-            .header("Cookie", &self.cookie)
-            .send()
-            .await
-        {
-            let app_info_response_text = app_info_response.text().await.unwrap();
-            let json: serde_json::Value = serde_json::from_str(&app_info_response_text).unwrap();
-            download_path = json["save_path"].to_string().replace("\"", "");
-            // log::debug!("download path: {:?}", download_path);
-        } else {
-            log::info!(
-                "[QB API] Unable to access qb web api: {}",
-                app_info_endpoint
-            );
-        }
-        Ok(download_path)
+        let json = self
+            .send_for_json(|client| client.get(&app_info_endpoint))
+            .await?;
+        Ok(json["save_path"].as_str().unwrap_or_default().to_string())
     }
 
     pub async fn qb_api_set_download_path(&self) -> Result<(), AnimeError> {
@@ -531,117 +439,351 @@ impl QbitTaskExecutor {
             "json",
             format!(
                 r#"{{"save_path": "{}/{}"}}"#,
-                env::current_dir().unwrap().to_string_lossy(),
+                env::current_dir()
+                    .map_err(|e| handle_error(e, "Failed to get current dir"))?
+                    .to_string_lossy(),
                 self.download_path
             ),
         );
 
-        if let Ok(res) = self
-            .qbt_client
-            .post(app_set_preferences_endpoint.clone())
-            .header("Cookie", &self.cookie)
-            .form(&param)
-            .send()
-            .await
-        {
-            let res_text = res.text().await.unwrap();
-            println!("{:?}", res_text)
-        }
+        self.send(|client| client.post(&app_set_preferences_endpoint).form(&param))
+            .await?;
         Ok(())
     }
 }
 
 unsafe impl Send for QbitTaskExecutor {}
 
-#[derive(Default, Debug, Deserialize, Serialize)]
-pub struct TorrentInfo {
-    pub name: String,
-    pub size: String,
-    pub done: String,           // progress
-    pub peers: String,          // num_leechs
-    pub seeds: String,          // num_seeds
-    pub download_speed: String, // dlspeed
-    pub eta: String,
-    pub hash: String,
-    pub state: String,
-}
+#[async_trait]
+impl TorrentClient for QbitTaskExecutor {
+    fn is_login(&self) -> bool {
+        self.is_login.load(Ordering::SeqCst)
+    }
 
-impl TorrentInfo {
-    pub fn new(item: &serde_json::Value) -> Result<Self, AnimeError> {
-        let item_size = item["size"]
-            .as_i64()
-            .ok_or("Field not found")
-            .map_err(|e| handle_error(e, "No item named 'size'"))?;
+    // a 403 is handled by send, so a failed version check means qbittorrent is down
+    async fn reconnect(&self) -> Result<(), AnimeError> {
+        self.qb_api_version().await.map(|_| ())
+    }
 
-        const GB: i64 = 1024 * 1024 * 1024;
-        const MB: i64 = 1024 * 1024;
-        let size = if item_size >= GB {
-            format!("{:.2} GB", item_size as f64 / GB as f64)
-        } else {
-            format!("{:.2} MB", item_size as f64 / MB as f64)
-        };
+    async fn add_torrent(
+        &self,
+        anime_name: &str,
+        anime_seed_info: &AnimeSeed,
+        speed_limit: SpeedLimit,
+    ) -> Result<(), AnimeError> {
+        self.qb_api_add_torrent(anime_name, anime_seed_info, speed_limit)
+            .await
+    }
 
-        let item_dlspeed = item["dlspeed"].as_i64().ok_or("Field not found").unwrap();
+    async fn torrent_info(&self, info_hash: &str) -> Result<TorrentInfo, AnimeError> {
+        self.qb_api_torrent_info(info_hash).await
+    }
 
-        const KILOBIT: i64 = 1000;
-        const MEGABIT: i64 = 1000000;
-        let download_speed = if item_dlspeed > MEGABIT {
-            format!("{:.2} Mbps", item_dlspeed as f64 / MEGABIT as f64)
-        } else {
-            format!("{:.2} Kbps", item_dlspeed as f64 / KILOBIT as f64)
-        };
+    async fn torrent_files(&self, info_hash: &str) -> Result<Vec<TorrentFile>, AnimeError> {
+        self.qb_api_torrent_files(info_hash).await
+    }
 
-        let item_eta = item["eta"].as_i64().ok_or("Field not found").unwrap();
-
-        let d = UNIX_EPOCH + Duration::from_secs(item_eta as u64);
-        let datetime = DateTime::<chrono::Utc>::from(d);
-        let eta: String = datetime.format("%H:%M:%S").to_string();
-
-        Ok(TorrentInfo {
-            name: item["name"]
-                .as_str()
-                .ok_or("Field not found")
-                .unwrap()
-                .to_owned(),
-            size,
-            done: format!(
-                "{:.2} %",
-                (item["progress"].as_f64().ok_or("Field not found").unwrap() * 100.0)
-            ),
-            peers: item["num_leechs"]
-                .as_i64()
-                .ok_or("Field not found")
-                .unwrap()
-                .to_string(),
-            seeds: item["num_seeds"]
-                .as_i64()
-                .ok_or("Field not found")
-                .unwrap()
-                .to_string(),
-            download_speed,
-            eta,
-            hash: item["hash"]
-                .as_str()
-                .ok_or("Field not found")
-                .unwrap()
-                .to_owned(),
-            state: item["state"]
-                .as_str()
-                .ok_or("Field not found")
-                .unwrap()
-                .to_owned(),
-        })
+    async fn del_torrent(&self, info_hash: &str, delete_files: bool) -> Result<(), AnimeError> {
+        self.qb_api_del_torrent(info_hash, delete_files).await
     }
+
+    async fn rename_file(
+        &self,
+        info_hash: &str,
+        old_path: &str,
+        new_path: &str,
+    ) -> Result<(), AnimeError> {
+        self.qb_api_torrent_rename_file(info_hash, old_path, new_path)
+            .await
+    }
+
+    async fn pause_torrent(&self, info_hash: &str) -> Result<(), AnimeError> {
+        self.qb_api_pause_torrent(info_hash).await
+    }
+
+    async fn resume_torrent(&self, info_hash: &str) -> Result<(), AnimeError> {
+        self.qb_api_resume_torrent(info_hash).await
+    }
+
+    async fn completed_torrent_set(&self) -> Result<HashSet<String>, AnimeError> {
+        self.qb_api_completed_torrent_set().await
+    }
+
+    async fn seeding_stats(&self) -> Result<HashMap<String, SeedingStats>, AnimeError> {
+        self.qb_api_seeding_stats().await
+    }
+}
+
+fn torrent_info_from_json(item: &serde_json::Value) -> Result<TorrentInfo, AnimeError> {
+    let item_size = item["size"]
+        .as_i64()
+        .ok_or("Field not found")
+        .map_err(|e| {
+            handle_error(
+                AnimeError::Qbittorrent(format!("{:?}", e)),
+                "No item named 'size'",
+            )
+        })?;
+    let item_dlspeed = item["dlspeed"].as_i64().ok_or("Field not found").unwrap();
+    let item_eta = item["eta"].as_i64().ok_or("Field not found").unwrap();
+
+    Ok(TorrentInfo {
+        name: item["name"]
+            .as_str()
+            .ok_or("Field not found")
+            .unwrap()
+            .to_owned(),
+        size: format_size(item_size),
+        done: format_progress(item["progress"].as_f64().ok_or("Field not found").unwrap()),
+        peers: item["num_leechs"]
+            .as_i64()
+            .ok_or("Field not found")
+            .unwrap()
+            .to_string(),
+        seeds: item["num_seeds"]
+            .as_i64()
+            .ok_or("Field not found")
+            .unwrap()
+            .to_string(),
+        download_speed: format_speed(item_dlspeed),
+        eta: format_eta(item_eta),
+        hash: item["hash"]
+            .as_str()
+            .ok_or("Field not found")
+            .unwrap()
+            .to_owned(),
+        state: item["state"]
+            .as_str()
+            .ok_or("Field not found")
+            .unwrap()
+            .to_owned(),
+    })
+}
+
+// info hash -> ratio and seconds spent seeding, of every torrent in qbittorrent
+fn seeding_stats_from_json(json: &serde_json::Value) -> HashMap<String, SeedingStats> {
+    let mut seeding_stats = HashMap::new();
+    if let serde_json::Value::Array(torrents) = json {
+        for torrent in torrents {
+            if let Some(hash) = torrent["hash"].as_str() {
+                seeding_stats.insert(
+                    hash.to_string(),
+                    SeedingStats {
+                        ratio: torrent["ratio"].as_f64().unwrap_or_default(),
+                        seeding_time: torrent["seeding_time"].as_i64().unwrap_or_default(),
+                    },
+                );
+            }
+        }
+    }
+    seeding_stats
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use actix_web::cookie::Cookie;
+    use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+    use std::sync::Mutex;
+
+    // a qbittorrent web api which hands out a new SID on every login
+    #[derive(Default)]
+    struct MockQb {
+        sid: String,
+        nb_login: i32,
+        down: bool,
+        save_path: String,
+    }
+
+    type MockState = web::Data<Mutex<MockQb>>;
+
+    async fn mock_login(
+        state: MockState,
+        form: web::Form<HashMap<String, String>>,
+    ) -> HttpResponse {
+        let mut state = state.lock().unwrap();
+        if state.down {
+            return HttpResponse::ServiceUnavailable().finish();
+        }
+        if form.get("password").map(String::as_str) != Some("adminadmin") {
+            return HttpResponse::Ok().body("Fails.");
+        }
+        state.nb_login += 1;
+        state.sid = format!("sid{}", state.nb_login);
+        HttpResponse::Ok()
+            .cookie(Cookie::new("SID", state.sid.clone()))
+            .body("Ok.")
+    }
+
+    fn mock_check_sid(state: &MockQb, req: &HttpRequest) -> Option<HttpResponse> {
+        if state.down {
+            return Some(HttpResponse::ServiceUnavailable().finish());
+        }
+        match req.cookie("SID") {
+            Some(cookie) if cookie.value() == state.sid => None,
+            _ => Some(HttpResponse::Forbidden().body("Forbidden")),
+        }
+    }
+
+    async fn mock_version(state: MockState, req: HttpRequest) -> HttpResponse {
+        let state = state.lock().unwrap();
+        mock_check_sid(&state, &req).unwrap_or_else(|| HttpResponse::Ok().body("2.9.3"))
+    }
+
+    async fn mock_torrent_info(
+        state: MockState,
+        req: HttpRequest,
+        form: web::Form<HashMap<String, String>>,
+    ) -> HttpResponse {
+        let state = state.lock().unwrap();
+        mock_check_sid(&state, &req).unwrap_or_else(|| {
+            if form.get("category").is_some_and(|c| c != "autoAnime") {
+                return HttpResponse::Ok().json(serde_json::json!([]));
+            }
+            HttpResponse::Ok().json(serde_json::json!([{
+                "name": "[LoliHouse] Migi to Dali - 03",
+                "size": 366372454,
+                "progress": 0.5,
+                "num_leechs": 1,
+                "num_seeds": 8,
+                "dlspeed": 2000000,
+                "eta": 90,
+                "hash": "bdd2f547cdfd8a38011a5ea451d65379c9572305",
+                "state": "downloading",
+                "ratio": 0.25,
+                "seeding_time": 0
+            }]))
+        })
+    }
+
+    async fn mock_preferences(state: MockState, req: HttpRequest) -> HttpResponse {
+        let state = state.lock().unwrap();
+        mock_check_sid(&state, &req).unwrap_or_else(|| {
+            HttpResponse::Ok().json(serde_json::json!({ "save_path": state.save_path }))
+        })
+    }
+
+    async fn mock_set_preferences(
+        state: MockState,
+        req: HttpRequest,
+        form: web::Form<HashMap<String, String>>,
+    ) -> HttpResponse {
+        let mut state = state.lock().unwrap();
+        if let Some(resp) = mock_check_sid(&state, &req) {
+            return resp;
+        }
+        let preferences: serde_json::Value = serde_json::from_str(&form["json"]).unwrap();
+        if let Some(save_path) = preferences["save_path"].as_str() {
+            state.save_path = save_path.to_string();
+        }
+        HttpResponse::Ok().finish()
+    }
+
+    fn start_mock_qb(state: MockState) -> String {
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(state.clone())
+                .route("/api/v2/auth/login", web::post().to(mock_login))
+                .route("/api/v2/app/webapiVersion", web::get().to(mock_version))
+                .route("/api/v2/torrents/info", web::post().to(mock_torrent_info))
+                .route("/api/v2/app/preferences", web::get().to(mock_preferences))
+                .route(
+                    "/api/v2/app/setPreferences",
+                    web::post().to(mock_set_preferences),
+                )
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let host = format!("http://127.0.0.1:{}/", server.addrs()[0].port());
+        actix_web::rt::spawn(server.run());
+        host
+    }
+
+    #[actix_web::test]
+    async fn test_relogin_on_forbidden() {
+        let state = web::Data::new(Mutex::new(MockQb::default()));
+        let host = start_mock_qb(state.clone());
+
+        let qb = QbitTaskExecutor::new(&host, "admin", "adminadmin", "downloads", "local");
+        qb.login().await.unwrap();
+        assert!(qb.is_login());
+        assert_eq!(qb.qb_api_version().await.unwrap(), "2.9.3");
+
+        // qbittorrent restarted and forgot our session
+        state.lock().unwrap().sid = "restarted".to_string();
+        let info = qb
+            .qb_api_torrent_info("bdd2f547cdfd8a38011a5ea451d65379c9572305")
+            .await
+            .unwrap();
+        assert_eq!(info.done, "50.00 %");
+        assert_eq!(state.lock().unwrap().nb_login, 2);
+        assert!(qb.is_login());
+
+        let seeding_stats = qb.qb_api_seeding_stats().await.unwrap();
+        assert_eq!(
+            seeding_stats["bdd2f547cdfd8a38011a5ea451d65379c9572305"],
+            SeedingStats {
+                ratio: 0.25,
+                seeding_time: 0
+            }
+        );
+
+        // only the torrents of our category are listed
+        let qb = QbitTaskExecutor {
+            category: "tv".to_string(),
+            ..qb
+        };
+        assert!(qb.qb_api_seeding_stats().await.unwrap().is_empty());
+        let qb = QbitTaskExecutor {
+            category: "autoAnime".to_string(),
+            ..qb
+        };
+        assert_eq!(qb.qb_api_seeding_stats().await.unwrap().len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_is_login_health() {
+        let state = web::Data::new(Mutex::new(MockQb::default()));
+        let host = start_mock_qb(state.clone());
+
+        let qb = QbitTaskExecutor::new(&host, "admin", "adminadmin", "downloads", "local");
+        qb.reconnect().await.unwrap();
+        assert!(qb.is_login());
+
+        state.lock().unwrap().down = true;
+        assert!(qb.reconnect().await.is_err());
+        assert!(!qb.is_login());
+        let e = qb.qb_api_completed_torrent_set().await.unwrap_err();
+        assert_eq!(e.code(), "qbittorrent");
+
+        state.lock().unwrap().down = false;
+        qb.reconnect().await.unwrap();
+        assert!(qb.is_login());
+
+        let wrong_password = QbitTaskExecutor::new(&host, "admin", "wrong", "downloads", "local");
+        assert!(wrong_password.reconnect().await.is_err());
+        assert!(!wrong_password.is_login());
+
+        // nothing listens on the port any more
+        let unreachable = QbitTaskExecutor::new(
+            "http://127.0.0.1:1/",
+            "admin",
+            "adminadmin",
+            "downloads",
+            "local",
+        );
+        assert!(unreachable.reconnect().await.is_err());
+        assert!(!unreachable.is_login());
+    }
 
-    #[tokio::test]
+    #[actix_web::test]
     async fn test_qb_api_add_torrent() {
-        let config = Config::load_config("./config/config.yaml").await.unwrap();
-        let qb_task_executor = QbitTaskExecutor::new_with_config(&config).await.unwrap();
+        let state = web::Data::new(Mutex::new(MockQb::default()));
+        let host = start_mock_qb(state.clone());
+        let qb_task_executor =
+            QbitTaskExecutor::new(&host, "admin", "adminadmin", "downloads", "local");
+        qb_task_executor.login().await.unwrap();
 
         // let anime_name = "test".to_string();
         // let anime_seed_info = AnimeSeed {
@@ -662,8 +804,9 @@ mod test {
         //     .qb_api_add_torrent(&anime_name, &anime_seed_info)
         //     .await
         //     .unwrap();
-        let _r = qb_task_executor.qb_api_set_download_path().await.unwrap();
-        let _r = qb_task_executor.qb_api_get_download_path().await.unwrap();
-        //println!("{:?}", r);
+        qb_task_executor.qb_api_set_download_path().await.unwrap();
+        let download_path = qb_task_executor.qb_api_get_download_path().await.unwrap();
+        assert!(download_path.ends_with("/downloads"));
+        assert_eq!(download_path, state.lock().unwrap().save_path);
     }
 }
@@ -0,0 +1,150 @@
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::collections::HashMap;
+use std::error::Error;
+
+/*
+    A small RSS 2.0 reader which only keeps what the spider needs.
+
+    Values of unknown child elements are kept in `extensions`, keyed by their path
+    relative to <item>, e.g. mikan's <torrent><pubDate> is stored as "torrent/pubDate".
+    Torznab style <torznab:attr name="..." value="..."/> are stored as "attr/{name}".
+*/
+#[derive(Debug, Default, Clone)]
+pub struct RssItem {
+    pub title: String,
+    pub link: String,
+    pub description: String,
+    pub pub_date: String,
+    pub enclosure_url: String,
+    pub enclosure_length: i64,
+    pub extensions: HashMap<String, String>,
+}
+
+pub fn parse_rss_items(xml: &str) -> Result<Vec<RssItem>, Box<dyn Error>> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut item_list: Vec<RssItem> = Vec::new();
+    let mut current: Option<RssItem> = None;
+    let mut path: Vec<String> = Vec::new();
+    let mut text = String::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                let name = local_name(&e);
+                if name == "item" {
+                    current = Some(RssItem::default());
+                    path.clear();
+                } else if current.is_some() {
+                    path.push(name);
+                }
+                text.clear();
+            }
+            Event::Empty(e) => {
+                if let Some(item) = current.as_mut() {
+                    let attrs = attributes(&e)?;
+                    match local_name(&e).as_str() {
+                        "enclosure" => {
+                            item.enclosure_url = attrs.get("url").cloned().unwrap_or_default();
+                            item.enclosure_length = attrs
+                                .get("length")
+                                .and_then(|l| l.parse::<i64>().ok())
+                                .unwrap_or(0);
+                        }
+                        "attr" => {
+                            if let (Some(name), Some(value)) =
+                                (attrs.get("name"), attrs.get("value"))
+                            {
+                                item.extensions
+                                    .insert(format!("attr/{}", name), value.to_string());
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Event::Text(t) if current.is_some() => {
+                text.push_str(&t.unescape()?);
+            }
+            Event::CData(t) if current.is_some() => {
+                text.push_str(&String::from_utf8_lossy(&t.into_inner()));
+            }
+            Event::End(e) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).to_string();
+                if name == "item" {
+                    if let Some(item) = current.take() {
+                        item_list.push(item);
+                    }
+                } else if let Some(item) = current.as_mut() {
+                    let value = text.trim().to_string();
+                    match path.join("/").as_str() {
+                        "title" => item.title = value,
+                        "link" => item.link = value,
+                        "description" => item.description = value,
+                        "pubDate" => item.pub_date = value,
+                        key => {
+                            if !value.is_empty() {
+                                item.extensions.insert(key.to_string(), value);
+                            }
+                        }
+                    }
+                    path.pop();
+                }
+                text.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(item_list)
+}
+
+fn local_name(e: &BytesStart) -> String {
+    String::from_utf8_lossy(e.local_name().as_ref()).to_string()
+}
+
+fn attributes(e: &BytesStart) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let mut attrs: HashMap<String, String> = HashMap::new();
+    for attr in e.attributes().flatten() {
+        let key = String::from_utf8_lossy(attr.key.local_name().as_ref()).to_string();
+        attrs.insert(key, attr.unescape_value()?.to_string());
+    }
+    Ok(attrs)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_rss_items() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<rss version="2.0" xmlns:torznab="http://torznab.com/schemas/2015/feed">
+  <channel>
+    <title>test</title>
+    <item>
+      <title><![CDATA[[ANi] Test - 01 [1080P][Baha][WEB-DL][AAC AVC][CHT].mp4]]></title>
+      <link>https://example.com/1</link>
+      <pubDate>Sat, 21 Oct 2023 18:28:00 +0800</pubDate>
+      <enclosure url="https://example.com/1.torrent" length="1024" type="application/x-bittorrent" />
+      <torznab:attr name="infohash" value="abcdef" />
+      <torrent xmlns="https://mikanani.me/0.1/">
+        <contentLength>1024</contentLength>
+      </torrent>
+    </item>
+  </channel>
+</rss>"#;
+        let item_list = parse_rss_items(xml).unwrap();
+        assert_eq!(item_list.len(), 1);
+        assert_eq!(
+            item_list[0].title,
+            "[ANi] Test - 01 [1080P][Baha][WEB-DL][AAC AVC][CHT].mp4"
+        );
+        assert_eq!(item_list[0].enclosure_url, "https://example.com/1.torrent");
+        assert_eq!(item_list[0].enclosure_length, 1024);
+        assert_eq!(item_list[0].extensions["attr/infohash"], "abcdef");
+        assert_eq!(item_list[0].extensions["torrent/contentLength"], "1024");
+    }
+}
@@ -0,0 +1,109 @@
+use crate::models::anime_list::AnimeList;
+use crate::mods::config::Config;
+use crate::mods::spider::{Anime, Mikan, Seed, Subgroup};
+use crate::mods::torznab::Torznab;
+use async_trait::async_trait;
+use futures::future::join_all;
+use std::error::Error;
+
+pub const SOURCE_MIKAN: &str = "mikan";
+pub const SOURCE_TORZNAB: &str = "torznab";
+
+/*
+    A site which can feed anime_seed.
+    anime_list.source records which implementation an anime belongs to, and
+    anime_list.mikan_id is the id of the anime inside that source.
+*/
+#[async_trait]
+pub trait SeedSource: Send + Sync {
+    async fn list_season(&self, year: i32, season: i32) -> Result<Vec<Anime>, Box<dyn Error>>;
+
+    async fn list_subgroups(&self, anime: &AnimeList) -> Result<Vec<Subgroup>, Box<dyn Error>>;
+
+    async fn list_seeds(
+        &self,
+        anime: &AnimeList,
+        subgroup_id: i32,
+    ) -> Result<Vec<Seed>, Box<dyn Error>>;
+
+    // the seeds of several subgroups, a subgroup which fails is skipped
+    async fn list_anime_seeds(
+        &self,
+        anime: &AnimeList,
+        subgroup_id_vec: &[i32],
+    ) -> Result<Vec<Seed>, Box<dyn Error>> {
+        let task_res_vec = join_all(subgroup_id_vec.iter().map(|subgroup_id| async move {
+            // Box<dyn Error> is not Send, keep only the message across the join
+            self.list_seeds(anime, *subgroup_id)
+                .await
+                .map_err(|e| e.to_string())
+        }))
+        .await;
+
+        let mut seed_list: Vec<Seed> = Vec::new();
+        for (subgroup_id, task_res) in subgroup_id_vec.iter().zip(task_res_vec) {
+            match task_res {
+                Ok(seeds) => seed_list.extend(seeds),
+                Err(e) => log::debug!(
+                    "list seeds of {} subgroup {} failed, err: {}",
+                    anime.anime_name,
+                    subgroup_id,
+                    e
+                ),
+            }
+        }
+        Ok(seed_list)
+    }
+}
+
+#[async_trait]
+impl SeedSource for Mikan {
+    async fn list_season(&self, year: i32, season: i32) -> Result<Vec<Anime>, Box<dyn Error>> {
+        self.get_anime(year, season).await
+    }
+
+    async fn list_subgroups(&self, anime: &AnimeList) -> Result<Vec<Subgroup>, Box<dyn Error>> {
+        self.get_subgroup(anime.mikan_id).await
+    }
+
+    async fn list_seeds(
+        &self,
+        anime: &AnimeList,
+        subgroup_id: i32,
+    ) -> Result<Vec<Seed>, Box<dyn Error>> {
+        self.get_seed_by_mode(
+            anime.mikan_id,
+            subgroup_id,
+            anime.anime_type,
+            anime.seed_mode,
+        )
+        .await
+    }
+}
+
+pub struct SeedSources {
+    pub mikan: Mikan,
+    pub torznab: Option<Torznab>,
+}
+
+impl SeedSources {
+    pub fn new(config: &Config) -> Result<Self, Box<dyn Error>> {
+        let torznab = if config.torznab_config.url.is_empty() {
+            None
+        } else {
+            Some(Torznab::new(&config.torznab_config, &config.scraper)?)
+        };
+        Ok(Self {
+            mikan: Mikan::new(&config.scraper)?,
+            torznab,
+        })
+    }
+
+    pub fn get(&self, source: &str) -> Option<&dyn SeedSource> {
+        match source {
+            SOURCE_MIKAN => Some(&self.mikan),
+            SOURCE_TORZNAB => self.torznab.as_ref().map(|t| t as &dyn SeedSource),
+            _ => None,
+        }
+    }
+}
@@ -1,24 +1,29 @@
+use crate::models::enums::{AnimeType, EpisodeType, SeedStatus, SubscribeStatus, UpdateDay};
+use crate::mods::config::ScraperConfig;
+use crate::mods::http_client::HttpClient;
+use crate::mods::{magnet, rss_parser};
+use crate::mods::title_parser;
+use chrono::{DateTime, NaiveDateTime};
 use regex::Regex;
-use reqwest::Client;
 use select::document::Document;
 use select::predicate::Class;
 use select::predicate::{Attr, Name, Predicate};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::error::Error;
-use std::time::Duration;
 use tokio::io::AsyncWriteExt;
 
 #[derive(Debug, Clone)]
 pub struct Mikan {
-    client: Client,
+    client: HttpClient,
     url: String,
 }
 
 #[derive(Debug, Clone)]
 pub struct Bangumi {
-    client: Client,
+    client: HttpClient,
     url: String,
+    api_url: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -26,9 +31,9 @@ pub struct Anime {
     pub mikan_id: i32,
     pub anime_name: String,
     pub img_url: String,
-    pub update_day: i32,
-    pub anime_type: i32,
-    pub subscribe_status: i32,
+    pub update_day: UpdateDay,
+    pub anime_type: AnimeType,
+    pub subscribe_status: SubscribeStatus,
 }
 
 #[derive(Debug)]
@@ -38,8 +43,11 @@ pub struct Seed {
     pub seed_url: String,
     pub subgroup_id: i32,
     pub seed_name: String,
-    pub seed_status: i32,
+    pub seed_status: SeedStatus,
     pub seed_size: String,
+    pub publish_date: String,
+    pub episode_end: i32, // -1 unless the seed is a batch of [episode, episode_end]
+    pub is_special: bool, // e.g. "12.5", episode is the one it follows
 }
 
 #[derive(Debug)]
@@ -58,23 +66,19 @@ pub struct Broadcast {
 
 #[allow(dead_code)]
 impl Mikan {
-    pub fn new() -> Result<Mikan, Box<dyn Error>> {
-        let client = Client::builder().timeout(Duration::from_secs(10)).build()?;
+    pub fn new(config: &ScraperConfig) -> Result<Mikan, Box<dyn Error>> {
         Ok(Mikan {
-            client,
-            url: "https://mikanani.me".to_string(),
+            client: HttpClient::new(config)?,
+            url: config.mikan_url.trim_end_matches('/').to_string(),
         })
     }
 
+    async fn request_text(&self, url: &str) -> Result<String, Box<dyn Error>> {
+        self.client.get_text(url).await
+    }
+
     async fn request_html(&self, url: &str) -> Result<Document, Box<dyn Error>> {
-        let response = self.client.get(url).send().await?;
-        if !response.status().is_success() {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Request failed",
-            )));
-        }
-        let body = response.text().await?;
+        let body = self.request_text(url).await?;
         return Ok(Document::from(body.as_str()));
     }
 
@@ -95,25 +99,9 @@ impl Mikan {
         let mut anime_list: Vec<Anime> = Vec::new();
         let mut anime_name_map: HashMap<i32, String> = HashMap::new();
         for node in document.find(Name("div").and(Attr("class", "sk-bangumi"))) {
-            let anime_type: i32;
-            let mut update_day = node.attr("data-dayofweek").unwrap().parse::<i32>().unwrap();
-            match update_day {
-                7 => {
-                    anime_type = 1; // movie
-                    update_day = 8;
-                }
-                8 => {
-                    anime_type = 2; // ova
-                    update_day = 9;
-                }
-                0 => {
-                    anime_type = 0;
-                    update_day = 7; // udpate on sunday
-                }
-                _ => {
-                    anime_type = 0;
-                }
-            }
+            let update_day =
+                UpdateDay::from_mikan(node.attr("data-dayofweek").unwrap().parse::<i32>().unwrap());
+            let anime_type = update_day.anime_type();
 
             for n in node.find(Name("span")) {
                 let img_url = n
@@ -130,7 +118,7 @@ impl Mikan {
                     img_url,
                     update_day,
                     anime_type,
-                    subscribe_status: 0,
+                    subscribe_status: SubscribeStatus::Unsubscribed,
                 });
             }
 
@@ -231,11 +219,25 @@ impl Mikan {
         Ok((bangumi_id, total_episodes))
     }
 
+    // seed_mode: 0 -> scrape episode table html, 1 -> mikan rss feed
+    pub async fn get_seed_by_mode(
+        &self,
+        mikan_id: i32,
+        subgroup_id: i32,
+        anime_type: AnimeType,
+        seed_mode: i32,
+    ) -> Result<Vec<Seed>, Box<dyn Error>> {
+        match seed_mode {
+            1 => self.get_seed_by_rss(mikan_id, subgroup_id, anime_type).await,
+            _ => self.get_seed(mikan_id, subgroup_id, anime_type).await,
+        }
+    }
+
     pub async fn get_seed(
         &self,
         mikan_id: i32,
         subgroup_id: i32,
-        anime_type: i32,
+        anime_type: AnimeType,
     ) -> Result<Vec<Seed>, Box<dyn Error>> {
         let url = format!(
             "{}/Home/ExpandEpisodeTable?bangumiId={}&subtitleGroupId={}&take=65",
@@ -258,35 +260,37 @@ impl Mikan {
             let parts: Vec<&str> = seed_info.trim().split('\n').collect();
             let seed_name = parts.get(0).unwrap().to_string();
             let seed_size = parts.get(1).unwrap().replace(" ", "");
+            let publish_date = normalize_publish_date(parts.get(2).unwrap_or(&""));
 
-            if anime_type == 0 {
-                if !regex_seed_1080(&seed_name) {
-                    continue;
-                }
-            }
-
-            let mut seed_episode = 1;
-            if anime_type == 0 {
-                if let Ok(episode) = regex_seed_episode(&seed_name) {
-                    seed_episode = episode;
-                } else {
-                    continue;
-                }
-            }
-
-            seed_list.push(Seed {
+            if let Some(seed) = build_seed(
                 mikan_id,
-                episode: seed_episode,
-                seed_url,
                 subgroup_id,
-                seed_name: seed_name[..seed_name.len() - 15].to_string(),
-                seed_status: 0,
+                anime_type,
+                seed_name[..seed_name.len() - 15].to_string(),
+                seed_url,
                 seed_size,
-            });
+                publish_date,
+            ) {
+                seed_list.push(seed);
+            }
         }
         return Ok(seed_list);
     }
 
+    pub async fn get_seed_by_rss(
+        &self,
+        mikan_id: i32,
+        subgroup_id: i32,
+        anime_type: AnimeType,
+    ) -> Result<Vec<Seed>, Box<dyn Error>> {
+        let url = format!(
+            "{}/RSS/Bangumi?bangumiId={}&subgroupid={}",
+            self.url, mikan_id, subgroup_id
+        );
+        let body = self.request_text(&url).await?;
+        parse_rss_seed(&body, mikan_id, subgroup_id, anime_type)
+    }
+
     async fn download(
         &self,
         download_url: &str,
@@ -298,9 +302,8 @@ impl Mikan {
             tokio::fs::create_dir_all(save_path).await?;
         }
 
-        let request = self.client.get(download_url);
+        let mut download = self.client.get(download_url).await?;
         let mut outfile = tokio::fs::File::create(format!("{}/{}", save_path, new_name)).await?;
-        let mut download = request.send().await?;
         while let Some(chunk) = download.chunk().await? {
             outfile.write(&chunk).await?;
         }
@@ -321,10 +324,14 @@ impl Mikan {
         seed_url: &str,
         save_path: &str,
     ) -> Result<(), Box<dyn Error>> {
-        let download_url = format!("{}{}", self.url, seed_url);
-        let mut parts = seed_url.split('/');
-        let new_name = parts.nth(3).unwrap();
-        self.download(&download_url, save_path, new_name).await
+        // seeds from other sources carry an absolute url
+        let download_url = if seed_url.starts_with("http") {
+            seed_url.to_string()
+        } else {
+            format!("{}{}", self.url, seed_url)
+        };
+        let new_name = get_torrent_name(seed_url);
+        self.download(&download_url, save_path, &new_name).await
     }
 
     pub async fn get_anime_by_mikan_id(&self, mikan_id: i32) -> Result<Anime, Box<dyn Error>> {
@@ -348,8 +355,7 @@ impl Mikan {
             }
         }
 
-        let anime_type;
-        let mut update_day = 0;
+        let mut day_of_week = 0;
 
         if let Some(node) = document.find(Class("bangumi-info")).next() {
             if node.text().contains("放送日期") {
@@ -365,29 +371,14 @@ impl Mikan {
                 let re = Regex::new(r"\d+").unwrap();
                 if let Some(captures) = re.captures(&text) {
                     if let Some(number) = captures.get(0) {
-                        update_day = number.as_str().parse::<i32>().unwrap();
+                        day_of_week = number.as_str().parse::<i32>().unwrap();
                     }
                 }
             }
         }
 
-        match update_day {
-            7 => {
-                anime_type = 1; // movie
-                update_day = 8;
-            }
-            8 => {
-                anime_type = 2; // ova
-                update_day = 9;
-            }
-            0 => {
-                anime_type = 0;
-                update_day = 7; // udpate on sunday
-            }
-            _ => {
-                anime_type = 0;
-            }
-        }
+        let update_day = UpdateDay::from_mikan(day_of_week);
+        let anime_type = update_day.anime_type();
 
         log::info!(
             "get anime: {}, {}, {}, {}",
@@ -402,74 +393,359 @@ impl Mikan {
             img_url: img_url,
             update_day: update_day,
             anime_type: anime_type,
-            subscribe_status: 0,
+            subscribe_status: SubscribeStatus::Unsubscribed,
         })
     }
 }
 
-fn regex_seed_episode(seed_name: &str) -> Result<i32, Box<dyn Error>> {
-    let re1 = Regex::new(r"\d{2}-\d{2}").unwrap();
-    let str_list1: Vec<&str> = re1.find_iter(seed_name).map(|mat| mat.as_str()).collect();
-    if !str_list1.is_empty() {
-        return Ok(-1);
+pub fn build_seed(
+    mikan_id: i32,
+    subgroup_id: i32,
+    anime_type: AnimeType,
+    seed_name: String,
+    seed_url: String,
+    seed_size: String,
+    publish_date: String,
+) -> Option<Seed> {
+    let mut seed_episode = 1;
+    let mut seed_episode_end = -1;
+    let mut is_special = false;
+    if anime_type == AnimeType::Tv {
+        // resolution and other quality rules are applied by anime_filter::filter_v3
+        let parsed = title_parser::parse_title(&seed_name);
+        if parsed.is_batch() {
+            match (parsed.episode, parsed.episode_end) {
+                (Some(start), Some(end)) if start >= 0.0 => {
+                    seed_episode = start.ceil() as i32;
+                    seed_episode_end = end.floor() as i32;
+                }
+                _ => return None,
+            }
+        } else if parsed.is_special() {
+            // listed with the seeds, it is neither downloaded nor counted as the episode
+            seed_episode = parsed.episode.unwrap().floor() as i32;
+            is_special = true;
+        } else if let Some(episode) = parsed.episode_number() {
+            seed_episode = episode;
+        } else {
+            return None;
+        }
     }
 
-    let re2 = Regex::new(r"\[\d{2}\]|\s\d{2}\s").unwrap();
-    let str_list2: Vec<&str> = re2.find_iter(seed_name).map(|mat| mat.as_str()).collect();
-    if str_list2.is_empty() {
-        let re3 = Regex::new(r"\[第\d+话\]").unwrap();
-        let str_list3: Vec<&str> = re3.find_iter(seed_name).map(|mat| mat.as_str()).collect();
-        if str_list3.is_empty() {
-            return Err("regex episode failed".into());
-        } else {
-            return Ok(str_list3[0][4..str_list3[0].len() - 4]
-                .parse::<i32>()
-                .unwrap());
+    Some(Seed {
+        mikan_id,
+        episode: seed_episode,
+        seed_url,
+        subgroup_id,
+        seed_name,
+        seed_status: SeedStatus::Unused,
+        seed_size,
+        publish_date,
+        episode_end: seed_episode_end,
+        is_special,
+    })
+}
+
+pub fn parse_rss_seed(
+    xml: &str,
+    mikan_id: i32,
+    subgroup_id: i32,
+    anime_type: AnimeType,
+) -> Result<Vec<Seed>, Box<dyn Error>> {
+    let re_size = Regex::new(r"\[(\d+(?:\.\d+)?\s*[KMGT]i?B)\]\s*$").unwrap();
+    let mut seed_list: Vec<Seed> = Vec::new();
+
+    for item in rss_parser::parse_rss_items(xml)? {
+        if item.enclosure_url.is_empty() {
+            continue;
         }
+
+        // keep the same relative url as the episode table, e.g. /Download/20231021/xxx.torrent
+        let seed_url = match item.enclosure_url.find("/Download/") {
+            Some(idx) => item.enclosure_url[idx..].to_string(),
+            None => item.enclosure_url.clone(),
+        };
+
+        let seed_size = match re_size.captures(&item.description) {
+            Some(captures) => captures.get(1).unwrap().as_str().replace(" ", ""),
+            None => {
+                let length = item
+                    .extensions
+                    .get("torrent/contentLength")
+                    .and_then(|l| l.parse::<i64>().ok())
+                    .unwrap_or(item.enclosure_length);
+                format_seed_size(length)
+            }
+        };
+
+        let publish_date = match item.extensions.get("torrent/pubDate") {
+            Some(date) => normalize_publish_date(date),
+            None => normalize_publish_date(&item.pub_date),
+        };
+
+        if let Some(seed) = build_seed(
+            mikan_id,
+            subgroup_id,
+            anime_type,
+            item.title.trim().to_string(),
+            seed_url,
+            seed_size,
+            publish_date,
+        ) {
+            seed_list.push(seed);
+        }
+    }
+    Ok(seed_list)
+}
+
+pub fn format_seed_size(length: i64) -> String {
+    const GB: i64 = 1024 * 1024 * 1024;
+    const MB: i64 = 1024 * 1024;
+    if length >= GB {
+        format!("{:.1}GB", length as f64 / GB as f64)
+    } else {
+        format!("{:.1}MB", length as f64 / MB as f64)
     }
-    return Ok(str_list2[str_list2.len() - 1][1..str_list2[0].len() - 1]
+}
+
+// the bytes of a size formatted like format_seed_size, 0 when it can not be parsed
+pub fn parse_seed_size(seed_size: &str) -> i64 {
+    let seed_size = seed_size.trim().to_uppercase();
+    let (number, unit) = match seed_size.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(idx) => seed_size.split_at(idx),
+        None => return 0,
+    };
+    let scale: i64 = match unit.trim() {
+        "TB" | "TIB" => 1 << 40,
+        "GB" | "GIB" => 1 << 30,
+        "MB" | "MIB" => 1 << 20,
+        "KB" | "KIB" => 1 << 10,
+        "B" => 1,
+        _ => return 0,
+    };
+    number
+        .trim()
+        .parse::<f64>()
+        .map_or(0, |number| (number * scale as f64) as i64)
+}
+
+// The torrent file is saved and tracked as "{info_hash}.torrent", which is the last segment of
+// a mikan seed_url. Seeds from other sources append it as a fragment, e.g. ".../123.torrent#{hash}.torrent"
+// A magnet seed has no file and is named by its info hash.
+pub fn get_torrent_name(seed_url: &str) -> String {
+    if let Some(info_hash) = magnet::get_info_hash(seed_url) {
+        return info_hash;
+    }
+    seed_url
+        .rsplit(['/', '#'])
+        .next()
+        .unwrap_or(seed_url)
         .to_string()
-        .parse::<i32>()
-        .unwrap());
 }
 
-fn regex_seed_1080(seed_name: &str) -> bool {
-    let re = Regex::new(r"1080").unwrap();
-    let str_list: Vec<&str> = re.find_iter(seed_name).map(|mat| mat.as_str()).collect();
-    !str_list.is_empty()
+// Episodes of a batch torrent share one torrent file, their anime_task.torrent_name is
+// "{info_hash}.torrent#{episode}" so every episode keeps its own task and progress
+pub fn get_batch_task_name(torrent_name: &str, episode: i32) -> String {
+    format!("{}#{}", torrent_name, episode)
 }
 
-#[derive(Debug)]
+// anime_task.torrent_name -> the torrent tracked by qbittorrent
+pub fn get_task_torrent_name(task_name: &str) -> &str {
+    task_name.split('#').next().unwrap_or(task_name)
+}
+
+// Convert the date formats used by mikan html / mikan rss / rfc2822 into "%Y-%m-%d %H:%M:%S"
+pub fn normalize_publish_date(date: &str) -> String {
+    let date = date.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc2822(date) {
+        return dt.naive_local().format("%Y-%m-%d %H:%M:%S").to_string();
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc3339(date) {
+        return dt.naive_local().format("%Y-%m-%d %H:%M:%S").to_string();
+    }
+    for fmt in [
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y/%m/%d %H:%M",
+        "%Y-%m-%d %H:%M",
+    ] {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(date, fmt) {
+            return dt.format("%Y-%m-%d %H:%M:%S").to_string();
+        }
+    }
+    String::new()
+}
+
+#[derive(Debug, Default)]
 pub struct BangumiInfo {
     pub bangumi_id: i32,
-    pub bangumi_rank: String,
+    pub bangumi_rank: String, // the rating score, e.g. 7.2
     pub bangumi_summary: String,
     pub website: String,
     pub total_episodes: i32,
+    pub air_date: String,
+    pub rating_rank: i32,
+    pub rating_votes: i32,
+    pub tags: Vec<String>,
+    pub studio: String,
+    pub alt_names: Vec<String>,
+    pub episodes: Vec<BangumiEpisode>, // only from the api
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BangumiEpisode {
+    pub episode: i32,
+    pub name: String, // name_cn when there is one
+    pub air_date: String,
+    pub duration_seconds: i32,
+    pub episode_type: EpisodeType,
+}
+
+/*
+    Responses of the bangumi v0 api, see https://bangumi.github.io/api/
+    GET /v0/subjects/{id} and GET /v0/episodes?subject_id={id}
+*/
+
+#[derive(Debug, Deserialize)]
+struct SubjectV0 {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    name_cn: String,
+    #[serde(default)]
+    summary: String,
+    date: Option<String>,
+    #[serde(default)]
+    eps: i32,
+    #[serde(default)]
+    rating: RatingV0,
+    #[serde(default)]
+    tags: Vec<TagV0>,
+    #[serde(default)]
+    infobox: Vec<InfoboxV0>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RatingV0 {
+    #[serde(default)]
+    rank: i32,
+    #[serde(default)]
+    total: i32,
+    #[serde(default)]
+    score: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagV0 {
+    name: String,
 }
 
+// value is a string, or a list of {"k": ..., "v": ...} such as the alias
+#[derive(Debug, Deserialize)]
+struct InfoboxV0 {
+    key: String,
+    value: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct EpisodePageV0 {
+    data: Vec<EpisodeV0>,
+    #[serde(default)]
+    total: i32,
+}
+
+#[derive(Debug, Deserialize)]
+struct EpisodeV0 {
+    #[serde(default)]
+    airdate: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    name_cn: String,
+    #[serde(default)]
+    duration_seconds: Option<i32>,
+    ep: Option<f64>,
+    #[serde(default)]
+    sort: f64,
+    #[serde(rename = "type", default)]
+    episode_type: i32,
+}
+
+const MAX_TAGS: usize = 10;
+const EPISODE_PAGE_SIZE: i32 = 100;
+
 impl Bangumi {
-    pub fn new() -> Result<Bangumi, Box<dyn Error>> {
-        let client = Client::builder().timeout(Duration::from_secs(10)).build()?;
+    pub fn new(config: &ScraperConfig) -> Result<Bangumi, Box<dyn Error>> {
         Ok(Bangumi {
-            client,
-            url: "https://bgm.tv".to_string(),
+            client: HttpClient::new(config)?,
+            url: config.bangumi_url.trim_end_matches('/').to_string(),
+            api_url: config.bangumi_api_url.trim_end_matches('/').to_string(),
         })
     }
 
     async fn request_html(&self, url: &str) -> Result<Document, Box<dyn Error>> {
-        let response = self.client.get(url).send().await?;
-        if !response.status().is_success() {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Request failed",
-            )));
-        }
-        let body = response.text().await?;
+        let body = self.client.get_text(url).await?;
         return Ok(Document::from(body.as_str()));
     }
 
+    // the api first, the subject page when it fails
     pub async fn get_bangumi_info(&self, bangumi_id: i32) -> Result<BangumiInfo, Box<dyn Error>> {
+        let err = match self.get_subject_by_api(bangumi_id).await {
+            Ok(info) => return Ok(info),
+            Err(e) => e.to_string(),
+        };
+        log::warn!(
+            "bangumi api of subject {} failed, scrape the page: {}",
+            bangumi_id,
+            err
+        );
+        self.get_subject_by_html(bangumi_id).await
+    }
+
+    pub async fn get_subject_by_api(&self, bangumi_id: i32) -> Result<BangumiInfo, Box<dyn Error>> {
+        let url = format!("{}/v0/subjects/{}", self.api_url, bangumi_id);
+        let body = self.client.get_text(&url).await?;
+        let mut info = parse_subject_v0(bangumi_id, &body)?;
+
+        // a subject without episodes is still usable
+        let err = match self.get_episodes_by_api(bangumi_id).await {
+            Ok(episodes) => {
+                info.episodes = episodes;
+                return Ok(info);
+            }
+            Err(e) => e.to_string(),
+        };
+        log::warn!("get episodes of subject {} failed: {}", bangumi_id, err);
+        Ok(info)
+    }
+
+    pub async fn get_episodes_by_api(
+        &self,
+        bangumi_id: i32,
+    ) -> Result<Vec<BangumiEpisode>, Box<dyn Error>> {
+        let mut episodes: Vec<BangumiEpisode> = Vec::new();
+        let mut offset = 0;
+        loop {
+            let url = format!(
+                "{}/v0/episodes?subject_id={}&limit={}&offset={}",
+                self.api_url, bangumi_id, EPISODE_PAGE_SIZE, offset
+            );
+            let body = self.client.get_text(&url).await?;
+            let (page, total) = parse_episodes_v0(&body)?;
+            let page_len = page.len() as i32;
+            episodes.extend(page);
+            offset += EPISODE_PAGE_SIZE;
+            if page_len < EPISODE_PAGE_SIZE || offset >= total {
+                break;
+            }
+        }
+        Ok(episodes)
+    }
+
+    pub async fn get_subject_by_html(
+        &self,
+        bangumi_id: i32,
+    ) -> Result<BangumiInfo, Box<dyn Error>> {
         let url = format!("{}/subject/{}", self.url, bangumi_id);
         let document = self.request_html(&url).await?;
 
@@ -521,10 +797,105 @@ impl Bangumi {
             bangumi_summary,
             website: bangumi_website,
             total_episodes,
+            rating_rank: -1,
+            ..Default::default()
         })
     }
 }
 
+fn parse_subject_v0(bangumi_id: i32, body: &str) -> Result<BangumiInfo, Box<dyn Error>> {
+    let subject: SubjectV0 = serde_json::from_str(body)?;
+
+    let infobox_text = |key: &str| -> Vec<String> {
+        subject
+            .infobox
+            .iter()
+            .filter(|item| item.key == key)
+            .flat_map(|item| match &item.value {
+                serde_json::Value::String(v) => vec![v.trim().to_string()],
+                serde_json::Value::Array(list) => list
+                    .iter()
+                    .filter_map(|v| v.get("v").and_then(|v| v.as_str()))
+                    .map(|v| v.trim().to_string())
+                    .collect(),
+                _ => Vec::new(),
+            })
+            .filter(|v| !v.is_empty())
+            .collect()
+    };
+
+    let mut total_episodes = infobox_text("话数")
+        .first()
+        .and_then(|v| convert_total_episodes(v).ok())
+        .unwrap_or(-1);
+    if total_episodes == -1 && subject.eps > 0 {
+        total_episodes = subject.eps;
+    }
+
+    let mut alt_names: Vec<String> = Vec::new();
+    for name in [subject.name.clone(), subject.name_cn.clone()]
+        .into_iter()
+        .chain(infobox_text("别名"))
+    {
+        if !name.is_empty() && !alt_names.contains(&name) {
+            alt_names.push(name);
+        }
+    }
+
+    Ok(BangumiInfo {
+        bangumi_id,
+        bangumi_rank: if subject.rating.score > 0.0 {
+            format!("{:.1}", subject.rating.score)
+        } else {
+            String::new()
+        },
+        bangumi_summary: subject.summary.clone(),
+        website: infobox_text("官方网站").join(" "),
+        total_episodes,
+        air_date: subject.date.clone().unwrap_or_default(),
+        rating_rank: if subject.rating.rank > 0 {
+            subject.rating.rank
+        } else {
+            -1
+        },
+        rating_votes: subject.rating.total,
+        tags: subject
+            .tags
+            .iter()
+            .take(MAX_TAGS)
+            .map(|tag| tag.name.clone())
+            .collect(),
+        studio: infobox_text("动画制作").join(" "),
+        alt_names,
+        episodes: Vec::new(),
+    })
+}
+
+// episodes of the page and the total of the subject, trailers and MADs are skipped
+fn parse_episodes_v0(body: &str) -> Result<(Vec<BangumiEpisode>, i32), Box<dyn Error>> {
+    let page: EpisodePageV0 = serde_json::from_str(body)?;
+    let episodes = page
+        .data
+        .into_iter()
+        .filter_map(|ep| {
+            let episode_type = EpisodeType::try_from(ep.episode_type).ok()?;
+            Some((ep, episode_type))
+        })
+        .map(|(ep, episode_type)| BangumiEpisode {
+            episode: ep.ep.unwrap_or(ep.sort) as i32,
+            name: if ep.name_cn.is_empty() {
+                ep.name
+            } else {
+                ep.name_cn
+            },
+            air_date: ep.airdate,
+            duration_seconds: ep.duration_seconds.unwrap_or_default(),
+            episode_type,
+        })
+        .collect();
+    Ok((episodes, page.total))
+}
+
 fn convert_total_episodes(total_episodes_str: &str) -> Result<i32, Box<dyn Error>> {
     match total_episodes_str {
         "" | "*" => Ok(-1),
@@ -534,3 +905,267 @@ fn convert_total_episodes(total_episodes_str: &str) -> Result<i32, Box<dyn Error
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mods::config::HttpCacheConfig;
+    use crate::mods::http_cache::{CacheEntry, HttpCache};
+
+    #[test]
+    fn test_parse_rss_seed() {
+        let xml = include_str!("../../tests/fixtures/mikan_rss_3143_382.xml");
+        let seed_list = parse_rss_seed(xml, 3143, 382, AnimeType::Tv).unwrap();
+
+        assert_eq!(seed_list.len(), 3);
+        assert_eq!(
+            seed_list[0].seed_name,
+            "【喵萌奶茶屋】★10月新番★[米基与达利 / Migi to Dali][04][1080p][简日双语][招募翻译]"
+        );
+        assert_eq!(seed_list[0].episode, 4);
+        assert_eq!(
+            seed_list[0].seed_url,
+            "/Download/20231028/2e5bd3e5a6b04b8ff6d1f0a8b3c9e0f8d6f0e2a1.torrent"
+        );
+        assert_eq!(seed_list[0].seed_size, "351.2MB");
+        assert_eq!(seed_list[0].publish_date, "2023-10-28 18:30:12");
+        assert_eq!(seed_list[1].episode, 3);
+        assert_eq!(seed_list[1].seed_size, "349.4MB");
+        assert_eq!(seed_list[2].episode, 3);
+        assert!(seed_list[2].seed_name.contains("[720p]"));
+    }
+
+    #[test]
+    fn test_parse_rss_seed_movie() {
+        let xml = include_str!("../../tests/fixtures/mikan_rss_3143_382.xml");
+        let seed_list = parse_rss_seed(xml, 3143, 382, AnimeType::Movie).unwrap();
+        assert_eq!(seed_list.len(), 3);
+        assert!(seed_list.iter().all(|s| s.episode == 1));
+    }
+
+    // the parsers run on pages recorded in the cache, without network
+    #[tokio::test]
+    async fn test_replay_rss() {
+        let dir = std::env::temp_dir().join("autoanime_spider_replay");
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = ScraperConfig {
+            cache: HttpCacheConfig {
+                replay: true,
+                dir: dir.to_string_lossy().to_string(),
+                ..HttpCacheConfig::default()
+            },
+            ..ScraperConfig::default()
+        };
+        HttpCache::new(&config.cache)
+            .unwrap()
+            .store(&CacheEntry {
+                url: "https://mikanani.me/RSS/Bangumi?bangumiId=3143&subgroupid=382".to_string(),
+                etag: "".to_string(),
+                last_modified: "".to_string(),
+                fetched_at: 0,
+                body: include_str!("../../tests/fixtures/mikan_rss_3143_382.xml").to_string(),
+            })
+            .unwrap();
+
+        let mikan = Mikan::new(&config).unwrap();
+        let seed_list = mikan
+            .get_seed_by_rss(3143, 382, AnimeType::Tv)
+            .await
+            .unwrap();
+        assert_eq!(seed_list.len(), 3);
+        assert!(mikan
+            .get_seed_by_rss(3143, 583, AnimeType::Tv)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_subject_v0() {
+        let info = parse_subject_v0(
+            400602,
+            include_str!("../../tests/fixtures/bangumi_subject_400602.json"),
+        )
+        .unwrap();
+        assert_eq!(info.bangumi_rank, "9.1");
+        assert_eq!(info.rating_rank, 1);
+        assert_eq!(info.rating_votes, 26543);
+        assert_eq!(info.total_episodes, 28);
+        assert_eq!(info.air_date, "2023-09-29");
+        assert_eq!(info.website, "https://frieren-anime.jp/");
+        assert_eq!(info.studio, "MADHOUSE");
+        assert_eq!(info.tags[0], "奇幻");
+        assert_eq!(
+            info.alt_names,
+            vec![
+                "葬送のフリーレン",
+                "葬送的芙莉莲",
+                "Frieren: Beyond Journey's End",
+                "芙莉莲"
+            ]
+        );
+
+        let (episodes, total) = parse_episodes_v0(include_str!(
+            "../../tests/fixtures/bangumi_episodes_400602.json"
+        ))
+        .unwrap();
+        assert_eq!(total, 5);
+        assert_eq!(episodes.len(), 4);
+        assert_eq!(episodes[0].name, "冒险的终点");
+        assert_eq!(episodes[1].name, "別に魔法じゃなくたって…");
+        assert_eq!(episodes[2].duration_seconds, 0);
+        assert_eq!(episodes[3].episode_type, EpisodeType::Opening);
+    }
+
+    // the api is used when it answers, the subject page otherwise
+    #[tokio::test]
+    async fn test_replay_bangumi() {
+        let dir = std::env::temp_dir().join("autoanime_spider_bangumi");
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = ScraperConfig {
+            cache: HttpCacheConfig {
+                replay: true,
+                dir: dir.to_string_lossy().to_string(),
+                ..HttpCacheConfig::default()
+            },
+            ..ScraperConfig::default()
+        };
+        let cache = HttpCache::new(&config.cache).unwrap();
+        for (url, body) in [
+            (
+                "https://api.bgm.tv/v0/subjects/400602",
+                include_str!("../../tests/fixtures/bangumi_subject_400602.json"),
+            ),
+            (
+                "https://api.bgm.tv/v0/episodes?subject_id=400602&limit=100&offset=0",
+                include_str!("../../tests/fixtures/bangumi_episodes_400602.json"),
+            ),
+            (
+                "https://bgm.tv/subject/1",
+                r#"<span class="number">7.5</span><div class="subject_summary">summary</div><ul><li><span class="tip">话数: </span>12</li></ul>"#,
+            ),
+        ] {
+            cache
+                .store(&CacheEntry {
+                    url: url.to_string(),
+                    etag: "".to_string(),
+                    last_modified: "".to_string(),
+                    fetched_at: 0,
+                    body: body.to_string(),
+                })
+                .unwrap();
+        }
+
+        let bangumi = Bangumi::new(&config).unwrap();
+        let info = bangumi.get_bangumi_info(400602).await.unwrap();
+        assert_eq!(info.studio, "MADHOUSE");
+        assert_eq!(info.episodes.len(), 4);
+        assert_eq!(info.episodes[2].name, "杀人的魔法");
+
+        let info = bangumi.get_bangumi_info(1).await.unwrap();
+        assert_eq!(info.bangumi_rank, "7.5");
+        assert_eq!(info.total_episodes, 12);
+        assert_eq!(info.rating_rank, -1);
+        assert!(info.episodes.is_empty());
+        assert!(bangumi.get_bangumi_info(2).await.is_err());
+    }
+
+    #[test]
+    fn test_mirror_url() {
+        let config = ScraperConfig {
+            mikan_url: "https://mikanime.tv/".to_string(),
+            ..ScraperConfig::default()
+        };
+        assert_eq!(Mikan::new(&config).unwrap().url, "https://mikanime.tv");
+        assert_eq!(Bangumi::new(&config).unwrap().url, "https://bgm.tv");
+    }
+
+    #[test]
+    fn test_get_torrent_name() {
+        assert_eq!(
+            get_torrent_name("/Download/20231021/55829bc76527a4868f9fd5c40e769f618f30e85b.torrent"),
+            "55829bc76527a4868f9fd5c40e769f618f30e85b.torrent"
+        );
+        assert_eq!(
+            get_torrent_name("https://nyaa.si/download/1234.torrent#abcdef.torrent"),
+            "abcdef.torrent"
+        );
+        assert_eq!(
+            get_torrent_name("magnet:?xt=urn:btih:55829BC76527A4868F9FD5C40E769F618F30E85B&tr=http://t/announce"),
+            "55829bc76527a4868f9fd5c40e769f618f30e85b"
+        );
+    }
+
+    #[test]
+    fn test_parse_seed_size() {
+        assert_eq!(parse_seed_size("351.2MB"), 368259891);
+        assert_eq!(parse_seed_size("1.5GB"), 1610612736);
+        assert_eq!(parse_seed_size("700 MiB"), 734003200);
+        assert_eq!(parse_seed_size(&format_seed_size(1 << 30)), 1 << 30);
+        assert_eq!(parse_seed_size(""), 0);
+        assert_eq!(parse_seed_size("1.5XB"), 0);
+    }
+
+    #[test]
+    fn test_build_batch_seed() {
+        let seed = build_seed(
+            3143,
+            382,
+            AnimeType::Tv,
+            "[xyx98] 葬送的芙莉莲 第1-28话 [BDRip 1080p HEVC][简繁日]".to_string(),
+            "/Download/20240401/abc.torrent".to_string(),
+            "30.1GB".to_string(),
+            "".to_string(),
+        )
+        .unwrap();
+        assert_eq!((seed.episode, seed.episode_end), (1, 28));
+
+        let task_name = get_batch_task_name("abc.torrent", 5);
+        assert_eq!(task_name, "abc.torrent#5");
+        assert_eq!(get_task_torrent_name(&task_name), "abc.torrent");
+        assert_eq!(get_task_torrent_name("abc.torrent"), "abc.torrent");
+    }
+
+    #[test]
+    fn test_build_special_seed() {
+        let seed = build_seed(
+            3143,
+            370,
+            AnimeType::Tv,
+            "[LoliHouse] Sousou no Frieren - 12.5 [WebRip 1080p HEVC-10bit AAC]".to_string(),
+            "/Download/20231201/def.torrent".to_string(),
+            "300.5MB".to_string(),
+            "".to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            (seed.episode, seed.episode_end, seed.is_special),
+            (12, -1, true)
+        );
+
+        let seed = build_seed(
+            3143,
+            370,
+            AnimeType::Tv,
+            "[LoliHouse] Sousou no Frieren - 12 [WebRip 1080p HEVC-10bit AAC]".to_string(),
+            "/Download/20231201/ghi.torrent".to_string(),
+            "300.5MB".to_string(),
+            "".to_string(),
+        )
+        .unwrap();
+        assert_eq!((seed.episode, seed.is_special), (12, false));
+    }
+
+    #[test]
+    fn test_normalize_publish_date() {
+        assert_eq!(
+            normalize_publish_date("2023-10-21T18:28:00.123"),
+            "2023-10-21 18:28:00"
+        );
+        assert_eq!(normalize_publish_date("2023/10/21 18:28"), "2023-10-21 18:28:00");
+        assert_eq!(
+            normalize_publish_date("Sat, 21 Oct 2023 18:28:00 +0800"),
+            "2023-10-21 18:28:00"
+        );
+        assert_eq!(normalize_publish_date("unknown"), "");
+    }
+}
@@ -0,0 +1,408 @@
+use crate::api::do_anime_task::aired_episodes;
+use crate::api::scheduler::TIME_FORMAT;
+use crate::dao;
+use crate::models::anime_seed::AnimeSeedJson;
+use crate::models::anime_subgroup::AnimeSubgroup;
+use crate::models::subgroup_release::{SubgroupRelease, SubgroupReleaseJson};
+use crate::mods::{spider, title_parser};
+use anyhow::Error;
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::SqliteConnection;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/*
+    The first seed of every episode a subgroup released is kept in subgroup_release.
+    A subgroup is scored out of 100 over the anime it released:
+        completion  60  released / aired main episodes
+        timeliness  30  1 - delay / MAX_DELAY_HOURS, the delay is the first seen time
+                        after the air date (00:00), 15 without air dates
+        resolution  10  the most released resolution / 1080
+*/
+const MAX_DELAY_HOURS: f64 = 72.0;
+pub const SUGGESTION_COUNT: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubgroupStats {
+    pub subgroup_id: i32,
+    pub subgroup_name: String,
+    pub anime_count: i32,
+    pub episode_count: i32,
+    pub completion_rate: f64,
+    pub avg_delay_hours: Option<f64>,
+    pub resolution: i32, // 0 when unknown
+    pub avg_size_mb: f64,
+    pub score: f64,
+}
+
+// an episode seed, batches and specials are not a release of their episodes
+pub fn release_of(anime_seed: &AnimeSeedJson) -> Option<SubgroupReleaseJson> {
+    if anime_seed.episode_end != -1
+        || anime_seed.is_special
+        || NaiveDateTime::parse_from_str(&anime_seed.publish_date, TIME_FORMAT).is_err()
+    {
+        return None;
+    }
+    Some(SubgroupReleaseJson {
+        subgroup_id: anime_seed.subgroup_id,
+        mikan_id: anime_seed.mikan_id,
+        episode: anime_seed.episode,
+        first_seen: anime_seed.publish_date.clone(),
+        resolution: title_parser::parse_title(&anime_seed.seed_name)
+            .resolution_height()
+            .unwrap_or(0),
+        size: spider::parse_seed_size(&anime_seed.seed_size),
+    })
+}
+
+pub async fn record_releases(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    anime_seed_vec: &[AnimeSeedJson],
+) -> Result<usize, diesel::result::Error> {
+    let release_vec: Vec<SubgroupReleaseJson> =
+        anime_seed_vec.iter().filter_map(release_of).collect();
+    if release_vec.is_empty() {
+        return Ok(0);
+    }
+    dao::subgroup_release::add_vec(db_connection, release_vec).await
+}
+
+fn round2(val: f64) -> f64 {
+    (val * 100.0).round() / 100.0
+}
+
+/*
+    aired_map: the aired main episodes of each anime
+    air_date_map: the air date ("%Y-%m-%d") of the main episodes, by (mikan_id, episode)
+*/
+pub fn rank_subgroups(
+    release_vec: &[SubgroupRelease],
+    aired_map: &HashMap<i32, HashSet<i32>>,
+    air_date_map: &HashMap<(i32, i32), String>,
+    name_map: &HashMap<i32, String>,
+) -> Vec<SubgroupStats> {
+    let mut release_map: HashMap<i32, Vec<&SubgroupRelease>> = HashMap::new();
+    for release in release_vec {
+        release_map
+            .entry(release.subgroup_id)
+            .or_default()
+            .push(release);
+    }
+
+    let mut stats_vec: Vec<SubgroupStats> = Vec::new();
+    for (subgroup_id, release_vec) in release_map {
+        let anime_set: HashSet<i32> = release_vec.iter().map(|r| r.mikan_id).collect();
+        let aired_count: usize = anime_set
+            .iter()
+            .map(|mikan_id| aired_map.get(mikan_id).map_or(0, |aired| aired.len()))
+            .sum();
+        let released_count = release_vec
+            .iter()
+            .filter(|r| {
+                aired_map
+                    .get(&r.mikan_id)
+                    .is_some_and(|aired| aired.contains(&r.episode))
+            })
+            .count();
+        let completion_rate = if aired_count > 0 {
+            (released_count as f64 / aired_count as f64).min(1.0)
+        } else {
+            0.0
+        };
+
+        let delay_vec: Vec<f64> = release_vec
+            .iter()
+            .filter_map(|r| {
+                let air_date = air_date_map.get(&(r.mikan_id, r.episode))?;
+                let air_time = NaiveDate::parse_from_str(air_date, "%Y-%m-%d")
+                    .ok()?
+                    .and_hms_opt(0, 0, 0)?;
+                let first_seen = NaiveDateTime::parse_from_str(&r.first_seen, TIME_FORMAT).ok()?;
+                Some(((first_seen - air_time).num_minutes() as f64 / 60.0).max(0.0))
+            })
+            .collect();
+        let avg_delay_hours =
+            (!delay_vec.is_empty()).then(|| delay_vec.iter().sum::<f64>() / delay_vec.len() as f64);
+
+        // the most released resolution, the higher one on a tie
+        let mut resolution_count: HashMap<i32, usize> = HashMap::new();
+        for release in release_vec.iter().filter(|r| r.resolution > 0) {
+            *resolution_count.entry(release.resolution).or_default() += 1;
+        }
+        let resolution = resolution_count
+            .into_iter()
+            .max_by_key(|(resolution, count)| (*count, *resolution))
+            .map_or(0, |(resolution, _)| resolution);
+
+        let size_vec: Vec<i64> = release_vec
+            .iter()
+            .map(|r| r.size)
+            .filter(|s| *s > 0)
+            .collect();
+        let avg_size_mb = if size_vec.is_empty() {
+            0.0
+        } else {
+            size_vec.iter().sum::<i64>() as f64 / size_vec.len() as f64 / (1 << 20) as f64
+        };
+
+        let timeliness =
+            avg_delay_hours.map_or(0.5, |delay| 1.0 - (delay / MAX_DELAY_HOURS).clamp(0.0, 1.0));
+        let score = 60.0 * completion_rate
+            + 30.0 * timeliness
+            + 10.0 * resolution.min(1080) as f64 / 1080.0;
+
+        stats_vec.push(SubgroupStats {
+            subgroup_id,
+            subgroup_name: name_map.get(&subgroup_id).cloned().unwrap_or_default(),
+            anime_count: anime_set.len() as i32,
+            episode_count: release_vec.len() as i32,
+            completion_rate: round2(completion_rate),
+            avg_delay_hours: avg_delay_hours.map(round2),
+            resolution,
+            avg_size_mb: round2(avg_size_mb),
+            score: round2(score),
+        });
+    }
+    stats_vec.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b.episode_count.cmp(&a.episode_count))
+            .then(a.subgroup_id.cmp(&b.subgroup_id))
+    });
+    stats_vec
+}
+
+// the ranked subgroups first, in the order of stats_vec, then the others
+pub fn order_subgroups(
+    mut subgroup_vec: Vec<AnimeSubgroup>,
+    stats_vec: &[SubgroupStats],
+) -> Vec<AnimeSubgroup> {
+    let mut position_map: HashMap<i32, usize> = HashMap::new();
+    for (position, stats) in stats_vec.iter().enumerate() {
+        position_map.entry(stats.subgroup_id).or_insert(position);
+    }
+    subgroup_vec.sort_by_key(|s| {
+        position_map
+            .get(&s.subgroup_id)
+            .cloned()
+            .unwrap_or(usize::MAX)
+    });
+    subgroup_vec
+}
+
+// the best subgroups which are neither preferred nor avoided yet, filter_set holds
+// the subgroup rules of anime_filter (negative to avoid)
+pub fn suggest(stats_vec: &[SubgroupStats], filter_set: &HashSet<i32>) -> Vec<SubgroupStats> {
+    stats_vec
+        .iter()
+        .filter(|s| !filter_set.contains(&s.subgroup_id) && !filter_set.contains(&-s.subgroup_id))
+        .take(SUGGESTION_COUNT)
+        .cloned()
+        .collect()
+}
+
+// rankings of all the recorded anime, or of a single one
+pub async fn get_subgroup_stats(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    query_mikan_id: Option<i32>,
+    today: &str,
+) -> Result<Vec<SubgroupStats>, Error> {
+    let release_vec = match query_mikan_id {
+        Some(mikan_id) => dao::subgroup_release::get_by_mikan_id(db_connection, mikan_id).await?,
+        None => dao::subgroup_release::get_all(db_connection).await?,
+    };
+
+    let mut last_episode_map: HashMap<i32, i32> = HashMap::new();
+    for release in &release_vec {
+        let last_episode = last_episode_map.entry(release.mikan_id).or_insert(0);
+        *last_episode = (*last_episode).max(release.episode);
+    }
+
+    let mut aired_map: HashMap<i32, HashSet<i32>> = HashMap::new();
+    let mut air_date_map: HashMap<(i32, i32), String> = HashMap::new();
+    for (mikan_id, last_episode) in last_episode_map {
+        // the releases of a removed anime are kept, without aired episodes
+        let anime = match dao::anime_list::get_by_mikanid(db_connection, mikan_id).await {
+            Ok(anime) => anime,
+            Err(_) => continue,
+        };
+        let main_episode_vec =
+            dao::anime_episode::get_main_by_mikan_id(db_connection, mikan_id).await?;
+        aired_map.insert(
+            mikan_id,
+            aired_episodes(&anime, &main_episode_vec, last_episode, today)
+                .into_iter()
+                .collect(),
+        );
+        for main_episode in main_episode_vec {
+            if !main_episode.air_date.is_empty() {
+                air_date_map.insert((mikan_id, main_episode.episode), main_episode.air_date);
+            }
+        }
+    }
+
+    let name_map: HashMap<i32, String> = dao::anime_subgroup::get_all(db_connection)
+        .await?
+        .into_iter()
+        .map(|s| (s.subgroup_id, s.subgroup_name))
+        .collect();
+    Ok(rank_subgroups(
+        &release_vec,
+        &aired_map,
+        &air_date_map,
+        &name_map,
+    ))
+}
+
+// preferred subgroups for an anime, by its own releases when it has any
+pub async fn suggest_subgroups(
+    db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
+    mikan_id: i32,
+    today: &str,
+) -> Result<Vec<SubgroupStats>, Error> {
+    let mut stats_vec = get_subgroup_stats(db_connection, Some(mikan_id), today).await?;
+    if stats_vec.is_empty() {
+        stats_vec = get_subgroup_stats(db_connection, None, today).await?;
+    }
+
+    let (global_perference_sub_set, global_avoid_sub_set) =
+        dao::anime_filter::get_global_subgroup_filter_set(db_connection).await;
+    let (local_perference_sub_set, local_avoid_sub_set) =
+        dao::anime_filter::get_local_subgroup_filter_set_by_mikan_id(&mikan_id, db_connection)
+            .await?;
+    let filter_set: HashSet<i32> = global_perference_sub_set
+        .into_iter()
+        .chain(global_avoid_sub_set)
+        .chain(local_perference_sub_set)
+        .chain(local_avoid_sub_set)
+        .collect();
+    Ok(suggest(&stats_vec, &filter_set))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::models::enums::SeedStatus;
+
+    fn release(
+        subgroup_id: i32,
+        episode: i32,
+        first_seen: &str,
+        resolution: i32,
+    ) -> SubgroupRelease {
+        SubgroupRelease {
+            id: None,
+            subgroup_id,
+            mikan_id: 3143,
+            episode,
+            first_seen: first_seen.to_string(),
+            resolution,
+            size: 300 << 20,
+        }
+    }
+
+    #[test]
+    fn test_release_of() {
+        let anime_seed = AnimeSeedJson {
+            mikan_id: 3143,
+            subgroup_id: 370,
+            episode: 5,
+            seed_name: "[LoliHouse] Frieren - 05 [WebRip 1080p HEVC-10bit AAC]".to_string(),
+            seed_url: "/Download/1.torrent".to_string(),
+            seed_status: SeedStatus::Unused,
+            seed_size: "351.2MB".to_string(),
+            publish_date: "2023-10-06 20:00:00".to_string(),
+            episode_end: -1,
+            is_special: false,
+        };
+        let release = release_of(&anime_seed).unwrap();
+        assert_eq!((release.resolution, release.size), (1080, 368259891));
+        assert!(release_of(&AnimeSeedJson {
+            episode_end: 12,
+            ..anime_seed.clone()
+        })
+        .is_none());
+        assert!(release_of(&AnimeSeedJson {
+            is_special: true,
+            ..anime_seed.clone()
+        })
+        .is_none());
+        assert!(release_of(&AnimeSeedJson {
+            publish_date: "".to_string(),
+            ..anime_seed
+        })
+        .is_none());
+    }
+
+    #[test]
+    fn test_rank_subgroups() {
+        let aired_map = HashMap::from([(3143, HashSet::from([1, 2]))]);
+        let air_date_map = HashMap::from([
+            ((3143, 1), "2023-09-29".to_string()),
+            ((3143, 2), "2023-10-06".to_string()),
+        ]);
+        let name_map = HashMap::from([(583, "ANi".to_string())]);
+        let stats_vec = rank_subgroups(
+            &[
+                release(583, 1, "2023-09-29 06:00:00", 1080),
+                release(583, 2, "2023-10-06 06:00:00", 1080),
+                release(370, 1, "2023-10-01 00:00:00", 1080),
+                release(370, 2, "2023-10-08 00:00:00", 720),
+                release(202, 1, "2023-09-29 01:00:00", 0),
+            ],
+            &aired_map,
+            &air_date_map,
+            &name_map,
+        );
+        assert_eq!(
+            stats_vec.iter().map(|s| s.subgroup_id).collect::<Vec<_>>(),
+            vec![583, 370, 202]
+        );
+        assert_eq!(stats_vec[0].subgroup_name, "ANi");
+        assert_eq!(stats_vec[0].completion_rate, 1.0);
+        assert_eq!(stats_vec[0].avg_delay_hours, Some(6.0));
+        assert_eq!(stats_vec[0].avg_size_mb, 300.0);
+        assert_eq!(stats_vec[0].score, 97.5);
+        assert_eq!(stats_vec[1].avg_delay_hours, Some(48.0));
+        // the higher resolution wins a tie
+        assert_eq!(stats_vec[1].resolution, 1080);
+        assert_eq!(stats_vec[2].completion_rate, 0.5);
+    }
+
+    #[test]
+    fn test_order_and_suggest() {
+        let subgroup = |subgroup_id: i32| AnimeSubgroup {
+            id: None,
+            subgroup_id,
+            subgroup_name: "".to_string(),
+        };
+        let stats = |subgroup_id: i32| SubgroupStats {
+            subgroup_id,
+            subgroup_name: "".to_string(),
+            anime_count: 1,
+            episode_count: 1,
+            completion_rate: 1.0,
+            avg_delay_hours: None,
+            resolution: 1080,
+            avg_size_mb: 0.0,
+            score: 0.0,
+        };
+        let stats_vec = vec![stats(382), stats(615), stats(370), stats(583), stats(1)];
+
+        let ordered = order_subgroups(
+            vec![subgroup(202), subgroup(370), subgroup(382), subgroup(615)],
+            &stats_vec,
+        );
+        assert_eq!(
+            ordered.iter().map(|s| s.subgroup_id).collect::<Vec<_>>(),
+            vec![382, 615, 370, 202]
+        );
+
+        let suggested = suggest(&stats_vec, &HashSet::from([382, -615]));
+        assert_eq!(
+            suggested.iter().map(|s| s.subgroup_id).collect::<Vec<_>>(),
+            vec![370, 583, 1]
+        );
+    }
+}
@@ -0,0 +1,524 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/*
+    Parse the release name of a seed into structured data, e.g.
+    "[LoliHouse] Sousou no Frieren - 05v2 [WebRip 1080p HEVC-10bit AAC][简繁内封字幕]"
+    -> episode 5, version 2, resolution 1080p, source WebRip, codec HEVC, subtitle [CHS, CHT] embedded
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedTitle {
+    pub episode: Option<f32>,     // 12.5 for specials
+    pub episode_end: Option<f32>, // only set for batch releases, e.g. [01-12]
+    pub version: i32,             // 1 if the title has no "vN"
+    pub season: Option<i32>,
+    pub resolution: Option<String>,     // 2160p, 1080p, 720p, 480p
+    pub source: Option<String>,         // WEB-DL, WebRip, BDRip, BD, TV
+    pub codec: Option<String>,          // AVC, HEVC, AV1
+    pub subtitle_language: Vec<String>, // CHS, CHT, JPN, ENG
+    pub subtitle_embedded: bool,        // 内封, soft subtitles muxed into the video
+    pub container: Option<String>,      // MKV, MP4
+}
+
+impl Default for ParsedTitle {
+    fn default() -> Self {
+        ParsedTitle {
+            episode: None,
+            episode_end: None,
+            version: 1,
+            season: None,
+            resolution: None,
+            source: None,
+            codec: None,
+            subtitle_language: Vec::new(),
+            subtitle_embedded: false,
+            container: None,
+        }
+    }
+}
+
+impl ParsedTitle {
+    pub fn is_batch(&self) -> bool {
+        self.episode_end.is_some()
+    }
+
+    pub fn is_special(&self) -> bool {
+        matches!(self.episode, Some(ep) if ep.fract() != 0.0)
+    }
+
+    // whole episode number of a single episode release
+    pub fn episode_number(&self) -> Option<i32> {
+        if self.is_batch() || self.is_special() {
+            return None;
+        }
+        self.episode.map(|ep| ep as i32)
+    }
+
+    // 1080 for "1080p"
+    pub fn resolution_height(&self) -> Option<i32> {
+        self.resolution
+            .as_deref()
+            .and_then(|r| r.trim_end_matches('p').parse::<i32>().ok())
+    }
+}
+
+const CJK_NUM: &str = "零〇一二两三四五六七八九十百千";
+
+static RE_NOISE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?ix)
+        \d{3,4}\s?[x×]\s?\d{3,4}        # 1920x1080
+        | \d{3,4}p                      # 1080p
+        | \b4K\b
+        | \b(?:Hi|Ma)10p
+        | \b[xh]\.?26[45]\b             # x264, H.265, x.264
+        | \d+-?bits?\b                  # 10bit, 10-bit
+        | \b(?:AAC|FLAC|DDP?|AC3|OPUS)\s?\d\.\d
+        | \d{1,2}月新番
+        | \d{4}年(?:\d{1,2}月)?
+        | \b(?:19|20)\d{2}\b            # year
+        | \[[0-9A-F]{8}\]               # crc32
+        | \.(?:mp4|mkv|avi)$
+        | \bS\d{1,2}\b
+        | \bSeason\s?\d{1,2}\b
+        | \b\d(?:st|nd|rd|th)\s?Season\b
+        | 第[0-9零〇一二两三四五六七八九十]+[季期]
+        ",
+    )
+    .unwrap()
+});
+
+static RE_SXXEXX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\bS(\d{1,2})\s?E(\d{1,4}(?:\.\d)?)(?:\s?[-~]\s?E?(\d{1,4}))?(?:v(\d))?")
+        .unwrap()
+});
+
+static RE_CJK_EPISODE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(&format!(
+        r"第\s?([0-9{cjk}]+(?:\.\d)?)\s?(?:[-~至]\s?第?\s?([0-9{cjk}]+)\s?)?[话話集回](?:\s?v(\d))?",
+        cjk = CJK_NUM
+    ))
+    .unwrap()
+});
+
+static RE_RANGE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)(?:^|[\[\s(_])(?:EP?)?(\d{1,4})\s?[-~]\s?(?:EP?)?(\d{1,4})(?:[^\d.p]|$)")
+        .unwrap()
+});
+
+static RE_EP: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)(?:\b(?:EP|E|Episode\s?)|#)(\d{1,4}(?:\.\d)?)(?:v(\d))?\b").unwrap()
+});
+
+static RE_BRACKET: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\[\s?(\d{1,4}(?:\.\d)?)(?:v(\d))?\s?(?:END|Fin|完)?\s?\]").unwrap()
+});
+
+static RE_DASH: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\s[-–—]\s?(\d{1,4}(?:\.\d)?)(?:v(\d))?(?:\s?(?:END|Fin))?(?:$|[\s\[(_])")
+        .unwrap()
+});
+
+static RE_SPACED: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)(?:^|\s)(\d{2,4}(?:\.\d)?)(?:v(\d))?(?:\s?(?:END|Fin))?(?:\s|$)").unwrap()
+});
+
+static RE_VERSION: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)[\[\s]v(\d)[\]\s]").unwrap());
+
+static RE_SEASON: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(&format!(
+        r"(?i)\bS(\d{{1,2}})(?:E\d|\b)|\bSeason\s?(\d{{1,2}})\b|\b(\d)(?:st|nd|rd|th)\s?Season\b|第([0-9{cjk}]+)[季期]",
+        cjk = CJK_NUM
+    ))
+    .unwrap()
+});
+
+static RE_RESOLUTION: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)(2160|1080|720|480)p|\d{3,4}\s?[x×]\s?(2160|1080|720|480)\b|\b(4K)\b").unwrap()
+});
+
+static RE_CODEC: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)(HEVC|[xh]\.?265)|(AVC|[xh]\.?264)|\b(AV1)\b").unwrap());
+
+static RE_CHS: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"简|(?:^|[^A-Za-z])(?:GB|CHS|SC|JPSC)(?:[^A-Za-z]|$)|(?i)Simplified").unwrap()
+});
+
+static RE_CHT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"繁|(?:^|[^A-Za-z])(?:BIG5|Big5|CHT|TC|JPTC)(?:[^A-Za-z]|$)|(?i)Traditional")
+        .unwrap()
+});
+
+static RE_JPN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"[简繁]日|日[简繁]|日语|日文|JPSC|JPTC|(?:^|[^A-Za-z])JPN?(?:[^A-Za-z]|$)").unwrap()
+});
+
+static RE_CONTAINER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)(?:^|[^A-Za-z0-9])(MKV|MP4)(?:[^A-Za-z0-9]|$)").unwrap());
+
+static RE_ENG: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"英|(?:^|[^A-Za-z])(?:ENG?|English)(?:[^A-Za-z]|$)").unwrap());
+
+pub fn parse_title(title: &str) -> ParsedTitle {
+    let title = normalize(title);
+    let mut parsed = ParsedTitle {
+        season: parse_season(&title),
+        resolution: parse_resolution(&title),
+        source: parse_source(&title),
+        codec: parse_codec(&title),
+        subtitle_language: parse_subtitle_language(&title),
+        subtitle_embedded: title.contains("内封") || title.contains("內封"),
+        container: RE_CONTAINER
+            .captures(&title)
+            .map(|captures| captures[1].to_uppercase()),
+        ..Default::default()
+    };
+
+    if let Some(captures) = RE_SXXEXX.captures(&title) {
+        parsed.season = captures[1].parse::<i32>().ok();
+        parsed.episode = captures[2].parse::<f32>().ok();
+        parsed.episode_end = captures.get(3).and_then(|m| m.as_str().parse::<f32>().ok());
+        parsed.version = parse_version(captures.get(4).map(|m| m.as_str()));
+        return parsed;
+    }
+
+    let cleaned = RE_NOISE.replace_all(&title, " ").to_string();
+    parse_episode(&cleaned, &mut parsed);
+    if parsed.version == 1 {
+        if let Some(captures) = RE_VERSION.captures(&cleaned) {
+            parsed.version = parse_version(Some(&captures[1]));
+        }
+    }
+    parsed
+}
+
+fn parse_episode(title: &str, parsed: &mut ParsedTitle) {
+    if let Some(captures) = RE_CJK_EPISODE.captures(title) {
+        if let Some(episode) = parse_number(&captures[1]) {
+            parsed.episode = Some(episode);
+            parsed.episode_end = captures.get(2).and_then(|m| parse_number(m.as_str()));
+            parsed.version = parse_version(captures.get(3).map(|m| m.as_str()));
+            return;
+        }
+    }
+
+    for captures in RE_RANGE.captures_iter(title) {
+        let start = captures[1].parse::<f32>().unwrap();
+        let end = captures[2].parse::<f32>().unwrap();
+        // "86 - 03" is a title followed by an episode, not a batch
+        if end > start {
+            parsed.episode = Some(start);
+            parsed.episode_end = Some(end);
+            return;
+        }
+    }
+
+    for re in [&RE_EP, &RE_BRACKET, &RE_DASH, &RE_SPACED] {
+        if let Some(captures) = re.captures(title) {
+            parsed.episode = captures[1].parse::<f32>().ok();
+            parsed.version = parse_version(captures.get(2).map(|m| m.as_str()));
+            return;
+        }
+    }
+}
+
+fn parse_version(version: Option<&str>) -> i32 {
+    version.and_then(|v| v.parse::<i32>().ok()).unwrap_or(1)
+}
+
+fn parse_season(title: &str) -> Option<i32> {
+    let captures = RE_SEASON.captures(title)?;
+    for i in 1..=3 {
+        if let Some(m) = captures.get(i) {
+            return m.as_str().parse::<i32>().ok();
+        }
+    }
+    captures
+        .get(4)
+        .and_then(|m| parse_number(m.as_str()))
+        .map(|s| s as i32)
+}
+
+fn parse_resolution(title: &str) -> Option<String> {
+    let captures = RE_RESOLUTION.captures(title)?;
+    if captures.get(3).is_some() {
+        return Some("2160p".to_string());
+    }
+    captures
+        .get(1)
+        .or(captures.get(2))
+        .map(|m| format!("{}p", m.as_str()))
+}
+
+fn parse_source(title: &str) -> Option<String> {
+    let upper = title.to_uppercase();
+    let source = if upper.contains("WEB-DL") || upper.contains("WEBDL") || upper.contains("WEB DL")
+    {
+        "WEB-DL"
+    } else if upper.contains("WEBRIP") || upper.contains("WEB-RIP") {
+        "WebRip"
+    } else if upper.contains("BDRIP") {
+        "BDRip"
+    } else if upper.contains("BLURAY") || upper.contains("BLU-RAY") || has_token(&upper, "BD") {
+        "BD"
+    } else if upper.contains("HDTV") || upper.contains("TVRIP") {
+        "TV"
+    } else if [
+        "BAHA", "CR", "B-GLOBAL", "ABEMA", "NETFLIX", "AMZN", "VIUTV",
+    ]
+    .iter()
+    .any(|t| has_token(&upper, t))
+    {
+        "WEB-DL"
+    } else {
+        return None;
+    };
+    Some(source.to_string())
+}
+
+fn parse_codec(title: &str) -> Option<String> {
+    let captures = RE_CODEC.captures(title)?;
+    let codec = if captures.get(1).is_some() {
+        "HEVC"
+    } else if captures.get(2).is_some() {
+        "AVC"
+    } else {
+        "AV1"
+    };
+    Some(codec.to_string())
+}
+
+fn parse_subtitle_language(title: &str) -> Vec<String> {
+    let mut language: Vec<String> = Vec::new();
+    for (re, lang) in [
+        (&RE_CHS, "CHS"),
+        (&RE_CHT, "CHT"),
+        (&RE_JPN, "JPN"),
+        (&RE_ENG, "ENG"),
+    ] {
+        if re.is_match(title) {
+            language.push(lang.to_string());
+        }
+    }
+    language
+}
+
+// token surrounded by non-alphanumeric characters
+fn has_token(upper: &str, token: &str) -> bool {
+    upper.match_indices(token).any(|(idx, _)| {
+        let before = upper[..idx].chars().last();
+        let after = upper[idx + token.len()..].chars().next();
+        !before.is_some_and(|c| c.is_ascii_alphanumeric())
+            && !after.is_some_and(|c| c.is_ascii_alphanumeric())
+    })
+}
+
+fn normalize(title: &str) -> String {
+    title
+        .chars()
+        .map(|c| match c {
+            '０'..='９' => char::from_u32(c as u32 - '０' as u32 + '0' as u32).unwrap(),
+            '【' | '［' => '[',
+            '】' | '］' => ']',
+            '（' => '(',
+            '）' => ')',
+            '～' => '~',
+            _ => c,
+        })
+        .collect()
+}
+
+// "12", "12.5", "十二", "一百零五"
+fn parse_number(s: &str) -> Option<f32> {
+    if let Ok(n) = s.parse::<f32>() {
+        return Some(n);
+    }
+    let mut total = 0;
+    let mut number = 0;
+    for c in s.chars() {
+        match c {
+            '零' | '〇' => number = 0,
+            '一' => number = 1,
+            '二' | '两' => number = 2,
+            '三' => number = 3,
+            '四' => number = 4,
+            '五' => number = 5,
+            '六' => number = 6,
+            '七' => number = 7,
+            '八' => number = 8,
+            '九' => number = 9,
+            '十' | '百' | '千' => {
+                let unit = match c {
+                    '十' => 10,
+                    '百' => 100,
+                    _ => 1000,
+                };
+                total += if number == 0 { 1 } else { number } * unit;
+                number = 0;
+            }
+            _ => return None,
+        }
+    }
+    Some((total + number) as f32)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // (title, episode, episode_end, version, season)
+    type EpisodeCase = (&'static str, Option<f32>, Option<f32>, i32, Option<i32>);
+
+    const EPISODE_CASES: &[EpisodeCase] = &[
+        ("【喵萌奶茶屋】★10月新番★[米基与达利 / Migi to Dali][04][1080p][简日双语][招募翻译]", Some(4.0), None, 1, None),
+        ("[ANi] 米基與達利 - 04 [1080P][Baha][WEB-DL][AAC AVC][CHT].mp4", Some(4.0), None, 1, None),
+        ("[Nekomoe kissaten&LoliHouse] Migi to Dali - 04 [WebRip 1080p HEVC-10bit AAC ASSx2].mkv", Some(4.0), None, 1, None),
+        ("[LoliHouse] Sousou no Frieren - 05 [WebRip 1080p HEVC-10bit AAC][简繁内封字幕]", Some(5.0), None, 1, None),
+        ("[桜都字幕组] 葬送的芙莉莲 / Sousou no Frieren [05][1080p][简繁内封]", Some(5.0), None, 1, None),
+        ("[北宇治字幕组] 葬送的芙莉莲 / Sousou no Frieren [05][WebRip][1080p][HEVC_AAC][简繁日内封]", Some(5.0), None, 1, None),
+        ("[SweetSub&LoliHouse] 药屋少女的呢喃 / Kusuriya no Hitorigoto - 05v2 [WebRip 1080p HEVC-10bit AAC][简繁日内封字幕]", Some(5.0), None, 2, None),
+        ("[SubsPlease] Jujutsu Kaisen - 28 (1080p) [E0A1B2C3].mkv", Some(28.0), None, 1, None),
+        ("[Erai-raws] Jujutsu Kaisen 2nd Season - 05 [1080p][Multiple Subtitle][ENG]", Some(5.0), None, 1, Some(2)),
+        ("[Sakurato] Spy x Family Season 2 [05][AVC-8bit 1080p AAC][CHS]", Some(5.0), None, 1, Some(2)),
+        ("[Sakurato] Jujutsu Kaisen 2nd Season [28][AVC-8bit 1080p AAC][CHT]", Some(28.0), None, 1, Some(2)),
+        ("[GM-Team][国漫][斗破苍穹 年番][Battle Through the Heavens][2022][75][AVC][GB][1080P]", Some(75.0), None, 1, None),
+        ("[ANi] Shangri-La Frontier - 12.5 [1080P][Baha][WEB-DL][AAC AVC][CHT].mp4", Some(12.5), None, 1, None),
+        ("[喵萌奶茶屋&LoliHouse] 间谍过家家 / SPY×FAMILY - 37 [WebRip 1080p HEVC-10bit AAC][简繁日内封字幕]", Some(37.0), None, 1, None),
+        ("[Up to 21°C] 迷宫饭 / Dungeon Meshi - 03 (CR 1920x1080 AVC AAC MKV)", Some(3.0), None, 1, None),
+        ("[千夏字幕组][葬送的芙莉莲_Sousou no Frieren][第12话][1080p_AVC][简繁内封]", Some(12.0), None, 1, None),
+        ("【幻樱字幕组】【10月新番】【葬送的芙莉莲 Sousou no Frieren】【05】【BIG5_MP4】【1920X1080】", Some(5.0), None, 1, None),
+        ("[云光字幕组] 葬送的芙莉莲 Sousou no Frieren [第十二话][简体双语][1080p]招募翻译", Some(12.0), None, 1, None),
+        ("[爱恋字幕社] 葬送的芙莉莲 第二十五話 [1080p][MP4][简中]", Some(25.0), None, 1, None),
+        ("[ANi] Oshi no Ko S2 - 03 [1080P][Baha][WEB-DL][AAC AVC][CHT].mp4", Some(3.0), None, 1, Some(2)),
+        ("[Nekomoe kissaten][Shikanoko Nokonoko Koshitantan][S01E05][1080p][JPTC].mp4", Some(5.0), None, 1, Some(1)),
+        ("[ANi] ONE PIECE 海賊王 - 1089 [1080P][Baha][WEB-DL][AAC AVC][CHT].mp4", Some(1089.0), None, 1, None),
+        ("[Moozzi2] Frieren - EP05v2 (BD 1920x1080 x.264 Flac)", Some(5.0), None, 2, None),
+        ("[LoliHouse] Frieren [01-28 Fin][WebRip 1080p HEVC-10bit AAC][简繁内封字幕]", Some(1.0), Some(28.0), 1, None),
+        ("[DBD-Raws][葬送的芙莉莲/Sousou no Frieren][01-28TV全集+特典映像][1080P][BDRip][HEVC-10bit][简繁日双语外挂][FLAC][MKV]", Some(1.0), Some(28.0), 1, None),
+        ("[Airota][Bocchi the Rock!][01-12][1080p BDRip HEVC-10bit][CHS]", Some(1.0), Some(12.0), 1, None),
+        ("【极影字幕社】★4月新番 [我独自升级][Solo Leveling][13-14][GB][1080P][MP4]", Some(13.0), Some(14.0), 1, None),
+        ("[ANi] 16bit 的感动 ANOTHER LAYER - 05 [1080P][Baha][WEB-DL][AAC AVC][CHT].mp4", Some(5.0), None, 1, None),
+        ("[猎户发布组] 86 不存在的战区 86 EIGHTY-SIX [13][1080p]", Some(13.0), None, 1, None),
+        ("[Lilith-Raws] 86 - 03 [Baha][WEB-DL][1080p][AVC AAC][CHT][MP4]", Some(3.0), None, 1, None),
+        ("[喵萌Production&LoliHouse] 2.5次元的诱惑 / 2.5-jigen no Ririsa - 05 [WebRip 1080p HEVC-10bit AAC][简繁日内封字幕]", Some(5.0), None, 1, None),
+        ("[Lilith-Raws] Kage no Jitsuryokusha ni Naritakute! S02 - 05 [Baha][WebDL 1080p AVC AAC][CHT][MP4]", Some(5.0), None, 1, Some(2)),
+        ("葬送的芙莉莲 第二季 第05集 [1080P]", Some(5.0), None, 1, Some(2)),
+        ("[SubsPlease] Kusuriya no Hitorigoto - 24 (1080p) [END]", Some(24.0), None, 1, None),
+        ("[Skymoon-Raws] 物语系列 / Monogatari Series: Off & Monster Season - 01 [ViuTV][WEB-DL][CHT][1080p][AVC AAC]", Some(1.0), None, 1, None),
+        ("[ANi] Mob Psycho 100 III - 12 [1080P][Baha][WEB-DL][AAC AVC][CHT].mp4", Some(12.0), None, 1, None),
+        ("[Lilith-Raws] Jujutsu Kaisen 0 [BD 1080p AVC AAC][CHT]", None, None, 1, None),
+        ("[VCB-Studio] Sousou no Frieren [Ma10p_1080p]", None, None, 1, None),
+        ("[喵萌奶茶屋] 葬送的芙莉莲 [12 END][1080p][简日双语]", Some(12.0), None, 1, None),
+        ("[HYSUB]Sousou no Frieren[05][GB_MP4][1280X720]", Some(5.0), None, 1, None),
+        ("[ANi] Sousou no Frieren - 05 [1080P][Baha][WEB-DL][AAC AVC][CHT].mp4", Some(5.0), None, 1, None),
+        ("[动漫国字幕组&LoliHouse] THE MARGINAL SERVICE - 08 [WebRip 1080p HEVC-10bit AAC][简繁内封字幕]", Some(8.0), None, 1, None),
+        ("[織夢字幕組][葬送的芙莉蓮 Sousou no Frieren][第１２集][1080P][AVC][繁日雙語]", Some(12.0), None, 1, None),
+        ("[ANi] Tsuki ga Michibiku Isekai Douchuu 第二幕 - 05 [1080P][Baha][WEB-DL][AAC AVC][CHT].mp4", Some(5.0), None, 1, None),
+        ("[Lilith-Raws] Detective Conan - 1100 [Baha][WEB-DL][1080p][AVC AAC][CHT][MP4]", Some(1100.0), None, 1, None),
+        ("[SubsPlease] Dr. Stone S3 - 11v2 (1080p) [4A0E6C0F].mkv", Some(11.0), None, 2, Some(3)),
+        ("[Erai-raws] Frieren - 01 ~ 28 [1080p][Multiple Subtitle]", Some(1.0), Some(28.0), 1, None),
+        ("[xyx98] 葬送的芙莉莲 第1-28话 [BDRip 1080p HEVC][简繁日]", Some(1.0), Some(28.0), 1, None),
+    ];
+
+    #[test]
+    fn test_parse_episode() {
+        for (title, episode, episode_end, version, season) in EPISODE_CASES {
+            let parsed = parse_title(title);
+            assert_eq!(parsed.episode, *episode, "episode of {}", title);
+            assert_eq!(parsed.episode_end, *episode_end, "episode_end of {}", title);
+            assert_eq!(parsed.version, *version, "version of {}", title);
+            assert_eq!(parsed.season, *season, "season of {}", title);
+        }
+    }
+
+    // (title, resolution, source, codec, subtitle_language)
+    type MediaCase = (
+        &'static str,
+        Option<&'static str>,
+        Option<&'static str>,
+        Option<&'static str>,
+        &'static [&'static str],
+    );
+
+    const MEDIA_CASES: &[MediaCase] = &[
+        ("[ANi] 米基與達利 - 04 [1080P][Baha][WEB-DL][AAC AVC][CHT].mp4", Some("1080p"), Some("WEB-DL"), Some("AVC"), &["CHT"]),
+        ("[LoliHouse] Sousou no Frieren - 05 [WebRip 1080p HEVC-10bit AAC][简繁内封字幕]", Some("1080p"), Some("WebRip"), Some("HEVC"), &["CHS", "CHT"]),
+        ("【喵萌奶茶屋】★10月新番★[米基与达利 / Migi to Dali][04][1080p][简日双语][招募翻译]", Some("1080p"), None, None, &["CHS", "JPN"]),
+        ("[北宇治字幕组] 葬送的芙莉莲 / Sousou no Frieren [05][WebRip][1080p][HEVC_AAC][简繁日内封]", Some("1080p"), Some("WebRip"), Some("HEVC"), &["CHS", "CHT", "JPN"]),
+        ("[Up to 21°C] 迷宫饭 / Dungeon Meshi - 03 (CR 1920x1080 AVC AAC MKV)", Some("1080p"), Some("WEB-DL"), Some("AVC"), &[]),
+        ("[DBD-Raws][葬送的芙莉莲/Sousou no Frieren][01-28TV全集+特典映像][1080P][BDRip][HEVC-10bit][简繁日双语外挂][FLAC][MKV]", Some("1080p"), Some("BDRip"), Some("HEVC"), &["CHS", "CHT", "JPN"]),
+        ("[Moozzi2] Frieren - EP05v2 (BD 1920x1080 x.264 Flac)", Some("1080p"), Some("BD"), Some("AVC"), &[]),
+        ("[HYSUB]Sousou no Frieren[05][GB_MP4][1280X720]", Some("720p"), None, None, &["CHS"]),
+        ("【幻樱字幕组】【10月新番】【葬送的芙莉莲 Sousou no Frieren】【05】【BIG5_MP4】【1920X1080】", Some("1080p"), None, None, &["CHT"]),
+        ("[Nekomoe kissaten][Shikanoko Nokonoko Koshitantan][S01E05][1080p][JPTC].mp4", Some("1080p"), None, None, &["CHT", "JPN"]),
+        ("[Erai-raws] Jujutsu Kaisen 2nd Season - 05 [1080p][Multiple Subtitle][ENG]", Some("1080p"), None, None, &["ENG"]),
+        ("[SubsPlease] Jujutsu Kaisen - 28 (720p) [E0A1B2C3].mkv", Some("720p"), None, None, &[]),
+        ("[Kawaiika-Raws] Frieren 05 [BDRip 3840x2160 HEVC AV1]", Some("2160p"), Some("BDRip"), Some("HEVC"), &[]),
+        ("[Breeze] Frieren - 05 [4K AV1 Opus]", Some("2160p"), None, Some("AV1"), &[]),
+    ];
+
+    #[test]
+    fn test_parse_media_info() {
+        for (title, resolution, source, codec, subtitle_language) in MEDIA_CASES {
+            let parsed = parse_title(title);
+            assert_eq!(
+                parsed.resolution.as_deref(),
+                *resolution,
+                "resolution of {}",
+                title
+            );
+            assert_eq!(parsed.source.as_deref(), *source, "source of {}", title);
+            assert_eq!(parsed.codec.as_deref(), *codec, "codec of {}", title);
+            assert_eq!(
+                parsed.subtitle_language,
+                subtitle_language.to_vec(),
+                "subtitle_language of {}",
+                title
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_container() {
+        let parsed =
+            parse_title("[LoliHouse] Frieren - 05 [WebRip 1080p HEVC-10bit AAC][简繁内封字幕]");
+        assert!(parsed.subtitle_embedded);
+        assert_eq!(parsed.container, None);
+        assert_eq!(parsed.resolution_height(), Some(1080));
+
+        let parsed = parse_title("[HYSUB]Sousou no Frieren[05][GB_MP4][1280X720]");
+        assert!(!parsed.subtitle_embedded);
+        assert_eq!(parsed.container.as_deref(), Some("MP4"));
+        assert_eq!(parsed.resolution_height(), Some(720));
+
+        let parsed = parse_title("[SubsPlease] Jujutsu Kaisen - 28 (720p) [E0A1B2C3].mkv");
+        assert_eq!(parsed.container.as_deref(), Some("MKV"));
+    }
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("十"), Some(10.0));
+        assert_eq!(parse_number("十二"), Some(12.0));
+        assert_eq!(parse_number("二十"), Some(20.0));
+        assert_eq!(parse_number("二十五"), Some(25.0));
+        assert_eq!(parse_number("一百零五"), Some(105.0));
+        assert_eq!(parse_number("12.5"), Some(12.5));
+        assert_eq!(parse_number("第"), None);
+    }
+
+    #[test]
+    fn test_episode_number() {
+        assert_eq!(
+            parse_title("[ANi] Frieren - 05 [1080P]").episode_number(),
+            Some(5)
+        );
+        assert_eq!(
+            parse_title("[ANi] Frieren - 12.5 [1080P]").episode_number(),
+            None
+        );
+        assert_eq!(
+            parse_title("[LoliHouse] Frieren [01-28 Fin]").episode_number(),
+            None
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use crate::error::error::AnimeError;
+use crate::models::anime_seed::AnimeSeed;
+use crate::mods::aria2_api::Aria2Client;
+use crate::mods::bencode::{self, TorrentMeta};
+use crate::mods::config::{Config, SpeedLimit};
+use crate::mods::path_mapping::PathMapper;
+use crate::mods::qb_api::QbitTaskExecutor;
+use crate::mods::spider;
+use crate::mods::transmission_api::TransmissionClient;
+use async_trait::async_trait;
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::time::{Duration, UNIX_EPOCH};
+
+pub const DOWNLOADER_QBITTORRENT: &str = "qbittorrent";
+pub const DOWNLOADER_TRANSMISSION: &str = "transmission";
+pub const DOWNLOADER_ARIA2: &str = "aria2";
+
+/*
+    The torrent client which downloads the seeds, chosen by config.downloader.
+    A torrent is addressed by the v1 info hash parsed from its seed file,
+    see read_torrent_meta.
+*/
+#[async_trait]
+pub trait TorrentClient: Send + Sync {
+    // the health of the connection, kept up to date by reconnect
+    fn is_login(&self) -> bool;
+
+    // check the connection and log in again if it was lost
+    async fn reconnect(&self) -> Result<(), AnimeError>;
+
+    // speed_limit of the anime, config.speed_limit applies where it is 0
+    async fn add_torrent(
+        &self,
+        anime_name: &str,
+        anime_seed_info: &AnimeSeed,
+        speed_limit: SpeedLimit,
+    ) -> Result<(), AnimeError>;
+
+    async fn torrent_info(&self, info_hash: &str) -> Result<TorrentInfo, AnimeError>;
+
+    // files of a torrent, name is the path relative to the save path of the torrent
+    async fn torrent_files(&self, info_hash: &str) -> Result<Vec<TorrentFile>, AnimeError>;
+
+    // the downloaded files are kept unless delete_files
+    async fn del_torrent(&self, info_hash: &str, delete_files: bool) -> Result<(), AnimeError>;
+
+    // move a file of the torrent, both paths are relative to the save path of the torrent,
+    // the torrent keeps seeding from the new path
+    async fn rename_file(
+        &self,
+        info_hash: &str,
+        old_path: &str,
+        new_path: &str,
+    ) -> Result<(), AnimeError>;
+
+    async fn pause_torrent(&self, info_hash: &str) -> Result<(), AnimeError>;
+
+    async fn resume_torrent(&self, info_hash: &str) -> Result<(), AnimeError>;
+
+    // info hash of every finished torrent
+    async fn completed_torrent_set(&self) -> Result<HashSet<String>, AnimeError>;
+
+    // info hash -> upload ratio and seeding time of every torrent
+    async fn seeding_stats(&self) -> Result<HashMap<String, SeedingStats>, AnimeError>;
+}
+
+// the client is returned even if it can not log in, check is_login,
+// an invalid path_mapping is an error
+pub async fn new_torrent_client(config: &Config) -> Result<Box<dyn TorrentClient>, AnimeError> {
+    PathMapper::new(config.path_mapping_rules()).validate(&config.download_path)?;
+    match config.downloader.kind.as_str() {
+        "" | DOWNLOADER_QBITTORRENT => {
+            Ok(Box::new(QbitTaskExecutor::new_with_config(config).await?))
+        }
+        DOWNLOADER_TRANSMISSION => Ok(Box::new(TransmissionClient::new_with_config(config).await)),
+        DOWNLOADER_ARIA2 => Ok(Box::new(Aria2Client::new_with_config(config).await)),
+        kind => Err(AnimeError::BadRequest(format!(
+            "unknown downloader: {}",
+            kind
+        ))),
+    }
+}
+
+// the seed file saved by do_anime_task::download_seed_handler
+pub fn read_seed_file(anime_seed_info: &AnimeSeed) -> Result<(String, Vec<u8>), AnimeError> {
+    let file_name = spider::get_torrent_name(&anime_seed_info.seed_url);
+    let seed_path = format!("downloads/seed/{}/{}", anime_seed_info.mikan_id, file_name);
+    let file_byte = std::fs::read(&seed_path)
+        .map_err(|e| AnimeError::from(e).context(&format!("Failed to read {}", seed_path)))?;
+    Ok((file_name, file_byte))
+}
+
+pub fn read_torrent_meta(anime_seed_info: &AnimeSeed) -> Result<TorrentMeta, AnimeError> {
+    let (file_name, file_byte) = read_seed_file(anime_seed_info)?;
+    bencode::parse_torrent(&file_byte).map_err(|e| e.context(&file_name))
+}
+
+// {download_path}/{anime_name}({mikan_id}), where autoAnime finds the files of an anime
+pub fn get_anime_dir(download_path: &str, anime_name: &str, mikan_id: i32) -> String {
+    format!("{}/{}({})", download_path, anime_name, mikan_id)
+}
+
+// the anime dir as the torrent client sees it, {exec_path}/{anime_dir} without path_mapping
+pub fn get_save_path(
+    path_mapper: &PathMapper,
+    download_path: &str,
+    anime_name: &str,
+    mikan_id: i32,
+) -> Result<String, AnimeError> {
+    let exec_path =
+        env::current_dir().map_err(|e| AnimeError::from(e).context("Failed to get current dir"))?;
+    let anime_dir = exec_path.join(get_anime_dir(download_path, anime_name, mikan_id));
+    Ok(path_mapper.to_remote(&anime_dir.to_string_lossy()))
+}
+
+// mikan_3143, episode_5 or episode_1-12 for a batch seed
+pub fn get_torrent_tags(anime_seed_info: &AnimeSeed) -> Vec<String> {
+    let episode = if anime_seed_info.episode_end > anime_seed_info.episode {
+        format!(
+            "episode_{}-{}",
+            anime_seed_info.episode, anime_seed_info.episode_end
+        )
+    } else {
+        format!("episode_{}", anime_seed_info.episode)
+    };
+    vec![format!("mikan_{}", anime_seed_info.mikan_id), episode]
+}
+
+#[derive(Default, Debug, Deserialize, Serialize)]
+pub struct TorrentInfo {
+    pub name: String,
+    pub size: String,
+    pub done: String,           // progress
+    pub peers: String,          // num_leechs
+    pub seeds: String,          // num_seeds
+    pub download_speed: String, // dlspeed
+    pub eta: String,
+    pub hash: String,
+    pub state: String,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct TorrentFile {
+    #[serde(default)]
+    pub index: i64,
+    pub name: String,
+    pub size: i64,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct SeedingStats {
+    pub ratio: f64,
+    pub seeding_time: i64, // seconds
+}
+
+pub fn format_size(size: i64) -> String {
+    const GB: i64 = 1024 * 1024 * 1024;
+    const MB: i64 = 1024 * 1024;
+    if size >= GB {
+        format!("{:.2} GB", size as f64 / GB as f64)
+    } else {
+        format!("{:.2} MB", size as f64 / MB as f64)
+    }
+}
+
+pub fn format_speed(speed: i64) -> String {
+    const KILOBIT: i64 = 1000;
+    const MEGABIT: i64 = 1000000;
+    if speed > MEGABIT {
+        format!("{:.2} Mbps", speed as f64 / MEGABIT as f64)
+    } else {
+        format!("{:.2} Kbps", speed as f64 / KILOBIT as f64)
+    }
+}
+
+pub fn format_progress(progress: f64) -> String {
+    format!("{:.2} %", progress * 100.0)
+}
+
+pub fn format_eta(eta: i64) -> String {
+    let d = UNIX_EPOCH + Duration::from_secs(eta.max(0) as u64);
+    let datetime = DateTime::<chrono::Utc>::from(d);
+    datetime.format("%H:%M:%S").to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::models::enums::SeedStatus;
+
+    #[test]
+    fn test_get_torrent_tags() {
+        let mut anime_seed = AnimeSeed {
+            id: None,
+            mikan_id: 3143,
+            subgroup_id: 370,
+            episode: 5,
+            seed_name: "[LoliHouse] Sousou no Frieren - 05".to_string(),
+            seed_url: "/Download/20231006/8f1c6d4b2a3e5f7081920a1b2c3d4e5f60718293.torrent"
+                .to_string(),
+            seed_status: SeedStatus::Unused,
+            seed_size: "500MB".to_string(),
+            publish_date: "2023-10-06 17:01:12".to_string(),
+            episode_end: -1,
+            info_hash: "".to_string(),
+            total_size: 0,
+            file_list: "[]".to_string(),
+            is_special: false,
+        };
+        assert_eq!(get_torrent_tags(&anime_seed), ["mikan_3143", "episode_5"]);
+
+        anime_seed.episode = 1;
+        anime_seed.episode_end = 12;
+        assert_eq!(
+            get_torrent_tags(&anime_seed),
+            ["mikan_3143", "episode_1-12"]
+        );
+    }
+}
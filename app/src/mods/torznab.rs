@@ -0,0 +1,253 @@
+use crate::models::anime_list::AnimeList;
+use crate::models::enums::AnimeType;
+use crate::mods::config::{ScraperConfig, TorznabConfig};
+use crate::mods::http_client::HttpClient;
+use crate::mods::seed_source::SeedSource;
+use crate::mods::spider::{self, Anime, Seed, Subgroup};
+use crate::mods::{magnet, rss_parser};
+use async_trait::async_trait;
+use regex::Regex;
+use reqwest::Url;
+use std::collections::HashMap;
+use std::error::Error;
+
+// Ids of anime and subgroups from torznab indexers are hashed from their names into
+// [TORZNAB_ID_BASE, i32::MAX) so they never collide with the ids used by mikan.
+pub const TORZNAB_ID_BASE: i32 = 1_000_000_000;
+
+/*
+    Generic torznab / nyaa style rss indexer (jackett, prowlarr, nyaa.si ...).
+    The anime_name of an anime_list row is used as the search keyword, and the
+    subgroup is taken from the leading "[...]" of each release title.
+*/
+#[derive(Debug, Clone)]
+pub struct Torznab {
+    client: HttpClient,
+    url: String,
+    apikey: String,
+    category: String,
+}
+
+impl Torznab {
+    pub fn new(
+        config: &TorznabConfig,
+        scraper_config: &ScraperConfig,
+    ) -> Result<Torznab, Box<dyn Error>> {
+        Ok(Torznab {
+            client: HttpClient::new(scraper_config)?,
+            url: config.url.clone(),
+            apikey: config.apikey.clone(),
+            category: config.category.clone(),
+        })
+    }
+
+    async fn search(&self, keyword: &str) -> Result<String, Box<dyn Error>> {
+        let mut query = vec![("t", "search"), ("q", keyword)];
+        if !self.apikey.is_empty() {
+            query.push(("apikey", &self.apikey));
+        }
+        if !self.category.is_empty() {
+            query.push(("cat", &self.category));
+        }
+        let url = Url::parse_with_params(&self.url, &query)?;
+        self.client.get_text(url.as_str()).await
+    }
+}
+
+#[async_trait]
+impl SeedSource for Torznab {
+    // indexers have no season listing, torznab anime are added by name through the api
+    async fn list_season(&self, _year: i32, _season: i32) -> Result<Vec<Anime>, Box<dyn Error>> {
+        Ok(Vec::new())
+    }
+
+    async fn list_subgroups(&self, anime: &AnimeList) -> Result<Vec<Subgroup>, Box<dyn Error>> {
+        let body = self.search(&anime.anime_name).await?;
+        parse_torznab_subgroup(&body)
+    }
+
+    async fn list_seeds(
+        &self,
+        anime: &AnimeList,
+        subgroup_id: i32,
+    ) -> Result<Vec<Seed>, Box<dyn Error>> {
+        self.list_anime_seeds(anime, &[subgroup_id]).await
+    }
+
+    // every subgroup is in the results of the same search, it is requested once
+    async fn list_anime_seeds(
+        &self,
+        anime: &AnimeList,
+        subgroup_id_vec: &[i32],
+    ) -> Result<Vec<Seed>, Box<dyn Error>> {
+        let body = self.search(&anime.anime_name).await?;
+        let seed_list = parse_torznab_seed(&body, anime.mikan_id, anime.anime_type)?;
+        Ok(seed_list
+            .into_iter()
+            .filter(|s| subgroup_id_vec.contains(&s.subgroup_id))
+            .collect())
+    }
+}
+
+pub fn anime_id(anime_name: &str) -> i32 {
+    stable_id(anime_name.trim())
+}
+
+pub fn subgroup_id(subgroup_name: &str) -> i32 {
+    stable_id(subgroup_name.trim())
+}
+
+// fnv-1a, std's DefaultHasher is not guaranteed to be stable between releases
+fn stable_id(name: &str) -> i32 {
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in name.to_lowercase().bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    TORZNAB_ID_BASE + (hash % (i32::MAX - TORZNAB_ID_BASE) as u32) as i32
+}
+
+fn get_subgroup_name(title: &str) -> String {
+    let re = Regex::new(r"^\s*[\[【]([^\]】]+)[\]】]").unwrap();
+    match re.captures(title) {
+        Some(captures) => captures.get(1).unwrap().as_str().trim().to_string(),
+        None => "unknown".to_string(),
+    }
+}
+
+pub fn parse_torznab_subgroup(xml: &str) -> Result<Vec<Subgroup>, Box<dyn Error>> {
+    let mut subgroup_map: HashMap<i32, String> = HashMap::new();
+    for item in rss_parser::parse_rss_items(xml)? {
+        let subgroup_name = get_subgroup_name(&item.title);
+        subgroup_map
+            .entry(subgroup_id(&subgroup_name))
+            .or_insert(subgroup_name);
+    }
+    Ok(subgroup_map
+        .into_iter()
+        .map(|(subgroup_id, subgroup_name)| Subgroup {
+            subgroup_id,
+            subgroup_name,
+        })
+        .collect())
+}
+
+pub fn parse_torznab_seed(
+    xml: &str,
+    mikan_id: i32,
+    anime_type: AnimeType,
+) -> Result<Vec<Seed>, Box<dyn Error>> {
+    let mut seed_list: Vec<Seed> = Vec::new();
+    for item in rss_parser::parse_rss_items(xml)? {
+        let download_url = if item.enclosure_url.is_empty() {
+            item.link.clone()
+        } else {
+            item.enclosure_url.clone()
+        };
+
+        // a magnet needs no .torrent download, some indexers only publish magnets
+        let magnet_url = [Some(&download_url), item.extensions.get("attr/magneturl")]
+            .into_iter()
+            .flatten()
+            .find_map(|url| magnet::normalize(url));
+
+        // the .torrent is tracked by "{info_hash}.torrent", a release without one falls back to its magnet
+        let seed_url = match item
+            .extensions
+            .get("attr/infohash")
+            .or(item.extensions.get("infoHash"))
+        {
+            Some(info_hash) if !download_url.is_empty() && !magnet::is_magnet(&download_url) => {
+                format!("{}#{}.torrent", download_url, info_hash.to_lowercase())
+            }
+            _ => match magnet_url {
+                Some(magnet_url) => magnet_url,
+                None => {
+                    log::debug!("torznab item without infohash: {}", item.title);
+                    continue;
+                }
+            },
+        };
+
+        let seed_size = spider::format_seed_size(
+            item.extensions
+                .get("attr/size")
+                .and_then(|l| l.parse::<i64>().ok())
+                .unwrap_or(item.enclosure_length),
+        );
+
+        if let Some(seed) = spider::build_seed(
+            mikan_id,
+            subgroup_id(&get_subgroup_name(&item.title)),
+            anime_type,
+            item.title.trim().to_string(),
+            seed_url,
+            seed_size,
+            spider::normalize_publish_date(&item.pub_date),
+        ) {
+            seed_list.push(seed);
+        }
+    }
+    Ok(seed_list)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_torznab_seed() {
+        let xml = include_str!("../../tests/fixtures/torznab_search.xml");
+        let seed_list =
+            parse_torznab_seed(xml, anime_id("Sousou no Frieren"), AnimeType::Tv).unwrap();
+
+        // the item without infohash is dropped, the magnet only item is kept
+        assert_eq!(seed_list.len(), 4);
+        assert_eq!(seed_list[0].episode, 5);
+        assert_eq!(seed_list[0].subgroup_id, subgroup_id("SubsPlease"));
+        assert_eq!(
+            seed_list[0].seed_url,
+            "https://nyaa.si/download/1730001.torrent#8f1c6d4b2a3e5f7081920a1b2c3d4e5f60718293.torrent"
+        );
+        assert_eq!(
+            spider::get_torrent_name(&seed_list[0].seed_url),
+            "8f1c6d4b2a3e5f7081920a1b2c3d4e5f60718293.torrent"
+        );
+        assert_eq!(seed_list[0].seed_size, "1.4GB");
+        assert_eq!(seed_list[0].publish_date, "2023-10-06 17:01:12");
+        assert_eq!(seed_list[1].subgroup_id, subgroup_id("SubsPlease"));
+        assert_eq!(seed_list[2].subgroup_id, subgroup_id("Erai-raws"));
+        assert_eq!(
+            seed_list[3].seed_url,
+            "magnet:?xt=urn:btih:bdd2f547cdfd8a38011a5ea451d65379c9572305&dn=Frieren"
+        );
+        assert_eq!(
+            spider::get_torrent_name(&seed_list[3].seed_url),
+            "bdd2f547cdfd8a38011a5ea451d65379c9572305"
+        );
+    }
+
+    #[test]
+    fn test_parse_torznab_subgroup() {
+        let xml = include_str!("../../tests/fixtures/torznab_search.xml");
+        let mut subgroup_list = parse_torznab_subgroup(xml).unwrap();
+        subgroup_list.sort_by(|a, b| a.subgroup_name.cmp(&b.subgroup_name));
+        let names: Vec<&str> = subgroup_list
+            .iter()
+            .map(|s| s.subgroup_name.as_str())
+            .collect();
+        assert_eq!(names, vec!["ANi", "ASW", "Erai-raws", "SubsPlease"]);
+        assert!(subgroup_list
+            .iter()
+            .all(|s| s.subgroup_id >= TORZNAB_ID_BASE));
+    }
+
+    #[test]
+    fn test_stable_id() {
+        assert_eq!(
+            anime_id("Sousou no Frieren"),
+            anime_id(" sousou no frieren ")
+        );
+        assert_ne!(anime_id("Sousou no Frieren"), anime_id("Frieren"));
+    }
+}
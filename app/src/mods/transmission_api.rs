@@ -0,0 +1,492 @@
+use crate::error::error::{handle_error, AnimeError};
+use crate::models::anime_seed::AnimeSeed;
+use crate::mods::config::{Config, SpeedLimit};
+use crate::mods::magnet;
+use crate::mods::path_mapping::PathMapper;
+use crate::mods::torrent_client::{
+    format_eta, format_progress, format_size, format_speed, get_save_path, read_seed_file,
+    SeedingStats, TorrentClient, TorrentFile, TorrentInfo,
+};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+const SESSION_ID_HEADER: &str = "X-Transmission-Session-Id";
+
+// tr_torrent_activity of the transmission rpc spec
+const STATUS_NAMES: [&str; 7] = [
+    "stopped",
+    "checkWait",
+    "checking",
+    "downloadWait",
+    "downloading",
+    "seedWait",
+    "seeding",
+];
+
+/*
+    Transmission rpc client, see
+    https://github.com/transmission/transmission/blob/main/docs/rpc-spec.md
+    Every request carries the session id which transmission hands out with a
+    409 response, it is refreshed whenever transmission answers 409 again.
+*/
+#[derive(Debug)]
+pub struct TransmissionClient {
+    is_login: AtomicBool,
+    client: reqwest::Client,
+    url: String,
+    username: String,
+    password: String,
+    session_id: RwLock<String>,
+    download_path: String,
+    speed_limit: SpeedLimit,
+    path_mapper: PathMapper,
+}
+
+impl TransmissionClient {
+    pub async fn new_with_config(config: &Config) -> Self {
+        let client = Self {
+            is_login: AtomicBool::new(false),
+            client: reqwest::Client::new(),
+            url: config.downloader.url.clone(),
+            username: config.downloader.username.clone(),
+            password: config.downloader.password.clone(),
+            session_id: RwLock::new(String::new()),
+            download_path: config.download_path.clone(),
+            speed_limit: config.speed_limit,
+            path_mapper: PathMapper::new(config.path_mapping_rules()),
+        };
+        if let Err(e) = client.login().await {
+            log::error!("[TR API] Login error, {}", e);
+        }
+        client
+    }
+
+    async fn login(&self) -> Result<(), AnimeError> {
+        self.rpc("session-get", json!({})).await?;
+        if !self.is_login.swap(true, Ordering::SeqCst) {
+            log::info!("[TR API] Successfully Connect to transmission rpc");
+        }
+        Ok(())
+    }
+
+    async fn rpc(&self, method: &str, arguments: Value) -> Result<Value, AnimeError> {
+        let body = json!({ "method": method, "arguments": arguments });
+
+        // the first 409 only hands out the session id
+        for _ in 0..2 {
+            let session_id = self.session_id.read().unwrap().clone();
+            let mut request = self
+                .client
+                .post(&self.url)
+                .header(SESSION_ID_HEADER, session_id)
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.to_string());
+            if !self.username.is_empty() {
+                request = request.basic_auth(&self.username, Some(&self.password));
+            }
+            let resp = request.send().await.map_err(|e| {
+                self.is_login.store(false, Ordering::SeqCst);
+                handle_error(
+                    AnimeError::Downloader(format!("{:?}", e)),
+                    "[TR API] Unable to access transmission rpc",
+                )
+            })?;
+
+            if resp.status() == StatusCode::CONFLICT {
+                if let Some(session_id) = resp
+                    .headers()
+                    .get(SESSION_ID_HEADER)
+                    .and_then(|v| v.to_str().ok())
+                {
+                    *self.session_id.write().unwrap() = session_id.to_string();
+                }
+                continue;
+            }
+            if !resp.status().is_success() {
+                return Err(AnimeError::Downloader(format!(
+                    "[TR API] {} failed, response status is {}",
+                    method,
+                    resp.status()
+                )));
+            }
+
+            let resp_text = resp.text().await.map_err(|e| {
+                handle_error(
+                    AnimeError::Downloader(format!("{:?}", e)),
+                    "[TR API] Failed to read rpc response",
+                )
+            })?;
+            let json: Value = serde_json::from_str(&resp_text).map_err(|e| {
+                handle_error(
+                    AnimeError::Downloader(format!("{:?}", e)),
+                    &format!("Failed to serialize {:?}", resp_text),
+                )
+            })?;
+            return parse_response(method, json);
+        }
+        Err(AnimeError::Downloader(
+            "[TR API] Failed to get transmission session id".to_string(),
+        ))
+    }
+
+    async fn ensure_login(&self) -> Result<(), AnimeError> {
+        if self.is_login.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        self.login().await.map_err(|e| {
+            AnimeError::Downloader(format!("[TR API] transmission client not started, {}", e))
+        })
+    }
+
+    async fn get_torrent(&self, info_hash: &str, fields: &[&str]) -> Result<Value, AnimeError> {
+        self.ensure_login().await?;
+        let arguments = self
+            .rpc(
+                "torrent-get",
+                json!({ "ids": [info_hash], "fields": fields }),
+            )
+            .await?;
+        arguments["torrents"].get(0).cloned().ok_or_else(|| {
+            AnimeError::NotFound(format!("[TR API] torrent not found: {}", info_hash))
+        })
+    }
+}
+
+#[async_trait]
+impl TorrentClient for TransmissionClient {
+    fn is_login(&self) -> bool {
+        self.is_login.load(Ordering::SeqCst)
+    }
+
+    async fn reconnect(&self) -> Result<(), AnimeError> {
+        self.login().await
+    }
+
+    async fn add_torrent(
+        &self,
+        anime_name: &str,
+        anime_seed_info: &AnimeSeed,
+        speed_limit: SpeedLimit,
+    ) -> Result<(), AnimeError> {
+        self.ensure_login().await?;
+        let save_path = get_save_path(
+            &self.path_mapper,
+            &self.download_path,
+            anime_name,
+            anime_seed_info.mikan_id,
+        )?;
+
+        // filename takes a magnet as well as an url
+        let arguments = if magnet::is_magnet(&anime_seed_info.seed_url) {
+            json!({ "filename": anime_seed_info.seed_url, "download-dir": save_path })
+        } else {
+            let (_, file_byte) = read_seed_file(anime_seed_info)?;
+            json!({ "metainfo": STANDARD.encode(file_byte), "download-dir": save_path })
+        };
+        let added = self.rpc("torrent-add", arguments).await?;
+
+        // torrent-add takes no limits, they are set on the added torrent
+        let speed_limit = speed_limit.or(self.speed_limit);
+        if speed_limit != SpeedLimit::default() {
+            let info_hash = added["torrent-added"]["hashString"]
+                .as_str()
+                .or(added["torrent-duplicate"]["hashString"].as_str())
+                .unwrap_or_default();
+            self.rpc("torrent-set", speed_limit_arguments(info_hash, speed_limit))
+                .await?;
+        }
+        log::info!("[TR API] Successfully added seeds: {:?}", anime_seed_info);
+        Ok(())
+    }
+
+    async fn torrent_info(&self, info_hash: &str) -> Result<TorrentInfo, AnimeError> {
+        let torrent = self
+            .get_torrent(
+                info_hash,
+                &[
+                    "name",
+                    "totalSize",
+                    "percentDone",
+                    "peersGettingFromUs",
+                    "peersSendingToUs",
+                    "rateDownload",
+                    "eta",
+                    "hashString",
+                    "status",
+                ],
+            )
+            .await?;
+        Ok(torrent_info_from_json(&torrent))
+    }
+
+    async fn torrent_files(&self, info_hash: &str) -> Result<Vec<TorrentFile>, AnimeError> {
+        let torrent = self.get_torrent(info_hash, &["files"]).await?;
+        Ok(torrent_files_from_json(&torrent))
+    }
+
+    async fn del_torrent(&self, info_hash: &str, delete_files: bool) -> Result<(), AnimeError> {
+        self.ensure_login().await?;
+        self.rpc(
+            "torrent-remove",
+            json!({ "ids": [info_hash], "delete-local-data": delete_files }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    // torrent-rename-path only renames the last component, it can not move a file
+    async fn rename_file(
+        &self,
+        info_hash: &str,
+        old_path: &str,
+        new_path: &str,
+    ) -> Result<(), AnimeError> {
+        let (old_path, new_path) = (Path::new(old_path), Path::new(new_path));
+        let new_name = match new_path.file_name() {
+            Some(new_name) if old_path.parent() == new_path.parent() => new_name.to_string_lossy(),
+            _ => {
+                return Err(AnimeError::Downloader(format!(
+                    "[TR API] can not move {:?} to {:?}",
+                    old_path, new_path
+                )))
+            }
+        };
+
+        self.ensure_login().await?;
+        self.rpc(
+            "torrent-rename-path",
+            json!({ "ids": [info_hash], "path": old_path, "name": new_name }),
+        )
+        .await?;
+        log::info!(
+            "[TR API] Successfully rename {:?} to {}",
+            old_path,
+            new_name
+        );
+        Ok(())
+    }
+
+    async fn pause_torrent(&self, info_hash: &str) -> Result<(), AnimeError> {
+        self.ensure_login().await?;
+        self.rpc("torrent-stop", json!({ "ids": [info_hash] }))
+            .await?;
+        log::info!("[TR API] Successfully pause seed: {}", info_hash);
+        Ok(())
+    }
+
+    async fn resume_torrent(&self, info_hash: &str) -> Result<(), AnimeError> {
+        self.ensure_login().await?;
+        self.rpc("torrent-start", json!({ "ids": [info_hash] }))
+            .await?;
+        log::info!("[TR API] Successfully resume seed: {}", info_hash);
+        Ok(())
+    }
+
+    async fn completed_torrent_set(&self) -> Result<HashSet<String>, AnimeError> {
+        self.ensure_login().await?;
+        let arguments = self
+            .rpc(
+                "torrent-get",
+                json!({ "fields": ["hashString", "percentDone"] }),
+            )
+            .await?;
+        Ok(completed_torrent_set_from_json(&arguments))
+    }
+
+    async fn seeding_stats(&self) -> Result<HashMap<String, SeedingStats>, AnimeError> {
+        self.ensure_login().await?;
+        let arguments = self
+            .rpc(
+                "torrent-get",
+                json!({ "fields": ["hashString", "uploadRatio", "secondsSeeding"] }),
+            )
+            .await?;
+        Ok(seeding_stats_from_json(&arguments))
+    }
+}
+
+fn parse_response(method: &str, json: Value) -> Result<Value, AnimeError> {
+    match json["result"].as_str() {
+        Some("success") => Ok(json["arguments"].clone()),
+        result => Err(AnimeError::Downloader(format!(
+            "[TR API] {} failed: {}",
+            method,
+            result.unwrap_or("no result")
+        ))),
+    }
+}
+
+// KiB/s, the same unit as transmission's limits
+fn speed_limit_arguments(info_hash: &str, speed_limit: SpeedLimit) -> Value {
+    json!({
+        "ids": [info_hash],
+        "downloadLimited": speed_limit.download > 0,
+        "downloadLimit": speed_limit.download,
+        "uploadLimited": speed_limit.upload > 0,
+        "uploadLimit": speed_limit.upload,
+    })
+}
+
+fn torrent_info_from_json(torrent: &Value) -> TorrentInfo {
+    let status = torrent["status"].as_u64().unwrap_or_default() as usize;
+    TorrentInfo {
+        name: torrent["name"].as_str().unwrap_or_default().to_owned(),
+        size: format_size(torrent["totalSize"].as_i64().unwrap_or_default()),
+        done: format_progress(torrent["percentDone"].as_f64().unwrap_or_default()),
+        peers: torrent["peersGettingFromUs"]
+            .as_i64()
+            .unwrap_or_default()
+            .to_string(),
+        seeds: torrent["peersSendingToUs"]
+            .as_i64()
+            .unwrap_or_default()
+            .to_string(),
+        download_speed: format_speed(torrent["rateDownload"].as_i64().unwrap_or_default()),
+        eta: format_eta(torrent["eta"].as_i64().unwrap_or_default()),
+        hash: torrent["hashString"]
+            .as_str()
+            .unwrap_or_default()
+            .to_owned(),
+        state: STATUS_NAMES.get(status).unwrap_or(&"unknown").to_string(),
+    }
+}
+
+// transmission names the files relative to its download-dir, like qbittorrent
+fn torrent_files_from_json(torrent: &Value) -> Vec<TorrentFile> {
+    torrent["files"]
+        .as_array()
+        .map(|files| {
+            files
+                .iter()
+                .enumerate()
+                .map(|(index, file)| TorrentFile {
+                    index: index as i64,
+                    name: file["name"].as_str().unwrap_or_default().to_owned(),
+                    size: file["length"].as_i64().unwrap_or_default(),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn completed_torrent_set_from_json(arguments: &Value) -> HashSet<String> {
+    arguments["torrents"]
+        .as_array()
+        .map(|torrents| {
+            torrents
+                .iter()
+                .filter(|t| t["percentDone"].as_f64().unwrap_or_default() >= 1.0)
+                .filter_map(|t| t["hashString"].as_str())
+                .map(|hash| hash.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// transmission reports a negative ratio while nothing has been downloaded
+fn seeding_stats_from_json(arguments: &Value) -> HashMap<String, SeedingStats> {
+    arguments["torrents"]
+        .as_array()
+        .map(|torrents| {
+            torrents
+                .iter()
+                .filter_map(|t| {
+                    let hash = t["hashString"].as_str()?.to_string();
+                    let stats = SeedingStats {
+                        ratio: t["uploadRatio"].as_f64().unwrap_or_default().max(0.0),
+                        seeding_time: t["secondsSeeding"].as_i64().unwrap_or_default(),
+                    };
+                    Some((hash, stats))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_torrent_get() {
+        let json: Value = serde_json::from_str(
+            r#"{
+                "result": "success",
+                "arguments": {
+                    "torrents": [
+                        {
+                            "name": "[LoliHouse] Migi to Dali - 03",
+                            "totalSize": 366372454,
+                            "percentDone": 1,
+                            "peersGettingFromUs": 2,
+                            "peersSendingToUs": 0,
+                            "rateDownload": 0,
+                            "eta": -1,
+                            "hashString": "bdd2f547cdfd8a38011a5ea451d65379c9572305",
+                            "status": 6,
+                            "uploadRatio": 1.5,
+                            "secondsSeeding": 3600,
+                            "files": [
+                                {"name": "Migi to Dali/03.mkv", "length": 366000000, "bytesCompleted": 366000000},
+                                {"name": "Migi to Dali/03.ass", "length": 372454, "bytesCompleted": 372454}
+                            ]
+                        },
+                        {"hashString": "0123456789abcdef0123456789abcdef01234567", "percentDone": 0.5, "uploadRatio": -1}
+                    ]
+                }
+            }"#,
+        )
+        .unwrap();
+        let arguments = parse_response("torrent-get", json).unwrap();
+        let torrent = &arguments["torrents"][0];
+
+        let info = torrent_info_from_json(torrent);
+        assert_eq!(info.size, "349.40 MB");
+        assert_eq!(info.done, "100.00 %");
+        assert_eq!(info.peers, "2");
+        assert_eq!(info.state, "seeding");
+
+        let files = torrent_files_from_json(torrent);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[1].index, 1);
+        assert_eq!(files[1].name, "Migi to Dali/03.ass");
+
+        let completed = completed_torrent_set_from_json(&arguments);
+        assert_eq!(completed.len(), 1);
+        assert!(completed.contains("bdd2f547cdfd8a38011a5ea451d65379c9572305"));
+
+        let seeding_stats = seeding_stats_from_json(&arguments);
+        assert_eq!(
+            seeding_stats["bdd2f547cdfd8a38011a5ea451d65379c9572305"],
+            SeedingStats {
+                ratio: 1.5,
+                seeding_time: 3600
+            }
+        );
+        assert_eq!(
+            seeding_stats["0123456789abcdef0123456789abcdef01234567"].ratio,
+            0.0
+        );
+
+        let arguments = speed_limit_arguments(
+            "bdd2f547cdfd8a38011a5ea451d65379c9572305",
+            SpeedLimit {
+                download: 0,
+                upload: 512,
+            },
+        );
+        assert_eq!(arguments["downloadLimited"], false);
+        assert_eq!(arguments["uploadLimited"], true);
+        assert_eq!(arguments["uploadLimit"], 512);
+
+        let failed = parse_response("torrent-add", json!({ "result": "duplicate torrent" }));
+        assert_eq!(failed.unwrap_err().code(), "downloader");
+    }
+}
@@ -1,4 +1,4 @@
-use crate::api::do_anime_task::handle_error;
+use crate::error::error::handle_error;
 use anyhow::Error;
 use ffmpeg::{
     codec, decoder, encoder, format, frame, media, picture, software, subtitle::Rect, Dictionary,
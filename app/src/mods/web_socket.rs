@@ -1,4 +1,5 @@
-use crate::mods::{config::Config, qb_api::QbitTaskExecutor};
+use crate::models::enums::QbTaskStatus;
+use crate::mods::torrent_client::TorrentClient;
 use actix::fut::wrap_future;
 use actix::ActorContext;
 use actix::Handler;
@@ -13,6 +14,7 @@ use serde_json::from_str;
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 use std::time::Duration;
+use tokio::sync::RwLock as TokioRwLock;
 
 #[derive(Message, Serialize, Deserialize, Debug)]
 #[rtype(result = "()")]
@@ -24,7 +26,9 @@ pub struct GetSeedsProgressRequest {
     pub mikan_id: i32,
     pub episode: i32,
     pub torrent_name: String,
-    pub qb_task_status: i32,
+    pub qb_task_status: QbTaskStatus,
+    #[serde(default)]
+    pub info_hash: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -73,7 +77,7 @@ pub struct QbTaskJson {
 }
 
 pub struct WebSocketActor {
-    pub qb: QbitTaskExecutor,
+    pub qb: Arc<TokioRwLock<Box<dyn TorrentClient>>>,
     pub task_map: Arc<RwLock<HashMap<&'static str, SpawnHandle>>>,
 }
 
@@ -154,10 +158,10 @@ impl StreamHandler<Result<WSMessage, ws::ProtocolError>> for WebSocketActor {
 }
 
 impl WebSocketActor {
-    pub async fn new() -> Self {
-        let config = Config::load_config("./config/config.yaml").await.unwrap();
+    // shares the torrent client of the app, it follows a relogin
+    pub fn new(qb: Arc<TokioRwLock<Box<dyn TorrentClient>>>) -> Self {
         WebSocketActor {
-            qb: QbitTaskExecutor::new_with_config(&config).await.unwrap(),
+            qb,
             task_map: Arc::new(RwLock::new(HashMap::new())),
         }
     }
@@ -167,12 +171,12 @@ impl WebSocketActor {
         task_data: Vec<GetSeedsProgressRequest>,
         ctx: &mut <WebSocketActor as Actor>::Context,
     ) -> SpawnHandle {
-        let qb: QbitTaskExecutor = self.qb.clone();
+        let qb = Arc::clone(&self.qb);
         let tasks_shared = Arc::new(RwLock::new(task_data));
         let actor_address = ctx.address().clone();
 
         let task_handle = ctx.run_interval(Duration::from_secs(2), move |_, ctx| {
-            let qb_clone = qb.clone();
+            let qb_clone = Arc::clone(&qb);
             let tasks_clone = Arc::clone(&tasks_shared);
             let actor_address_clone = actor_address.clone();
 
@@ -185,7 +189,7 @@ impl WebSocketActor {
                     let tasks = task_lock.clone();
                     drop(task_lock);
 
-                    let (qt, ft) = get_qb_task(&qb_clone, tasks).await;
+                    let (qt, ft) = get_qb_task(qb_clone.read().await.as_ref(), tasks).await;
                     qb_tasks = qt;
                     failed_tasks = ft;
                 }
@@ -237,14 +241,14 @@ impl WebSocketActor {
 }
 
 pub async fn get_qb_task(
-    qb: &QbitTaskExecutor,
+    qb: &dyn TorrentClient,
     task_list: Vec<GetSeedsProgressRequest>,
 ) -> (Vec<QbTaskJson>, Vec<GetSeedsProgressRequest>) {
     let mut task_qb_info_list: Vec<QbTaskJson> = Vec::new();
     let mut failed_tasks: Vec<GetSeedsProgressRequest> = Vec::new();
 
     for t in task_list {
-        match qb.qb_api_torrent_info(&t.torrent_name).await {
+        match qb.torrent_info(&t.info_hash).await {
             Ok(torrent_info) => {
                 task_qb_info_list.push(QbTaskJson {
                     torrent_name: t.torrent_name,
@@ -14,10 +14,29 @@ pub fn anime_routes_v2(cfg: &mut web::ServiceConfig) {
             .service(seed_update_handler)
             .service(seed_delete_handler)
             .service(seed_download_handler)
+            .service(update_seed_mode_handler)
             .service(get_anime_detail_handler)
             .service(task_delete_handler)
             .service(task_update_handler)
-            .service(search_anime_handler),
+            .service(get_task_upgrade_handler)
+            .service(search_anime_handler)
+            .service(add_torznab_anime_handler)
+            .service(get_quality_filter_handler)
+            .service(add_quality_filter_handler)
+            .service(delete_quality_filter_handler)
+            .service(add_subgroup_filter_handler)
+            .service(delete_subgroup_filter_handler)
+            .service(get_subgroup_stats_handler)
+            .service(get_anime_subgroup_stats_handler)
+            .service(suggest_subgroup_handler)
+            .service(get_speed_limit_handler)
+            .service(update_speed_limit_handler)
+            .service(get_grace_period_handler)
+            .service(update_grace_period_handler)
+            .service(get_pending_decision_handler)
+            .service(get_dead_download_job_handler)
+            .service(retry_download_job_handler)
+            .service(get_episode_gap_handler),
     );
 }
 
@@ -29,6 +48,9 @@ pub fn setting_routes_v2(cfg: &mut web::ServiceConfig) {
             .service(start_schedule_task_handler)
             .service(change_task_interval_handler)
             .service(get_task_status_handler)
+            .service(get_scheduler_jobs_handler)
+            .service(update_scheduler_job_handler)
+            .service(run_scheduler_job_handler)
             .service(reload_task_handler)
             .service(relogin_qb_handler)
             .service(modify_config_handler)
@@ -19,6 +19,18 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    anime_episode (id) {
+        id -> Nullable<Integer>,
+        mikan_id -> Integer,
+        episode -> Integer,
+        episode_type -> Integer,
+        title -> Text,
+        air_date -> Text,
+        duration -> Integer,
+    }
+}
+
 diesel::table! {
     anime_list (id) {
         id -> Nullable<Integer>,
@@ -35,6 +47,14 @@ diesel::table! {
         anime_status -> Integer,
         total_episodes -> Integer,
         new_finished_episode -> Integer,
+        seed_mode -> Integer,
+        source -> Text,
+        air_date -> Text,
+        rating_rank -> Integer,
+        rating_votes -> Integer,
+        tags -> Text,
+        studio -> Text,
+        alt_names -> Text,
     }
 }
 
@@ -59,6 +79,12 @@ diesel::table! {
         seed_url -> Text,
         seed_status -> Integer,
         seed_size -> Text,
+        publish_date -> Text,
+        episode_end -> Integer,
+        info_hash -> Text,
+        total_size -> BigInt,
+        file_list -> Text,
+        is_special -> Bool,
     }
 }
 
@@ -80,15 +106,91 @@ diesel::table! {
         rename_status -> Integer,
         filename -> Text,
         is_new -> Integer,
+        info_hash -> Text,
+    }
+}
+
+diesel::table! {
+    anime_task_upgrade (id) {
+        id -> Nullable<Integer>,
+        mikan_id -> Integer,
+        episode -> Integer,
+        old_torrent_name -> Text,
+        new_torrent_name -> Text,
+        old_filename -> Text,
+        new_filename -> Text,
+        upgrade_status -> Integer,
+        reason -> Text,
+        created_at -> Text,
+        finished_at -> Text,
+        new_info_hash -> Text,
+    }
+}
+
+diesel::table! {
+    pending_decision (id) {
+        id -> Nullable<Integer>,
+        mikan_id -> Integer,
+        episode -> Integer,
+        first_seen -> Text,
+        deadline -> Text,
+        seed_url -> Text,
+        seed_name -> Text,
+        subgroup_id -> Integer,
+        updated_at -> Text,
+    }
+}
+
+diesel::table! {
+    scheduler_job (id) {
+        id -> Nullable<Integer>,
+        job_name -> Text,
+        schedule -> Text,
+        enabled -> Bool,
+        last_run -> Text,
+        next_run -> Text,
+        last_error -> Text,
+    }
+}
+
+diesel::table! {
+    download_job (id) {
+        id -> Nullable<Integer>,
+        seed_url -> Text,
+        mikan_id -> Integer,
+        episode -> Integer,
+        job_status -> Integer,
+        attempts -> Integer,
+        next_attempt -> Text,
+        last_error -> Text,
+        updated_at -> Text,
+    }
+}
+
+diesel::table! {
+    subgroup_release (id) {
+        id -> Nullable<Integer>,
+        subgroup_id -> Integer,
+        mikan_id -> Integer,
+        episode -> Integer,
+        first_seen -> Text,
+        resolution -> Integer,
+        size -> BigInt,
     }
 }
 
 diesel::allow_tables_to_appear_in_same_query!(
     anime_broadcast,
+    anime_episode,
     anime_filter,
     anime_list,
     anime_progress,
     anime_seed,
     anime_subgroup,
     anime_task,
+    anime_task_upgrade,
+    download_job,
+    pending_decision,
+    scheduler_job,
+    subgroup_release,
 );
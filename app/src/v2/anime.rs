@@ -1,18 +1,30 @@
 use crate::api::do_anime_task;
+use crate::api::scheduler::{JOB_DOWNLOAD_QUEUE, TIME_FORMAT};
 use crate::dao;
-use crate::models::anime_subgroup::AnimeSubgroup;
-use crate::models::{anime_broadcast, anime_list, anime_seed, anime_subgroup, anime_task};
+use crate::error::error::{handle_error, AnimeError};
+use crate::models::enums::{
+    AnimeStatus, AnimeType, JobStatus, SeedStatus, SubscribeStatus, UpdateDay,
+};
+use crate::models::{
+    anime_broadcast, anime_episode, anime_filter, anime_list, anime_seed, anime_subgroup,
+    anime_task, anime_task_upgrade, download_job, pending_decision,
+};
+use crate::mods::anime_filter::{quality_filter_val, QUALITY_FILTER_TYPES};
+use crate::mods::config::{ScraperConfig, SpeedLimit};
+use crate::mods::seed_source::{SeedSource, SeedSources, SOURCE_MIKAN, SOURCE_TORZNAB};
 use crate::mods::spider::BangumiInfo;
 use crate::mods::spider::{self, Mikan};
+use crate::mods::subgroup_stats::{self, SubgroupStats};
+use crate::mods::torznab;
 use crate::register_handler;
-use crate::v2::common::handle_error;
 use crate::{WebData, DB};
 use actix_web::{web, Error, HttpResponse};
 use anyhow::Result;
+use chrono::Local;
 use futures::future::join_all;
 use log;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AnimeMikanIdReqJson {
@@ -22,7 +34,7 @@ pub struct AnimeMikanIdReqJson {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AnimeSubscribeReqJson {
     pub mikan_id: i32,
-    pub subscribe_status: i32,
+    pub subscribe_status: SubscribeStatus,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -43,8 +55,66 @@ pub struct SeedReqJson {
     pub episode: i32,
     pub seed_name: String,
     pub seed_url: String,
-    pub seed_status: i32,
+    pub seed_status: SeedStatus,
     pub seed_size: String,
+    #[serde(default)]
+    pub publish_date: String,
+    #[serde(default = "default_episode_end")]
+    pub episode_end: i32,
+    #[serde(default)]
+    pub is_special: bool,
+}
+
+fn default_episode_end() -> i32 {
+    -1
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnimeSeedModeReqJson {
+    pub mikan_id: i32,
+    pub seed_mode: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnimeQualityFilterReqJson {
+    pub mikan_id: i32,       // 0 for the global rule
+    pub filter_type: String, // resolution, codec, subtitle, container
+    pub value: String,       // e.g. 1080p, HEVC, 内封, MKV
+    #[serde(default)]
+    pub avoid: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DownloadJobIdReqJson {
+    pub id: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnimeSpeedLimitReqJson {
+    pub mikan_id: i32,
+    pub download: i32, // KiB/s, 0 falls back to config.speed_limit
+    pub upload: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnimeSubgroupFilterReqJson {
+    pub mikan_id: i32,
+    pub subgroup_id: i32,
+    #[serde(default)]
+    pub avoid: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnimeGracePeriodReqJson {
+    pub mikan_id: i32,
+    pub hours: i32, // 0 disables it for the anime, -1 falls back to config.anime_config.grace_policy
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnimeTorznabReqJson {
+    pub anime_name: String, // also used as the search keyword
+    pub update_day: UpdateDay,
+    pub anime_type: AnimeType,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -52,7 +122,16 @@ pub struct AnimeDetail {
     pub anime_info: anime_list::AnimeList,
     pub seed_info: Vec<anime_seed::AnimeSeed>,
     pub subgroup_info: Vec<anime_subgroup::AnimeSubgroup>,
-    pub task_info: Vec<anime_task::AnimeTask>,
+    pub task_info: Vec<AnimeTaskInfo>,
+    pub episode_info: Vec<anime_episode::AnimeEpisode>,
+}
+
+// a task with the bangumi data of its episode, null when bangumi has none
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnimeTaskInfo {
+    #[serde(flatten)]
+    pub task: anime_task::AnimeTask,
+    pub episode_info: Option<anime_episode::AnimeEpisode>,
 }
 
 register_handler!(GET "/home" => get_anime_home);
@@ -60,13 +139,32 @@ register_handler!(POST "/broadcast" => get_anime_broadcast, AnimeBroadcastReqJso
 register_handler!(POST "/broadcast/update" => update_anime_broadcast, AnimeBroadcastReqJson);
 register_handler!(POST "/subscribe" => subscribe_anime, AnimeSubscribeReqJson);
 register_handler!(POST "/search" => search_anime, AnimeKeyWordReqJson);
+register_handler!(POST "/torznab/add" => add_torznab_anime, AnimeTorznabReqJson);
 register_handler!(GET "/subgroup" => get_subgroup);
 register_handler!(POST "/seed" => get_anime_seed, AnimeMikanIdReqJson);
 register_handler!(POST "/seed/update" => seed_update, AnimeMikanIdReqJson);
 register_handler!(POST "/seed/download" => seed_download, SeedReqJson);
+register_handler!(POST "/seed/mode" => update_seed_mode, AnimeSeedModeReqJson);
 register_handler!(POST "seed/delete" => seed_delete, AnimeMikanIdReqJson);
+register_handler!(POST "/filter/quality" => get_quality_filter, AnimeMikanIdReqJson);
+register_handler!(POST "/filter/quality/add" => add_quality_filter, AnimeQualityFilterReqJson);
+register_handler!(POST "/filter/quality/delete" => delete_quality_filter, AnimeQualityFilterReqJson);
+register_handler!(POST "/filter/subgroup/add" => add_subgroup_filter, AnimeSubgroupFilterReqJson);
+register_handler!(POST "/filter/subgroup/delete" => delete_subgroup_filter, AnimeSubgroupFilterReqJson);
+register_handler!(GET "/subgroup/stats" => get_subgroup_stats);
+register_handler!(POST "/subgroup/stats/anime" => get_anime_subgroup_stats, AnimeMikanIdReqJson);
+register_handler!(POST "/subgroup/suggest" => suggest_subgroup, AnimeMikanIdReqJson);
+register_handler!(POST "/speed_limit" => get_speed_limit, AnimeMikanIdReqJson);
+register_handler!(POST "/speed_limit/update" => update_speed_limit, AnimeSpeedLimitReqJson);
+register_handler!(POST "/grace_period" => get_grace_period, AnimeMikanIdReqJson);
+register_handler!(POST "/grace_period/update" => update_grace_period, AnimeGracePeriodReqJson);
+register_handler!(GET "/pending_decision" => get_pending_decision);
+register_handler!(GET "/download_job/dead" => get_dead_download_job);
+register_handler!(POST "/download_job/retry" => retry_download_job, DownloadJobIdReqJson);
+register_handler!(GET "/episode_gap" => get_episode_gap);
 register_handler!(POST "/task" => get_task, AnimeMikanIdReqJson);
 register_handler!(GET "/task/update" => task_update);
+register_handler!(POST "/task/upgrade" => get_task_upgrade, AnimeMikanIdReqJson);
 register_handler!(POST "/task/delete" => task_delete, SeedReqJson);
 register_handler!(POST "/detail" => get_anime_detail, AnimeMikanIdReqJson);
 
@@ -76,7 +174,7 @@ async fn get_anime_home(web_data: web::Data<WebData>) -> Result<Vec<anime_list::
         .get()
         .map_err(|e| handle_error(e, "failed to get db connection"))?;
 
-    let mut anime_vec = dao::anime_list::get_by_subscribestatus(db, 1)
+    let mut anime_vec = dao::anime_list::get_by_subscribestatus(db, SubscribeStatus::Subscribed)
         .await
         .map_err(|e| {
             handle_error(
@@ -98,7 +196,7 @@ async fn get_anime_home(web_data: web::Data<WebData>) -> Result<Vec<anime_list::
             .await
             .map_err(|e| handle_error(e, "get_anime_home, dao::anime_list::get_by_mikanid failed"))
         {
-            if anime.subscribe_status == 0 {
+            if anime.subscribe_status == SubscribeStatus::Unsubscribed {
                 anime_vec.push(anime);
             }
         }
@@ -112,17 +210,22 @@ async fn get_anime_home(web_data: web::Data<WebData>) -> Result<Vec<anime_list::
     Ok(anime_vec)
 }
 
+// returns the suggested preferred subgroups when subscribing
 async fn subscribe_anime(
     web_data: web::Data<WebData>,
     item: web::Json<AnimeSubscribeReqJson>,
-) -> Result<(), Error> {
+) -> Result<Vec<SubgroupStats>, Error> {
     let db = &mut web_data
         .pool
         .get()
         .map_err(|e| handle_error(e, "failed to get db connection"))?;
 
     let mikan_id = item.mikan_id;
-    let subscribe_status = if item.subscribe_status == 1 { 0 } else { 1 };
+    let subscribe_status = if item.subscribe_status == SubscribeStatus::Subscribed {
+        SubscribeStatus::Unsubscribed
+    } else {
+        SubscribeStatus::Subscribed
+    };
 
     dao::anime_list::update_subscribestatus_by_mikanid(db, mikan_id, subscribe_status)
         .await
@@ -131,7 +234,19 @@ async fn subscribe_anime(
                 e,
                 "subscribe_anime, dao::anime_list::update_subscribestatus_by_mikanid failed",
             )
-        })
+        })?;
+
+    if subscribe_status == SubscribeStatus::Unsubscribed {
+        return Ok(Vec::new());
+    }
+    let today = Local::now().format("%Y-%m-%d").to_string();
+    match subgroup_stats::suggest_subgroups(db, mikan_id, &today).await {
+        Ok(suggestion_vec) => Ok(suggestion_vec),
+        Err(e) => {
+            log::warn!("subscribe_anime, suggest_subgroups failed, err: {}", e);
+            Ok(Vec::new())
+        }
+    }
 }
 
 async fn get_anime_detail(
@@ -161,13 +276,32 @@ async fn get_anime_detail(
     let subgroup_vec = get_subgroup(web_data.clone())
         .await
         .map_err(|e| handle_error(e, "get_anime_detail, get_subgroup failed"))?;
-    let reorderd_subgroups = reoder_subgroups(subgroup_vec);
+    // the subgroups releasing this anime first, then by the overall ranking
+    let today = Local::now().format("%Y-%m-%d").to_string();
+    let mut stats_vec = subgroup_stats::get_subgroup_stats(db, Some(mikan_id), &today)
+        .await
+        .map_err(|e| handle_error(e, "get_anime_detail, get_subgroup_stats failed"))?;
+    stats_vec.extend(
+        subgroup_stats::get_subgroup_stats(db, None, &today)
+            .await
+            .map_err(|e| handle_error(e, "get_anime_detail, get_subgroup_stats failed"))?,
+    );
+    let reorderd_subgroups = subgroup_stats::order_subgroups(subgroup_vec, &stats_vec);
+    let episode_vec = dao::anime_episode::get_by_mikan_id(db, mikan_id)
+        .await
+        .map_err(|e| {
+            handle_error(
+                e,
+                "get_anime_detail, dao::anime_episode::get_by_mikan_id failed",
+            )
+        })?;
 
     let anime_detail = AnimeDetail {
         anime_info: anime,
         seed_info: seed_vec,
         subgroup_info: reorderd_subgroups,
         task_info: task_vec,
+        episode_info: episode_vec,
     };
     Ok(anime_detail)
 }
@@ -234,8 +368,8 @@ async fn update_anime_broadcast(
     let year = item.year;
     let season = item.season;
 
-    let mikan = spider::Mikan::new()?;
-    let anime_list = mikan.get_anime(year, season).await?;
+    let mikan = spider::Mikan::new(&web_data.config.read().await.scraper)?;
+    let anime_list = mikan.list_season(year, season).await?;
     let mut anime_list_json_vec: Vec<anime_list::AnimeListJson> = Vec::new();
     let mut anime_broadcast_json_vec: Vec<anime_broadcast::AnimeBroadcastJson> = Vec::new();
     let mut img_url_vec: Vec<String> = Vec::new();
@@ -252,9 +386,11 @@ async fn update_anime_broadcast(
             bangumi_rank: "".to_string(),
             bangumi_summary: "".to_string(),
             website: "".to_string(),
-            anime_status: -1,
+            anime_status: AnimeStatus::Unknown,
             total_episodes: -1,
             new_finished_episode: 0,
+            seed_mode: 0,
+            source: SOURCE_MIKAN.to_string(),
         });
         anime_broadcast_json_vec.push(anime_broadcast::AnimeBroadcastJson {
             mikan_id: anime.mikan_id,
@@ -340,13 +476,13 @@ async fn get_subgroup(
 async fn get_task(
     web_data: web::Data<WebData>,
     item: web::Json<AnimeMikanIdReqJson>,
-) -> Result<Vec<anime_task::AnimeTask>, Error> {
+) -> Result<Vec<AnimeTaskInfo>, Error> {
     let db = &mut web_data
         .pool
         .get()
         .map_err(|e| handle_error(e, "failed to get db connection"))?;
 
-    let task_info = dao::anime_task::get_exist_anime_task_by_mikan_id(db, item.mikan_id)
+    let task_vec = dao::anime_task::get_exist_anime_task_by_mikan_id(db, item.mikan_id)
         .await
         .map_err(|e| {
             handle_error(
@@ -355,9 +491,50 @@ async fn get_task(
             )
         })?;
 
+    let episode_map: HashMap<i32, anime_episode::AnimeEpisode> =
+        dao::anime_episode::get_main_by_mikan_id(db, item.mikan_id)
+            .await
+            .map_err(|e| {
+                handle_error(
+                    e,
+                    "get_task, dao::anime_episode::get_main_by_mikan_id failed",
+                )
+            })?
+            .into_iter()
+            .map(|ep| (ep.episode, ep))
+            .collect();
+
+    let task_info = task_vec
+        .into_iter()
+        .map(|task| AnimeTaskInfo {
+            episode_info: episode_map.get(&task.episode).cloned(),
+            task,
+        })
+        .collect();
+
     Ok(task_info)
 }
 
+async fn get_task_upgrade(
+    web_data: web::Data<WebData>,
+    item: web::Json<AnimeMikanIdReqJson>,
+) -> Result<Vec<anime_task_upgrade::AnimeTaskUpgrade>, Error> {
+    let db = &mut web_data
+        .pool
+        .get()
+        .map_err(|e| handle_error(e, "failed to get db connection"))?;
+
+    dao::anime_task_upgrade::get_by_mikan_id(db, item.mikan_id)
+        .await
+        .map_err(|e| {
+            handle_error(
+                e,
+                "get_task_upgrade, dao::anime_task_upgrade::get_by_mikan_id failed",
+            )
+            .into()
+        })
+}
+
 pub async fn seed_update(
     web_data: web::Data<WebData>,
     item: web::Json<AnimeMikanIdReqJson>,
@@ -367,10 +544,86 @@ pub async fn seed_update(
         .get()
         .map_err(|e| handle_error(e, "failed to get db connection"))?;
 
-    let mikan = spider::Mikan::new()?;
-    let bangumi = spider::Bangumi::new()?;
+    let (sources, scraper_config) = {
+        let config = web_data.config.read().await;
+        (SeedSources::new(&config)?, config.scraper.clone())
+    };
 
     let mikan_id = item.mikan_id;
+    let anime_info = dao::anime_list::get_by_mikanid(db, mikan_id)
+        .await
+        .map_err(|e| handle_error(e, "seed_update, dao::anime_list::get_by_mikanid failed"))?;
+
+    let mut total_episodes = anime_info.total_episodes;
+    // bangumi info is only linked from mikan pages
+    if anime_info.source == SOURCE_MIKAN {
+        total_episodes = update_bangumi_info(db, &sources.mikan, &scraper_config, mikan_id).await?;
+    }
+
+    let source = sources.get(&anime_info.source).ok_or_else(|| {
+        handle_error(
+            AnimeError::Conflict(format!("{} is not enabled", anime_info.source)),
+            "seed_update, seed source is not enabled",
+        )
+    })?;
+
+    let subgroup_list = source.list_subgroups(&anime_info).await?;
+
+    let mut subgroup_id_vec: Vec<i32> = Vec::new();
+    for s in &subgroup_list {
+        subgroup_id_vec.push(s.subgroup_id);
+    }
+
+    let anime_subgroup_list = convert_spider_subgroup_to_anime_subgroup(subgroup_list);
+
+    dao::anime_subgroup::add_vec(db, anime_subgroup_list)
+        .await
+        .map_err(|e| handle_error(e, "update_seed, dao::anime_subgroup::add_vec failed"))?;
+
+    let mut seed_vec: Vec<anime_seed::AnimeSeedJson> = Vec::new();
+    if !subgroup_id_vec.is_empty() {
+        seed_vec = get_anime_seed_by_spider(&anime_info, &subgroup_id_vec, source).await?;
+    }
+
+    let max_episode = seed_vec.iter().map(|seed| seed.episode).max().unwrap_or(-1);
+    if max_episode == total_episodes {
+        dao::anime_list::update_animestatus_by_mikanid(db, mikan_id, AnimeStatus::Finished)
+            .await
+            .map_err(|e| {
+                handle_error(
+                    e,
+                    "seed_update, dao::anime_list::update_animestatus_by_mikanid failed",
+                )
+            })?;
+    } else {
+        dao::anime_list::update_animestatus_by_mikanid(db, mikan_id, AnimeStatus::Airing)
+            .await
+            .map_err(|e| {
+                handle_error(
+                    e,
+                    "seed_update, dao::anime_list::update_animestatus_by_mikanid failed",
+                )
+            })?;
+    }
+
+    subgroup_stats::record_releases(db, &seed_vec)
+        .await
+        .map_err(|e| handle_error(e, "update_seed, record_releases failed"))?;
+    dao::anime_seed::add_bulk(db, seed_vec)
+        .await
+        .map_err(|e| handle_error(e, "update_seed, dao::anime_seed::add_bulk failed"))?;
+
+    Ok(())
+}
+
+// return total episodes of the anime
+pub async fn update_bangumi_info(
+    db: &mut DB,
+    mikan: &Mikan,
+    scraper_config: &ScraperConfig,
+    mikan_id: i32,
+) -> Result<i32, Error> {
+    let bangumi = spider::Bangumi::new(scraper_config)?;
     let (bangumi_id, total_episodes) = mikan.get_bangumi_id_and_total_episodes(mikan_id).await?;
 
     let mut bangumi_info = BangumiInfo {
@@ -379,6 +632,8 @@ pub async fn seed_update(
         bangumi_rank: "暂无".to_string(),
         bangumi_summary: "暂无".to_string(),
         website: "暂无".to_string(),
+        rating_rank: -1,
+        ..Default::default()
     };
 
     match bangumi.get_bangumi_info(bangumi_id).await {
@@ -393,6 +648,19 @@ pub async fn seed_update(
     if bangumi_info.total_episodes == -1 {
         bangumi_info.total_episodes = total_episodes;
     }
+    let total_episodes = bangumi_info.total_episodes;
+    let episode_vec: Vec<anime_episode::AnimeEpisodeJson> = bangumi_info
+        .episodes
+        .iter()
+        .map(|ep| anime_episode::AnimeEpisodeJson {
+            mikan_id,
+            episode: ep.episode,
+            episode_type: ep.episode_type,
+            title: ep.name.clone(),
+            air_date: ep.air_date.clone(),
+            duration: ep.duration_seconds,
+        })
+        .collect();
 
     dao::anime_list::update_bangumiinfo_by_mikanid(
         db,
@@ -403,6 +671,12 @@ pub async fn seed_update(
             bangumi_summary: bangumi_info.bangumi_summary,
             website: bangumi_info.website,
             total_episodes: bangumi_info.total_episodes,
+            air_date: bangumi_info.air_date,
+            rating_rank: bangumi_info.rating_rank,
+            rating_votes: bangumi_info.rating_votes,
+            tags: serde_json::to_string(&bangumi_info.tags).unwrap_or_default(),
+            studio: bangumi_info.studio,
+            alt_names: serde_json::to_string(&bangumi_info.alt_names).unwrap_or_default(),
         },
     )
     .await
@@ -413,68 +687,418 @@ pub async fn seed_update(
         )
     })?;
 
-    let anime_info = dao::anime_list::get_by_mikanid(db, mikan_id)
+    // only the api has episodes, the ones from an earlier refresh are kept otherwise
+    if !episode_vec.is_empty() {
+        dao::anime_episode::add_vec(db, episode_vec)
+            .await
+            .map_err(|e| handle_error(e, "update_seed, dao::anime_episode::add_vec failed"))?;
+    }
+
+    Ok(total_episodes)
+}
+
+async fn add_torznab_anime(
+    web_data: web::Data<WebData>,
+    item: web::Json<AnimeTorznabReqJson>,
+) -> Result<anime_list::AnimeList, Error> {
+    let db = &mut web_data
+        .pool
+        .get()
+        .map_err(|e| handle_error(e, "failed to get db connection"))?;
+
+    let anime = dao::anime_list::add(
+        db,
+        anime_list::AnimeListJson {
+            mikan_id: torznab::anime_id(&item.anime_name),
+            anime_name: item.anime_name.trim().to_string(),
+            update_day: item.update_day,
+            img_url: "".to_string(),
+            anime_type: item.anime_type,
+            subscribe_status: SubscribeStatus::Subscribed,
+            bangumi_id: -1,
+            bangumi_rank: "".to_string(),
+            bangumi_summary: "".to_string(),
+            website: "".to_string(),
+            anime_status: AnimeStatus::Unknown,
+            total_episodes: -1,
+            new_finished_episode: 0,
+            seed_mode: 0,
+            source: SOURCE_TORZNAB.to_string(),
+        },
+    )
+    .await
+    .map_err(|e| handle_error(e, "add_torznab_anime, dao::anime_list::add failed"))?;
+
+    Ok(anime)
+}
+
+async fn update_seed_mode(
+    web_data: web::Data<WebData>,
+    item: web::Json<AnimeSeedModeReqJson>,
+) -> Result<(), Error> {
+    // 0 -> scrape episode table html, 1 -> mikan rss feed
+    if !matches!(item.seed_mode, 0 | 1) {
+        return Err(AnimeError::BadRequest(format!("unknown seed mode {}", item.seed_mode)).into());
+    }
+    let db = &mut web_data
+        .pool
+        .get()
+        .map_err(|e| handle_error(e, "failed to get db connection"))?;
+
+    dao::anime_list::update_seedmode_by_mikanid(db, item.mikan_id, item.seed_mode)
         .await
-        .map_err(|e| handle_error(e, "seed_update, dao::anime_list::get_by_mikanid failed"))?;
+        .map_err(|e| {
+            handle_error(
+                e,
+                "update_seed_mode, dao::anime_list::update_seedmode_by_mikanid failed",
+            )
+            .into()
+        })
+}
 
-    let anime_type = anime_info.anime_type;
+async fn get_quality_filter(
+    web_data: web::Data<WebData>,
+    item: web::Json<AnimeMikanIdReqJson>,
+) -> Result<Vec<anime_filter::AnimeFilter>, Error> {
+    let db = &mut web_data
+        .pool
+        .get()
+        .map_err(|e| handle_error(e, "failed to get db connection"))?;
 
-    let subgroup_list = mikan.get_subgroup(mikan_id).await?;
+    dao::anime_filter::get_quality_filter_by_mikan_id(&item.mikan_id, &QUALITY_FILTER_TYPES, db)
+        .await
+        .map_err(|e| {
+            handle_error(
+                e,
+                "get_quality_filter, dao::anime_filter::get_quality_filter_by_mikan_id failed",
+            )
+            .into()
+        })
+}
 
-    let mut subgroup_id_vec: Vec<i32> = Vec::new();
-    for s in &subgroup_list {
-        subgroup_id_vec.push(s.subgroup_id);
+// positive filter_val prefers (or allows, for resolution) the value, negative avoids it
+fn get_quality_filter_val(item: &AnimeQualityFilterReqJson) -> Result<i32, Error> {
+    match quality_filter_val(&item.filter_type, &item.value) {
+        Some(val) if item.avoid => Ok(-val),
+        Some(val) => Ok(val),
+        None => Err(
+            AnimeError::BadRequest(format!("unknown {} {}", item.filter_type, item.value)).into(),
+        ),
     }
+}
 
-    let anime_subgroup_list = convert_spider_subgroup_to_anime_subgroup(subgroup_list);
+async fn add_quality_filter(
+    web_data: web::Data<WebData>,
+    item: web::Json<AnimeQualityFilterReqJson>,
+) -> Result<anime_filter::AnimeFilter, Error> {
+    let val = get_quality_filter_val(&item)?;
+    let db = &mut web_data
+        .pool
+        .get()
+        .map_err(|e| handle_error(e, "failed to get db connection"))?;
 
-    dao::anime_subgroup::add_vec(db, anime_subgroup_list)
+    dao::anime_filter::add_quality_filter(item.mikan_id, &item.filter_type, val, db)
         .await
-        .map_err(|e| handle_error(e, "update_seed, dao::anime_subgroup::add_vec failed"))?;
+        .map_err(|e| {
+            handle_error(
+                e,
+                "add_quality_filter, dao::anime_filter::add_quality_filter failed",
+            )
+            .into()
+        })
+}
 
-    let mut seed_vec: Vec<anime_seed::AnimeSeedJson> = Vec::new();
-    if !subgroup_id_vec.is_empty() {
-        let task_res_vec = join_all(subgroup_id_vec.into_iter().map(|subgroup_id| {
-            get_anime_seed_by_spider(mikan_id, subgroup_id, anime_type, &mikan)
-        }))
-        .await;
+async fn delete_quality_filter(
+    web_data: web::Data<WebData>,
+    item: web::Json<AnimeQualityFilterReqJson>,
+) -> Result<usize, Error> {
+    let val = get_quality_filter_val(&item)?;
+    let db = &mut web_data
+        .pool
+        .get()
+        .map_err(|e| handle_error(e, "failed to get db connection"))?;
 
-        for task_res in task_res_vec {
-            match task_res {
-                Ok(seed_list) => {
-                    seed_vec.extend(seed_list);
-                }
-                Err(_) => continue,
-            }
-        }
-    }
+    dao::anime_filter::delete_quality_filter(item.mikan_id, &item.filter_type, val, db)
+        .await
+        .map_err(|e| {
+            handle_error(
+                e,
+                "delete_quality_filter, dao::anime_filter::delete_quality_filter failed",
+            )
+            .into()
+        })
+}
 
-    let max_episode = seed_vec.iter().map(|seed| seed.episode).max().unwrap_or(-1);
-    if max_episode == bangumi_info.total_episodes {
-        dao::anime_list::update_animestatus_by_mikanid(db, mikan_id, 1)
-            .await
-            .map_err(|e| {
-                handle_error(
-                    e,
-                    "seed_update, dao::anime_list::update_animestatus_by_mikanid failed",
-                )
-            })?;
+// local subgroup rules, e.g. to accept a suggested subgroup, negative to avoid it
+fn subgroup_filter_val(item: &AnimeSubgroupFilterReqJson) -> i32 {
+    if item.avoid {
+        -item.subgroup_id
     } else {
-        dao::anime_list::update_animestatus_by_mikanid(db, mikan_id, 0)
+        item.subgroup_id
+    }
+}
+
+async fn add_subgroup_filter(
+    web_data: web::Data<WebData>,
+    item: web::Json<AnimeSubgroupFilterReqJson>,
+) -> Result<(), Error> {
+    let db = &mut web_data
+        .pool
+        .get()
+        .map_err(|e| handle_error(e, "failed to get db connection"))?;
+
+    let val = subgroup_filter_val(&item);
+    dao::anime_filter::add_local_subgroup_filter_by_mikan_id(item.mikan_id, val, db)
+        .await
+        .map_err(|e| {
+            handle_error(
+                e,
+                "add_subgroup_filter, dao::anime_filter::add_local_subgroup_filter_by_mikan_id failed",
+            ).into()
+        })
+}
+
+async fn delete_subgroup_filter(
+    web_data: web::Data<WebData>,
+    item: web::Json<AnimeSubgroupFilterReqJson>,
+) -> Result<usize, Error> {
+    let db = &mut web_data
+        .pool
+        .get()
+        .map_err(|e| handle_error(e, "failed to get db connection"))?;
+
+    let val = subgroup_filter_val(&item);
+    dao::anime_filter::delete_local_subgroup_filter_by_mikan_id(item.mikan_id, val, db)
+        .await
+        .map_err(|e| {
+            handle_error(
+                e,
+                "delete_subgroup_filter, dao::anime_filter::delete_local_subgroup_filter_by_mikan_id failed",
+            ).into()
+        })
+}
+
+// the ranking over all the recorded anime
+async fn get_subgroup_stats(web_data: web::Data<WebData>) -> Result<Vec<SubgroupStats>, Error> {
+    let db = &mut web_data
+        .pool
+        .get()
+        .map_err(|e| handle_error(e, "failed to get db connection"))?;
+    let today = Local::now().format("%Y-%m-%d").to_string();
+    subgroup_stats::get_subgroup_stats(db, None, &today)
+        .await
+        .map_err(|e| handle_error(e, "get_subgroup_stats, get_subgroup_stats failed").into())
+}
+
+async fn get_anime_subgroup_stats(
+    web_data: web::Data<WebData>,
+    item: web::Json<AnimeMikanIdReqJson>,
+) -> Result<Vec<SubgroupStats>, Error> {
+    let db = &mut web_data
+        .pool
+        .get()
+        .map_err(|e| handle_error(e, "failed to get db connection"))?;
+    let today = Local::now().format("%Y-%m-%d").to_string();
+    subgroup_stats::get_subgroup_stats(db, Some(item.mikan_id), &today)
+        .await
+        .map_err(|e| handle_error(e, "get_anime_subgroup_stats, get_subgroup_stats failed").into())
+}
+
+async fn suggest_subgroup(
+    web_data: web::Data<WebData>,
+    item: web::Json<AnimeMikanIdReqJson>,
+) -> Result<Vec<SubgroupStats>, Error> {
+    let db = &mut web_data
+        .pool
+        .get()
+        .map_err(|e| handle_error(e, "failed to get db connection"))?;
+    let today = Local::now().format("%Y-%m-%d").to_string();
+    subgroup_stats::suggest_subgroups(db, item.mikan_id, &today)
+        .await
+        .map_err(|e| handle_error(e, "suggest_subgroup, suggest_subgroups failed").into())
+}
+
+async fn get_speed_limit(
+    web_data: web::Data<WebData>,
+    item: web::Json<AnimeMikanIdReqJson>,
+) -> Result<SpeedLimit, Error> {
+    let db = &mut web_data
+        .pool
+        .get()
+        .map_err(|e| handle_error(e, "failed to get db connection"))?;
+
+    dao::anime_filter::get_speed_limit(item.mikan_id, db)
+        .await
+        .map_err(|e| {
+            handle_error(
+                e,
+                "get_speed_limit, dao::anime_filter::get_speed_limit failed",
+            )
+            .into()
+        })
+}
+
+// applies to the torrents added afterwards
+async fn update_speed_limit(
+    web_data: web::Data<WebData>,
+    item: web::Json<AnimeSpeedLimitReqJson>,
+) -> Result<SpeedLimit, Error> {
+    if item.download < 0 || item.upload < 0 {
+        return Err(AnimeError::BadRequest("speed limit must not be negative".to_string()).into());
+    }
+    let db = &mut web_data
+        .pool
+        .get()
+        .map_err(|e| handle_error(e, "failed to get db connection"))?;
+
+    for (limit_type, val) in [
+        (dao::anime_filter::LIMIT_DOWNLOAD, item.download),
+        (dao::anime_filter::LIMIT_UPLOAD, item.upload),
+    ] {
+        dao::anime_filter::set_speed_limit(item.mikan_id, limit_type, val, db)
             .await
             .map_err(|e| {
                 handle_error(
                     e,
-                    "seed_update, dao::anime_list::update_animestatus_by_mikanid failed",
+                    "update_speed_limit, dao::anime_filter::set_speed_limit failed",
                 )
             })?;
     }
+    dao::anime_filter::get_speed_limit(item.mikan_id, db)
+        .await
+        .map_err(|e| {
+            handle_error(
+                e,
+                "update_speed_limit, dao::anime_filter::get_speed_limit failed",
+            )
+            .into()
+        })
+}
 
-    dao::anime_seed::add_bulk(db, seed_vec)
+async fn get_grace_period(
+    web_data: web::Data<WebData>,
+    item: web::Json<AnimeMikanIdReqJson>,
+) -> Result<AnimeGracePeriodReqJson, Error> {
+    let db = &mut web_data
+        .pool
+        .get()
+        .map_err(|e| handle_error(e, "failed to get db connection"))?;
+
+    let hours = dao::anime_filter::get_grace_hours(item.mikan_id, db)
         .await
-        .map_err(|e| handle_error(e, "update_seed, dao::anime_seed::add_bulk failed"))?;
+        .map_err(|e| {
+            handle_error(
+                e,
+                "get_grace_period, dao::anime_filter::get_grace_hours failed",
+            )
+        })?;
+    Ok(AnimeGracePeriodReqJson {
+        mikan_id: item.mikan_id,
+        hours,
+    })
+}
 
-    Ok(())
+// held episodes keep their first seen time, the deadline follows the new period
+async fn update_grace_period(
+    web_data: web::Data<WebData>,
+    item: web::Json<AnimeGracePeriodReqJson>,
+) -> Result<AnimeGracePeriodReqJson, Error> {
+    if item.mikan_id == 0 || item.hours < -1 {
+        return Err(AnimeError::BadRequest("invalid grace period".to_string()).into());
+    }
+    let db = &mut web_data
+        .pool
+        .get()
+        .map_err(|e| handle_error(e, "failed to get db connection"))?;
+
+    dao::anime_filter::set_grace_hours(item.mikan_id, item.hours, db)
+        .await
+        .map_err(|e| {
+            handle_error(
+                e,
+                "update_grace_period, dao::anime_filter::set_grace_hours failed",
+            )
+        })?;
+    let hours = dao::anime_filter::get_grace_hours(item.mikan_id, db)
+        .await
+        .map_err(|e| {
+            handle_error(
+                e,
+                "update_grace_period, dao::anime_filter::get_grace_hours failed",
+            )
+        })?;
+    Ok(AnimeGracePeriodReqJson {
+        mikan_id: item.mikan_id,
+        hours,
+    })
+}
+
+// episodes waiting for a preferred subgroup, seed_url is taken after the deadline
+async fn get_pending_decision(
+    web_data: web::Data<WebData>,
+) -> Result<Vec<pending_decision::PendingDecision>, Error> {
+    let db = &mut web_data
+        .pool
+        .get()
+        .map_err(|e| handle_error(e, "failed to get db connection"))?;
+    dao::pending_decision::get_all(db).await.map_err(|e| {
+        handle_error(
+            e,
+            "get_pending_decision, dao::pending_decision::get_all failed",
+        )
+        .into()
+    })
+}
+
+// seeds which failed max_attempts times, they are skipped until retried
+async fn get_dead_download_job(
+    web_data: web::Data<WebData>,
+) -> Result<Vec<download_job::DownloadJob>, Error> {
+    let db = &mut web_data
+        .pool
+        .get()
+        .map_err(|e| handle_error(e, "failed to get db connection"))?;
+    dao::download_job::get_by_status(db, JobStatus::Dead)
+        .await
+        .map_err(|e| {
+            handle_error(
+                e,
+                "get_dead_download_job, dao::download_job::get_by_status failed",
+            )
+            .into()
+        })
+}
+
+async fn retry_download_job(
+    web_data: web::Data<WebData>,
+    item: web::Json<DownloadJobIdReqJson>,
+) -> Result<(), Error> {
+    let db = &mut web_data
+        .pool
+        .get()
+        .map_err(|e| handle_error(e, "failed to get db connection"))?;
+    let now = Local::now().format(TIME_FORMAT).to_string();
+    let retried = dao::download_job::retry(db, item.id, &now)
+        .await
+        .map_err(|e| handle_error(e, "retry_download_job, dao::download_job::retry failed"))?;
+    if !retried {
+        return Err(AnimeError::NotFound(format!("no dead download job {}", item.id)).into());
+    }
+    Ok(web_data.scheduler.trigger(JOB_DOWNLOAD_QUEUE).await?)
+}
+
+// aired episodes of the subscribed anime which are neither downloaded nor queued
+async fn get_episode_gap(
+    web_data: web::Data<WebData>,
+) -> Result<Vec<do_anime_task::EpisodeGap>, Error> {
+    let db = &mut web_data
+        .pool
+        .get()
+        .map_err(|e| handle_error(e, "failed to get db connection"))?;
+    let today = Local::now().format("%Y-%m-%d").to_string();
+    do_anime_task::find_episode_gaps(db, &today)
+        .await
+        .map_err(|e| handle_error(e, "get_episode_gap, find_episode_gaps failed").into())
 }
 
 pub async fn seed_delete(
@@ -516,17 +1140,31 @@ pub async fn seed_download(
         .get()
         .map_err(|e| handle_error(e, "failed to get db connection"))?;
 
-    let mikan = spider::Mikan::new()?;
+    let mikan = spider::Mikan::new(&web_data.config.read().await.scraper)?;
     let qb = web_data.qb.read().await;
 
-    if !qb.is_login {
+    if !qb.is_login() {
         return Err(handle_error(
-            anyhow::Error::msg("qbittorrent client not started"),
+            anyhow::Error::msg("torrent client not started"),
             "failed to down load seed",
-        ));
+        )
+        .into());
+    }
+
+    // a special shares the episode number it follows, its task would replace that episode
+    let is_special = item.is_special
+        || dao::anime_seed::get_anime_seed_by_seed_url(db, &item.seed_url)
+            .await
+            .is_ok_and(|anime_seed| anime_seed.is_special);
+    if is_special {
+        return Err(AnimeError::BadRequest(format!(
+            "special seed {} can not be downloaded as an episode",
+            item.seed_name
+        ))
+        .into());
     }
 
-    dao::anime_seed::update_seedstatus_by_seedurl(db, &item.seed_url, 1)
+    dao::anime_seed::update_seedstatus_by_seedurl(db, &item.seed_url, SeedStatus::Used)
         .await
         .map_err(|e| {
             handle_error(
@@ -537,7 +1175,7 @@ pub async fn seed_download(
 
     let anime_seed = convert_json_seed_to_anime_seed(item);
 
-    do_anime_task::create_anime_task_by_seed(&mikan, anime_seed, &qb, db)
+    do_anime_task::create_anime_task_by_seed(&mikan, anime_seed, qb.as_ref(), db)
         .await
         .map_err(|e| {
             handle_error(
@@ -557,14 +1195,27 @@ pub async fn task_delete(
         .get()
         .map_err(|e| handle_error(e, "failed to get db connection"))?;
 
-    let torrent_name =
-        get_torrent_name_from_url(&item.seed_url).unwrap_or_else(|| item.seed_url.clone());
+    let torrent_name = spider::get_torrent_name(&item.seed_url);
 
-    let qb = web_data.qb.read().await;
-
-    qb.qb_api_del_torrent(&torrent_name)
+    let info_hash = dao::anime_task::get_by_torrent_name_with_batch(db, &torrent_name)
         .await
-        .map_err(|e| handle_error(e, "task_delete, qb_api_del_torrent failed"))?;
+        .map_err(|e| {
+            handle_error(
+                e,
+                "task_delete, dao::anime_task::get_by_torrent_name_with_batch failed",
+            )
+        })?
+        .into_iter()
+        .map(|task| task.info_hash)
+        .find(|info_hash| !info_hash.is_empty());
+
+    // a task without info hash never made it to the torrent client
+    if let Some(info_hash) = info_hash {
+        let qb = web_data.qb.read().await;
+        qb.del_torrent(&info_hash, true)
+            .await
+            .map_err(|e| handle_error(e, "task_delete, del_torrent failed"))?;
+    }
 
     dao::anime_task::delete_anime_task_by_torrent_name(db, &torrent_name)
         .await
@@ -584,7 +1235,7 @@ pub async fn task_update(web_data: web::Data<WebData>) -> Result<(), Error> {
         .map_err(|e| handle_error(e, "failed to get db connection"))?;
 
     let qb = web_data.qb.read().await;
-    do_anime_task::update_qb_task_status(&qb, db)
+    do_anime_task::update_qb_task_status(qb.as_ref(), db)
         .await
         .map_err(|e| {
             handle_error(
@@ -612,7 +1263,7 @@ async fn search_anime(
                 "search_anime, dao::anime_list::search_by_anime_name failed",
             )
         })?;
-    result.sort_by(|a, b| b.subscribe_status.cmp(&a.subscribe_status));
+    result.sort_by(|a, b| i32::from(b.subscribe_status).cmp(&i32::from(a.subscribe_status)));
 
     Ok(result)
 }
@@ -627,39 +1278,15 @@ fn convert_json_seed_to_anime_seed(sj: web::Json<SeedReqJson>) -> anime_seed::An
         seed_url: sj.seed_url.clone(),
         seed_status: sj.seed_status,
         seed_size: sj.seed_size.clone(),
+        publish_date: sj.publish_date.clone(),
+        episode_end: sj.episode_end,
+        info_hash: "".to_string(),
+        total_size: 0,
+        file_list: "[]".to_string(),
+        is_special: sj.is_special,
     }
 }
 
-fn reoder_subgroups(subgroup_vec: Vec<AnimeSubgroup>) -> Vec<AnimeSubgroup> {
-    let mut subgroup_583 = None; // ANi
-    let mut subgroup_382 = None; // 喵萌奶茶屋
-    let mut subgroup_370 = None; // LoliHouse
-    let mut other_subgroups = Vec::new();
-
-    for subgroup in subgroup_vec {
-        match subgroup.subgroup_id {
-            583 => subgroup_583 = Some(subgroup),
-            382 => subgroup_382 = Some(subgroup),
-            370 => subgroup_370 = Some(subgroup),
-            _ => other_subgroups.push(subgroup),
-        }
-    }
-
-    let mut reordered_subgroups = Vec::new();
-    if let Some(subgroup) = subgroup_583 {
-        reordered_subgroups.push(subgroup);
-    }
-    if let Some(subgroup) = subgroup_382 {
-        reordered_subgroups.push(subgroup);
-    }
-    if let Some(subgroup) = subgroup_370 {
-        reordered_subgroups.push(subgroup);
-    }
-    reordered_subgroups.extend(other_subgroups);
-
-    reordered_subgroups
-}
-
 fn get_img_name_from_url(img_url: &str) -> Option<String> {
     let parts: Vec<&str> = img_url.split('/').collect();
     if let Some(img_name) = parts.get(4) {
@@ -670,16 +1297,6 @@ fn get_img_name_from_url(img_url: &str) -> Option<String> {
     }
 }
 
-fn get_torrent_name_from_url(seed_url: &str) -> Option<String> {
-    let parts: Vec<&str> = seed_url.split('/').collect();
-    if let Some(torrent_name) = parts.get(3) {
-        Some(torrent_name.to_string())
-    } else {
-        log::warn!("unexpected seed_url format: {}", seed_url);
-        None
-    }
-}
-
 fn convert_spider_subgroup_to_anime_subgroup(
     spider_vec: Vec<spider::Subgroup>,
 ) -> Vec<anime_subgroup::AnimeSubgroupJson> {
@@ -693,12 +1310,11 @@ fn convert_spider_subgroup_to_anime_subgroup(
 }
 
 pub async fn get_anime_seed_by_spider(
-    mikan_id: i32,
-    subgroup_id: i32,
-    anime_type: i32,
-    mikan: &spider::Mikan,
+    anime: &anime_list::AnimeList,
+    subgroup_id_vec: &[i32],
+    source: &dyn SeedSource,
 ) -> Result<Vec<anime_seed::AnimeSeedJson>, Error> {
-    let seed_list: Vec<spider::Seed> = mikan.get_seed(mikan_id, subgroup_id, anime_type).await?;
+    let seed_list: Vec<spider::Seed> = source.list_anime_seeds(anime, subgroup_id_vec).await?;
     Ok(convert_spider_seed_to_anime_seed(seed_list))
 }
 
@@ -715,6 +1331,9 @@ fn convert_spider_seed_to_anime_seed(
             seed_url: s.seed_url,
             seed_status: s.seed_status,
             seed_size: s.seed_size,
+            publish_date: s.publish_date,
+            episode_end: s.episode_end,
+            is_special: s.is_special,
         })
         .collect()
 }
@@ -1,8 +1,3 @@
-pub fn handle_error<E: std::fmt::Debug>(e: E, message: &str) -> actix_web::Error {
-    log::error!("{}, error: {:?}", message, e);
-    actix_web::error::ErrorInternalServerError("Internal server error")
-}
-
 #[macro_export]
 macro_rules! register_handler {
     (GET $path:expr => $handler:ident) => {
@@ -14,7 +9,7 @@ macro_rules! register_handler {
                 log::info!("{}", stringify!([<$handler _handler>]));
                 let result = $handler(web_data).await.map_err(|e| {
                     log::error!("{} failed: {:?}", stringify!($handler), e);
-                    actix_web::error::ErrorInternalServerError("Internal server error")
+                    e
                 })?;
                 Ok(HttpResponse::Ok().json(result))
             }
@@ -34,7 +29,7 @@ macro_rules! register_handler {
                     item
                 ).await.map_err(|e| {
                     log::error!("{} failed: {:?}", stringify!($handler), e);
-                    actix_web::error::ErrorInternalServerError("Internal server error")
+                    e
                 })?;
                 Ok(HttpResponse::Ok().json(result))
             }
@@ -1,6 +1,11 @@
 use crate::api::do_anime_task;
+use crate::api::scheduler::JOB_SEED_REFRESH;
+use crate::error::error::{handle_error, AnimeError};
+use crate::models::scheduler_job::{SchedulerJob, SchedulerJobJson};
 use crate::mods::config::Config;
-use crate::mods::qb_api::QbitTaskExecutor;
+use crate::mods::path_mapping::PathMapper;
+use crate::mods::torrent_client::{self, TorrentClient};
+use crate::register_handler;
 use crate::Pool;
 use crate::WebData;
 use actix_web::web;
@@ -14,27 +19,32 @@ pub struct TaskInterval {
     pub interval: i32,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SchedulerJobNameReqJson {
+    pub job_name: String,
+}
+
+// start/exit/change_interval drive the seed_refresh job of the scheduler
 #[post("/exit")]
 pub async fn exit_schedule_task_handler(
-    status: web::Data<Arc<TokioRwLock<bool>>>,
+    web_data: web::Data<WebData>,
 ) -> Result<HttpResponse, Error> {
-    do_anime_task::exit_task(&status).await;
+    web_data
+        .scheduler
+        .set_enabled(JOB_SEED_REFRESH, false)
+        .await?;
+    log::info!("exit schedule task");
     Ok(HttpResponse::Ok().body("ok"))
 }
 
 #[post("/start")]
 pub async fn start_schedule_task_handler(
-    status: web::Data<Arc<TokioRwLock<bool>>>,
-    qb: web::Data<Arc<TokioRwLock<QbitTaskExecutor>>>,
-    pool: web::Data<Pool>,
+    web_data: web::Data<WebData>,
 ) -> Result<HttpResponse, Error> {
-    let qb_clone = Arc::clone(&qb);
-    let mut db_connection = pool.get().unwrap();
-
-    let run_handle = tokio::spawn(async move {
-        do_anime_task::run_task(&status, &qb_clone, &mut db_connection).await;
-    });
-    drop(run_handle);
+    web_data
+        .scheduler
+        .set_enabled(JOB_SEED_REFRESH, true)
+        .await?;
     log::info!("start schedule task");
     Ok(HttpResponse::Ok().body("ok"))
 }
@@ -42,98 +52,128 @@ pub async fn start_schedule_task_handler(
 #[post("/change_interval")]
 pub async fn change_task_interval_handler(
     item: web::Json<TaskInterval>,
-    status: web::Data<Arc<TokioRwLock<bool>>>,
-    qb: web::Data<Arc<TokioRwLock<QbitTaskExecutor>>>,
-    pool: web::Data<Pool>,
+    web_data: web::Data<WebData>,
 ) -> Result<HttpResponse, Error> {
-    do_anime_task::exit_task(&status).await;
-    let qb_clone = Arc::clone(&qb);
-    let mut db_connection = pool.get().unwrap();
-
-    let interval = item.interval.clone();
-    let run_handle = actix::spawn(async move {
-        do_anime_task::change_task_interval(interval, &status, &qb_clone, &mut db_connection).await;
-    });
-    drop(run_handle);
+    web_data
+        .scheduler
+        .update_job(&SchedulerJobJson {
+            job_name: JOB_SEED_REFRESH.to_string(),
+            schedule: format!("@every {}m", item.interval),
+            enabled: true,
+        })
+        .await?;
     log::info!("change schedule task with new interval: {}", item.interval);
     Ok(HttpResponse::Ok().body("ok"))
 }
 
 #[get("/get_task_status")]
-pub async fn get_task_status_handler(
-    status: web::Data<Arc<TokioRwLock<bool>>>,
-) -> Result<HttpResponse, Error> {
-    Ok(match do_anime_task::get_task_status(&status).await {
-        Ok(task_status) => {
-            if task_status {
-                HttpResponse::Ok().body("Task is Running")
-            } else {
-                HttpResponse::Ok().body("Task is not Running")
-            }
-        }
-        Err(_) => HttpResponse::from(HttpResponse::InternalServerError()),
+pub async fn get_task_status_handler(web_data: web::Data<WebData>) -> Result<HttpResponse, Error> {
+    let job_vec = web_data.scheduler.get_jobs().await?;
+    let running = job_vec
+        .iter()
+        .any(|job| job.job_name == JOB_SEED_REFRESH && job.enabled);
+    Ok(if running {
+        HttpResponse::Ok().body("Task is Running")
+    } else {
+        HttpResponse::Ok().body("Task is not Running")
     })
 }
 
+register_handler!(GET "/get_scheduler_jobs" => get_scheduler_jobs);
+register_handler!(POST "/update_scheduler_job" => update_scheduler_job, SchedulerJobJson);
+register_handler!(POST "/run_scheduler_job" => run_scheduler_job, SchedulerJobNameReqJson);
+
+async fn get_scheduler_jobs(web_data: web::Data<WebData>) -> Result<Vec<SchedulerJob>, Error> {
+    Ok(web_data.scheduler.get_jobs().await?)
+}
+
+async fn update_scheduler_job(
+    web_data: web::Data<WebData>,
+    item: web::Json<SchedulerJobJson>,
+) -> Result<SchedulerJob, Error> {
+    Ok(web_data.scheduler.update_job(&item).await?)
+}
+
+async fn run_scheduler_job(
+    web_data: web::Data<WebData>,
+    item: web::Json<SchedulerJobNameReqJson>,
+) -> Result<(), Error> {
+    Ok(web_data.scheduler.trigger(&item.job_name).await?)
+}
+
 #[get("/reload_task")]
 pub async fn reload_task_handler(
     video_file_lock: web::Data<Arc<TokioRwLock<bool>>>,
     pool: web::Data<Pool>,
     config: web::Data<Arc<TokioRwLock<Config>>>,
     web_data: web::Data<WebData>,
-) -> Result<HttpResponse, Error> {
-    let db_connection = &mut pool.get().unwrap();
-    Ok(
-        match do_anime_task::create_anime_task_from_exist_files(
-            &video_file_lock,
-            db_connection,
-            config.get_ref(),
-            web_data,
-        )
-        .await
-        {
-            Ok(_) => HttpResponse::Ok().body("ok"),
-            Err(_) => HttpResponse::from(HttpResponse::InternalServerError()),
-        },
+) -> Result<HttpResponse, AnimeError> {
+    let db_connection = &mut pool
+        .get()
+        .map_err(|e| handle_error(e, "reload_task, failed to get db connection"))?;
+    do_anime_task::create_anime_task_from_exist_files(
+        &video_file_lock,
+        db_connection,
+        config.get_ref(),
+        web_data,
     )
+    .await
+    .map_err(|e| handle_error(e, "reload_task, create_anime_task_from_exist_files failed"))?;
+    Ok(HttpResponse::Ok().body("ok"))
 }
 
 #[get("/relogin_qb")]
 pub async fn relogin_qb_handler(
-    qb: web::Data<Arc<TokioRwLock<QbitTaskExecutor>>>,
+    qb: web::Data<Arc<TokioRwLock<Box<dyn TorrentClient>>>>,
     config: web::Data<Arc<TokioRwLock<Config>>>,
-) -> Result<HttpResponse, Error> {
+) -> Result<HttpResponse, AnimeError> {
     let mut config = config.write().await;
 
-    Ok(match config.reload_config().await {
-        Ok(_) => {
-            let mut qb = qb.write().await;
-            match qb.relogin(&config).await {
-                Ok(_) => HttpResponse::Ok().body("ok"),
-                Err(_) => HttpResponse::from(HttpResponse::InternalServerError()),
-            }
-        }
-        Err(_) => HttpResponse::from(HttpResponse::InternalServerError()),
-    })
+    config
+        .reload_config()
+        .await
+        .map_err(|e| handle_error(e, "relogin_qb, failed to reload config"))?;
+    // recreate the client, config.downloader may switch to another one
+    let client = torrent_client::new_torrent_client(&config)
+        .await
+        .map_err(|e| handle_error(e, "relogin_qb, failed to create the torrent client"))?;
+    if !client.is_login() {
+        return Err(AnimeError::Downloader(format!(
+            "relogin_qb, failed to log in to {}",
+            config.downloader.kind
+        )));
+    }
+    *qb.write().await = client;
+    Ok(HttpResponse::Ok().body("ok"))
 }
 
 #[post("/modify_config")]
 pub async fn modify_config_handler(
     mut item: web::Json<Config>,
     config: web::Data<Arc<TokioRwLock<Config>>>,
-) -> Result<HttpResponse, Error> {
+) -> Result<HttpResponse, AnimeError> {
     let mut config = config.write().await;
 
-    Ok(match config.modify_filed(&mut item).await {
-        Ok(_) => {
-            log::info!("update config with new value: {:?}", &config);
-            HttpResponse::Ok().body("ok")
-        }
-        Err(_) => {
-            log::warn!("Failed to update config with new value: {:?}", &item);
-            HttpResponse::from(HttpResponse::InternalServerError())
-        }
-    })
+    // check the rules against the download path they will be used with
+    let path_mapping = match item.path_mapping {
+        Some(_) => item.path_mapping_rules(),
+        None => config.path_mapping_rules(),
+    };
+    let download_path = if item.download_path.is_empty() {
+        &config.download_path
+    } else {
+        &item.download_path
+    };
+    PathMapper::new(path_mapping).validate(download_path)?;
+
+    config.modify_filed(&mut item).await.map_err(|e| {
+        handle_error(
+            e,
+            &format!("Failed to update config with new value: {:?}", &item),
+        )
+    })?;
+    log::info!("update config with new value: {:?}", &config);
+    Ok(HttpResponse::Ok().body("ok"))
 }
 
 #[get("/get_config")]
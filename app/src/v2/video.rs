@@ -1,8 +1,11 @@
 use crate::api::do_anime_task::{self, VideoConfig};
+use crate::error::error::handle_error;
 use crate::models::anime_progess::AnimeProgressJson;
 use crate::models::anime_task::UpdateAnimeTask;
+use crate::models::enums::{NewStatus, QbTaskStatus, RenameStatus};
 use crate::mods::config::Config;
-use crate::mods::qb_api::QbitTaskExecutor;
+use crate::mods::spider;
+use crate::mods::torrent_client::{get_anime_dir, TorrentClient};
 use crate::mods::video_proccessor::{self, get_av_hwaccels, trans_mkv_2_mp4};
 use crate::{dao, Pool};
 use actix_web::{get, post, web, Error, HttpResponse};
@@ -14,11 +17,6 @@ use std::io::{Seek, SeekFrom, Write};
 use std::sync::Arc;
 use tokio::sync::RwLock as TokioRwLock;
 
-pub fn handle_error<E: std::fmt::Debug>(e: E, message: &str) -> actix_web::Error {
-    log::error!("{}, error: {:?}", message, e);
-    actix_web::error::ErrorInternalServerError("Internal server error")
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TorrentName {
     pub torrent_name: String,
@@ -58,7 +56,10 @@ async fn get_anime_detail(
         .await
         .map_err(|e| handle_error(e, "dao::anime_task::get_by_torrent_name failed"))?;
 
-    let subgroup_id = dao::anime_seed::get_anime_seed_by_seed_url(db_connection, torrent_name)
+    let subgroup_id = dao::anime_seed::get_anime_seed_by_seed_url(
+        db_connection,
+        spider::get_task_torrent_name(torrent_name),
+    )
         .await
         .map_err(|e| handle_error(e, "dao::anime_task::get_by_torrent_name failed"))?
         .subgroup_id;
@@ -126,7 +127,7 @@ async fn get_subtitle_vec(
 pub async fn extract_subtitle_handle(
     item: web::Json<TorrentName>,
     video_file_lock: web::Data<Arc<TokioRwLock<bool>>>,
-    qb: web::Data<Arc<TokioRwLock<QbitTaskExecutor>>>,
+    qb: web::Data<Arc<TokioRwLock<Box<dyn TorrentClient>>>>,
     pool: web::Data<Pool>,
     config: web::Data<Arc<TokioRwLock<Config>>>,
 ) -> Result<HttpResponse, Error> {
@@ -148,7 +149,7 @@ pub async fn extract_subtitle_handle(
 pub async fn extract_subtitle(
     torrent_name: &str,
     video_file_lock: web::Data<Arc<TokioRwLock<bool>>>,
-    qb: web::Data<Arc<TokioRwLock<QbitTaskExecutor>>>,
+    qb: web::Data<Arc<TokioRwLock<Box<dyn TorrentClient>>>>,
     db_connection: &mut PooledConnection<ConnectionManager<SqliteConnection>>,
     config: web::Data<Arc<TokioRwLock<Config>>>,
 ) -> Result<(), Error> {
@@ -156,7 +157,7 @@ pub async fn extract_subtitle(
         .await
         .map_err(|e| handle_error(e, "dao::anime_task::get_by_torrent_name failed"))?;
 
-    if anime_task.qb_task_status == 0 {
+    if anime_task.qb_task_status == QbTaskStatus::Downloading {
         let nb_new_finished_task = do_anime_task::auto_update_handler(&qb, db_connection)
             .await
             .map_err(|e| handle_error(e, "Failed to get finished task"))?;
@@ -174,7 +175,7 @@ pub async fn extract_subtitle(
         return Ok(());
     }
 
-    if anime_task.rename_status == 0 {
+    if anime_task.rename_status == RenameStatus::Pending {
         do_anime_task::auto_rename_and_extract_handler(
             &video_file_lock,
             &qb,
@@ -196,8 +197,9 @@ pub async fn extract_subtitle(
         .anime_name;
 
     let cur_total_file_path = format!(
-        "{}/{}({})/{}",
-        download_path, anime_name, anime_task.mikan_id, anime_task.filename
+        "{}/{}",
+        get_anime_dir(&download_path, &anime_name, anime_task.mikan_id),
+        anime_task.filename
     );
 
     let mut subtitle_vec: Vec<String> = vec![];
@@ -227,14 +229,14 @@ pub async fn extract_subtitle(
 
     video_config.insert(anime_task.filename.clone(), cur_config);
 
-    file.seek(std::io::SeekFrom::Start(0)).unwrap();
-    file.set_len(0).unwrap();
-    file.write_all(
-        serde_json::to_string_pretty(&video_config)
-            .unwrap()
-            .as_bytes(),
-    )
-    .map_err(|e| handle_error(e, "Failed to update video config file."))?;
+    file.seek(std::io::SeekFrom::Start(0))
+        .map_err(|e| handle_error(e, "Failed to seek in video config file"))?;
+    file.set_len(0)
+        .map_err(|e| handle_error(e, "Failed to truncate video config file"))?;
+    let new_content = serde_json::to_string_pretty(&video_config)
+        .map_err(|e| handle_error(e, "Failed to serialize video config"))?;
+    file.write_all(new_content.as_bytes())
+        .map_err(|e| handle_error(e, "Failed to update video config file."))?;
 
     Ok(())
 }
@@ -374,8 +376,12 @@ async fn set_anime_progress_by_mikanid_and_episode(
             )
         })?;
 
-    if let Err(e) =
-        dao::anime_task::update_isnew_status(&mut db_connection, &quary_item.torrent_name, 0).await
+    if let Err(e) = dao::anime_task::update_isnew_status(
+        &mut db_connection,
+        &quary_item.torrent_name,
+        NewStatus::Watched,
+    )
+    .await
     {
         log::warn!(
             "Failed to update isnew status for torrent: {}, {}",
@@ -415,13 +421,16 @@ async fn set_anime_progress_by_torrent(
             .await
         {
             Ok(task) => task,
-            Err(e) => return Err(handle_error(e, "Failed to fetch anime task")),
+            Err(e) => return Err(handle_error(e, "Failed to fetch anime task").into()),
         };
 
-    if anime_task.is_new >= 1 {
-        if let Err(e) =
-            dao::anime_task::update_isnew_status(&mut db_connection, &quary_item.torrent_name, 0)
-                .await
+    if anime_task.is_new == NewStatus::New {
+        if let Err(e) = dao::anime_task::update_isnew_status(
+            &mut db_connection,
+            &quary_item.torrent_name,
+            NewStatus::Watched,
+        )
+        .await
         {
             log::warn!(
                 "Failed to update is_new status for torrent: {}, {}",
@@ -524,8 +533,9 @@ async fn trans_video_format_handler(
         .anime_name;
 
     let path = format!(
-        "{}/{}({})/{}",
-        download_path, anime_name, anime_task.mikan_id, anime_task.filename
+        "{}/{}",
+        get_anime_dir(&download_path, &anime_name, anime_task.mikan_id),
+        anime_task.filename
     );
 
     match trans_mkv_2_mp4(&path).await {
@@ -545,7 +555,7 @@ async fn trans_video_format_handler(
                 },
             )
             .await
-            .unwrap();
+            .map_err(|e| handle_error(e, "Failed to update the filename of the anime task"))?;
 
             {
                 let _ = video_file_lock.write().await;
@@ -569,7 +579,8 @@ async fn trans_video_format_handler(
                     return Err(handle_error(
                         std::io::Error::new(std::io::ErrorKind::NotFound, "Torrent name not found"),
                         "Torrent name not found in video config",
-                    ));
+                    )
+                    .into());
                 }
             }
         }
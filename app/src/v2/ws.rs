@@ -1,9 +1,14 @@
 use crate::mods::web_socket::WebSocketActor;
+use crate::QB;
 use actix_web::{web, Error, HttpRequest, HttpResponse};
 use actix_web_actors::ws;
 use anyhow::Result;
 
-pub async fn ws_index(req: HttpRequest, stream: web::Payload) -> Result<HttpResponse, Error> {
+pub async fn ws_index(
+    req: HttpRequest,
+    stream: web::Payload,
+    qb: QB,
+) -> Result<HttpResponse, Error> {
     log::info!("ws_index: /v2/ws/");
-    ws::start(WebSocketActor::new().await, &req, stream)
+    ws::start(WebSocketActor::new(qb.get_ref().clone()), &req, stream)
 }